@@ -8,6 +8,7 @@ use std::fmt::{Display, Formatter, Result};
 #[derive(Debug)]
 pub enum QueryContextError {
     InvalidDate(String),
+    InvalidMessageKind(String),
 }
 
 impl Display for QueryContextError {
@@ -15,7 +16,11 @@ impl Display for QueryContextError {
         match self {
             QueryContextError::InvalidDate(date) => write!(
                 fmt,
-                "Invalid date provided: {date}! Must be in format YYYY-MM-DD."
+                "Invalid date provided: {date}! Must be a date like 2023-01-01, a time like 2023-01-01T09:30 or 2023-01-01T09:30:00-08:00, optionally followed by a time zone like America/New_York, a duration like 7d, or a period like last-month."
+            ),
+            QueryContextError::InvalidMessageKind(kind) => write!(
+                fmt,
+                "Invalid message kind provided: {kind}! Must be one of tapbacks, announcements, apps, shareplay, edited, with-attachments, or without-attachments."
             ),
         }
     }
@@ -15,7 +15,7 @@ use crate::{
     message_types::sticker::{get_sticker_effect, StickerEffect},
     tables::{
         messages::Message,
-        table::{Table, ATTACHMENT},
+        table::{Table, ATTACHMENT, CHAT_MESSAGE_JOIN, MESSAGE, MESSAGE_ATTACHMENT_JOIN},
     },
     util::{
         dates::TIMESTAMP_FACTOR,
@@ -260,8 +260,17 @@ impl Attachment {
                     if context.start.is_some() {
                         statement.push_str(" AND ");
                     }
-                    statement
-                        .push_str(&format!("    a.created_date <= {}", end / TIMESTAMP_FACTOR));
+                    statement.push_str(&format!("    a.created_date < {}", end / TIMESTAMP_FACTOR));
+                }
+                if context.has_message_filters() {
+                    if context.start.is_some() || context.end.is_some() {
+                        statement.push_str(" AND ");
+                    }
+                    // Only count attachments sent with messages that match the filters
+                    statement.push_str(&format!(
+                        "    a.ROWID IN (SELECT j.attachment_id FROM {MESSAGE_ATTACHMENT_JOIN} j JOIN {MESSAGE} m ON j.message_id = m.ROWID LEFT JOIN {CHAT_MESSAGE_JOIN} c ON m.ROWID = c.message_id WHERE {})",
+                        context.generate_message_filters().join(" AND ")
+                    ));
                 }
             }
 
@@ -271,8 +280,10 @@ impl Attachment {
                 .map_err(TableError::Attachment)?
         };
 
+        // `SUM` is `NULL` when no attachments match the filters
         bytes_query
-            .query_row([], |r| r.get(0))
+            .query_row([], |r| r.get::<_, Option<u64>>(0))
+            .map(Option::unwrap_or_default)
             .map_err(TableError::Attachment)
     }
 
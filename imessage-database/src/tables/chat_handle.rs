@@ -7,7 +7,7 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 use crate::{
     error::table::TableError,
     tables::table::{
-        Cacheable, Deduplicate, Diagnostic, Table, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN,
+        Cacheable, Deduplicate, Diagnostic, Table, CHAT_HANDLE_JOIN, CHAT_MESSAGE_JOIN, MESSAGE,
     },
     util::output::{done_processing, processing},
 };
@@ -79,6 +79,43 @@ impl Cacheable for ChatToHandle {
     }
 }
 
+impl ChatToHandle {
+    /// Generate a hashmap containing each chatroom's ID pointing to a `BTreeSet` of the handle IDs that sent messages to it
+    ///
+    /// People who leave a chat are removed from the `chat_handle_join` table, but their messages still belong to the chat.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::dirs::default_db_path;
+    /// use imessage_database::tables::table::get_connection;
+    /// use imessage_database::tables::chat_handle::ChatToHandle;
+    ///
+    /// let db_path = default_db_path();
+    /// let conn = get_connection(&db_path).unwrap();
+    /// let senders = ChatToHandle::cache_senders(&conn);
+    /// ```
+    pub fn cache_senders(db: &Connection) -> Result<HashMap<i32, BTreeSet<i32>>, TableError> {
+        let mut cache: HashMap<i32, BTreeSet<i32>> = HashMap::new();
+
+        let mut statement = db
+            .prepare(&format!(
+                "SELECT DISTINCT c.chat_id, m.handle_id FROM {CHAT_MESSAGE_JOIN} as c JOIN {MESSAGE} as m ON c.message_id = m.ROWID WHERE m.handle_id IS NOT NULL"
+            ))
+            .map_err(TableError::ChatToHandle)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(TableError::ChatToHandle)?;
+
+        for row in rows {
+            let (chat_id, handle_id): (i32, i32) = row.map_err(TableError::ChatToHandle)?;
+            cache.entry(chat_id).or_default().insert(handle_id);
+        }
+
+        Ok(cache)
+    }
+}
+
 impl Deduplicate for ChatToHandle {
     type T = BTreeSet<i32>;
 
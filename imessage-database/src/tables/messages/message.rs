@@ -635,7 +635,7 @@ impl Message {
     pub fn get_count(db: &Connection, context: &QueryContext) -> Result<u64, TableError> {
         let mut statement = if context.has_filters() {
             db.prepare(&format!(
-                "SELECT COUNT(*) FROM {MESSAGE} as m {}",
+                "SELECT COUNT(*) FROM {MESSAGE} as m LEFT JOIN {CHAT_MESSAGE_JOIN} as c ON m.ROWID = c.message_id {}",
                 context.generate_filter_statement("m.date")
             ))
             .map_err(TableError::Messages)?
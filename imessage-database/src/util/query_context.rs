@@ -1,13 +1,73 @@
 /*!
  Contains logic for handling query filter configurations.
 */
-use chrono::prelude::*;
+use std::collections::BTreeSet;
+
+use chrono::{prelude::*, Duration, Months};
+use chrono_tz::Tz;
 
 use crate::{
     error::query_context::QueryContextError,
+    tables::table::MESSAGE_ATTACHMENT_JOIN,
     util::dates::{get_offset, TIMESTAMP_FACTOR},
 };
 
+/// Kinds of messages that can be excluded from a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageKind {
+    /// [`Tapback`](crate::message_types::variants::Variant::Tapback) messages and stickers placed on other messages
+    Tapback,
+    /// Messages that describe a change to a conversation, like a new name or group photo
+    Announcement,
+    /// [`App`](crate::message_types::variants::Variant::App) messages, including link previews
+    App,
+    /// [`SharePlay`](crate::message_types::variants::Variant::SharePlay) messages
+    SharePlay,
+    /// [`Edited`](crate::message_types::variants::Variant::Edited) messages, including messages that were unsent
+    Edited,
+    /// Messages with at least one attachment
+    WithAttachments,
+    /// Messages without any attachments
+    WithoutAttachments,
+}
+
+impl MessageKind {
+    /// Given the name of a kind of message, return a variant if the name matches one
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::MessageKind;
+    ///
+    /// assert_eq!(MessageKind::from_name("tapbacks"), Some(MessageKind::Tapback));
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "tapbacks" => Some(Self::Tapback),
+            "announcements" => Some(Self::Announcement),
+            "apps" => Some(Self::App),
+            "shareplay" => Some(Self::SharePlay),
+            "edited" => Some(Self::Edited),
+            "with-attachments" => Some(Self::WithAttachments),
+            "without-attachments" => Some(Self::WithoutAttachments),
+            _ => None,
+        }
+    }
+
+    /// Generate the SQL condition that matches messages of this kind in the `message` table aliased as `m`
+    fn generate_condition(&self) -> String {
+        match self {
+            MessageKind::Tapback => "(COALESCE(m.associated_message_type, 0) BETWEEN 2000 AND 3999 OR (m.associated_message_type = 1000 AND m.associated_message_guid IS NOT NULL))".to_string(),
+            MessageKind::Announcement => "(m.group_title IS NOT NULL OR COALESCE(m.group_action_type, 0) != 0)".to_string(),
+            MessageKind::App => "(COALESCE(m.balloon_bundle_id, '') != '' AND COALESCE(m.associated_message_type, 0) IN (0, 2, 3))".to_string(),
+            MessageKind::SharePlay => "m.item_type = 6".to_string(),
+            MessageKind::Edited => "COALESCE(m.date_edited, 0) != 0".to_string(),
+            MessageKind::WithAttachments => format!("EXISTS (SELECT 1 FROM {MESSAGE_ATTACHMENT_JOIN} j WHERE j.message_id = m.ROWID)"),
+            MessageKind::WithoutAttachments => format!("NOT EXISTS (SELECT 1 FROM {MESSAGE_ATTACHMENT_JOIN} j WHERE j.message_id = m.ROWID)"),
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 /// Represents filter configurations for a SQL query.
 pub struct QueryContext {
@@ -15,10 +75,24 @@ pub struct QueryContext {
     pub start: Option<i64>,
     /// The end date filter. Only messages sent before this date will be included.
     pub end: Option<i64>,
+    /// The chat filter. Only messages in chats with these IDs will be included.
+    pub selected_chat_ids: Option<BTreeSet<i32>>,
+    /// The chat exclusion filter. Messages in chats with these IDs will not be included.
+    pub excluded_chat_ids: BTreeSet<i32>,
+    /// The sender exclusion filter. Messages sent by handles with these IDs will not be included.
+    pub excluded_handle_ids: BTreeSet<i32>,
+    /// The message kind exclusion filter. Messages of these kinds will not be included.
+    pub excluded_kinds: BTreeSet<MessageKind>,
+    /// The message `ROWID` filter. Only messages with a greater `ROWID` will be included.
+    pub after_rowid: Option<i32>,
+    /// The edit date filter. Only messages edited after this date will be included, or also messages that pass the `ROWID` filter if one is set.
+    pub edited_after: Option<i64>,
 }
 
 impl QueryContext {
-    /// Generate a `QueryContext` with a start date
+    /// Generate a `QueryContext` with a start date that includes the time it describes
+    ///
+    /// See [`QueryContext::parse_date`] for the formats `start` can use.
     /// # Example:
     ///
     /// ```
@@ -28,13 +102,33 @@ impl QueryContext {
     /// context.set_start("2023-01-01");
     /// ```
     pub fn set_start(&mut self, start: &str) -> Result<(), QueryContextError> {
-        let timestamp = QueryContext::sanitize_date(start)
+        let (begin, _) = QueryContext::parse_date(start, &Local::now())
+            .ok_or(QueryContextError::InvalidDate(start.to_string()))?;
+        self.start = Some(begin);
+        Ok(())
+    }
+
+    /// Generate a `QueryContext` with a start date that excludes the time it describes
+    ///
+    /// For example, an exclusive start of `2023-01-01` only includes messages sent on or after `2023-01-02`.
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_start_exclusive("2023-01-01T09:30:00Z");
+    /// ```
+    pub fn set_start_exclusive(&mut self, start: &str) -> Result<(), QueryContextError> {
+        let (_, end) = QueryContext::parse_date(start, &Local::now())
             .ok_or(QueryContextError::InvalidDate(start.to_string()))?;
-        self.start = Some(timestamp);
+        self.start = Some(end);
         Ok(())
     }
 
-    /// Generate a `QueryContext` with an end date
+    /// Generate a `QueryContext` with an end date that excludes the time it describes
+    ///
+    /// See [`QueryContext::parse_date`] for the formats `end` can use.
     /// # Example:
     ///
     /// ```
@@ -44,42 +138,265 @@ impl QueryContext {
     /// context.set_end("2023-01-01");
     /// ```
     pub fn set_end(&mut self, end: &str) -> Result<(), QueryContextError> {
-        let timestamp = QueryContext::sanitize_date(end)
+        let (begin, _) = QueryContext::parse_date(end, &Local::now())
             .ok_or(QueryContextError::InvalidDate(end.to_string()))?;
-        self.end = Some(timestamp);
+        self.end = Some(begin);
         Ok(())
     }
 
-    /// Ensure a date string is valid
-    fn sanitize_date(date: &str) -> Option<i64> {
-        if date.len() < 9 {
-            return None;
-        }
+    /// Generate a `QueryContext` with an end date that includes the time it describes
+    ///
+    /// For example, an inclusive end of `2023-01-01` includes messages sent at any time on `2023-01-01`.
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_end_inclusive("last-month");
+    /// ```
+    pub fn set_end_inclusive(&mut self, end: &str) -> Result<(), QueryContextError> {
+        let (_, end_of_span) = QueryContext::parse_date(end, &Local::now())
+            .ok_or(QueryContextError::InvalidDate(end.to_string()))?;
+        self.end = Some(end_of_span);
+        Ok(())
+    }
 
-        let year = date.get(0..4)?.parse::<i32>().ok()?;
+    /// Generate a `QueryContext` that only includes messages from the given chats
+    ///
+    /// Chat IDs are the `ROWID`s of the `chat` table; an empty set excludes every message.
+    /// # Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
+    /// ```
+    pub fn set_selected_chat_ids(&mut self, selected_chat_ids: BTreeSet<i32>) {
+        self.selected_chat_ids = Some(selected_chat_ids);
+    }
 
-        if !date.get(4..5)?.eq("-") {
-            return None;
-        }
+    /// Generate a `QueryContext` that excludes messages from the given chats
+    ///
+    /// Chat IDs are the `ROWID`s of the `chat` table.
+    /// # Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_excluded_chat_ids(BTreeSet::from([1, 2, 3]));
+    /// ```
+    pub fn set_excluded_chat_ids(&mut self, excluded_chat_ids: BTreeSet<i32>) {
+        self.excluded_chat_ids = excluded_chat_ids;
+    }
 
-        let month = date.get(5..7)?.parse::<u32>().ok()?;
-        if month > 12 {
-            return None;
+    /// Generate a `QueryContext` that excludes messages sent by the given handles
+    ///
+    /// Handle IDs are the `ROWID`s of the `handle` table; messages sent by the database owner are never excluded.
+    /// # Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_excluded_handle_ids(BTreeSet::from([1, 2, 3]));
+    /// ```
+    pub fn set_excluded_handle_ids(&mut self, excluded_handle_ids: BTreeSet<i32>) {
+        self.excluded_handle_ids = excluded_handle_ids;
+    }
+
+    /// Generate a `QueryContext` that only includes messages added after the message with the given `ROWID`
+    ///
+    /// New messages always have a greater `ROWID`, so this selects the messages added since a previous read of the table.
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_after_rowid(1000);
+    /// ```
+    pub fn set_after_rowid(&mut self, rowid: i32) {
+        self.after_rowid = Some(rowid);
+    }
+
+    /// Generate a `QueryContext` that only includes messages edited or unsent after the given date
+    ///
+    /// When combined with [`QueryContext::set_after_rowid`], messages that were added or edited since a previous read of the table are included.
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_after_rowid(1000);
+    /// context.set_edited_after(674526582885055488);
+    /// ```
+    pub fn set_edited_after(&mut self, date: i64) {
+        self.edited_after = Some(date);
+    }
+
+    /// Generate a `QueryContext` that excludes a kind of message, by the name used in [`MessageKind::from_name`]
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_excluded_kind("tapbacks");
+    /// ```
+    pub fn set_excluded_kind(&mut self, kind: &str) -> Result<(), QueryContextError> {
+        let kind = MessageKind::from_name(kind)
+            .ok_or(QueryContextError::InvalidMessageKind(kind.to_string()))?;
+        self.excluded_kinds.insert(kind);
+        Ok(())
+    }
+
+    /// Resolve a date expression to the span of time it describes, relative to `now`
+    ///
+    /// Returns the first timestamp in the span and the first timestamp after it, in nanoseconds since the iMessage epoch.
+    /// Supported expressions are:
+    ///
+    /// - A calendar day, i.e. `2023-01-01`, which spans the whole day in local time
+    /// - A local time, i.e. `2023-01-01T09:30` or `2023-01-01 09:30:15`
+    /// - An [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339) timestamp, i.e. `2023-01-01T09:30:00-08:00` or `2023-01-01T17:30:00Z`
+    /// - A calendar day or time followed by an IANA time zone, i.e. `2023-01-01 09:30 America/New_York`
+    /// - A duration before `now` in hours, days, weeks, months, or years, i.e. `12h`, `7d`, `2w`, `3mo`, or `1y`
+    /// - `now`, `today`, `yesterday`, `this-week`, `last-week`, `this-month`, `last-month`, `this-year`, or `last-year`,
+    ///   where weeks start on Monday and every span is in local time
+    ///
+    /// Times and durations span a single nanosecond.
+    pub fn parse_date(date: &str, now: &DateTime<Local>) -> Option<(i64, i64)> {
+        let date = date.trim();
+        let (begin, end) = match QueryContext::parse_relative(date, now) {
+            Some(span) => span,
+            None => match date.rsplit_once(' ') {
+                // Dates and times can be followed by an IANA time zone
+                Some((local, zone)) => match zone.parse::<Tz>() {
+                    Ok(tz) => QueryContext::parse_absolute(local, &tz)?,
+                    Err(_) => QueryContext::parse_absolute(date, &Local)?,
+                },
+                None => QueryContext::parse_absolute(date, &Local)?,
+            },
+        };
+
+        let offset = get_offset() * TIMESTAMP_FACTOR;
+        Some((
+            begin.timestamp_nanos_opt()? - offset,
+            end.timestamp_nanos_opt()? - offset,
+        ))
+    }
+
+    /// Resolve a calendar day, local time, or RFC 3339 timestamp to the span of time it describes
+    fn parse_absolute<T: TimeZone>(date: &str, tz: &T) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        if let Some(day) = QueryContext::parse_day(date) {
+            return Some((
+                QueryContext::midnight(day, tz)?,
+                QueryContext::midnight(day.succ_opt()?, tz)?,
+            ));
         }
 
-        if !date.get(7..8)?.eq("-") {
-            return None;
+        // Allow a space between the date and the time
+        let date = match date.get(10..11) {
+            Some(" ") => format!("{}T{}", &date[..10], &date[11..]),
+            _ => date.to_string(),
+        };
+
+        let instant = match DateTime::parse_from_rfc3339(&date) {
+            Ok(instant) => instant.with_timezone(&Utc),
+            // RFC 3339 requires seconds, but times with an offset can omit them
+            Err(_) => {
+                match DateTime::parse_from_str(&date.replace('Z', "+00:00"), "%Y-%m-%dT%H:%M%:z") {
+                    Ok(instant) => instant.with_timezone(&Utc),
+                    Err(_) => {
+                        let local = NaiveDateTime::parse_from_str(&date, "%Y-%m-%dT%H:%M:%S%.f")
+                            .or_else(|_| NaiveDateTime::parse_from_str(&date, "%Y-%m-%dT%H:%M"))
+                            .ok()?;
+                        // Reject single digit fields, which `chrono` accepts
+                        QueryContext::parse_day(date.get(..10)?)?;
+                        tz.from_local_datetime(&local)
+                            .earliest()?
+                            .with_timezone(&Utc)
+                    }
+                }
+            }
+        };
+
+        Some((instant, instant + Duration::nanoseconds(1)))
+    }
+
+    /// Resolve a duration before `now` or the name of a calendar period to the span of time it describes
+    fn parse_relative(date: &str, now: &DateTime<Local>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let today = now.date_naive();
+        let instant = |instant: DateTime<Local>| {
+            let instant = instant.with_timezone(&Utc);
+            Some((instant, instant + Duration::nanoseconds(1)))
+        };
+        let days = |first: NaiveDate, last: NaiveDate| {
+            Some((
+                QueryContext::midnight(first, &Local)?,
+                QueryContext::midnight(last, &Local)?,
+            ))
+        };
+
+        let week = today - Duration::days(today.weekday().num_days_from_monday().into());
+        let month = today.with_day(1)?;
+        let year = month.with_month(1)?;
+
+        match date.to_lowercase().as_str() {
+            "now" => instant(*now),
+            "today" => days(today, today.succ_opt()?),
+            "yesterday" => days(today.pred_opt()?, today),
+            "this-week" => days(week, week + Duration::days(7)),
+            "last-week" => days(week - Duration::days(7), week),
+            "this-month" => days(month, month.checked_add_months(Months::new(1))?),
+            "last-month" => days(month.checked_sub_months(Months::new(1))?, month),
+            "this-year" => days(year, year.checked_add_months(Months::new(12))?),
+            "last-year" => days(year.checked_sub_months(Months::new(12))?, year),
+            duration => {
+                let unit_start = duration.find(|c: char| !c.is_ascii_digit())?;
+                let (amount, unit) = duration.split_at(unit_start);
+                let amount = amount.parse::<u32>().ok()?;
+                // Amounts too large to subtract from `now` are rejected instead of overflowing
+                let before = |delta: Option<Duration>| now.checked_sub_signed(delta?);
+                match unit {
+                    "h" => instant(before(Duration::try_hours(amount.into()))?),
+                    "d" => instant(before(Duration::try_days(amount.into()))?),
+                    "w" => instant(before(Duration::try_weeks(amount.into()))?),
+                    "mo" => instant(now.checked_sub_months(Months::new(amount))?),
+                    "y" => instant(now.checked_sub_months(Months::new(amount.checked_mul(12)?))?),
+                    _ => None,
+                }
+            }
         }
+    }
 
-        let day = date.get(8..)?.parse::<u32>().ok()?;
-        if day > 31 {
+    /// Parse a calendar day in the format `YYYY-MM-DD`
+    fn parse_day(date: &str) -> Option<NaiveDate> {
+        let is_day = date.len() == 10
+            && date.char_indices().all(|(idx, c)| {
+                if idx == 4 || idx == 7 {
+                    c == '-'
+                } else {
+                    c.is_ascii_digit()
+                }
+            });
+        if !is_day {
             return None;
         }
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+    }
 
-        let local = Local.with_ymd_and_hms(year, month, day, 0, 0, 0).single()?;
-        let stamp = local.timestamp_nanos_opt().unwrap_or(0);
-
-        Some(stamp - (get_offset() * TIMESTAMP_FACTOR))
+    /// Get the first instant of a calendar day in a time zone
+    fn midnight<T: TimeZone>(day: NaiveDate, tz: &T) -> Option<DateTime<Utc>> {
+        tz.from_local_datetime(&day.and_hms_opt(0, 0, 0)?)
+            .earliest()
+            .map(|midnight| midnight.with_timezone(&Utc))
     }
 
     /// Determine if the current `QueryContext` has any filters present
@@ -95,10 +412,78 @@ impl QueryContext {
     /// assert!(context.has_filters());
     /// ```
     pub fn has_filters(&self) -> bool {
-        [self.start, self.end].iter().any(Option::is_some)
+        [self.start, self.end].iter().any(Option::is_some) || self.has_message_filters()
+    }
+
+    /// Determine if the current `QueryContext` has any filters present other than dates
+    pub fn has_message_filters(&self) -> bool {
+        self.selected_chat_ids.is_some()
+            || !self.excluded_chat_ids.is_empty()
+            || !self.excluded_handle_ids.is_empty()
+            || !self.excluded_kinds.is_empty()
+            || self.after_rowid.is_some()
+            || self.edited_after.is_some()
+    }
+
+    /// Generate the SQL conditions described by the filters of this `QueryContext` other than dates
+    ///
+    /// Conditions apply to the `message` table aliased as `m` and the `chat_message_join` table aliased as `c`.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_selected_chat_ids(BTreeSet::from([1, 2]));
+    /// assert_eq!(context.generate_message_filters(), vec!["c.chat_id IN (1, 2)"]);
+    /// ```
+    pub fn generate_message_filters(&self) -> Vec<String> {
+        let mut filters = vec![];
+        if let Some(chat_ids) = &self.selected_chat_ids {
+            filters.push(format!(
+                "c.chat_id IN ({})",
+                QueryContext::id_list(chat_ids)
+            ));
+        }
+        if !self.excluded_chat_ids.is_empty() {
+            filters.push(format!(
+                "(c.chat_id IS NULL OR c.chat_id NOT IN ({}))",
+                QueryContext::id_list(&self.excluded_chat_ids)
+            ));
+        }
+        if !self.excluded_handle_ids.is_empty() {
+            filters.push(format!(
+                "(m.is_from_me = 1 OR COALESCE(m.handle_id, 0) NOT IN ({}))",
+                QueryContext::id_list(&self.excluded_handle_ids)
+            ));
+        }
+        for kind in &self.excluded_kinds {
+            filters.push(format!("NOT {}", kind.generate_condition()));
+        }
+        match (self.after_rowid, self.edited_after) {
+            (Some(rowid), Some(date)) => {
+                filters.push(format!("(m.ROWID > {rowid} OR m.date_edited > {date})"));
+            }
+            (Some(rowid), None) => filters.push(format!("m.ROWID > {rowid}")),
+            (None, Some(date)) => filters.push(format!("m.date_edited > {date}")),
+            (None, None) => {}
+        }
+        filters
+    }
+
+    /// Join a set of IDs into a list for a SQL `IN` condition
+    fn id_list(ids: &BTreeSet<i32>) -> String {
+        ids.iter()
+            .map(i32::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
     }
 
     /// Generate the SQL `WHERE` clause described by this `QueryContext`
+    ///
+    /// Date filters apply to `field`; see [`QueryContext::generate_message_filters`] for the tables other filters apply to.
     /// # Example:
     ///
     /// ```
@@ -117,7 +502,13 @@ impl QueryContext {
             if !filters.is_empty() {
                 filters.push_str(" AND ");
             }
-            filters.push_str(&format!("    {field} <= {end}"));
+            filters.push_str(&format!("    {field} < {end}"));
+        }
+        for filter in self.generate_message_filters() {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            filters.push_str(&format!("    {filter}"));
         }
 
         if !filters.is_empty() {
@@ -132,7 +523,7 @@ impl QueryContext {
 
 #[cfg(test)]
 mod use_tests {
-    use std::env::set_var;
+    use std::{collections::BTreeSet, env::set_var};
 
     use chrono::prelude::*;
 
@@ -146,6 +537,12 @@ mod use_tests {
         let context = QueryContext::default();
         assert!(context.start.is_none());
         assert!(context.end.is_none());
+        assert!(context.selected_chat_ids.is_none());
+        assert!(context.excluded_chat_ids.is_empty());
+        assert!(context.excluded_handle_ids.is_empty());
+        assert!(context.excluded_kinds.is_empty());
+        assert!(context.after_rowid.is_none());
+        assert!(context.edited_after.is_none());
         assert!(!context.has_filters());
     }
 
@@ -192,7 +589,7 @@ mod use_tests {
         assert_eq!(format(&Ok(local)), "Jan 01, 2020 12:00:00 AM");
         assert_eq!(
             context.generate_filter_statement("m.date"),
-            " WHERE\n                     m.date <= 599558400000000000"
+            " WHERE\n                     m.date < 599558400000000000"
         );
         assert!(context.start.is_none());
         assert!(context.end.is_some());
@@ -226,13 +623,175 @@ mod use_tests {
         assert_eq!(format(&Ok(local_end)), "Feb 02, 2020 12:00:00 AM");
         assert_eq!(
             context.generate_filter_statement("m.date"),
-            " WHERE\n                     m.date >= 599558400000000000 AND     m.date <= 602323200000000000"
+            " WHERE\n                     m.date >= 599558400000000000 AND     m.date < 602323200000000000"
         );
         assert!(context.start.is_some());
         assert!(context.end.is_some());
         assert!(context.has_filters());
     }
 
+    #[test]
+    fn can_create_exclusive_start() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let mut context = QueryContext::default();
+        context.set_start_exclusive("2020-01-01").unwrap();
+
+        // Messages sent on the start date are excluded
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     m.date >= 599644800000000000"
+        );
+    }
+
+    #[test]
+    fn can_create_inclusive_end() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let mut context = QueryContext::default();
+        context.set_end_inclusive("2020-01-01").unwrap();
+
+        // Messages sent on the end date are included
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     m.date < 599644800000000000"
+        );
+    }
+
+    #[test]
+    fn can_create_exact_window() {
+        let mut context = QueryContext::default();
+        context.set_start_exclusive("2020-01-01T17:30:00Z").unwrap();
+        context.set_end_inclusive("2020-01-01T18:00:00Z").unwrap();
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     m.date >= 599592600000000001 AND     m.date < 599594400000000001"
+        );
+    }
+
+    #[test]
+    fn can_create_chats() {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([3, 1, 2]));
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     c.chat_id IN (1, 2, 3)"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_empty_chats() {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::new());
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     c.chat_id IN ()"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_dates_and_chats() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let mut context = QueryContext::default();
+        context.set_start("2020-01-01").unwrap();
+        context.set_selected_chat_ids(BTreeSet::from([4]));
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     m.date >= 599558400000000000 AND     c.chat_id IN (4)"
+        );
+    }
+
+    #[test]
+    fn can_create_excluded_chats() {
+        let mut context = QueryContext::default();
+        context.set_excluded_chat_ids(BTreeSet::from([2, 1]));
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     (c.chat_id IS NULL OR c.chat_id NOT IN (1, 2))"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_excluded_handles() {
+        let mut context = QueryContext::default();
+        context.set_excluded_handle_ids(BTreeSet::from([5]));
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     (m.is_from_me = 1 OR COALESCE(m.handle_id, 0) NOT IN (5))"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_excluded_kinds() {
+        let mut context = QueryContext::default();
+        context.set_excluded_kind("SharePlay").unwrap();
+        context.set_excluded_kind("edited").unwrap();
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     NOT m.item_type = 6 AND     NOT COALESCE(m.date_edited, 0) != 0"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_after_rowid() {
+        let mut context = QueryContext::default();
+        context.set_after_rowid(1000);
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     m.ROWID > 1000"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_edited_after() {
+        let mut context = QueryContext::default();
+        context.set_edited_after(674526582885055488);
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     m.date_edited > 674526582885055488"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_after_rowid_or_edited_after() {
+        let mut context = QueryContext::default();
+        context.set_after_rowid(1000);
+        context.set_edited_after(674526582885055488);
+
+        assert_eq!(
+            context.generate_filter_statement("m.date"),
+            " WHERE\n                     (m.ROWID > 1000 OR m.date_edited > 674526582885055488)"
+        );
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn can_create_invalid_kind() {
+        let mut context = QueryContext::default();
+        assert!(context.set_excluded_kind("stickers").is_err());
+        assert!(!context.has_filters());
+    }
+
     #[test]
     fn can_create_invalid_start() {
         let mut context = QueryContext::default();
@@ -251,48 +810,177 @@ mod use_tests {
 }
 
 #[cfg(test)]
-mod sanitize_tests {
+mod parse_tests {
+    use std::env::set_var;
+
+    use chrono::prelude::*;
+
     use crate::util::query_context::QueryContext;
 
+    /// Wednesday, May 17, 2023 3:30 PM
+    fn now() -> DateTime<Local> {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+        Local.with_ymd_and_hms(2023, 5, 17, 15, 30, 0).unwrap()
+    }
+
+    /// Parse a date expression relative to [`now`]
+    fn parse(date: &str) -> (i64, i64) {
+        QueryContext::parse_date(date, &now()).unwrap()
+    }
+
     #[test]
-    fn can_sanitize_good() {
-        let res = QueryContext::sanitize_date("2020-01-01");
+    fn can_parse_good() {
+        let res = QueryContext::parse_date("2020-01-01", &now());
         assert!(res.is_some());
     }
 
+    #[test]
+    fn can_parse_day() {
+        assert_eq!(
+            parse("2020-01-01"),
+            (599558400000000000, 599644800000000000)
+        );
+    }
+
+    #[test]
+    fn can_parse_local_time() {
+        assert_eq!(
+            parse("2020-01-01T09:30"),
+            (599592600000000000, 599592600000000001)
+        );
+        assert_eq!(parse("2020-01-01 09:30:00"), parse("2020-01-01T09:30"));
+        assert_eq!(parse("2020-01-01T09:30:15.5").0, 599592615500000000);
+    }
+
+    #[test]
+    fn can_parse_rfc3339() {
+        assert_eq!(
+            parse("2020-01-01T09:30:00-08:00"),
+            parse("2020-01-01T09:30")
+        );
+        assert_eq!(parse("2020-01-01T17:30:00Z"), parse("2020-01-01T09:30"));
+        assert_eq!(
+            parse("2020-01-01 17:30:00+00:00"),
+            parse("2020-01-01T09:30")
+        );
+    }
+
+    #[test]
+    fn can_parse_offset_without_seconds() {
+        assert_eq!(parse("2020-01-01T17:30Z"), parse("2020-01-01T09:30"));
+        assert_eq!(parse("2020-01-01T12:30-05:00"), parse("2020-01-01T09:30"));
+    }
+
+    #[test]
+    fn can_parse_time_zone() {
+        assert_eq!(
+            parse("2020-01-01 12:30 America/New_York"),
+            parse("2020-01-01T09:30")
+        );
+        assert_eq!(
+            parse("2020-01-01 America/New_York"),
+            (599547600000000000, 599634000000000000)
+        );
+        assert_eq!(parse("2020-01-01 17:30:00 UTC"), parse("2020-01-01T09:30"));
+    }
+
+    #[test]
+    fn can_parse_durations() {
+        assert_eq!(parse("12h"), parse("2023-05-17T03:30"));
+        assert_eq!(parse("7d"), parse("2023-05-10T15:30"));
+        assert_eq!(parse("2w"), parse("2023-05-03T15:30"));
+        assert_eq!(parse("3mo"), parse("2023-02-17T15:30"));
+        assert_eq!(parse("1y"), parse("2022-05-17T15:30"));
+        assert_eq!(parse("now"), parse("2023-05-17T15:30"));
+    }
+
+    #[test]
+    fn can_parse_periods() {
+        assert_eq!(parse("today"), parse("2023-05-17"));
+        assert_eq!(parse("Yesterday"), parse("2023-05-16"));
+        assert_eq!(
+            parse("this-week"),
+            (parse("2023-05-15").0, parse("2023-05-22").0)
+        );
+        assert_eq!(
+            parse("last-week"),
+            (parse("2023-05-08").0, parse("2023-05-15").0)
+        );
+        assert_eq!(
+            parse("this-month"),
+            (parse("2023-05-01").0, parse("2023-06-01").0)
+        );
+        assert_eq!(
+            parse("last-month"),
+            (parse("2023-04-01").0, parse("2023-05-01").0)
+        );
+        assert_eq!(
+            parse("this-year"),
+            (parse("2023-01-01").0, parse("2024-01-01").0)
+        );
+        assert_eq!(
+            parse("last-year"),
+            (parse("2022-01-01").0, parse("2023-01-01").0)
+        );
+    }
+
+    #[test]
+    fn can_reject_bad_duration() {
+        assert!(QueryContext::parse_date("7x", &now()).is_none());
+        assert!(QueryContext::parse_date("d", &now()).is_none());
+        assert!(QueryContext::parse_date("-7d", &now()).is_none());
+        assert!(QueryContext::parse_date("next-month", &now()).is_none());
+    }
+
+    #[test]
+    fn can_reject_out_of_range_duration() {
+        assert!(QueryContext::parse_date("4000000000h", &now()).is_none());
+        assert!(QueryContext::parse_date("3000000000d", &now()).is_none());
+        assert!(QueryContext::parse_date("400000000w", &now()).is_none());
+        assert!(QueryContext::default().set_start("3000000000d").is_err());
+    }
+
+    #[test]
+    fn can_reject_bad_time() {
+        assert!(QueryContext::parse_date("2020-01-01T25:00", &now()).is_none());
+        assert!(QueryContext::parse_date("2020-1-1T09:30", &now()).is_none());
+        assert!(QueryContext::parse_date("2020-01-01 09:30 Mars/Olympus_Mons", &now()).is_none());
+    }
+
     #[test]
     fn can_reject_bad_short() {
-        let res = QueryContext::sanitize_date("1-1-20");
+        let res = QueryContext::parse_date("1-1-20", &now());
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_order() {
-        let res = QueryContext::sanitize_date("01-01-2020");
+        let res = QueryContext::parse_date("01-01-2020", &now());
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_month() {
-        let res = QueryContext::sanitize_date("2020-31-01");
+        let res = QueryContext::parse_date("2020-31-01", &now());
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_day() {
-        let res = QueryContext::sanitize_date("2020-01-32");
+        let res = QueryContext::parse_date("2020-01-32", &now());
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_bad_data() {
-        let res = QueryContext::sanitize_date("2020-AB-CD");
+        let res = QueryContext::parse_date("2020-AB-CD", &now());
         assert!(res.is_none());
     }
 
     #[test]
     fn can_reject_wrong_hyphen() {
-        let res = QueryContext::sanitize_date("2020–01–01");
+        let res = QueryContext::parse_date("2020–01–01", &now());
         assert!(res.is_none());
     }
 }
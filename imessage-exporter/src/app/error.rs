@@ -19,6 +19,9 @@ pub enum RuntimeError {
     CreateError(IoError, PathBuf),
     DiskError(IoError),
     DatabaseError(TableError),
+    ExportDatabaseError(rusqlite::Error),
+    ParquetError(parquet::errors::ParquetError),
+    IndexError(tantivy::TantivyError),
     NotEnoughAvailableSpace(u64, u64),
 }
 
@@ -29,6 +32,11 @@ impl Display for RuntimeError {
             RuntimeError::CreateError(why, path) => write!(fmt, "{why}: {path:?}"),
             RuntimeError::DiskError(why) => write!(fmt, "{why}"),
             RuntimeError::DatabaseError(why) => write!(fmt, "{why}"),
+            RuntimeError::ExportDatabaseError(why) => {
+                write!(fmt, "Failed to write export database: {why}")
+            }
+            RuntimeError::ParquetError(why) => write!(fmt, "Failed to write Parquet file: {why}"),
+            RuntimeError::IndexError(why) => write!(fmt, "Failed to use search index: {why}"),
             RuntimeError::NotEnoughAvailableSpace(estimated_bytes, available_bytes) => {
                 write!(
                     fmt, 
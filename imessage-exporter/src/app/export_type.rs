@@ -2,7 +2,12 @@
  Contains data structures used to describe export types.
 */
 
-use std::fmt::Display;
+use std::{fmt::Display, path::Path};
+
+use crate::exporters::{
+    slack::{CHANNELS, USERS},
+    telegram::{FRAGMENTS, RESULT},
+};
 
 /// Represents the type of file to export iMessage data into
 #[derive(PartialEq, Eq, Debug)]
@@ -11,6 +16,36 @@ pub enum ExportType {
     Html,
     /// Text file export
     Txt,
+    /// JSON file export, one array of messages per conversation
+    Json,
+    /// Newline-delimited JSON file export, one message per line
+    Ndjson,
+    /// Comma-separated values file export, one row per message
+    Csv,
+    /// Markdown file export
+    Markdown,
+    /// Android SMS Backup & Restore XML file export
+    Xml,
+    /// Normalized SQLite database export
+    Sqlite,
+    /// Apache Parquet columnar file export
+    Parquet,
+    /// RFC 5322 email export, one mbox file per conversation
+    Mbox,
+    /// Telegram Desktop `result.json` export
+    Telegram,
+    /// Slack workspace export, one directory of daily message files per conversation
+    Slack,
+    /// vCard file export of every conversation participant
+    Contacts,
+    /// GeoJSON export of every shared location
+    Geojson,
+    /// Keyhole Markup Language export of every shared location
+    Kml,
+    /// GPS Exchange Format export of every shared location with coordinates
+    Gpx,
+    /// HTML and CSV indexes of every shared link
+    Links,
 }
 
 impl ExportType {
@@ -19,9 +54,75 @@ impl ExportType {
         match platform.to_lowercase().as_str() {
             "txt" => Some(Self::Txt),
             "html" => Some(Self::Html),
+            "json" => Some(Self::Json),
+            "ndjson" => Some(Self::Ndjson),
+            "csv" => Some(Self::Csv),
+            "md" => Some(Self::Markdown),
+            "xml" => Some(Self::Xml),
+            "sqlite" => Some(Self::Sqlite),
+            "parquet" => Some(Self::Parquet),
+            "mbox" => Some(Self::Mbox),
+            "telegram" => Some(Self::Telegram),
+            "slack" => Some(Self::Slack),
+            "contacts" => Some(Self::Contacts),
+            "geojson" => Some(Self::Geojson),
+            "kml" => Some(Self::Kml),
+            "gpx" => Some(Self::Gpx),
+            "links" => Some(Self::Links),
             _ => None,
         }
     }
+
+    /// The extension of the files written by the export
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportType::Txt => "txt",
+            ExportType::Html => "html",
+            ExportType::Json => "json",
+            ExportType::Ndjson => "ndjson",
+            ExportType::Csv => "csv",
+            ExportType::Markdown => "md",
+            ExportType::Xml => "xml",
+            ExportType::Sqlite => "sqlite",
+            ExportType::Parquet => "parquet",
+            ExportType::Mbox => "mbox",
+            ExportType::Telegram => "json",
+            ExportType::Slack => "json",
+            ExportType::Contacts => "vcf",
+            ExportType::Geojson => "geojson",
+            ExportType::Kml => "kml",
+            ExportType::Gpx => "gpx",
+            // Links exports write `html` pages alongside `csv` files
+            ExportType::Links => "html",
+        }
+    }
+
+    /// Determine if a file or directory in the export path holds data this export would overwrite
+    pub fn would_overwrite(&self, path: &Path) -> bool {
+        match self {
+            // Telegram exports are written to a single file, built from fragments staged in a hidden directory
+            ExportType::Telegram => path
+                .file_name()
+                .is_some_and(|name| name == RESULT || name == FRAGMENTS),
+            // Slack exports write workspace files alongside a directory of daily files for each channel
+            ExportType::Slack => {
+                path.file_name()
+                    .is_some_and(|name| name == CHANNELS || name == USERS)
+                    || path.read_dir().is_ok_and(|mut files| {
+                        files.any(|file| {
+                            file.is_ok_and(|file| ExportType::Json.would_overwrite(&file.path()))
+                        })
+                    })
+            }
+            // Links exports write `html` pages alongside `csv` files, named after each conversation
+            ExportType::Links => {
+                ExportType::Html.would_overwrite(path) || ExportType::Csv.would_overwrite(path)
+            }
+            _ => path
+                .extension()
+                .is_some_and(|extension| extension == self.extension()),
+        }
+    }
 }
 
 impl Display for ExportType {
@@ -29,12 +130,32 @@ impl Display for ExportType {
         match self {
             ExportType::Txt => write!(fmt, "txt"),
             ExportType::Html => write!(fmt, "html"),
+            ExportType::Json => write!(fmt, "json"),
+            ExportType::Ndjson => write!(fmt, "ndjson"),
+            ExportType::Csv => write!(fmt, "csv"),
+            ExportType::Markdown => write!(fmt, "md"),
+            ExportType::Xml => write!(fmt, "xml"),
+            ExportType::Sqlite => write!(fmt, "sqlite"),
+            ExportType::Parquet => write!(fmt, "parquet"),
+            ExportType::Mbox => write!(fmt, "mbox"),
+            ExportType::Telegram => write!(fmt, "telegram"),
+            ExportType::Slack => write!(fmt, "slack"),
+            ExportType::Contacts => write!(fmt, "contacts"),
+            ExportType::Geojson => write!(fmt, "geojson"),
+            ExportType::Kml => write!(fmt, "kml"),
+            ExportType::Gpx => write!(fmt, "gpx"),
+            ExportType::Links => write!(fmt, "links"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all, File},
+        path::{Path, PathBuf},
+    };
+
     use crate::app::export_type::ExportType;
 
     #[test]
@@ -60,10 +181,249 @@ mod tests {
         assert!(matches!(ExportType::from_cli("tXt"), Some(ExportType::Txt)));
     }
 
+    #[test]
+    fn can_parse_json_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("json"),
+            Some(ExportType::Json)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("JSON"),
+            Some(ExportType::Json)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("NdJson"),
+            Some(ExportType::Ndjson)
+        ));
+    }
+
+    #[test]
+    fn can_parse_csv_any_case() {
+        assert!(matches!(ExportType::from_cli("csv"), Some(ExportType::Csv)));
+        assert!(matches!(ExportType::from_cli("CSV"), Some(ExportType::Csv)));
+        assert!(matches!(ExportType::from_cli("cSv"), Some(ExportType::Csv)));
+    }
+
+    #[test]
+    fn can_parse_md_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("md"),
+            Some(ExportType::Markdown)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MD"),
+            Some(ExportType::Markdown)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Md"),
+            Some(ExportType::Markdown)
+        ));
+    }
+
+    #[test]
+    fn can_parse_xml_any_case() {
+        assert!(matches!(ExportType::from_cli("xml"), Some(ExportType::Xml)));
+        assert!(matches!(ExportType::from_cli("XML"), Some(ExportType::Xml)));
+        assert!(matches!(ExportType::from_cli("XmL"), Some(ExportType::Xml)));
+    }
+
+    #[test]
+    fn can_parse_sqlite_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("sqlite"),
+            Some(ExportType::Sqlite)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("SQLITE"),
+            Some(ExportType::Sqlite)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("SqLite"),
+            Some(ExportType::Sqlite)
+        ));
+    }
+
+    #[test]
+    fn can_parse_parquet_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("parquet"),
+            Some(ExportType::Parquet)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("PARQUET"),
+            Some(ExportType::Parquet)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("PaRqUeT"),
+            Some(ExportType::Parquet)
+        ));
+    }
+
+    #[test]
+    fn can_parse_mbox_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("mbox"),
+            Some(ExportType::Mbox)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MBOX"),
+            Some(ExportType::Mbox)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("MbOx"),
+            Some(ExportType::Mbox)
+        ));
+    }
+
+    #[test]
+    fn can_parse_telegram_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("telegram"),
+            Some(ExportType::Telegram)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("TELEGRAM"),
+            Some(ExportType::Telegram)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("TeLeGrAm"),
+            Some(ExportType::Telegram)
+        ));
+    }
+
+    #[test]
+    fn can_parse_slack_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("slack"),
+            Some(ExportType::Slack)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("SLACK"),
+            Some(ExportType::Slack)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("SlAcK"),
+            Some(ExportType::Slack)
+        ));
+    }
+
+    #[test]
+    fn can_parse_contacts_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("contacts"),
+            Some(ExportType::Contacts)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("CONTACTS"),
+            Some(ExportType::Contacts)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("CoNtAcTs"),
+            Some(ExportType::Contacts)
+        ));
+    }
+
+    #[test]
+    fn can_parse_geojson_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("geojson"),
+            Some(ExportType::Geojson)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("GEOJSON"),
+            Some(ExportType::Geojson)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("GeOjSoN"),
+            Some(ExportType::Geojson)
+        ));
+    }
+
+    #[test]
+    fn can_parse_kml_any_case() {
+        assert!(matches!(ExportType::from_cli("kml"), Some(ExportType::Kml)));
+        assert!(matches!(ExportType::from_cli("KML"), Some(ExportType::Kml)));
+        assert!(matches!(ExportType::from_cli("KmL"), Some(ExportType::Kml)));
+    }
+
+    #[test]
+    fn can_parse_gpx_any_case() {
+        assert!(matches!(ExportType::from_cli("gpx"), Some(ExportType::Gpx)));
+        assert!(matches!(ExportType::from_cli("GPX"), Some(ExportType::Gpx)));
+        assert!(matches!(ExportType::from_cli("GpX"), Some(ExportType::Gpx)));
+    }
+
+    #[test]
+    fn can_parse_links_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("links"),
+            Some(ExportType::Links)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("LINKS"),
+            Some(ExportType::Links)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("LiNkS"),
+            Some(ExportType::Links)
+        ));
+    }
+
+    #[test]
+    fn can_display_cli_name() {
+        for name in [
+            "txt", "html", "json", "md", "sqlite", "telegram", "slack", "contacts", "links",
+        ] {
+            assert_eq!(ExportType::from_cli(name).unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn can_detect_telegram_overwrite() {
+        assert!(ExportType::Telegram.would_overwrite(Path::new("/tmp/result.json")));
+        assert!(ExportType::Telegram.would_overwrite(Path::new("/tmp/.telegram")));
+        assert!(!ExportType::Telegram.would_overwrite(Path::new("/tmp/chat.json")));
+        assert!(!ExportType::Telegram.would_overwrite(Path::new("/tmp/orphaned.html")));
+    }
+
+    #[test]
+    fn can_detect_slack_overwrite() {
+        let export_path = PathBuf::from("/tmp/imessage-exporter-slack-overwrite");
+        let channel = export_path.join("general");
+        let empty = export_path.join("attachments");
+        create_dir_all(&channel).unwrap();
+        create_dir_all(&empty).unwrap();
+        File::create(channel.join("2022-05-17.json")).unwrap();
+
+        assert!(ExportType::Slack.would_overwrite(&export_path.join("channels.json")));
+        assert!(ExportType::Slack.would_overwrite(&export_path.join("users.json")));
+        assert!(ExportType::Slack.would_overwrite(&channel));
+        assert!(!ExportType::Slack.would_overwrite(&empty));
+        assert!(!ExportType::Slack.would_overwrite(&export_path.join("orphaned.html")));
+
+        remove_dir_all(&export_path).unwrap();
+    }
+
+    #[test]
+    fn can_detect_links_overwrite() {
+        assert!(ExportType::Links.would_overwrite(Path::new("/tmp/links.html")));
+        assert!(ExportType::Links.would_overwrite(Path::new("/tmp/links.csv")));
+        assert!(!ExportType::Links.would_overwrite(Path::new("/tmp/links.json")));
+    }
+
+    #[test]
+    fn can_detect_overwrite_by_extension() {
+        assert!(ExportType::Html.would_overwrite(Path::new("/tmp/orphaned.html")));
+        assert!(!ExportType::Html.would_overwrite(Path::new("/tmp/orphaned.txt")));
+        assert!(ExportType::Markdown.would_overwrite(Path::new("/tmp/orphaned.md")));
+        assert!(ExportType::Contacts.would_overwrite(Path::new("/tmp/contacts.vcf")));
+        assert!(!ExportType::Contacts.would_overwrite(Path::new("/tmp/contacts.html")));
+    }
+
     #[test]
     fn cant_parse_invalid() {
         assert!(ExportType::from_cli("pdf").is_none());
-        assert!(ExportType::from_cli("json").is_none());
+        assert!(ExportType::from_cli("jsonl").is_none());
         assert!(ExportType::from_cli("").is_none());
     }
 }
@@ -0,0 +1,123 @@
+/*!
+ Contains data shared by the unit tests. Tests that need other options should override only the fields they use with `..fake_options()`.
+*/
+
+use std::{collections::HashMap, fs::create_dir_all, path::PathBuf};
+
+use imessage_database::{
+    tables::{messages::Message, table::get_connection},
+    util::{
+        dates::get_offset, dirs::default_db_path, platform::Platform, query_context::QueryContext,
+    },
+};
+
+use crate::app::{
+    attachment_manager::AttachmentManager, export_type::ExportType, options::Options,
+    runtime::Config,
+};
+
+/// Build a message with every field set to its default
+pub fn blank() -> Message {
+    Message {
+        rowid: i32::default(),
+        guid: String::default(),
+        text: None,
+        service: Some("iMessage".to_string()),
+        handle_id: Some(i32::default()),
+        destination_caller_id: None,
+        subject: None,
+        date: i64::default(),
+        date_read: i64::default(),
+        date_delivered: i64::default(),
+        is_from_me: false,
+        is_read: false,
+        item_type: 0,
+        other_handle: 0,
+        share_status: false,
+        share_direction: false,
+        group_title: None,
+        group_action_type: 0,
+        associated_message_guid: None,
+        associated_message_type: Some(i32::default()),
+        balloon_bundle_id: None,
+        expressive_send_style_id: None,
+        thread_originator_guid: None,
+        thread_originator_part: None,
+        date_edited: 0,
+        associated_message_emoji: None,
+        chat_id: None,
+        num_attachments: 0,
+        deleted_from: None,
+        num_replies: 0,
+        components: None,
+        edited_parts: None,
+    }
+}
+
+/// Build options that export to `/tmp` with every feature disabled
+pub fn fake_options() -> Options {
+    Options {
+        db_path: default_db_path(),
+        attachment_root: None,
+        attachment_manager: AttachmentManager::Disabled,
+        diagnostic: false,
+        export_type: None,
+        export_path: PathBuf::from("/tmp"),
+        query_context: QueryContext::default(),
+        no_lazy: false,
+        custom_name: None,
+        use_caller_id: false,
+        platform: Platform::macOS,
+        ignore_disk_space: false,
+        combine_chats: false,
+        embed_attachments: false,
+        embed_media_limit: None,
+        paginate: None,
+        template_dir: None,
+        chat_filters: vec![],
+        participant_filters: vec![],
+        excluded_chat_filters: vec![],
+        excluded_sender_filters: vec![],
+        search: None,
+        search_regex: false,
+        search_context: None,
+        index: false,
+        query: None,
+        index_path: None,
+        query_limit: None,
+    }
+}
+
+/// Build options for the given export type that export to `/tmp`
+pub fn fake_options_for(export_type: ExportType) -> Options {
+    Options {
+        export_type: Some(export_type),
+        ..fake_options()
+    }
+}
+
+/// Build options for the given export type that export to `dir`, creating it if needed
+pub fn fake_options_at(export_type: ExportType, dir: &str) -> Options {
+    create_dir_all(dir).unwrap();
+    Options {
+        export_path: PathBuf::from(dir),
+        ..fake_options_for(export_type)
+    }
+}
+
+/// Build a config with empty caches for the given options
+pub fn fake_config(options: Options) -> Config {
+    let db = get_connection(&options.get_db_path()).unwrap();
+    Config {
+        chatrooms: HashMap::new(),
+        real_chatrooms: HashMap::new(),
+        chatroom_participants: HashMap::new(),
+        participants: HashMap::new(),
+        real_participants: HashMap::new(),
+        tapbacks: HashMap::new(),
+        options,
+        offset: get_offset(),
+        db,
+        converter: None,
+    }
+}
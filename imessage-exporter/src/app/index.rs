@@ -0,0 +1,461 @@
+/*!
+ Maintains an on-disk full-text index of decoded messages so repeated searches do not need to decode the database again.
+*/
+
+use std::fs::create_dir_all;
+
+use tantivy::{
+    collector::TopDocs,
+    directory::MmapDirectory,
+    query::QueryParser,
+    schema::{DateOptions, Field as IndexField, Schema, Value, INDEXED, STORED, TEXT},
+    DateTime, Index, IndexWriter, TantivyDocument, Term,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+    util::{
+        dates::{format, get_local_time, TIMESTAMP_FACTOR},
+        query_context::QueryContext,
+    },
+};
+
+use crate::app::{
+    error::RuntimeError,
+    progress::build_progress_bar_export,
+    runtime::Config,
+    search::{searchable_text, Field},
+};
+
+/// Default number of query results to print
+pub const DEFAULT_QUERY_LIMIT: usize = 50;
+
+/// Memory used to buffer new documents before they are written to disk
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// The fields stored for every indexed message
+///
+/// Field names are used in queries, i.e. `sender:alice`.
+struct Fields {
+    /// The message's `ROWID`, used to replace documents when a message is indexed again
+    rowid: IndexField,
+    /// The name of the conversation the message belongs to
+    chat: IndexField,
+    /// The name of the message's sender, as resolved by the exporter
+    sender: IndexField,
+    /// When the message was sent
+    date: IndexField,
+    /// The message body
+    text: IndexField,
+    /// Previous versions of an edited message
+    history: IndexField,
+    /// The titles and captions of link previews and app messages
+    link: IndexField,
+}
+
+impl Fields {
+    fn new() -> (Schema, Self) {
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            rowid: builder.add_u64_field("rowid", INDEXED | STORED),
+            chat: builder.add_text_field("chat", TEXT | STORED),
+            sender: builder.add_text_field("sender", TEXT | STORED),
+            date: builder
+                .add_date_field("date", DateOptions::from(INDEXED).set_stored().set_fast()),
+            text: builder.add_text_field("text", TEXT | STORED),
+            history: builder.add_text_field("history", TEXT | STORED),
+            link: builder.add_text_field("link", TEXT | STORED),
+        };
+        (builder.build(), fields)
+    }
+}
+
+/// A full-text index of messages that is updated incrementally by `ROWID` and edit date
+pub struct SearchIndex<'a> {
+    /// Data that is setup from the application's runtime
+    config: &'a Config,
+    /// The index stored on disk
+    index: Index,
+    /// The fields in the index's schema
+    fields: Fields,
+}
+
+impl<'a> SearchIndex<'a> {
+    /// Open the index in the configured directory, creating it if it does not exist
+    pub fn open(config: &'a Config) -> Result<Self, RuntimeError> {
+        let path = config.options.get_index_path();
+        create_dir_all(&path).map_err(|why| RuntimeError::CreateError(why, path.clone()))?;
+
+        let (schema, fields) = Fields::new();
+        let directory =
+            MmapDirectory::open(&path).map_err(|why| RuntimeError::IndexError(why.into()))?;
+        let index = Index::open_or_create(directory, schema).map_err(RuntimeError::IndexError)?;
+
+        Ok(SearchIndex {
+            config,
+            index,
+            fields,
+        })
+    }
+
+    /// Get the greatest `ROWID` and edit date of the messages read by a previous update, if any
+    ///
+    /// Both are stored as the payload of the index's last commit.
+    fn last_update(&self) -> Result<Option<(i32, i64)>, RuntimeError> {
+        let metas = self.index.load_metas().map_err(RuntimeError::IndexError)?;
+        Ok(metas
+            .payload
+            .and_then(|payload| SearchIndex::parse_payload(&payload)))
+    }
+
+    /// Parse a commit payload in the format `<ROWID> <date_edited>`
+    ///
+    /// Indexes built before edit dates were stored only have a `ROWID`, so every edited message is read again.
+    fn parse_payload(payload: &str) -> Option<(i32, i64)> {
+        match payload.split_once(' ') {
+            Some((rowid, edited)) => Some((rowid.parse().ok()?, edited.parse().ok()?)),
+            None => Some((payload.parse().ok()?, 0)),
+        }
+    }
+
+    /// Add every message that was added, edited, or unsent since the last update to the index
+    pub fn update(&self) -> Result<(), RuntimeError> {
+        let mut context = QueryContext::default();
+        let last_update = self.last_update()?;
+        if let Some((rowid, edited)) = last_update {
+            context.set_after_rowid(rowid);
+            context.set_edited_after(edited);
+        }
+
+        // Tell the user what we are doing
+        eprintln!(
+            "Updating search index in {}...",
+            self.config.options.get_index_path().display()
+        );
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &context).map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &context).map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        let mut writer: IndexWriter = self
+            .index
+            .writer(WRITER_MEMORY)
+            .map_err(RuntimeError::IndexError)?;
+        let (mut max_rowid, mut max_edited) = match last_update {
+            Some((rowid, edited)) => (Some(rowid), edited),
+            None => (None, 0),
+        };
+        let mut indexed = 0;
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and index the same message GUID twice
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Messages are read in date order, so the last message read may not have the greatest `ROWID`
+            max_rowid = max_rowid.max(Some(msg.rowid));
+            max_edited = max_edited.max(msg.date_edited);
+
+            // Remove the message if it was already indexed, so each message has at most one document
+            writer.delete_term(Term::from_field_u64(self.fields.rowid, msg.rowid as u64));
+
+            // Tapbacks and announcements are not messages someone wrote, so they are not indexed
+            if !msg.is_tapback() && !msg.is_announcement() {
+                let _ = msg.generate_text(&self.config.db);
+                writer
+                    .add_document(self.build_document(&msg))
+                    .map_err(RuntimeError::IndexError)?;
+                indexed += 1;
+            }
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+
+        let mut commit = writer.prepare_commit().map_err(RuntimeError::IndexError)?;
+        if let Some(rowid) = max_rowid {
+            commit.set_payload(&format!("{rowid} {max_edited}"));
+        }
+        commit.commit().map_err(RuntimeError::IndexError)?;
+        pb.finish_and_clear();
+
+        match indexed {
+            1 => eprintln!("Indexed 1 new or edited message"),
+            indexed => eprintln!("Indexed {indexed} new or edited messages"),
+        }
+        Ok(())
+    }
+
+    /// Build the document stored in the index for a message
+    fn build_document(&self, message: &Message) -> TantivyDocument {
+        let mut document = TantivyDocument::default();
+        document.add_u64(self.fields.rowid, message.rowid as u64);
+
+        let chat = match self.config.conversation(message) {
+            Some((chatroom, _)) => chatroom.name(),
+            None => ORPHANED,
+        };
+        document.add_text(self.fields.chat, chat);
+        document.add_text(
+            self.fields.sender,
+            self.config.who(
+                message.handle_id,
+                message.is_from_me(),
+                &message.destination_caller_id,
+            ),
+        );
+        if let Ok(date) = message.date(&self.config.offset) {
+            document.add_date(
+                self.fields.date,
+                DateTime::from_timestamp_secs(date.timestamp()),
+            );
+        }
+
+        for (field, text) in searchable_text(self.config, message) {
+            let field = match field {
+                Field::Text => self.fields.text,
+                Field::EditHistory => self.fields.history,
+                Field::LinkPreview | Field::App => self.fields.link,
+            };
+            document.add_text(field, text);
+        }
+
+        document
+    }
+
+    /// Print the messages that best match a query
+    ///
+    /// Queries match the message text, edit history, and link text unless they name a field, and every term must match.
+    pub fn query(&self, query: &str, limit: usize) -> Result<(), RuntimeError> {
+        let mut parser = QueryParser::for_index(
+            &self.index,
+            vec![self.fields.text, self.fields.history, self.fields.link],
+        );
+        parser.set_conjunction_by_default();
+        let query = parser.parse_query(query).map_err(|why| {
+            RuntimeError::InvalidOptions(format!("{query} is not a valid query!\n{why}"))
+        })?;
+
+        let reader = self.index.reader().map_err(RuntimeError::IndexError)?;
+        let searcher = reader.searcher();
+        let results = searcher
+            .search(&query, &TopDocs::with_limit(limit))
+            .map_err(RuntimeError::IndexError)?;
+
+        for (_, address) in &results {
+            let document: TantivyDocument =
+                searcher.doc(*address).map_err(RuntimeError::IndexError)?;
+            println!("{}\n", self.format_result(&document));
+        }
+
+        match results.len() {
+            1 => eprintln!("Found 1 matching message"),
+            found => eprintln!("Found {found} matching messages"),
+        }
+        Ok(())
+    }
+
+    /// Build the lines printed for a query result, in the same layout as search results
+    fn format_result(&self, document: &TantivyDocument) -> String {
+        let text = |field: IndexField| -> Vec<&str> {
+            document
+                .get_all(field)
+                .filter_map(|value| value.as_str())
+                .collect()
+        };
+
+        let chat = text(self.fields.chat).first().copied().unwrap_or(ORPHANED);
+        let sender = text(self.fields.sender)
+            .first()
+            .copied()
+            .unwrap_or_default();
+        let date = document
+            .get_first(self.fields.date)
+            .and_then(|value| value.as_datetime())
+            .map(|date| {
+                format(&get_local_time(
+                    &(date.into_timestamp_secs() * TIMESTAMP_FACTOR),
+                    &0,
+                ))
+            })
+            .unwrap_or_default();
+        let body = text(self.fields.text)
+            .first()
+            .map(|body| body.replace('\n', " "))
+            .unwrap_or_default();
+
+        let mut lines = format!("{chat}\n> {date} | {sender}: {body}");
+        for (name, field) in [
+            (Field::EditHistory, self.fields.history),
+            (Field::LinkPreview, self.fields.link),
+        ] {
+            for value in text(field) {
+                lines.push_str(&format!("\n      {name}: {}", value.replace('\n', " ")));
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::remove_dir_all, path::PathBuf};
+
+    use tantivy::TantivyDocument;
+
+    use crate::{
+        app::{
+            fixtures::{self, blank, fake_config},
+            index::SearchIndex,
+        },
+        Options,
+    };
+    use imessage_database::message_types::edited::{
+        EditStatus, EditedEvent, EditedMessage, EditedMessagePart,
+    };
+
+    /// Build options that keep each test's index in its own directory
+    fn fake_options(name: &str) -> Options {
+        let index_path = PathBuf::from(format!("/tmp/imessage-exporter-index/{name}"));
+        let _ = remove_dir_all(&index_path);
+        Options {
+            index: true,
+            index_path: Some(index_path),
+            ..fixtures::fake_options()
+        }
+    }
+
+    fn count(index: &SearchIndex) -> u64 {
+        index.index.reader().unwrap().searcher().num_docs()
+    }
+
+    #[test]
+    fn can_create() {
+        let config = fake_config(fake_options("can_create"));
+        let index = SearchIndex::open(&config).unwrap();
+
+        assert_eq!(index.last_update().unwrap(), None);
+        assert_eq!(count(&index), 0);
+    }
+
+    #[test]
+    fn can_update() {
+        let config = fake_config(fake_options("can_update"));
+        let index = SearchIndex::open(&config).unwrap();
+        index.update().unwrap();
+
+        let last_update = index.last_update().unwrap();
+        assert!(last_update.is_some());
+        let indexed = count(&index);
+        assert!(indexed > 0);
+
+        // Nothing is added when no messages were added to the database
+        index.update().unwrap();
+        assert_eq!(index.last_update().unwrap(), last_update);
+        assert_eq!(count(&index), indexed);
+    }
+
+    #[test]
+    fn can_parse_payload() {
+        assert_eq!(
+            SearchIndex::parse_payload("1000 674526582885055488"),
+            Some((1000, 674526582885055488))
+        );
+        // Indexes built before edit dates were stored read every edited message again
+        assert_eq!(SearchIndex::parse_payload("1000"), Some((1000, 0)));
+        assert_eq!(SearchIndex::parse_payload("1000 later"), None);
+        assert_eq!(SearchIndex::parse_payload(""), None);
+    }
+
+    #[test]
+    fn can_reopen() {
+        let config = fake_config(fake_options("can_reopen"));
+        let last_update = {
+            let index = SearchIndex::open(&config).unwrap();
+            index.update().unwrap();
+            index.last_update().unwrap()
+        };
+
+        let index = SearchIndex::open(&config).unwrap();
+        assert_eq!(index.last_update().unwrap(), last_update);
+    }
+
+    #[test]
+    fn can_query() {
+        let config = fake_config(fake_options("can_query"));
+        let index = SearchIndex::open(&config).unwrap();
+        index.update().unwrap();
+
+        assert!(index.query("\"hello there\"", 10).is_ok());
+        assert!(index.query("sender:me", 10).is_ok());
+        assert!(index
+            .query("date:[2020-01-01T00:00:00Z TO 2030-01-01T00:00:00Z]", 10)
+            .is_ok());
+    }
+
+    #[test]
+    fn cant_query_invalid() {
+        let config = fake_config(fake_options("cant_query_invalid"));
+        let index = SearchIndex::open(&config).unwrap();
+
+        assert!(index.query("text:(", 10).is_err());
+        assert!(index.query("missing_field:hello", 10).is_err());
+    }
+
+    #[test]
+    fn can_format_result() {
+        let config = fake_config(fake_options("can_format_result"));
+        let index = SearchIndex::open(&config).unwrap();
+
+        let mut message = blank();
+        message.rowid = 1;
+        message.is_from_me = true;
+        message.text = Some("Family lunch?".to_string());
+        message.edited_parts = Some(EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: vec![
+                    EditedEvent {
+                        date: 0,
+                        text: "Family dinner?".to_string(),
+                        guid: None,
+                    },
+                    EditedEvent {
+                        date: 0,
+                        text: "Family lunch?".to_string(),
+                        guid: None,
+                    },
+                ],
+            }],
+        });
+
+        let document: TantivyDocument = index.build_document(&message);
+        let result = index.format_result(&document);
+
+        assert!(result.starts_with("orphaned\n> "));
+        assert!(result.ends_with("| Me: Family lunch?\n      edit history: Family dinner?"));
+    }
+}
@@ -2,7 +2,12 @@ pub mod attachment_manager;
 pub mod converter;
 pub mod error;
 pub mod export_type;
+#[cfg(test)]
+pub mod fixtures;
+pub mod index;
 pub mod options;
+pub mod pagination;
 pub mod progress;
 pub mod runtime;
 pub mod sanitizers;
+pub mod search;
@@ -12,11 +12,18 @@ use imessage_database::{
 };
 
 use crate::app::{
-    attachment_manager::AttachmentManager, error::RuntimeError, export_type::ExportType,
+    attachment_manager::AttachmentManager,
+    error::RuntimeError,
+    export_type::ExportType,
+    index::DEFAULT_QUERY_LIMIT,
+    pagination::Pagination,
+    search::{build_pattern, DEFAULT_SEARCH_CONTEXT},
 };
 
 /// Default export directory name
 pub const DEFAULT_OUTPUT_DIR: &str = "imessage_export";
+/// Default search index directory name
+pub const DEFAULT_INDEX_DIR: &str = "imessage_index";
 
 // CLI Arg Names
 pub const OPTION_DB_PATH: &str = "db-path";
@@ -27,20 +34,45 @@ pub const OPTION_EXPORT_TYPE: &str = "format";
 pub const OPTION_EXPORT_PATH: &str = "export-path";
 pub const OPTION_START_DATE: &str = "start-date";
 pub const OPTION_END_DATE: &str = "end-date";
+pub const OPTION_START_EXCLUSIVE: &str = "start-exclusive";
+pub const OPTION_END_INCLUSIVE: &str = "end-inclusive";
 pub const OPTION_DISABLE_LAZY_LOADING: &str = "no-lazy";
 pub const OPTION_CUSTOM_NAME: &str = "custom-name";
 pub const OPTION_PLATFORM: &str = "platform";
 pub const OPTION_BYPASS_FREE_SPACE_CHECK: &str = "ignore-disk-warning";
 pub const OPTION_USE_CALLER_ID: &str = "use-caller-id";
+pub const OPTION_COMBINE_CHATS: &str = "combine-chats";
+pub const OPTION_EMBED_ATTACHMENTS: &str = "embed-attachments";
+pub const OPTION_EMBED_MEDIA_LIMIT: &str = "embed-media-limit";
+pub const OPTION_PAGINATE: &str = "paginate";
+pub const OPTION_TEMPLATE_DIR: &str = "template-dir";
+pub const OPTION_CHAT_FILTER: &str = "chat";
+pub const OPTION_PARTICIPANT_FILTER: &str = "participant";
+pub const OPTION_EXCLUDE_CHAT: &str = "exclude-chat";
+pub const OPTION_EXCLUDE_SENDER: &str = "exclude-sender";
+pub const OPTION_EXCLUDE_KIND: &str = "exclude";
+pub const OPTION_SEARCH: &str = "search";
+pub const OPTION_SEARCH_REGEX: &str = "regex";
+pub const OPTION_SEARCH_CONTEXT: &str = "context";
+pub const OPTION_INDEX: &str = "index";
+pub const OPTION_QUERY: &str = "query";
+pub const OPTION_INDEX_PATH: &str = "index-path";
+pub const OPTION_QUERY_LIMIT: &str = "limit";
 
 // Other CLI Text
-pub const SUPPORTED_FILE_TYPES: &str = "txt, html";
+pub const SUPPORTED_FILE_TYPES: &str =
+    "txt, html, json, ndjson, csv, md, xml, sqlite, parquet, mbox, telegram, slack, contacts, geojson, kml, gpx, links";
 pub const SUPPORTED_PLATFORMS: &str = "macOS, iOS";
 pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str = "compatible, efficient, disabled";
+pub const SUPPORTED_PAGINATION_MODES: &str = "month, year, or a number of messages";
+pub const SUPPORTED_EXCLUDED_KINDS: &str =
+    "tapbacks, announcements, apps, shareplay, edited, with-attachments, without-attachments";
 pub const ABOUT: &str = concat!(
     "The `imessage-exporter` binary exports iMessage data to\n",
-    "`txt` or `html` formats. It can also run diagnostics\n",
-    "to find problems with the iMessage database."
+    "`txt`, `html`, `json`, `ndjson`, `csv`, `md`, `xml`, `sqlite`, `parquet`,\n",
+    "`mbox`, `telegram`, `slack`, `contacts`, `geojson`, `kml`, `gpx`, or `links` formats.\n",
+    "It can also search the text of messages, maintain a search index for fast queries,\n",
+    "or run diagnostics to find problems with the iMessage database."
 );
 
 #[derive(Debug, PartialEq, Eq)]
@@ -69,6 +101,38 @@ pub struct Options {
     pub platform: Platform,
     /// If true, disable the free disk space check
     pub ignore_disk_space: bool,
+    /// If true, write every conversation to a single file
+    pub combine_chats: bool,
+    /// If true, inline attachments in HTML exports as `data:` URIs
+    pub embed_attachments: bool,
+    /// The largest audio or video attachment to inline in HTML exports, in bytes
+    pub embed_media_limit: Option<u64>,
+    /// How to split HTML conversations across multiple pages, if at all
+    pub paginate: Option<Pagination>,
+    /// Directory of templates that change how HTML and TXT exports render messages
+    pub template_dir: Option<PathBuf>,
+    /// Chat IDs, identifiers, or display names of the chats to export
+    pub chat_filters: Vec<String>,
+    /// Handles of the participants whose chats to export
+    pub participant_filters: Vec<String>,
+    /// Chat IDs, identifiers, or display names of the chats to leave out of the export
+    pub excluded_chat_filters: Vec<String>,
+    /// Handles of the senders whose messages to leave out of the export
+    pub excluded_sender_filters: Vec<String>,
+    /// If set, print the messages that match this term instead of exporting
+    pub search: Option<String>,
+    /// If true, treat the search term as a regular expression
+    pub search_regex: bool,
+    /// The number of messages to print before and after each search result
+    pub search_context: Option<usize>,
+    /// If true, add new messages to the search index and exit
+    pub index: bool,
+    /// If set, print the indexed messages that match this query instead of exporting
+    pub query: Option<String>,
+    /// Custom path to the search index
+    pub index_path: Option<PathBuf>,
+    /// The largest number of query results to print
+    pub query_limit: Option<usize>,
 }
 
 impl Options {
@@ -81,11 +145,45 @@ impl Options {
         let user_export_path: Option<&String> = args.get_one(OPTION_EXPORT_PATH);
         let start_date: Option<&String> = args.get_one(OPTION_START_DATE);
         let end_date: Option<&String> = args.get_one(OPTION_END_DATE);
+        let start_exclusive = args.get_flag(OPTION_START_EXCLUSIVE);
+        let end_inclusive = args.get_flag(OPTION_END_INCLUSIVE);
         let no_lazy = args.get_flag(OPTION_DISABLE_LAZY_LOADING);
         let custom_name: Option<&String> = args.get_one(OPTION_CUSTOM_NAME);
         let use_caller_id = args.get_flag(OPTION_USE_CALLER_ID);
         let platform_type: Option<&String> = args.get_one(OPTION_PLATFORM);
         let ignore_disk_space = args.get_flag(OPTION_BYPASS_FREE_SPACE_CHECK);
+        let combine_chats = args.get_flag(OPTION_COMBINE_CHATS);
+        let embed_attachments = args.get_flag(OPTION_EMBED_ATTACHMENTS);
+        let embed_media_limit: Option<&String> = args.get_one(OPTION_EMBED_MEDIA_LIMIT);
+        let paginate: Option<&String> = args.get_one(OPTION_PAGINATE);
+        let template_dir: Option<&String> = args.get_one(OPTION_TEMPLATE_DIR);
+        let chat_filters: Vec<String> = args
+            .get_many::<String>(OPTION_CHAT_FILTER)
+            .map(|filters| filters.cloned().collect())
+            .unwrap_or_default();
+        let participant_filters: Vec<String> = args
+            .get_many::<String>(OPTION_PARTICIPANT_FILTER)
+            .map(|filters| filters.cloned().collect())
+            .unwrap_or_default();
+        let excluded_chat_filters: Vec<String> = args
+            .get_many::<String>(OPTION_EXCLUDE_CHAT)
+            .map(|filters| filters.cloned().collect())
+            .unwrap_or_default();
+        let excluded_sender_filters: Vec<String> = args
+            .get_many::<String>(OPTION_EXCLUDE_SENDER)
+            .map(|filters| filters.cloned().collect())
+            .unwrap_or_default();
+        let search: Option<&String> = args.get_one(OPTION_SEARCH);
+        let search_regex = args.get_flag(OPTION_SEARCH_REGEX);
+        let search_context: Option<&String> = args.get_one(OPTION_SEARCH_CONTEXT);
+        let index = args.get_flag(OPTION_INDEX);
+        let query: Option<&String> = args.get_one(OPTION_QUERY);
+        let index_path: Option<&String> = args.get_one(OPTION_INDEX_PATH);
+        let query_limit: Option<&String> = args.get_one(OPTION_QUERY_LIMIT);
+        let excluded_kinds: Vec<&String> = args
+            .get_many::<String>(OPTION_EXCLUDE_KIND)
+            .map(Iterator::collect)
+            .unwrap_or_default();
 
         // Build the export type
         let export_type: Option<ExportType> = match export_file_type {
@@ -108,19 +206,94 @@ impl Options {
                 "Option {OPTION_EXPORT_PATH} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
             )));
         }
-        if start_date.is_some() && export_file_type.is_none() {
+        if start_date.is_some() && export_file_type.is_none() && search.is_none() {
             return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_START_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+                "Option {OPTION_START_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
             )));
         }
-        if end_date.is_some() && export_file_type.is_none() {
+        if end_date.is_some() && export_file_type.is_none() && search.is_none() {
             return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_END_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+                "Option {OPTION_END_DATE} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
             )));
         }
-        if use_caller_id && export_file_type.is_none() {
+        if !chat_filters.is_empty() && export_file_type.is_none() && search.is_none() {
             return Err(RuntimeError::InvalidOptions(format!(
-                "Option {OPTION_USE_CALLER_ID} is enabled, which requires `--{OPTION_EXPORT_TYPE}`"
+                "Option {OPTION_CHAT_FILTER} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
+            )));
+        }
+        if !participant_filters.is_empty() && export_file_type.is_none() && search.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_PARTICIPANT_FILTER} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
+            )));
+        }
+        if !excluded_chat_filters.is_empty() && export_file_type.is_none() && search.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_EXCLUDE_CHAT} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
+            )));
+        }
+        if !excluded_sender_filters.is_empty() && export_file_type.is_none() && search.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_EXCLUDE_SENDER} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
+            )));
+        }
+        if !excluded_kinds.is_empty() && export_file_type.is_none() && search.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_EXCLUDE_KIND} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
+            )));
+        }
+        if start_exclusive && start_date.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_START_EXCLUSIVE} is enabled, which requires `--{OPTION_START_DATE}`"
+            )));
+        }
+        if end_inclusive && end_date.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_END_INCLUSIVE} is enabled, which requires `--{OPTION_END_DATE}`"
+            )));
+        }
+        if use_caller_id && export_file_type.is_none() && search.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_USE_CALLER_ID} is enabled, which requires `--{OPTION_EXPORT_TYPE}` or `--{OPTION_SEARCH}`"
+            )));
+        }
+        if search_regex && search.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_SEARCH_REGEX} is enabled, which requires `--{OPTION_SEARCH}`"
+            )));
+        }
+        if search_context.is_some() && search.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_SEARCH_CONTEXT} is enabled, which requires `--{OPTION_SEARCH}`"
+            )));
+        }
+        if search.is_some() && export_file_type.is_some() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Search is enabled; {OPTION_EXPORT_TYPE} is disallowed"
+            )));
+        }
+        if index_path.is_some() && !index && query.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_INDEX_PATH} is enabled, which requires `--{OPTION_INDEX}` or `--{OPTION_QUERY}`"
+            )));
+        }
+        if query_limit.is_some() && query.is_none() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_QUERY_LIMIT} is enabled, which requires `--{OPTION_QUERY}`"
+            )));
+        }
+        if (index || query.is_some()) && export_file_type.is_some() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "The search index is enabled; {OPTION_EXPORT_TYPE} is disallowed"
+            )));
+        }
+        if (index || query.is_some()) && search.is_some() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "The search index is enabled; {OPTION_SEARCH} is disallowed"
+            )));
+        }
+        if embed_media_limit.is_some() && !embed_attachments {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option {OPTION_EMBED_MEDIA_LIMIT} is enabled, which requires `--{OPTION_EMBED_ATTACHMENTS}`"
             )));
         }
 
@@ -131,6 +304,41 @@ impl Options {
             );
         }
 
+        // Warn the user if they are exporting to a file type that does not embed attachments
+        if embed_attachments && !matches!(export_type, Some(ExportType::Html)) {
+            eprintln!(
+                "Option {OPTION_EMBED_ATTACHMENTS} is enabled, but the format specified is not `html`!"
+            );
+        }
+
+        // Warn the user if they are exporting to a file type that is not split into pages
+        if paginate.is_some() && !matches!(export_type, Some(ExportType::Html)) {
+            eprintln!(
+                "Option {OPTION_PAGINATE} is enabled, but the format specified is not `html`!"
+            );
+        }
+
+        // Warn the user if they are exporting to a file type that does not use templates
+        if template_dir.is_some()
+            && !matches!(export_type, Some(ExportType::Html | ExportType::Txt))
+        {
+            eprintln!(
+                "Option {OPTION_TEMPLATE_DIR} is enabled, but the format specified is not `html` or `txt`!"
+            );
+        }
+
+        // Warn the user if they are exporting to a file type that always writes one file per chat
+        if combine_chats
+            && !matches!(
+                export_type,
+                Some(ExportType::Csv | ExportType::Xml | ExportType::Mbox)
+            )
+        {
+            eprintln!(
+                "Option {OPTION_COMBINE_CHATS} is enabled, but the format specified is not `csv`, `xml`, or `mbox`!"
+            );
+        }
+
         // Ensure that if diagnostics are enabled, no other options are
         if diagnostic && attachment_manager_type.is_some() {
             return Err(RuntimeError::InvalidOptions(format!(
@@ -162,6 +370,16 @@ impl Options {
                 "Diagnostics are enabled; {OPTION_USE_CALLER_ID} is disallowed"
             )));
         }
+        if diagnostic && search.is_some() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Diagnostics are enabled; {OPTION_SEARCH} is disallowed"
+            )));
+        }
+        if diagnostic && (index || query.is_some()) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Diagnostics are enabled; {OPTION_INDEX} and {OPTION_QUERY} are disallowed"
+            )));
+        }
 
         // Ensure that there are no custom name conflicts
         if custom_name.is_some() && use_caller_id {
@@ -170,15 +388,88 @@ impl Options {
             )));
         }
 
+        // Parse the media size limit, which is provided in megabytes
+        let embed_media_limit = match embed_media_limit {
+            Some(limit) => Some(
+                limit
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|megabytes| megabytes.is_finite() && *megabytes >= 0.)
+                    .map(|megabytes| (megabytes * 1024. * 1024.) as u64)
+                    .ok_or(RuntimeError::InvalidOptions(format!(
+                        "{limit} is not a valid {OPTION_EMBED_MEDIA_LIMIT}! Must be a positive number of megabytes"
+                    )))?,
+            ),
+            None => None,
+        };
+
+        // Ensure the search term can be used to find messages
+        if let Some(term) = search {
+            if let Err(why) = build_pattern(term, search_regex) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "{term} is not a valid {OPTION_SEARCH} pattern!\n{why}"
+                )));
+            }
+        }
+
+        // Parse the number of messages printed around each search result
+        let search_context = match search_context {
+            Some(context) => Some(context.parse::<usize>().map_err(|_| {
+                RuntimeError::InvalidOptions(format!(
+                    "{context} is not a valid {OPTION_SEARCH_CONTEXT}! Must be a number of messages"
+                ))
+            })?),
+            None => None,
+        };
+
+        // Parse the largest number of query results to print
+        let query_limit = match query_limit {
+            Some(limit) => Some(
+                limit
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|limit| *limit > 0)
+                    .ok_or(RuntimeError::InvalidOptions(format!(
+                        "{limit} is not a valid {OPTION_QUERY_LIMIT}! Must be a positive number of messages"
+                    )))?,
+            ),
+            None => None,
+        };
+
+        // Determine how conversations are split into pages
+        let paginate = match paginate {
+            Some(pagination) => Some(Pagination::from_cli(pagination).ok_or(
+                RuntimeError::InvalidOptions(format!(
+                    "{pagination} is not a valid {OPTION_PAGINATE} mode! Must be one of <{SUPPORTED_PAGINATION_MODES}>"
+                )),
+            )?),
+            None => None,
+        };
+
         // Build query context
         let mut query_context = QueryContext::default();
         if let Some(start) = start_date {
-            if let Err(why) = query_context.set_start(start) {
+            let result = if start_exclusive {
+                query_context.set_start_exclusive(start)
+            } else {
+                query_context.set_start(start)
+            };
+            if let Err(why) = result {
                 return Err(RuntimeError::InvalidOptions(format!("{why}")));
             }
         }
         if let Some(end) = end_date {
-            if let Err(why) = query_context.set_end(end) {
+            let result = if end_inclusive {
+                query_context.set_end_inclusive(end)
+            } else {
+                query_context.set_end(end)
+            };
+            if let Err(why) = result {
+                return Err(RuntimeError::InvalidOptions(format!("{why}")));
+            }
+        }
+        for kind in excluded_kinds {
+            if let Err(why) = query_context.set_excluded_kind(kind) {
                 return Err(RuntimeError::InvalidOptions(format!("{why}")));
             }
         }
@@ -208,6 +499,15 @@ impl Options {
             }
         };
 
+        // Validate that the template directory exists, if provided
+        if let Some(path) = template_dir {
+            if !PathBuf::from(path).is_dir() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Supplied {OPTION_TEMPLATE_DIR} `{path}` is not a directory!"
+                )));
+            }
+        };
+
         // Warn the user that custom attachment roots have no effect on iOS backups
         if attachment_root.is_some() && platform == Platform::iOS {
             eprintln!(
@@ -241,6 +541,22 @@ impl Options {
             use_caller_id,
             platform,
             ignore_disk_space,
+            combine_chats,
+            embed_attachments,
+            embed_media_limit,
+            paginate,
+            template_dir: template_dir.map(PathBuf::from),
+            chat_filters,
+            participant_filters,
+            excluded_chat_filters,
+            excluded_sender_filters,
+            search: search.cloned(),
+            search_regex,
+            search_context,
+            index,
+            query: query.cloned(),
+            index_path: index_path.map(PathBuf::from),
+            query_limit,
         })
     }
 
@@ -251,6 +567,14 @@ impl Options {
             Platform::macOS => self.db_path.clone(),
         }
     }
+
+    /// Get the path to the search index, or the default location if none was provided
+    pub fn get_index_path(&self) -> PathBuf {
+        match &self.index_path {
+            Some(path) => path.clone(),
+            None => PathBuf::from(format!("{}/{DEFAULT_INDEX_DIR}", home())),
+        }
+    }
 }
 
 /// Ensure export path is empty or does not contain files of the existing export type
@@ -276,13 +600,8 @@ fn validate_path(
             // Ensure the directory exists and does not contain files of the same export type
             match resolved_path.read_dir() {
                 Ok(files) => {
-                    let export_type_extension = export_type.to_string();
                     for file in files.flatten() {
-                        if file
-                            .path()
-                            .extension()
-                            .is_some_and(|s| s.to_str().unwrap_or("") == export_type_extension)
-                        {
+                        if export_type.would_overwrite(&file.path()) {
                             return Err(RuntimeError::InvalidOptions(format!(
                                 "{path_word} export path {resolved_path:?} contains existing \"{export_type}\" export data!"
                             )));
@@ -367,17 +686,31 @@ fn get_command() -> Command {
             Arg::new(OPTION_START_DATE)
                 .short('s')
                 .long(OPTION_START_DATE)
-                .help("The start date filter\nOnly messages sent on or after this date will be included\n")
+                .help("The start date filter\nOnly messages sent on or after this date will be included\nAccepts a date, a local or RFC 3339 time, a date or time followed by a time zone,\na duration before now like `7d`, or a period like `today` or `last-month`\n")
                 .display_order(7)
-                .value_name("YYYY-MM-DD"),
+                .value_name("date"),
+        )
+        .arg(
+            Arg::new(OPTION_START_EXCLUSIVE)
+                .long(OPTION_START_EXCLUSIVE)
+                .help(format!("Exclude the time described by --{OPTION_START_DATE}, i.e. only include messages sent after that day or time\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(8),
         )
         .arg(
             Arg::new(OPTION_END_DATE)
                 .short('e')
                 .long(OPTION_END_DATE)
-                .help("The end date filter\nOnly messages sent before this date will be included\n")
-                .display_order(8)
-                .value_name("YYYY-MM-DD"),
+                .help(format!("The end date filter\nOnly messages sent before this date will be included\nAccepts the same formats as --{OPTION_START_DATE}\n"))
+                .display_order(9)
+                .value_name("date"),
+        )
+        .arg(
+            Arg::new(OPTION_END_INCLUSIVE)
+                .long(OPTION_END_INCLUSIVE)
+                .help(format!("Include the time described by --{OPTION_END_DATE}, i.e. also include messages sent on that day\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(10),
         )
         .arg(
             Arg::new(OPTION_DISABLE_LAZY_LOADING)
@@ -385,14 +718,14 @@ fn get_command() -> Command {
                 .long(OPTION_DISABLE_LAZY_LOADING)
                 .help("Do not include `loading=\"lazy\"` in HTML export `img` tags\nThis will make pages load slower but PDF generation work\n")
                 .action(ArgAction::SetTrue)
-                .display_order(9),
+                .display_order(11),
         )
         .arg(
             Arg::new(OPTION_CUSTOM_NAME)
                 .short('m')
                 .long(OPTION_CUSTOM_NAME)
                 .help(format!("Specify an optional custom name for the database owner's messages in exports\nConflicts with --{OPTION_USE_CALLER_ID}\n"))
-                .display_order(10)
+                .display_order(12)
         )
         .arg(
             Arg::new(OPTION_USE_CALLER_ID)
@@ -400,7 +733,7 @@ fn get_command() -> Command {
                 .long(OPTION_USE_CALLER_ID)
                 .help(format!("Use the database owner's caller ID in exports instead of \"Me\"\nConflicts with --{OPTION_CUSTOM_NAME}\n"))
                 .action(ArgAction::SetTrue)
-                .display_order(11)
+                .display_order(13)
         )
         .arg(
             Arg::new(OPTION_BYPASS_FREE_SPACE_CHECK)
@@ -408,7 +741,132 @@ fn get_command() -> Command {
                 .long(OPTION_BYPASS_FREE_SPACE_CHECK)
                 .help("Bypass the disk space check when exporting data\nBy default, exports will not run if there is not enough free disk space\n")
                 .action(ArgAction::SetTrue)
-                .display_order(12)
+                .display_order(14)
+        )
+        .arg(
+            Arg::new(OPTION_COMBINE_CHATS)
+                .long(OPTION_COMBINE_CHATS)
+                .help("Write every conversation to a single file instead of one file per chat\nOnly applies to `csv`, `xml`, and `mbox` exports\n")
+                .action(ArgAction::SetTrue)
+                .display_order(15)
+        )
+        .arg(
+            Arg::new(OPTION_EMBED_ATTACHMENTS)
+                .long(OPTION_EMBED_ATTACHMENTS)
+                .help(format!("Inline attachments in HTML exports as `data:` URIs so each conversation is a single self-contained file\nImages and stickers are always inlined; audio and video are only inlined when smaller than --{OPTION_EMBED_MEDIA_LIMIT}\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(16)
+        )
+        .arg(
+            Arg::new(OPTION_EMBED_MEDIA_LIMIT)
+                .long(OPTION_EMBED_MEDIA_LIMIT)
+                .help(format!("The largest audio or video attachment to inline, in megabytes\nIf omitted, audio and video are linked instead of inlined\nRequires --{OPTION_EMBED_ATTACHMENTS}\n"))
+                .display_order(17)
+                .value_name("megabytes"),
+        )
+        .arg(
+            Arg::new(OPTION_PAGINATE)
+                .long(OPTION_PAGINATE)
+                .help("Split each HTML conversation into multiple pages, linked by a table of contents\nPages can hold a calendar month, a calendar year, or a fixed number of messages\n")
+                .display_order(18)
+                .value_name(SUPPORTED_PAGINATION_MODES),
+        )
+        .arg(
+            Arg::new(OPTION_TEMPLATE_DIR)
+                .long(OPTION_TEMPLATE_DIR)
+                .help("Specify a directory of templates that change how HTML and TXT exports render messages\nTemplates are named for what they render, i.e. `message.html` or `attachment.txt`; a `style.css` replaces the HTML styles\n")
+                .display_order(19)
+                .value_name("path/to/templates"),
+        )
+        .arg(
+            Arg::new(OPTION_CHAT_FILTER)
+                .long(OPTION_CHAT_FILTER)
+                .help(format!("Only export the chat with this ID, identifier, or display name\nChats that are merged into the same conversation are included together\nCan be repeated; combined with --{OPTION_PARTICIPANT_FILTER}, chats matching either filter are exported\n"))
+                .action(ArgAction::Append)
+                .display_order(20)
+                .value_name("chat"),
+        )
+        .arg(
+            Arg::new(OPTION_PARTICIPANT_FILTER)
+                .long(OPTION_PARTICIPANT_FILTER)
+                .help("Only export chats that include the participant with this phone number or email address\nCan be repeated\n")
+                .action(ArgAction::Append)
+                .display_order(21)
+                .value_name("handle"),
+        )
+        .arg(
+            Arg::new(OPTION_EXCLUDE_CHAT)
+                .long(OPTION_EXCLUDE_CHAT)
+                .help("Do not export the chat with this ID, identifier, or display name\nChats that are merged into the same conversation are excluded together\nCan be repeated\n")
+                .action(ArgAction::Append)
+                .display_order(22)
+                .value_name("chat"),
+        )
+        .arg(
+            Arg::new(OPTION_EXCLUDE_SENDER)
+                .long(OPTION_EXCLUDE_SENDER)
+                .help("Do not export messages sent by the participant with this phone number or email address, i.e. a short code that sends login codes\nCan be repeated\n")
+                .action(ArgAction::Append)
+                .display_order(23)
+                .value_name("handle"),
+        )
+        .arg(
+            Arg::new(OPTION_EXCLUDE_KIND)
+                .long(OPTION_EXCLUDE_KIND)
+                .help("Do not export messages of these kinds, separated by commas\nExcluded tapbacks are also left off of the messages they react to\n")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .display_order(24)
+                .value_name(SUPPORTED_EXCLUDED_KINDS),
+        )
+        .arg(
+            Arg::new(OPTION_SEARCH)
+                .long(OPTION_SEARCH)
+                .help("Print the messages that contain this term, ignoring case, instead of exporting\nSearches message text, edit history, link previews, and app messages\n")
+                .display_order(25)
+                .value_name("term"),
+        )
+        .arg(
+            Arg::new(OPTION_SEARCH_REGEX)
+                .long(OPTION_SEARCH_REGEX)
+                .help(format!("Treat the --{OPTION_SEARCH} term as a regular expression\n"))
+                .action(ArgAction::SetTrue)
+                .display_order(26),
+        )
+        .arg(
+            Arg::new(OPTION_SEARCH_CONTEXT)
+                .long(OPTION_SEARCH_CONTEXT)
+                .help(format!("The number of messages to print before and after each search result\nIf omitted, the default is {DEFAULT_SEARCH_CONTEXT}\n"))
+                .display_order(27)
+                .value_name("n"),
+        )
+        .arg(
+            Arg::new(OPTION_INDEX)
+                .long(OPTION_INDEX)
+                .help("Add the messages sent, edited, or unsent since the last update to the search index and exit\nThe first update indexes every message\n")
+                .action(ArgAction::SetTrue)
+                .display_order(28),
+        )
+        .arg(
+            Arg::new(OPTION_QUERY)
+                .long(OPTION_QUERY)
+                .help("Update the search index, then print the messages that best match this query\nSupports phrases like \"dinner tonight\", fields like sender:alice, and date ranges like date:[2023-01-01T00:00:00Z TO 2023-02-01T00:00:00Z]\n")
+                .display_order(29)
+                .value_name("query"),
+        )
+        .arg(
+            Arg::new(OPTION_INDEX_PATH)
+                .long(OPTION_INDEX_PATH)
+                .help(format!("Specify an optional custom directory for the search index\nIf omitted, the default directory is {}/{DEFAULT_INDEX_DIR}\n", home()))
+                .display_order(30)
+                .value_name("path/to/index"),
+        )
+        .arg(
+            Arg::new(OPTION_QUERY_LIMIT)
+                .long(OPTION_QUERY_LIMIT)
+                .help(format!("The largest number of query results to print\nIf omitted, the default is {DEFAULT_QUERY_LIMIT}\n"))
+                .display_order(31)
+                .value_name("n"),
         )
 }
 
@@ -419,16 +877,19 @@ pub fn from_command_line() -> ArgMatches {
 
 #[cfg(test)]
 mod arg_tests {
-    use std::fs;
+    use std::{collections::BTreeSet, fs, path::PathBuf};
 
     use imessage_database::util::{
-        dirs::default_db_path, platform::Platform, query_context::QueryContext,
+        dirs::{default_db_path, home},
+        platform::Platform,
+        query_context::{MessageKind, QueryContext},
     };
 
     use crate::app::{
         attachment_manager::AttachmentManager,
         export_type::ExportType,
-        options::{get_command, validate_path, Options},
+        options::{get_command, validate_path, Options, DEFAULT_INDEX_DIR},
+        pagination::Pagination,
     };
 
     #[test]
@@ -455,6 +916,22 @@ mod arg_tests {
             use_caller_id: false,
             platform: Platform::default(),
             ignore_disk_space: false,
+            combine_chats: false,
+            embed_attachments: false,
+            embed_media_limit: None,
+            paginate: None,
+            template_dir: None,
+            chat_filters: vec![],
+            participant_filters: vec![],
+            excluded_chat_filters: vec![],
+            excluded_sender_filters: vec![],
+            search: None,
+            search_regex: false,
+            search_context: None,
+            index: false,
+            query: None,
+            index_path: None,
+            query_limit: None,
         };
 
         assert_eq!(actual, expected);
@@ -566,6 +1043,22 @@ mod arg_tests {
             use_caller_id: false,
             platform: Platform::default(),
             ignore_disk_space: false,
+            combine_chats: false,
+            embed_attachments: false,
+            embed_media_limit: None,
+            paginate: None,
+            template_dir: None,
+            chat_filters: vec![],
+            participant_filters: vec![],
+            excluded_chat_filters: vec![],
+            excluded_sender_filters: vec![],
+            search: None,
+            search_regex: false,
+            search_context: None,
+            index: false,
+            query: None,
+            index_path: None,
+            query_limit: None,
         };
 
         assert_eq!(actual, expected);
@@ -598,6 +1091,78 @@ mod arg_tests {
             use_caller_id: false,
             platform: Platform::default(),
             ignore_disk_space: false,
+            combine_chats: false,
+            embed_attachments: false,
+            embed_media_limit: None,
+            paginate: None,
+            template_dir: None,
+            chat_filters: vec![],
+            participant_filters: vec![],
+            excluded_chat_filters: vec![],
+            excluded_sender_filters: vec![],
+            search: None,
+            search_regex: false,
+            search_context: None,
+            index: false,
+            query: None,
+            index_path: None,
+            query_limit: None,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_export_csv_combined() {
+        // Cleanup existing temp data
+        let _ = fs::remove_file("/tmp/orphaned.csv");
+
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "csv",
+            "-o",
+            "/tmp",
+            "--combine-chats",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let tmp_dir = String::from("/tmp");
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            diagnostic: false,
+            export_type: Some(ExportType::Csv),
+            export_path: validate_path(Some(&tmp_dir), &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            combine_chats: true,
+            embed_attachments: false,
+            embed_media_limit: None,
+            paginate: None,
+            template_dir: None,
+            chat_filters: vec![],
+            participant_filters: vec![],
+            excluded_chat_filters: vec![],
+            excluded_sender_filters: vec![],
+            search: None,
+            search_regex: false,
+            search_context: None,
+            index: false,
+            query: None,
+            index_path: None,
+            query_limit: None,
         };
 
         assert_eq!(actual, expected);
@@ -643,9 +1208,9 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_end_date_path_no_export_type() {
+    fn cant_build_option_chat_filter_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-e", "2020-01-01"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--chat", "Family"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -656,9 +1221,9 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_invalid_date() {
+    fn cant_build_option_participant_filter_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-e", "2020-32-32"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--participant", "+15558675309"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -669,9 +1234,71 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_invalid_platform() {
+    fn can_build_option_chat_and_participant_filters() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-a", "iPad"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-o",
+            "/tmp/imessage-exporter-filters",
+            "--chat",
+            "Family",
+            "--chat",
+            "12",
+            "--participant",
+            "+15558675309",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.chat_filters, vec!["Family", "12"]);
+        assert_eq!(actual.participant_filters, vec!["+15558675309"]);
+    }
+
+    #[test]
+    fn can_build_option_exclusion_filters() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-o",
+            "/tmp/imessage-exporter-filters",
+            "--exclude-chat",
+            "Family",
+            "--exclude-sender",
+            "12345",
+            "--exclude",
+            "tapbacks,Announcements",
+            "--exclude",
+            "with-attachments",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.excluded_chat_filters, vec!["Family"]);
+        assert_eq!(actual.excluded_sender_filters, vec!["12345"]);
+        assert_eq!(
+            actual.query_context.excluded_kinds,
+            BTreeSet::from([
+                MessageKind::Tapback,
+                MessageKind::Announcement,
+                MessageKind::WithAttachments
+            ])
+        );
+    }
+
+    #[test]
+    fn cant_build_option_invalid_excluded_kind() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--exclude", "stickers"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -682,9 +1309,9 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_invalid_export_type() {
+    fn cant_build_option_exclusion_filters_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "pdf"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--exclude", "tapbacks"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -695,67 +1322,48 @@ mod arg_tests {
     }
 
     #[test]
-    fn can_build_option_custom_name() {
+    fn can_build_option_date_bounds() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name"];
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-o",
+            "/tmp/imessage-exporter-filters",
+            "-s",
+            "2020-01-01T09:30:00-08:00",
+            "--start-exclusive",
+            "-e",
+            "2020-01-01 America/Los_Angeles",
+            "--end-inclusive",
+        ];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
         let actual = Options::from_args(&args).unwrap();
 
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::default(),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: Some("Name".to_string()),
-            use_caller_id: false,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-        };
-
-        assert_eq!(actual, expected);
+        assert_eq!(actual.query_context.start, Some(599592600000000001));
+        assert_eq!(actual.query_context.end, Some(599644800000000000));
     }
 
     #[test]
-    fn can_build_option_caller_id() {
+    fn cant_build_option_start_exclusive_no_start_date() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-i"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--start-exclusive"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
         // Build the Options
-        let actual = Options::from_args(&args).unwrap();
-
-        // Expected data
-        let expected = Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::default(),
-            diagnostic: false,
-            export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, &None).unwrap(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: true,
-            platform: Platform::default(),
-            ignore_disk_space: false,
-        };
+        let actual = Options::from_args(&args);
 
-        assert_eq!(actual, expected);
+        assert!(actual.is_err());
     }
 
     #[test]
-    fn cant_build_option_custom_name_and_caller_id() {
+    fn cant_build_option_end_inclusive_no_end_date() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name", "-i"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--end-inclusive"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -766,9 +1374,9 @@ mod arg_tests {
     }
 
     #[test]
-    fn cant_build_option_caller_id_no_export() {
+    fn cant_build_option_end_date_path_no_export_type() {
         // Get matches from sample args
-        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name", "-i"];
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-e", "2020-01-01"];
         let command = get_command();
         let args = command.get_matches_from(cli_args);
 
@@ -777,9 +1385,525 @@ mod arg_tests {
 
         assert!(actual.is_err());
     }
-}
 
-#[cfg(test)]
+    #[test]
+    fn cant_build_option_invalid_date() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "-e", "2020-32-32"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_platform() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-a", "iPad"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "pdf"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_custom_name() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            diagnostic: false,
+            export_type: Some(ExportType::Txt),
+            export_path: validate_path(None, &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: Some("Name".to_string()),
+            use_caller_id: false,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            combine_chats: false,
+            embed_attachments: false,
+            embed_media_limit: None,
+            paginate: None,
+            template_dir: None,
+            chat_filters: vec![],
+            participant_filters: vec![],
+            excluded_chat_filters: vec![],
+            excluded_sender_filters: vec![],
+            search: None,
+            search_regex: false,
+            search_context: None,
+            index: false,
+            query: None,
+            index_path: None,
+            query_limit: None,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_caller_id() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        // Expected data
+        let expected = Options {
+            db_path: default_db_path(),
+            attachment_root: None,
+            attachment_manager: AttachmentManager::default(),
+            diagnostic: false,
+            export_type: Some(ExportType::Txt),
+            export_path: validate_path(None, &None).unwrap(),
+            query_context: QueryContext::default(),
+            no_lazy: false,
+            custom_name: None,
+            use_caller_id: true,
+            platform: Platform::default(),
+            ignore_disk_space: false,
+            combine_chats: false,
+            embed_attachments: false,
+            embed_media_limit: None,
+            paginate: None,
+            template_dir: None,
+            chat_filters: vec![],
+            participant_filters: vec![],
+            excluded_chat_filters: vec![],
+            excluded_sender_filters: vec![],
+            search: None,
+            search_regex: false,
+            search_context: None,
+            index: false,
+            query: None,
+            index_path: None,
+            query_limit: None,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_option_embed_attachments() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--embed-attachments",
+            "--embed-media-limit",
+            "2.5",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.embed_attachments);
+        assert_eq!(actual.embed_media_limit, Some(2621440));
+    }
+
+    #[test]
+    fn cant_build_option_embed_media_limit_without_embed_attachments() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--embed-media-limit",
+            "5",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_embed_media_limit() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--embed-attachments",
+            "--embed-media-limit",
+            "five",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_paginate() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--paginate", "month"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.paginate, Some(Pagination::Month));
+    }
+
+    #[test]
+    fn cant_build_option_invalid_paginate() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "html", "--paginate", "week"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_missing_template_dir() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--template-dir",
+            "/does/not/exist",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_custom_name_and_caller_id() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_caller_id_no_export() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "-m", "Name", "-i"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_search() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "--search",
+            "dinner",
+            "--context",
+            "5",
+            "-s",
+            "yesterday",
+            "--chat",
+            "Family",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.export_type, None);
+        assert_eq!(actual.search, Some("dinner".to_string()));
+        assert!(!actual.search_regex);
+        assert_eq!(actual.search_context, Some(5));
+        assert!(actual.query_context.start.is_some());
+        assert_eq!(actual.chat_filters, vec!["Family"]);
+    }
+
+    #[test]
+    fn can_build_option_search_regex() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--search", "din+er", "--regex"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert_eq!(actual.search, Some("din+er".to_string()));
+        assert!(actual.search_regex);
+        assert_eq!(actual.search_context, None);
+    }
+
+    #[test]
+    fn cant_build_option_search_invalid_regex() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--search", "(", "--regex"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_search_invalid_context() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "--search",
+            "dinner",
+            "--context",
+            "few",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_search_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--search", "dinner", "-f", "txt"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_search_diagnostic() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--search", "dinner", "-d"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_regex_no_search() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--regex"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn can_build_option_index() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--index"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(actual.index);
+        assert_eq!(actual.query, None);
+        assert_eq!(
+            actual.get_index_path(),
+            PathBuf::from(format!("{}/{DEFAULT_INDEX_DIR}", home()))
+        );
+    }
+
+    #[test]
+    fn can_build_option_query() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "--query",
+            "sender:alice \"dinner tonight\"",
+            "--index-path",
+            "/tmp/index",
+            "--limit",
+            "10",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args).unwrap();
+
+        assert!(!actual.index);
+        assert_eq!(
+            actual.query,
+            Some("sender:alice \"dinner tonight\"".to_string())
+        );
+        assert_eq!(actual.get_index_path(), PathBuf::from("/tmp/index"));
+        assert_eq!(actual.query_limit, Some(10));
+    }
+
+    #[test]
+    fn cant_build_option_index_path_no_index() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--index-path", "/tmp/index"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_limit_no_query() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--index", "--limit", "10"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_limit() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--query", "dinner", "--limit", "0"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_index_export_type() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--index", "-f", "txt"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_query_search() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec![
+            "imessage-exporter",
+            "--query",
+            "dinner",
+            "--search",
+            "dinner",
+        ];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_query_diagnostic() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "--query", "dinner", "-d"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn cant_build_option_context_no_search() {
+        // Get matches from sample args
+        let cli_args: Vec<&str> = vec!["imessage-exporter", "-f", "txt", "--context", "3"];
+        let command = get_command();
+        let args = command.get_matches_from(cli_args);
+
+        // Build the Options
+        let actual = Options::from_args(&args);
+
+        assert!(actual.is_err());
+    }
+}
+
+#[cfg(test)]
 mod path_tests {
     use std::fs;
     use std::io::Write;
@@ -845,6 +1969,21 @@ mod path_tests {
         fs::remove_file(&tmp).unwrap();
     }
 
+    #[test]
+    fn cant_validate_links_over_csv() {
+        let tmp = String::from("/tmp/imessage-exporter-links-csv");
+        fs::create_dir_all(&tmp).unwrap();
+        fs::File::create(PathBuf::from(&tmp).join("links.csv")).unwrap();
+
+        let export_path = Some(&tmp);
+        let export_type = Some(ExportType::Links);
+
+        let result = validate_path(export_path, &export_type.as_ref());
+
+        assert!(result.is_err());
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+
     #[test]
     fn can_validate_none() {
         let export_path = None;
@@ -0,0 +1,79 @@
+/*!
+ Contains data structures used to describe how HTML exports are split into pages.
+*/
+
+use std::fmt::Display;
+
+/// Represents the ways a conversation can be split across multiple HTML pages
+#[derive(PartialEq, Eq, Debug)]
+pub enum Pagination {
+    /// Start a new page for each calendar month
+    Month,
+    /// Start a new page for each calendar year
+    Year,
+    /// Start a new page after the given number of messages
+    Messages(usize),
+}
+
+impl Pagination {
+    /// Given user's input, return a variant if the input matches one
+    pub fn from_cli(pagination: &str) -> Option<Self> {
+        match pagination.to_lowercase().as_str() {
+            "month" => Some(Self::Month),
+            "year" => Some(Self::Year),
+            count => count
+                .parse::<usize>()
+                .ok()
+                .filter(|count| *count > 0)
+                .map(Self::Messages),
+        }
+    }
+}
+
+impl Display for Pagination {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pagination::Month => write!(fmt, "month"),
+            Pagination::Year => write!(fmt, "year"),
+            Pagination::Messages(count) => write!(fmt, "{count}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::pagination::Pagination;
+
+    #[test]
+    fn can_parse_month_any_case() {
+        assert_eq!(Pagination::from_cli("month"), Some(Pagination::Month));
+        assert_eq!(Pagination::from_cli("MONTH"), Some(Pagination::Month));
+        assert_eq!(Pagination::from_cli("MoNtH"), Some(Pagination::Month));
+    }
+
+    #[test]
+    fn can_parse_year_any_case() {
+        assert_eq!(Pagination::from_cli("year"), Some(Pagination::Year));
+        assert_eq!(Pagination::from_cli("YEAR"), Some(Pagination::Year));
+    }
+
+    #[test]
+    fn can_parse_message_count() {
+        assert_eq!(
+            Pagination::from_cli("5000"),
+            Some(Pagination::Messages(5000))
+        );
+    }
+
+    #[test]
+    fn cant_parse_zero_messages() {
+        assert_eq!(Pagination::from_cli("0"), None);
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert_eq!(Pagination::from_cli("week"), None);
+        assert_eq!(Pagination::from_cli("-5"), None);
+        assert_eq!(Pagination::from_cli(""), None);
+    }
+}
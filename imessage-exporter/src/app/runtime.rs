@@ -1,7 +1,7 @@
 use std::{
     cmp::min,
     collections::{BTreeSet, HashMap, HashSet},
-    fs::create_dir_all,
+    fs::{create_dir_all, metadata, read},
     path::PathBuf,
 };
 
@@ -11,10 +11,20 @@ use rusqlite::Connection;
 
 use crate::{
     app::{
-        attachment_manager::AttachmentManager, converter::Converter, error::RuntimeError,
-        export_type::ExportType, options::Options, sanitizers::sanitize_filename,
+        attachment_manager::AttachmentManager,
+        converter::Converter,
+        error::RuntimeError,
+        export_type::ExportType,
+        index::{SearchIndex, DEFAULT_QUERY_LIMIT},
+        options::{
+            Options, OPTION_CHAT_FILTER, OPTION_EXCLUDE_CHAT, OPTION_EXCLUDE_SENDER,
+            OPTION_PARTICIPANT_FILTER,
+        },
+        sanitizers::sanitize_filename,
+        search::Search,
     },
-    Exporter, HTML, TXT,
+    Contacts, Exporter, Links, Locations, Parquet, SQLite, Slack, Telegram, CSV, HTML, JSON, MBOX,
+    MD, TXT, XML,
 };
 
 use imessage_database::{
@@ -30,7 +40,7 @@ use imessage_database::{
             MAX_LENGTH, ME, ORPHANED, UNKNOWN,
         },
     },
-    util::{dates::get_offset, size::format_file_size},
+    util::{dates::get_offset, query_context::MessageKind, size::format_file_size},
 };
 
 /// Stores the application state and handles application lifecycle
@@ -114,6 +124,53 @@ impl Config {
         }
     }
 
+    /// Read the contents of an attachment
+    ///
+    /// If the attachment was copied, read the copy so converted files are used
+    pub fn message_attachment_bytes(&self, attachment: &Attachment) -> Option<Vec<u8>> {
+        match &attachment.copied_path {
+            Some(path) => read(path).ok(),
+            None => attachment
+                .as_bytes(
+                    &self.options.platform,
+                    &self.options.db_path,
+                    self.options.attachment_root.as_deref(),
+                )
+                .ok()
+                .flatten(),
+        }
+    }
+
+    /// Get the size of an attachment in bytes
+    ///
+    /// If the attachment was copied, use the size of the copy
+    /// if not, default to the size stored in the database
+    pub fn message_attachment_size(&self, attachment: &Attachment) -> u64 {
+        attachment
+            .copied_path
+            .as_ref()
+            .and_then(|path| metadata(path).ok())
+            .map_or(attachment.total_bytes, |meta| meta.len())
+    }
+
+    /// Get the MIME type of an attachment, accounting for any conversion done when it was copied
+    pub fn message_attachment_content_type<'b>(&self, attachment: &'b Attachment) -> &'b str {
+        let converted = attachment
+            .copied_path
+            .as_ref()
+            .and_then(|path| path.extension())
+            .and_then(|extension| extension.to_str());
+        match converted {
+            Some("jpeg" | "jpg") => "image/jpeg",
+            Some("png") => "image/png",
+            Some("gif") => "image/gif",
+            _ => attachment
+                .mime_type
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+        }
+    }
+
     /// Get a relative path for the provided file.
     pub fn relative_path(&self, path: PathBuf) -> Option<String> {
         if let Ok(relative_path) = path.strip_prefix(&self.options.export_path) {
@@ -221,7 +278,7 @@ impl Config {
             AttachmentManager::Efficient => None,
         };
 
-        Ok(Config {
+        let mut config = Config {
             chatrooms,
             real_chatrooms: ChatToHandle::dedupe(&chatroom_participants),
             chatroom_participants,
@@ -232,7 +289,224 @@ impl Config {
             offset: get_offset(),
             db: conn,
             converter,
-        })
+        };
+
+        // Chat and participant filters can only be resolved once the caches are built
+        if let Some(chat_ids) = config.selected_chat_ids()? {
+            config.options.query_context.set_selected_chat_ids(chat_ids);
+        }
+        let excluded_chat_ids = config.excluded_chat_ids()?;
+        config
+            .options
+            .query_context
+            .set_excluded_chat_ids(excluded_chat_ids);
+        let excluded_handle_ids = config.excluded_handle_ids()?;
+        config.exclude_tapbacks(&excluded_handle_ids);
+        config
+            .options
+            .query_context
+            .set_excluded_handle_ids(excluded_handle_ids);
+
+        // Exporters that write every chat or participant read them from the caches
+        if config.options.query_context.selected_chat_ids.is_some()
+            || !config.options.query_context.excluded_chat_ids.is_empty()
+        {
+            let chat_senders =
+                ChatToHandle::cache_senders(&config.db).map_err(RuntimeError::DatabaseError)?;
+            config.prune_caches(&chat_senders);
+        }
+
+        Ok(config)
+    }
+
+    /// Remove the chats and participants that the filters leave out of the export from the caches
+    ///
+    /// People are only removed if every chat they are a member of or sent messages to is removed, since someone who
+    /// left an exported chat is no longer one of its members.
+    pub fn prune_caches(&mut self, chat_senders: &HashMap<i32, BTreeSet<i32>>) {
+        let context = &self.options.query_context;
+        let is_selected = |chat_id: &i32| {
+            context
+                .selected_chat_ids
+                .as_ref()
+                .is_none_or(|chat_ids| chat_ids.contains(chat_id))
+                && !context.excluded_chat_ids.contains(chat_id)
+        };
+
+        let mut kept_handle_ids: BTreeSet<i32> = BTreeSet::new();
+        let mut removed_handle_ids: BTreeSet<i32> = BTreeSet::new();
+        for (chat_id, handle_ids) in self.chatroom_participants.iter().chain(chat_senders) {
+            if is_selected(chat_id) {
+                kept_handle_ids.extend(handle_ids);
+            } else {
+                removed_handle_ids.extend(handle_ids);
+            }
+        }
+        let is_kept = |handle_id: &i32| {
+            kept_handle_ids.contains(handle_id) || !removed_handle_ids.contains(handle_id)
+        };
+
+        self.chatrooms.retain(|chat_id, _| is_selected(chat_id));
+        self.real_chatrooms
+            .retain(|chat_id, _| is_selected(chat_id));
+        self.chatroom_participants
+            .retain(|chat_id, _| is_selected(chat_id));
+        self.participants.retain(|handle_id, _| is_kept(handle_id));
+        self.real_participants
+            .retain(|handle_id, _| is_kept(handle_id));
+    }
+
+    /// Determine if a participant is written to exports that list every participant
+    ///
+    /// Excluded senders stay in the participant cache so the names of the chats they are in do not change.
+    pub fn is_exported_participant(&self, handle_id: &i32) -> bool {
+        self.participants.contains_key(handle_id)
+            && !self
+                .options
+                .query_context
+                .excluded_handle_ids
+                .contains(handle_id)
+    }
+
+    /// Resolve the chat and participant filters to the IDs of every chat they select
+    ///
+    /// Chats that are deduplicated into the same conversation as a selected chat are also selected.
+    fn selected_chat_ids(&self) -> Result<Option<BTreeSet<i32>>, RuntimeError> {
+        if self.options.chat_filters.is_empty() && self.options.participant_filters.is_empty() {
+            return Ok(None);
+        }
+
+        let mut chat_ids = BTreeSet::new();
+
+        for filter in &self.options.chat_filters {
+            chat_ids.extend(self.matching_chat_ids(filter, OPTION_CHAT_FILTER)?);
+        }
+
+        for filter in &self.options.participant_filters {
+            let handle_ids = self.matching_handle_ids(filter, OPTION_PARTICIPANT_FILTER)?;
+            chat_ids.extend(
+                self.chatroom_participants
+                    .iter()
+                    .filter(|(_, handles)| {
+                        handles
+                            .iter()
+                            .any(|handle_id| handle_ids.contains(handle_id))
+                    })
+                    .map(|(chat_id, _)| *chat_id),
+            );
+        }
+
+        Ok(Some(chat_ids))
+    }
+
+    /// Resolve the chat exclusion filters to the IDs of every chat they exclude
+    fn excluded_chat_ids(&self) -> Result<BTreeSet<i32>, RuntimeError> {
+        let mut chat_ids = BTreeSet::new();
+        for filter in &self.options.excluded_chat_filters {
+            chat_ids.extend(self.matching_chat_ids(filter, OPTION_EXCLUDE_CHAT)?);
+        }
+        Ok(chat_ids)
+    }
+
+    /// Resolve the sender exclusion filters to the IDs of every handle they exclude
+    fn excluded_handle_ids(&self) -> Result<BTreeSet<i32>, RuntimeError> {
+        let mut handle_ids = BTreeSet::new();
+        for filter in &self.options.excluded_sender_filters {
+            handle_ids.extend(self.matching_handle_ids(filter, OPTION_EXCLUDE_SENDER)?);
+        }
+        Ok(handle_ids)
+    }
+
+    /// Find the IDs of the chats with the given ID, identifier, or display name
+    ///
+    /// Chats that are deduplicated into the same conversation as a matching chat also match.
+    fn matching_chat_ids(&self, filter: &str, option: &str) -> Result<BTreeSet<i32>, RuntimeError> {
+        let mut chat_ids: BTreeSet<i32> = self
+            .chatrooms
+            .values()
+            .filter(|chat| {
+                chat.rowid.to_string() == filter
+                    || chat.chat_identifier.eq_ignore_ascii_case(filter)
+                    || chat
+                        .display_name()
+                        .is_some_and(|name| name.eq_ignore_ascii_case(filter))
+            })
+            .map(|chat| chat.rowid)
+            .collect();
+        if chat_ids.is_empty() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "No chat matches `--{option} {filter}`!"
+            )));
+        }
+
+        // Include every chat that is merged into the same conversation as a matching chat
+        let real_ids: BTreeSet<&i32> = chat_ids
+            .iter()
+            .filter_map(|chat_id| self.real_chatrooms.get(chat_id))
+            .collect();
+        chat_ids.extend(
+            self.real_chatrooms
+                .iter()
+                .filter(|(_, real_id)| real_ids.contains(real_id))
+                .map(|(chat_id, _)| *chat_id),
+        );
+
+        Ok(chat_ids)
+    }
+
+    /// Find the IDs of the handles with the given phone number or email address
+    ///
+    /// A person can have several handles, so every handle that belongs to the same person also matches.
+    fn matching_handle_ids(
+        &self,
+        filter: &str,
+        option: &str,
+    ) -> Result<BTreeSet<i32>, RuntimeError> {
+        let mut handle_ids: BTreeSet<i32> = self
+            .participants
+            .iter()
+            .filter(|(_, handle)| handle.eq_ignore_ascii_case(filter))
+            .map(|(handle_id, _)| *handle_id)
+            .collect();
+        if handle_ids.is_empty() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "No participant matches `--{option} {filter}`!"
+            )));
+        }
+
+        let real_ids: BTreeSet<&i32> = handle_ids
+            .iter()
+            .filter_map(|handle_id| self.real_participants.get(handle_id))
+            .collect();
+        handle_ids.extend(
+            self.real_participants
+                .iter()
+                .filter(|(_, real_id)| real_ids.contains(real_id))
+                .map(|(handle_id, _)| *handle_id),
+        );
+
+        Ok(handle_ids)
+    }
+
+    /// Remove excluded tapbacks from the cache, since tapbacks are rendered with the messages they react to
+    fn exclude_tapbacks(&mut self, excluded_handle_ids: &BTreeSet<i32>) {
+        if self
+            .options
+            .query_context
+            .excluded_kinds
+            .contains(&MessageKind::Tapback)
+        {
+            self.tapbacks.clear();
+        } else if !excluded_handle_ids.is_empty() {
+            for tapbacks in self.tapbacks.values_mut().flat_map(HashMap::values_mut) {
+                tapbacks.retain(|tapback| {
+                    tapback.is_from_me()
+                        || !tapback
+                            .handle_id
+                            .is_some_and(|handle_id| excluded_handle_ids.contains(&handle_id))
+                });
+            }
+        }
     }
 
     /// Ensure there is available disk space for the requested export
@@ -309,7 +583,7 @@ impl Config {
     }
 
     /// Start the app given the provided set of options. This will either run
-    /// diagnostic tests on the database or export data to the specified file type.
+    /// diagnostic tests on the database, search or index its messages, or export data to the specified file type.
     ///
     // # Example:
     ///
@@ -327,6 +601,17 @@ impl Config {
     pub fn start(&self) -> Result<(), RuntimeError> {
         if self.options.diagnostic {
             self.run_diagnostic().map_err(RuntimeError::DatabaseError)?;
+        } else if let Some(term) = &self.options.search {
+            // Results are printed as they are found, so there is nothing left to report
+            return Search::new(self, term)?.run();
+        } else if self.options.index || self.options.query.is_some() {
+            // Queries update the index first so they include the latest messages
+            let index = SearchIndex::open(self)?;
+            index.update()?;
+            if let Some(query) = &self.options.query {
+                let limit = self.options.query_limit.unwrap_or(DEFAULT_QUERY_LIMIT);
+                return index.query(query, limit);
+            }
         } else if let Some(export_type) = &self.options.export_type {
             // Ensure the path we want to export to exists
             create_dir_all(&self.options.export_path).map_err(RuntimeError::DiskError)?;
@@ -352,6 +637,42 @@ impl Config {
                 ExportType::Txt => {
                     TXT::new(self)?.iter_messages()?;
                 }
+                ExportType::Json | ExportType::Ndjson => {
+                    JSON::new(self)?.iter_messages()?;
+                }
+                ExportType::Csv => {
+                    CSV::new(self)?.iter_messages()?;
+                }
+                ExportType::Markdown => {
+                    MD::new(self)?.iter_messages()?;
+                }
+                ExportType::Xml => {
+                    XML::new(self)?.iter_messages()?;
+                }
+                ExportType::Sqlite => {
+                    SQLite::new(self)?.iter_messages()?;
+                }
+                ExportType::Parquet => {
+                    Parquet::new(self)?.iter_messages()?;
+                }
+                ExportType::Mbox => {
+                    MBOX::new(self)?.iter_messages()?;
+                }
+                ExportType::Telegram => {
+                    Telegram::new(self)?.iter_messages()?;
+                }
+                ExportType::Slack => {
+                    Slack::new(self)?.iter_messages()?;
+                }
+                ExportType::Contacts => {
+                    Contacts::new(self)?.iter_messages()?;
+                }
+                ExportType::Geojson | ExportType::Kml | ExportType::Gpx => {
+                    Locations::new(self)?.iter_messages()?;
+                }
+                ExportType::Links => {
+                    Links::new(self)?.iter_messages()?;
+                }
             }
         }
         println!("Done!");
@@ -382,35 +703,12 @@ impl Config {
 
 #[cfg(test)]
 mod filename_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
-    use imessage_database::{
-        tables::{
-            chat::Chat,
-            table::{get_connection, MAX_LENGTH},
-        },
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
-    };
-    use std::{
-        collections::{BTreeSet, HashMap},
-        path::PathBuf,
+    use crate::{app::fixtures::fake_options, Config, Options};
+    use imessage_database::tables::{
+        chat::Chat,
+        table::{get_connection, MAX_LENGTH},
     };
-
-    fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::new(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
+    use std::collections::{BTreeSet, HashMap};
 
     fn fake_chat() -> Chat {
         Chat {
@@ -619,29 +917,12 @@ mod filename_tests {
 
 #[cfg(test)]
 mod who_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
-    use imessage_database::{
-        tables::{chat::Chat, messages::Message, table::get_connection},
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
+    use crate::{
+        app::fixtures::{blank, fake_options},
+        Config, Options,
     };
-    use std::{collections::HashMap, path::PathBuf};
-
-    fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::new(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
+    use imessage_database::tables::{chat::Chat, table::get_connection};
+    use std::collections::HashMap;
 
     fn fake_chat() -> Chat {
         Chat {
@@ -668,43 +949,6 @@ mod who_tests {
         }
     }
 
-    fn blank() -> Message {
-        Message {
-            rowid: i32::default(),
-            guid: String::default(),
-            text: None,
-            service: Some("iMessage".to_string()),
-            handle_id: Some(i32::default()),
-            destination_caller_id: None,
-            subject: None,
-            date: i64::default(),
-            date_read: i64::default(),
-            date_delivered: i64::default(),
-            is_from_me: false,
-            is_read: false,
-            item_type: 0,
-            other_handle: 0,
-            share_status: false,
-            share_direction: false,
-            group_title: None,
-            group_action_type: 0,
-            associated_message_guid: None,
-            associated_message_type: Some(i32::default()),
-            balloon_bundle_id: None,
-            expressive_send_style_id: None,
-            thread_originator_guid: None,
-            thread_originator_part: None,
-            date_edited: 0,
-            associated_message_emoji: None,
-            chat_id: None,
-            num_attachments: 0,
-            deleted_from: None,
-            num_replies: 0,
-            components: None,
-            edited_parts: None,
-        }
-    }
-
     #[test]
     fn can_get_who_them() {
         let options = fake_options();
@@ -874,30 +1118,10 @@ mod who_tests {
 
 #[cfg(test)]
 mod directory_tests {
-    use crate::{app::attachment_manager::AttachmentManager, Config, Options};
-    use imessage_database::{
-        tables::{attachment::Attachment, table::get_connection},
-        util::{dirs::default_db_path, platform::Platform, query_context::QueryContext},
-    };
+    use crate::{app::fixtures::fake_options, Config, Options};
+    use imessage_database::tables::{attachment::Attachment, table::get_connection};
     use std::{collections::HashMap, path::PathBuf};
 
-    fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::new(),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
-
     fn fake_app(options: Options) -> Config {
         let connection = get_connection(&options.db_path).unwrap();
         Config {
@@ -1015,4 +1239,350 @@ mod directory_tests {
         let expected = String::from("a/b/c/d.jpg");
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn can_get_content_type_not_copied() {
+        let options = fake_options();
+        let app = fake_app(options);
+
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("image/heic".to_string());
+
+        assert_eq!(
+            app.message_attachment_content_type(&attachment),
+            "image/heic"
+        );
+    }
+
+    #[test]
+    fn can_get_content_type_converted() {
+        let options = fake_options();
+        let app = fake_app(options);
+
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("image/heic".to_string());
+        attachment.copied_path = Some(PathBuf::from("attachments/1/d.jpg"));
+
+        assert_eq!(
+            app.message_attachment_content_type(&attachment),
+            "image/jpeg"
+        );
+    }
+
+    #[test]
+    fn can_get_content_type_missing() {
+        let options = fake_options();
+        let app = fake_app(options);
+
+        let mut attachment = fake_attachment();
+        attachment.mime_type = None;
+
+        assert_eq!(
+            app.message_attachment_content_type(&attachment),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn can_get_size_not_copied() {
+        let options = fake_options();
+        let app = fake_app(options);
+
+        let mut attachment = fake_attachment();
+        attachment.total_bytes = 100;
+
+        assert_eq!(app.message_attachment_size(&attachment), 100);
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use crate::{
+        app::fixtures::{blank, fake_options},
+        Config, Options,
+    };
+    use imessage_database::tables::{chat::Chat, table::get_connection};
+    use std::collections::{BTreeSet, HashMap};
+
+    fn fake_chat(rowid: i32, chat_identifier: &str, display_name: Option<&str>) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: chat_identifier.to_string(),
+            service_name: Some(String::new()),
+            display_name: display_name.map(str::to_string),
+        }
+    }
+
+    /// Chats 1 and 2 are the same conversation with `+15558675309`, whose handles 10 and 11 are the same person;
+    /// chat 3 is a group with that person and `jane@example.com`
+    fn fake_app(options: Options) -> Config {
+        let connection = get_connection(&options.db_path).unwrap();
+        let mut app = Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            tapbacks: HashMap::new(),
+            options,
+            offset: 0,
+            db: connection,
+            converter: None,
+        };
+
+        app.chatrooms.insert(1, fake_chat(1, "+15558675309", None));
+        app.chatrooms.insert(2, fake_chat(2, "+15558675309", None));
+        app.chatrooms
+            .insert(3, fake_chat(3, "chat123", Some("Book Club")));
+        app.real_chatrooms.insert(1, 0);
+        app.real_chatrooms.insert(2, 0);
+        app.real_chatrooms.insert(3, 1);
+
+        app.participants.insert(10, "+15558675309".to_string());
+        app.participants
+            .insert(11, "Person@Example.com".to_string());
+        app.participants.insert(12, "jane@example.com".to_string());
+        app.real_participants.insert(10, 0);
+        app.real_participants.insert(11, 0);
+        app.real_participants.insert(12, 1);
+
+        app.chatroom_participants.insert(1, BTreeSet::from([10]));
+        app.chatroom_participants.insert(2, BTreeSet::from([11]));
+        app.chatroom_participants
+            .insert(3, BTreeSet::from([11, 12]));
+
+        app
+    }
+
+    #[test]
+    fn can_skip_without_filters() {
+        let options = fake_options();
+        let app = fake_app(options);
+
+        assert_eq!(app.selected_chat_ids().unwrap(), None);
+    }
+
+    #[test]
+    fn can_select_chat_by_id() {
+        let mut options = fake_options();
+        options.chat_filters = vec!["3".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(app.selected_chat_ids().unwrap(), Some(BTreeSet::from([3])));
+    }
+
+    #[test]
+    fn can_select_chat_by_display_name() {
+        let mut options = fake_options();
+        options.chat_filters = vec!["book club".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(app.selected_chat_ids().unwrap(), Some(BTreeSet::from([3])));
+    }
+
+    #[test]
+    fn can_select_deduplicated_chats() {
+        let mut options = fake_options();
+        options.chat_filters = vec!["2".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(
+            app.selected_chat_ids().unwrap(),
+            Some(BTreeSet::from([1, 2]))
+        );
+    }
+
+    #[test]
+    fn can_select_chats_by_identifier() {
+        let mut options = fake_options();
+        options.chat_filters = vec!["+15558675309".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(
+            app.selected_chat_ids().unwrap(),
+            Some(BTreeSet::from([1, 2]))
+        );
+    }
+
+    #[test]
+    fn can_select_chats_by_deduplicated_participant() {
+        let mut options = fake_options();
+        options.participant_filters = vec!["+15558675309".to_string()];
+        let app = fake_app(options);
+
+        // Handle 11 belongs to the same person as handle 10, so chats 2 and 3 are included
+        assert_eq!(
+            app.selected_chat_ids().unwrap(),
+            Some(BTreeSet::from([1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn can_select_chats_by_participant() {
+        let mut options = fake_options();
+        options.participant_filters = vec!["JANE@example.com".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(app.selected_chat_ids().unwrap(), Some(BTreeSet::from([3])));
+    }
+
+    #[test]
+    fn can_combine_filters() {
+        let mut options = fake_options();
+        options.chat_filters = vec!["1".to_string()];
+        options.participant_filters = vec!["jane@example.com".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(
+            app.selected_chat_ids().unwrap(),
+            Some(BTreeSet::from([1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn cant_select_missing_chat() {
+        let mut options = fake_options();
+        options.chat_filters = vec!["Not a chat".to_string()];
+        let app = fake_app(options);
+
+        assert!(app.selected_chat_ids().is_err());
+    }
+
+    #[test]
+    fn can_exclude_deduplicated_chats() {
+        let mut options = fake_options();
+        options.excluded_chat_filters = vec!["1".to_string(), "Book Club".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(app.excluded_chat_ids().unwrap(), BTreeSet::from([1, 2, 3]));
+    }
+
+    #[test]
+    fn can_exclude_deduplicated_senders() {
+        let mut options = fake_options();
+        options.excluded_sender_filters = vec!["person@example.com".to_string()];
+        let app = fake_app(options);
+
+        assert_eq!(app.excluded_handle_ids().unwrap(), BTreeSet::from([10, 11]));
+    }
+
+    #[test]
+    fn cant_exclude_missing_sender() {
+        let mut options = fake_options();
+        options.excluded_sender_filters = vec!["+15550000000".to_string()];
+        let app = fake_app(options);
+
+        assert!(app.excluded_handle_ids().is_err());
+    }
+
+    #[test]
+    fn can_exclude_tapbacks_from_senders() {
+        let options = fake_options();
+        let mut app = fake_app(options);
+
+        let mut from_excluded = blank();
+        from_excluded.handle_id = Some(12);
+        let mut from_other = blank();
+        from_other.handle_id = Some(10);
+        app.tapbacks.insert(
+            "ABC".to_string(),
+            HashMap::from([(0, vec![from_excluded, from_other])]),
+        );
+
+        app.exclude_tapbacks(&BTreeSet::from([12]));
+        assert_eq!(app.tapbacks["ABC"][&0].len(), 1);
+        assert_eq!(app.tapbacks["ABC"][&0][0].handle_id, Some(10));
+    }
+
+    #[test]
+    fn can_exclude_all_tapbacks() {
+        let mut options = fake_options();
+        options.query_context.set_excluded_kind("tapbacks").unwrap();
+        let mut app = fake_app(options);
+        app.tapbacks
+            .insert("ABC".to_string(), HashMap::from([(0, vec![blank()])]));
+
+        app.exclude_tapbacks(&BTreeSet::new());
+        assert!(app.tapbacks.is_empty());
+    }
+
+    #[test]
+    fn can_prune_unselected_chats() {
+        let mut options = fake_options();
+        options
+            .query_context
+            .set_selected_chat_ids(BTreeSet::from([3]));
+        let mut app = fake_app(options);
+        app.participants.insert(13, "left@example.com".to_string());
+        app.participants.insert(14, "other@example.com".to_string());
+
+        // Handle 13 left the book club, and handle 14 only sent messages to chat 1
+        let chat_senders = HashMap::from([(3, BTreeSet::from([13])), (1, BTreeSet::from([14]))]);
+        app.prune_caches(&chat_senders);
+
+        assert_eq!(
+            app.chatrooms.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&3])
+        );
+        assert_eq!(
+            app.real_chatrooms.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&3])
+        );
+        assert_eq!(
+            app.chatroom_participants.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&3])
+        );
+        assert_eq!(
+            app.participants.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&11, &12, &13])
+        );
+        assert_eq!(
+            app.real_participants.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&11, &12])
+        );
+    }
+
+    #[test]
+    fn can_prune_excluded_chats() {
+        let mut options = fake_options();
+        options
+            .query_context
+            .set_excluded_chat_ids(BTreeSet::from([1, 2]));
+        let mut app = fake_app(options);
+
+        app.prune_caches(&HashMap::new());
+
+        assert_eq!(
+            app.chatrooms.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&3])
+        );
+        assert_eq!(
+            app.participants.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&11, &12])
+        );
+    }
+
+    #[test]
+    fn can_skip_excluded_senders() {
+        let mut options = fake_options();
+        options
+            .query_context
+            .set_excluded_handle_ids(BTreeSet::from([12]));
+        let app = fake_app(options);
+
+        assert!(app.is_exported_participant(&11));
+        assert!(!app.is_exported_participant(&12));
+        assert!(!app.is_exported_participant(&13));
+        // Excluded senders keep their names so chat names do not change
+        assert_eq!(app.who(Some(12), false, &None), "jane@example.com");
+    }
+
+    #[test]
+    fn cant_select_missing_participant() {
+        let mut options = fake_options();
+        options.participant_filters = vec!["+15550000000".to_string()];
+        let app = fake_app(options);
+
+        assert!(app.selected_chat_ids().is_err());
+    }
 }
@@ -30,6 +30,23 @@ static HTML_DISALLOWED_CHARS: LazyLock<HashMap<&char, &str>> = LazyLock::new(||
     map.insert(&' ', "&nbsp;");
     map
 });
+
+/// Characters that carry meaning in Markdown and must be escaped to render literally
+static MARKDOWN_ESCAPED_CHARS: LazyLock<HashSet<&char>> = LazyLock::new(|| {
+    let mut set = HashSet::new();
+    set.insert(&'\\');
+    set.insert(&'`');
+    set.insert(&'*');
+    set.insert(&'_');
+    set.insert(&'[');
+    set.insert(&']');
+    set.insert(&'<');
+    set.insert(&'>');
+    set.insert(&'~');
+    set.insert(&'#');
+    set
+});
+
 /// The character to replace disallowed chars with
 const FILENAME_REPLACEMENT_CHAR: char = '_';
 
@@ -64,6 +81,107 @@ pub fn sanitize_html(input: &str) -> Cow<str> {
     Cow::Borrowed(input)
 }
 
+/// Escapes Markdown special characters in the input string with a backslash.
+pub fn sanitize_markdown(input: &str) -> Cow<'_, str> {
+    for (idx, c) in input.char_indices() {
+        if MARKDOWN_ESCAPED_CHARS.contains(&c) {
+            let mut res = String::from(&input[..idx]);
+            input[idx..].chars().for_each(|c| {
+                if MARKDOWN_ESCAPED_CHARS.contains(&c) {
+                    res.push('\\');
+                }
+                res.push(c);
+            });
+            return Cow::Owned(res);
+        }
+    }
+    Cow::Borrowed(input)
+}
+
+/// Escapes the start of each line of Markdown text and keeps its line breaks.
+///
+/// Lines that begin with a list, table, or heading marker are escaped so they render as text, and each line
+/// break becomes a hard break so the lines are not joined into a single paragraph. Inline characters should
+/// already be escaped with [`sanitize_markdown`].
+pub fn sanitize_markdown_lines(input: &str) -> Cow<'_, str> {
+    if !input.contains('\n') && markdown_block_marker(input).is_none() {
+        return Cow::Borrowed(input);
+    }
+    let lines: Vec<String> = input
+        .split('\n')
+        .map(|line| match markdown_block_marker(line) {
+            Some(idx) => format!("{}\\{}", &line[..idx], &line[idx..]),
+            None => line.to_string(),
+        })
+        .collect();
+    Cow::Owned(lines.join("\\\n"))
+}
+
+/// Find the index of the character that would make a line start a Markdown block, if any
+fn markdown_block_marker(line: &str) -> Option<usize> {
+    let start = line.len() - line.trim_start_matches(' ').len();
+    let content = &line[start..];
+    match content.chars().next()? {
+        '-' | '+' | '=' | '|' => Some(start),
+        // Ordered lists start with a number followed by a period or parenthesis
+        c if c.is_ascii_digit() => {
+            let digits = content.len()
+                - content
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            matches!(content[digits..].chars().next(), Some('.' | ')')).then_some(start + digits)
+        }
+        _ => None,
+    }
+}
+
+/// Escapes a string for use in an XML attribute value.
+///
+/// Whitespace characters are encoded so they survive attribute value normalization, and
+/// control characters that XML 1.0 does not allow are dropped.
+pub fn sanitize_xml(input: &str) -> Cow<'_, str> {
+    let needs_escape = |c: char| matches!(c, '&' | '<' | '>' | '"' | '\'') || c.is_control();
+    match input.find(needs_escape) {
+        Some(idx) => {
+            let mut res = String::from(&input[..idx]);
+            input[idx..].chars().for_each(|c| match c {
+                '&' => res.push_str("&amp;"),
+                '<' => res.push_str("&lt;"),
+                '>' => res.push_str("&gt;"),
+                '"' => res.push_str("&quot;"),
+                '\'' => res.push_str("&apos;"),
+                '\n' => res.push_str("&#10;"),
+                '\r' => res.push_str("&#13;"),
+                '\t' => res.push_str("&#9;"),
+                c if c.is_control() => {}
+                c => res.push(c),
+            });
+            Cow::Owned(res)
+        }
+        None => Cow::Borrowed(input),
+    }
+}
+
+/// Percent-encodes a relative file path for use in a link.
+///
+/// Path separators and unreserved characters are kept, everything else is encoded byte by byte.
+pub fn sanitize_url_path(input: &str) -> Cow<'_, str> {
+    let is_safe =
+        |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~' | b'/');
+    if input.bytes().all(is_safe) {
+        return Cow::Borrowed(input);
+    }
+    let mut res = String::with_capacity(input.len() * 3);
+    for byte in input.bytes() {
+        if is_safe(byte) {
+            res.push(byte as char);
+        } else {
+            res.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    Cow::Owned(res)
+}
+
 #[cfg(test)]
 mod test_filename {
     use crate::app::sanitizers::sanitize_filename;
@@ -146,3 +264,120 @@ mod tests {
         );
     }
 }
+
+#[cfg(test)]
+mod test_markdown {
+    use crate::app::sanitizers::{sanitize_markdown, sanitize_markdown_lines};
+
+    #[test]
+    fn doesnt_sanitize_plain_text() {
+        assert_eq!(&sanitize_markdown("Hello world"), "Hello world");
+    }
+
+    #[test]
+    fn can_sanitize_emphasis() {
+        assert_eq!(&sanitize_markdown("*not bold*"), "\\*not bold\\*");
+    }
+
+    #[test]
+    fn can_sanitize_link() {
+        assert_eq!(
+            &sanitize_markdown("[text](url) <b>"),
+            "\\[text\\](url) \\<b\\>"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_all_special_chars() {
+        assert_eq!(
+            &sanitize_markdown("\\`*_[]<>~#"),
+            "\\\\\\`\\*\\_\\[\\]\\<\\>\\~\\#"
+        );
+    }
+
+    #[test]
+    fn doesnt_sanitize_single_line() {
+        assert_eq!(&sanitize_markdown_lines("Hello - world"), "Hello - world");
+    }
+
+    #[test]
+    fn can_sanitize_block_markers() {
+        assert_eq!(&sanitize_markdown_lines("- item"), "\\- item");
+        assert_eq!(&sanitize_markdown_lines("+ item"), "\\+ item");
+        assert_eq!(&sanitize_markdown_lines("| a | b |"), "\\| a | b |");
+        assert_eq!(&sanitize_markdown_lines("==="), "\\===");
+        assert_eq!(&sanitize_markdown_lines("  1. first"), "  1\\. first");
+        assert_eq!(&sanitize_markdown_lines("10) tenth"), "10\\) tenth");
+        assert_eq!(&sanitize_markdown_lines("2022 was fun"), "2022 was fun");
+    }
+
+    #[test]
+    fn can_keep_line_breaks() {
+        assert_eq!(
+            &sanitize_markdown_lines("Shopping list:\n- eggs\n\n1. milk"),
+            "Shopping list:\\\n\\- eggs\\\n\\\n1\\. milk"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_quote_on_new_line() {
+        assert_eq!(
+            &sanitize_markdown_lines(&sanitize_markdown("Hi\n> quote")),
+            "Hi\\\n\\> quote"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_xml {
+    use crate::app::sanitizers::sanitize_xml;
+
+    #[test]
+    fn doesnt_sanitize_plain_text() {
+        assert_eq!(&sanitize_xml("Hello world"), "Hello world");
+    }
+
+    #[test]
+    fn can_sanitize_markup() {
+        assert_eq!(
+            &sanitize_xml("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_whitespace() {
+        assert_eq!(&sanitize_xml("a\nb\r\tc"), "a&#10;b&#13;&#9;c");
+    }
+
+    #[test]
+    fn can_drop_control_chars() {
+        assert_eq!(&sanitize_xml("a\u{0}b\u{1b}c"), "abc");
+    }
+}
+
+#[cfg(test)]
+mod test_url_path {
+    use crate::app::sanitizers::sanitize_url_path;
+
+    #[test]
+    fn doesnt_sanitize_safe_path() {
+        assert_eq!(
+            &sanitize_url_path("attachments/1/a_b-c.jpg"),
+            "attachments/1/a_b-c.jpg"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_reserved_chars() {
+        assert_eq!(
+            &sanitize_url_path("Family #1 - 5?.html"),
+            "Family%20%231%20-%205%3F.html"
+        );
+    }
+
+    #[test]
+    fn can_sanitize_unicode() {
+        assert_eq!(&sanitize_url_path("Café.html"), "Caf%C3%A9.html");
+    }
+}
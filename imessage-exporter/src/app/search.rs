@@ -0,0 +1,580 @@
+/*!
+ Finds the messages whose decoded text matches a term or regular expression.
+*/
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+};
+
+use regex::{Error, Regex, RegexBuilder};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        app::AppMessage,
+        url::URLMessage,
+        variants::{BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        messages::Message,
+        table::{Table, ORPHANED},
+    },
+    util::{dates::format, plist::parse_plist},
+};
+
+use crate::app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config};
+
+/// Default number of messages printed before and after each result
+pub const DEFAULT_SEARCH_CONTEXT: usize = 2;
+
+/// Placeholder for messages that only contain attachments
+const ATTACHMENT: &str = "[Attachment]";
+
+/// Build the pattern used to find messages
+///
+/// Terms match anywhere in the text, ignoring case; regular expressions are used as written.
+pub fn build_pattern(term: &str, is_regex: bool) -> Result<Regex, Error> {
+    if is_regex {
+        Regex::new(term)
+    } else {
+        RegexBuilder::new(&regex::escape(term))
+            .case_insensitive(true)
+            .build()
+    }
+}
+
+/// The parts of a message that are searched
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Field {
+    /// The message body, including text only stored in the `attributedBody`
+    Text,
+    /// A previous version of an edited or unsent message
+    EditHistory,
+    /// The title, summary, or site of a link preview
+    LinkPreview,
+    /// The title or captions of an app message
+    App,
+}
+
+impl Display for Field {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Field::Text => write!(fmt, "text"),
+            Field::EditHistory => write!(fmt, "edit history"),
+            Field::LinkPreview => write!(fmt, "link preview"),
+            Field::App => write!(fmt, "app"),
+        }
+    }
+}
+
+/// Collect the decoded text of every part of a message that can be searched
+///
+/// Each piece of text is only collected once, so the current text of an edited message is not repeated in its history.
+pub fn searchable_text(config: &Config, message: &Message) -> Vec<(Field, String)> {
+    let mut fields: Vec<(Field, String)> = vec![];
+    let mut add = |field: Field, text: &str| {
+        // Remove the placeholders for attachments and app messages
+        let text = text
+            .replace(['\u{FFFC}', '\u{FFFD}'], "")
+            .trim()
+            .to_string();
+        if !text.is_empty() && !fields.iter().any(|(_, existing)| *existing == text) {
+            fields.push((field, text));
+        }
+    };
+
+    if let Some(text) = &message.text {
+        add(Field::Text, text);
+    }
+
+    // Edited messages keep every version of each part, even if the part was later unsent
+    if let Some(edited) = &message.edited_parts {
+        for part in &edited.parts {
+            for event in &part.edit_history {
+                add(Field::EditHistory, &event.text);
+            }
+        }
+    }
+
+    if let Variant::App(balloon) = message.variant() {
+        if let Some(parsed) = message
+            .payload_data(&config.db)
+            .and_then(|payload| parse_plist(&payload).ok())
+        {
+            match balloon {
+                CustomBalloon::URL => {
+                    let preview = match URLMessage::get_url_message_override(&parsed) {
+                        Ok(URLOverride::Normal(balloon)) => {
+                            vec![balloon.title, balloon.summary, balloon.site_name]
+                        }
+                        Ok(URLOverride::AppleMusic(balloon)) => {
+                            vec![balloon.track_name, balloon.album, balloon.artist]
+                        }
+                        Ok(URLOverride::AppStore(balloon)) => {
+                            vec![balloon.app_name, balloon.description, balloon.genre]
+                        }
+                        Ok(URLOverride::Collaboration(balloon)) => {
+                            vec![balloon.title, balloon.app_name]
+                        }
+                        Ok(URLOverride::SharedPlacemark(balloon)) => vec![
+                            balloon.placemark.name,
+                            balloon.place_name,
+                            balloon.placemark.address,
+                        ],
+                        Err(_) => vec![],
+                    };
+                    preview
+                        .into_iter()
+                        .flatten()
+                        .for_each(|text| add(Field::LinkPreview, text));
+                }
+                // Handwriting is drawn, so it has no text to search
+                CustomBalloon::Handwriting => {}
+                _ => {
+                    if let Ok(balloon) = AppMessage::from_map(&parsed) {
+                        [
+                            balloon.title,
+                            balloon.subtitle,
+                            balloon.caption,
+                            balloon.subcaption,
+                            balloon.trailing_caption,
+                            balloon.trailing_subcaption,
+                            balloon.ldtext,
+                            balloon.app_name,
+                        ]
+                        .into_iter()
+                        .flatten()
+                        .for_each(|text| add(Field::App, text));
+                    }
+                }
+            }
+        }
+    }
+
+    fields
+}
+
+/// A result that is waiting for the messages that follow it
+struct Pending {
+    /// The order the result was found in
+    order: usize,
+    /// The header and message lines printed for the result
+    lines: Vec<String>,
+    /// The number of following messages still needed
+    remaining: usize,
+}
+
+/// The most recent messages in a conversation, and the results that still need context
+#[derive(Default)]
+struct Conversation {
+    previous: VecDeque<String>,
+    pending: Vec<Pending>,
+}
+
+/// Collects the messages around each result as messages are read in order
+struct Results {
+    /// Number of messages to include before and after each result
+    context: usize,
+    /// Map of deduplicated chat ID to the conversation's state
+    conversations: HashMap<Option<i32>, Conversation>,
+    /// Number of results found so far
+    found: usize,
+}
+
+impl Results {
+    fn new(context: usize) -> Self {
+        Self {
+            context,
+            conversations: HashMap::new(),
+            found: 0,
+        }
+    }
+
+    /// Add a message's line to its conversation, returning the results that have all of their context
+    ///
+    /// Results pass the `header` for the block of lines they start.
+    fn push(&mut self, chat_id: Option<i32>, line: String, header: Option<String>) -> Vec<String> {
+        let context = self.context;
+        let conversation = self.conversations.entry(chat_id).or_default();
+        let mut complete = vec![];
+
+        // Messages after a result in the same conversation are its trailing context
+        conversation.pending.retain_mut(|result| {
+            result.lines.push(line.clone());
+            result.remaining -= 1;
+            if result.remaining == 0 {
+                complete.push(result.lines.join("\n"));
+                return false;
+            }
+            true
+        });
+
+        if let Some(header) = header {
+            let mut lines = vec![header];
+            lines.extend(conversation.previous.iter().cloned());
+            lines.push(line.clone());
+            if context == 0 {
+                complete.push(lines.join("\n"));
+            } else {
+                conversation.pending.push(Pending {
+                    order: self.found,
+                    lines,
+                    remaining: context,
+                });
+            }
+            self.found += 1;
+        }
+
+        if context > 0 {
+            if conversation.previous.len() == context {
+                conversation.previous.pop_front();
+            }
+            conversation.previous.push_back(line);
+        }
+
+        complete
+    }
+
+    /// Get the results at the end of their conversations, in the order they were found
+    fn finish(&mut self) -> Vec<String> {
+        let mut pending: Vec<Pending> = self
+            .conversations
+            .values_mut()
+            .flat_map(|conversation| conversation.pending.drain(..))
+            .collect();
+        pending.sort_by_key(|result| result.order);
+        pending
+            .into_iter()
+            .map(|result| result.lines.join("\n"))
+            .collect()
+    }
+}
+
+/// Searches the decoded text of every message and prints each match with the messages around it
+pub struct Search<'a> {
+    /// Data that is setup from the application's runtime
+    config: &'a Config,
+    /// The term or regular expression provided by the user
+    term: &'a str,
+    /// The pattern messages must match
+    pattern: Regex,
+    /// Number of messages to print before and after each result
+    context: usize,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(config: &'a Config, term: &'a str) -> Result<Self, RuntimeError> {
+        let pattern = build_pattern(term, config.options.search_regex)
+            .map_err(|why| RuntimeError::InvalidOptions(format!("{why}")))?;
+        Ok(Search {
+            config,
+            term,
+            pattern,
+            context: config
+                .options
+                .search_context
+                .unwrap_or(DEFAULT_SEARCH_CONTEXT),
+        })
+    }
+
+    /// Search every message that matches the query context, printing results as they are found
+    pub fn run(&self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!("Searching messages for `{}`...", self.term);
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        let mut results = Results::new(self.context);
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and search the same message GUID twice
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Tapbacks and announcements are not messages someone wrote, so they are neither results nor context
+            if !msg.is_tapback() && !msg.is_announcement() {
+                let _ = msg.generate_text(&self.config.db);
+                let fields = searchable_text(self.config, &msg);
+                let matches: Vec<&(Field, String)> = fields
+                    .iter()
+                    .filter(|(_, text)| self.pattern.is_match(text))
+                    .collect();
+
+                let line = self.format_line(&msg, &fields, &matches);
+                let header = (!matches.is_empty()).then(|| self.format_header(&msg));
+                let chat_id = self.config.conversation(&msg).map(|(_, id)| *id);
+                for result in results.push(chat_id, line, header) {
+                    pb.suspend(|| println!("{result}\n"));
+                }
+            }
+
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish_and_clear();
+
+        for result in results.finish() {
+            println!("{result}\n");
+        }
+        match results.found {
+            1 => eprintln!("Found 1 matching message"),
+            found => eprintln!("Found {found} matching messages"),
+        }
+
+        Ok(())
+    }
+
+    /// Build the line printed for a message, marking results and listing the other parts of the message that matched
+    fn format_line(
+        &self,
+        message: &Message,
+        fields: &[(Field, String)],
+        matches: &[&(Field, String)],
+    ) -> String {
+        let marker = if matches.is_empty() { ' ' } else { '>' };
+        let date = format(&message.date(&self.config.offset));
+        let who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        let text = match fields.first() {
+            Some((_, text)) => text.replace('\n', " "),
+            None if message.has_attachments() => ATTACHMENT.to_string(),
+            None => String::new(),
+        };
+
+        let mut line = format!("{marker} {date} | {who}: {text}");
+        // The first field is already in the line, so only list the other fields that matched
+        for (field, text) in matches
+            .iter()
+            .filter(|matched| Some(**matched) != fields.first())
+        {
+            line.push_str(&format!("\n      {field}: {}", text.replace('\n', " ")));
+        }
+        line
+    }
+
+    /// Build the line that names the conversation a result was found in
+    fn format_header(&self, message: &Message) -> String {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => chatroom.name().to_string(),
+            None => ORPHANED.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::{
+        fixtures::{blank, fake_config, fake_options},
+        search::{build_pattern, searchable_text, Field, Results, Search},
+    };
+    use imessage_database::message_types::edited::{
+        EditStatus, EditedEvent, EditedMessage, EditedMessagePart,
+    };
+
+    fn edited(history: &[&str]) -> EditedMessage {
+        EditedMessage {
+            parts: vec![EditedMessagePart {
+                status: EditStatus::Edited,
+                edit_history: history
+                    .iter()
+                    .map(|text| EditedEvent {
+                        date: 0,
+                        text: text.to_string(),
+                        guid: None,
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn can_match_term_ignoring_case() {
+        let pattern = build_pattern("Dinner", false).unwrap();
+        assert!(pattern.is_match("Family DINNER?"));
+        assert!(!pattern.is_match("Family lunch?"));
+    }
+
+    #[test]
+    fn can_match_term_literally() {
+        let pattern = build_pattern("a.b (c)", false).unwrap();
+        assert!(pattern.is_match("see a.b (c)"));
+        assert!(!pattern.is_match("see axb c"));
+    }
+
+    #[test]
+    fn can_match_regex() {
+        let pattern = build_pattern(r"^\d{3}-\d{4}$", true).unwrap();
+        assert!(pattern.is_match("555-0100"));
+        assert!(!pattern.is_match("call 555-0100"));
+    }
+
+    #[test]
+    fn cant_build_invalid_regex() {
+        assert!(build_pattern("(", true).is_err());
+        assert!(build_pattern("(", false).is_ok());
+    }
+
+    #[test]
+    fn can_search_text() {
+        let config = fake_config(fake_options());
+
+        let mut message = blank();
+        message.text = Some("\u{FFFC}Family dinner?".to_string());
+
+        assert_eq!(
+            searchable_text(&config, &message),
+            vec![(Field::Text, "Family dinner?".to_string())]
+        );
+    }
+
+    #[test]
+    fn can_search_edit_history() {
+        let config = fake_config(fake_options());
+
+        let mut message = blank();
+        message.text = Some("Family lunch?".to_string());
+        message.edited_parts = Some(edited(&["Family dinner?", "Family lunch?"]));
+
+        assert_eq!(
+            searchable_text(&config, &message),
+            vec![
+                (Field::Text, "Family lunch?".to_string()),
+                (Field::EditHistory, "Family dinner?".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn can_search_unsent_history() {
+        let config = fake_config(fake_options());
+
+        let mut message = blank();
+        let mut history = edited(&["Family dinner?"]);
+        history.parts[0].status = EditStatus::Unsent;
+        message.edited_parts = Some(history);
+
+        assert_eq!(
+            searchable_text(&config, &message),
+            vec![(Field::EditHistory, "Family dinner?".to_string())]
+        );
+    }
+
+    #[test]
+    fn can_format_match_in_other_field() {
+        let config = fake_config(fake_options());
+        let search = Search::new(&config, "dinner").unwrap();
+
+        let mut message = blank();
+        message.is_from_me = true;
+        message.text = Some("Family lunch?".to_string());
+        message.edited_parts = Some(edited(&["Family dinner?", "Family lunch?"]));
+
+        let fields = searchable_text(&config, &message);
+        let matches: Vec<&(Field, String)> = fields.iter().skip(1).collect();
+        let line = search.format_line(&message, &fields, &matches);
+
+        assert!(line.starts_with("> "));
+        assert!(line.ends_with("| Me: Family lunch?\n      edit history: Family dinner?"));
+    }
+
+    #[test]
+    fn can_format_context_with_attachment() {
+        let config = fake_config(fake_options());
+        let search = Search::new(&config, "dinner").unwrap();
+
+        let mut message = blank();
+        message.is_from_me = true;
+        message.text = Some("\u{FFFC}".to_string());
+        message.num_attachments = 1;
+
+        let fields = searchable_text(&config, &message);
+        let line = search.format_line(&message, &fields, &[]);
+
+        assert!(line.starts_with("  "));
+        assert!(line.ends_with("| Me: [Attachment]"));
+    }
+
+    #[test]
+    fn can_collect_context() {
+        let mut results = Results::new(1);
+
+        assert!(results.push(Some(1), "a".to_string(), None).is_empty());
+        assert!(results
+            .push(Some(1), "b".to_string(), Some("Chat".to_string()))
+            .is_empty());
+        // Messages in other conversations are not context
+        assert!(results.push(Some(2), "x".to_string(), None).is_empty());
+        assert_eq!(
+            results.push(Some(1), "c".to_string(), None),
+            vec!["Chat\na\nb\nc"]
+        );
+        assert!(results.finish().is_empty());
+        assert_eq!(results.found, 1);
+    }
+
+    #[test]
+    fn can_collect_without_context() {
+        let mut results = Results::new(0);
+
+        assert!(results.push(Some(1), "a".to_string(), None).is_empty());
+        assert_eq!(
+            results.push(Some(1), "b".to_string(), Some("Chat".to_string())),
+            vec!["Chat\nb"]
+        );
+        assert!(results.finish().is_empty());
+    }
+
+    #[test]
+    fn can_collect_overlapping_results() {
+        let mut results = Results::new(1);
+
+        results.push(Some(1), "a".to_string(), Some("Chat".to_string()));
+        assert_eq!(
+            results.push(Some(1), "b".to_string(), Some("Chat".to_string())),
+            vec!["Chat\na\nb"]
+        );
+        assert_eq!(results.finish(), vec!["Chat\na\nb"]);
+        assert_eq!(results.found, 2);
+    }
+
+    #[test]
+    fn can_finish_results_in_order() {
+        let mut results = Results::new(2);
+
+        results.push(Some(2), "a".to_string(), Some("Second".to_string()));
+        results.push(Some(1), "b".to_string(), Some("First".to_string()));
+        results.push(None, "c".to_string(), Some("orphaned".to_string()));
+
+        assert_eq!(
+            results.finish(),
+            vec!["Second\na", "First\nb", "orphaned\nc"]
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::exporter::Exporter,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    tables::{handle::Handle, messages::Message, table::Table},
+    util::dates::{format, get_local_time},
+};
+
+/// Name of the file every card is written to
+const CONTACTS: &str = "contacts";
+
+/// Maximum length of a line in a vCard, in bytes, before it must be folded
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Messages exchanged with a single participant
+#[derive(Default, Debug, PartialEq, Eq)]
+struct ContactStats {
+    /// Number of messages the participant sent
+    received: u64,
+    /// Number of messages the database owner sent to conversations the participant is in
+    sent: u64,
+    /// Date of the earliest message exchanged
+    first: Option<i64>,
+    /// Date of the latest message exchanged
+    last: Option<i64>,
+}
+
+impl ContactStats {
+    /// Include a message sent at the given date
+    fn add(&mut self, date: i64, is_from_me: bool) {
+        if is_from_me {
+            self.sent += 1;
+        } else {
+            self.received += 1;
+        }
+        self.first = Some(self.first.map_or(date, |first| first.min(date)));
+        self.last = Some(self.last.map_or(date, |last| last.max(date)));
+    }
+}
+
+/// Exports a vCard for every unique conversation participant
+///
+/// Handles that belong to the same person are merged into a single card with all of their phone numbers and email
+/// addresses, annotated with the messages exchanged with them.
+pub struct Contacts<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Writer instance for the file every card is written to
+    pub file: BufWriter<File>,
+}
+
+impl<'a> Exporter<'a> for Contacts<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut path = config.options.export_path.clone();
+        path.push(CONTACTS);
+        path.set_extension("vcf");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        Ok(Contacts {
+            config,
+            file: BufWriter::new(file),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as vcf...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        // Map of deduplicated participant ID to the messages exchanged with them
+        let mut stats: BTreeMap<i32, ContactStats> = BTreeMap::new();
+
+        for message in messages {
+            let msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Tapbacks are reactions to other messages, not messages of their own
+            if !msg.is_tapback() {
+                for participant in self.participants(&msg) {
+                    stats
+                        .entry(participant)
+                        .or_default()
+                        .add(msg.date, msg.is_from_me());
+                }
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Map of deduplicated participant ID to the handles that belong to them
+        let mut people: BTreeMap<i32, Vec<Handle>> = BTreeMap::new();
+        let mut statement = Handle::get(&self.config.db).map_err(RuntimeError::DatabaseError)?;
+        let rows = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+        for row in rows {
+            let handle = Handle::extract(row).map_err(RuntimeError::DatabaseError)?;
+            // Skip people who are excluded or only in chats that the filters leave out of the export
+            if !self.config.is_exported_participant(&handle.rowid) {
+                continue;
+            }
+            if let Some(id) = self.config.real_participants.get(&handle.rowid) {
+                people.entry(*id).or_default().push(handle);
+            }
+        }
+
+        for (id, handles) in &people {
+            let card = self.format_card(handles, stats.get(id));
+            Contacts::write_to_file(&mut self.file, &card)?;
+        }
+
+        Ok(())
+    }
+
+    /// Every card is written to the same file
+    fn get_or_create_file(&mut self, _: &Message) -> Result<&mut BufWriter<File>, RuntimeError> {
+        Ok(&mut self.file)
+    }
+}
+
+impl<'a> Contacts<'a> {
+    /// Get the deduplicated IDs of the participants a message was exchanged with
+    ///
+    /// Messages someone else sent count toward their sender, and messages the database owner sent count toward
+    /// everyone in the conversation.
+    fn participants(&self, message: &Message) -> Vec<i32> {
+        let handles: Vec<i32> = if message.is_from_me() {
+            match message
+                .chat_id
+                .and_then(|chat_id| self.config.chatroom_participants.get(&chat_id))
+            {
+                Some(participants) => participants.iter().copied().collect(),
+                None => message.handle_id.into_iter().collect(),
+            }
+        } else {
+            message.handle_id.into_iter().collect()
+        };
+
+        let mut participants: Vec<i32> = handles
+            .iter()
+            .filter_map(|handle_id| self.config.real_participants.get(handle_id))
+            .copied()
+            .collect();
+        participants.sort_unstable();
+        participants.dedup();
+        participants
+    }
+
+    /// Build the vCard for a person from all of their handles
+    fn format_card(&self, handles: &[Handle], stats: Option<&ContactStats>) -> String {
+        let mut lines = vec![String::from("BEGIN:VCARD"), String::from("VERSION:3.0")];
+
+        let name = handles
+            .first()
+            .map(|handle| self.config.who(Some(handle.rowid), false, &None))
+            .unwrap_or_default();
+        lines.push(format!("FN:{}", Contacts::escape(name)));
+        lines.push(String::from("N:;;;;"));
+
+        // The same address can appear once per service, i.e. iMessage and SMS
+        let mut seen = HashSet::new();
+        for handle in handles {
+            if seen.insert(&handle.id) {
+                lines.push(Contacts::format_address(&handle.id));
+            }
+        }
+
+        if let Some(uid) = handles
+            .iter()
+            .find_map(|handle| handle.person_centric_id.as_deref())
+        {
+            lines.push(format!("UID:{}", Contacts::escape(uid)));
+        }
+
+        if let Some(stats) = stats {
+            lines.extend(self.format_stats(stats));
+        }
+        lines.push(String::from("END:VCARD"));
+
+        let mut card = String::new();
+        for line in lines {
+            card.push_str(&Contacts::fold(&line));
+            card.push_str("\r\n");
+        }
+        card
+    }
+
+    /// Build the property for a phone number or email address
+    fn format_address(address: &str) -> String {
+        if address.contains('@') {
+            return format!("EMAIL;TYPE=INTERNET:{}", Contacts::escape(address));
+        }
+        if address
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '(' | ')' | ' ' | '.'))
+        {
+            return format!("TEL;TYPE=CELL:{}", Contacts::escape(address));
+        }
+        // Other handles, like business chat identifiers, can only be reached over iMessage
+        format!(
+            "IMPP;X-SERVICE-TYPE=iMessage:x-apple:{}",
+            Contacts::escape(address)
+        )
+    }
+
+    /// Build the properties that describe the messages exchanged with a person
+    fn format_stats(&self, stats: &ContactStats) -> Vec<String> {
+        let total = stats.sent + stats.received;
+        let first = stats
+            .first
+            .map(|date| get_local_time(&date, &self.config.offset));
+        let last = stats
+            .last
+            .map(|date| get_local_time(&date, &self.config.offset));
+
+        let mut note = format!(
+            "Messages: {total} ({} sent, {} received)",
+            stats.sent, stats.received
+        );
+        if let Some(first) = &first {
+            note.push_str(&format!("\nFirst message: {}", format(first)));
+        }
+        if let Some(last) = &last {
+            note.push_str(&format!("\nLast message: {}", format(last)));
+        }
+
+        let mut lines = vec![format!("NOTE:{}", Contacts::escape(&note))];
+        if let Some(Ok(first)) = first {
+            lines.push(format!("X-IMESSAGE-FIRST-MESSAGE:{}", first.to_rfc3339()));
+        }
+        if let Some(Ok(last)) = last {
+            lines.push(format!("X-IMESSAGE-LAST-MESSAGE:{}", last.to_rfc3339()));
+        }
+        lines.push(format!("X-IMESSAGE-MESSAGE-COUNT:{total}"));
+        lines
+    }
+
+    /// Escape the characters that have special meaning in vCard text values
+    fn escape(text: &str) -> String {
+        text.replace('\\', "\\\\")
+            .replace(',', "\\,")
+            .replace(';', "\\;")
+            .replace('\n', "\\n")
+    }
+
+    /// Fold a line longer than the maximum length onto continuation lines that start with a space
+    fn fold(line: &str) -> String {
+        if line.len() <= MAX_LINE_LENGTH {
+            return line.to_string();
+        }
+
+        let mut out_s = String::with_capacity(line.len() + line.len() / MAX_LINE_LENGTH * 3);
+        let mut length = 0;
+        for c in line.chars() {
+            // Never split a multi-byte character across lines
+            if length + c.len_utf8() > MAX_LINE_LENGTH {
+                out_s.push_str("\r\n ");
+                // The leading space counts toward the length of the continuation line
+                length = 1;
+            }
+            out_s.push(c);
+            length += c.len_utf8();
+        }
+        out_s
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::set_var;
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::contacts::ContactStats,
+        Contacts, Exporter,
+    };
+    use imessage_database::tables::handle::Handle;
+
+    fn handle(rowid: i32, id: &str, person_centric_id: Option<&str>) -> Handle {
+        Handle {
+            rowid,
+            id: id.to_string(),
+            person_centric_id: person_centric_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Contacts);
+        let config = fake_config(options);
+        let exporter = Contacts::new(&config).unwrap();
+        assert_eq!(
+            exporter.config.options.export_type,
+            Some(ExportType::Contacts)
+        );
+    }
+
+    #[test]
+    fn can_escape() {
+        assert_eq!(
+            Contacts::escape("Smith, Jane; \\ PhD\nHome"),
+            "Smith\\, Jane\\; \\\\ PhD\\nHome"
+        );
+    }
+
+    #[test]
+    fn can_not_fold_short_line() {
+        assert_eq!(Contacts::fold("FN:Jane"), "FN:Jane");
+    }
+
+    #[test]
+    fn can_fold_long_line() {
+        let line = format!("NOTE:{}", "a".repeat(100));
+        let actual = Contacts::fold(&line);
+        let lines: Vec<&str> = actual.split("\r\n").collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "a".repeat(30)));
+    }
+
+    #[test]
+    fn can_fold_multibyte_line() {
+        let line = format!("FN:{}", "é".repeat(50));
+        let actual = Contacts::fold(&line);
+
+        for line in actual.split("\r\n") {
+            assert!(line.len() <= 75);
+        }
+        assert_eq!(actual.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn can_format_phone() {
+        assert_eq!(
+            Contacts::format_address("+1 (555) 555-0100"),
+            "TEL;TYPE=CELL:+1 (555) 555-0100"
+        );
+    }
+
+    #[test]
+    fn can_format_email() {
+        assert_eq!(
+            Contacts::format_address("person@example.com"),
+            "EMAIL;TYPE=INTERNET:person@example.com"
+        );
+    }
+
+    #[test]
+    fn can_format_other_address() {
+        assert_eq!(
+            Contacts::format_address("urn:biz:1234"),
+            "IMPP;X-SERVICE-TYPE=iMessage:x-apple:urn:biz:1234"
+        );
+    }
+
+    #[test]
+    fn can_add_stats() {
+        let mut stats = ContactStats::default();
+        stats.add(20, false);
+        stats.add(10, true);
+        stats.add(30, false);
+
+        assert_eq!(
+            stats,
+            ContactStats {
+                received: 2,
+                sent: 1,
+                first: Some(10),
+                last: Some(30),
+            }
+        );
+    }
+
+    #[test]
+    fn can_get_participants_from_sender() {
+        let options = fake_options_for(ExportType::Contacts);
+        let mut config = fake_config(options);
+        config.real_participants.insert(1, 0);
+        config.real_participants.insert(2, 0);
+        config.real_participants.insert(3, 1);
+        config
+            .chatroom_participants
+            .insert(5, [1, 2, 3].into_iter().collect());
+        let exporter = Contacts::new(&config).unwrap();
+
+        let mut message = blank();
+        message.handle_id = Some(3);
+        message.chat_id = Some(5);
+
+        assert_eq!(exporter.participants(&message), vec![1]);
+    }
+
+    #[test]
+    fn can_get_participants_from_me() {
+        let options = fake_options_for(ExportType::Contacts);
+        let mut config = fake_config(options);
+        config.real_participants.insert(1, 0);
+        config.real_participants.insert(2, 0);
+        config.real_participants.insert(3, 1);
+        config
+            .chatroom_participants
+            .insert(5, [1, 2, 3].into_iter().collect());
+        let exporter = Contacts::new(&config).unwrap();
+
+        let mut message = blank();
+        message.is_from_me = true;
+        message.handle_id = Some(0);
+        message.chat_id = Some(5);
+
+        assert_eq!(exporter.participants(&message), vec![0, 1]);
+    }
+
+    #[test]
+    fn can_format_card() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options_for(ExportType::Contacts);
+        let mut config = fake_config(options);
+        config.participants.insert(1, "Jane Doe".to_string());
+        let exporter = Contacts::new(&config).unwrap();
+
+        let handles = vec![
+            handle(1, "+15555550100", Some("ABC-123")),
+            handle(2, "jane@example.com", Some("ABC-123")),
+            handle(3, "+15555550100", Some("ABC-123")),
+        ];
+        let mut stats = ContactStats::default();
+        // May 17, 2022  8:29:42 PM
+        stats.add(674526582885055488, false);
+        stats.add(674526582885055488, true);
+
+        let actual = exporter.format_card(&handles, Some(&stats));
+        let expected = [
+            "BEGIN:VCARD",
+            "VERSION:3.0",
+            "FN:Jane Doe",
+            "N:;;;;",
+            "TEL;TYPE=CELL:+15555550100",
+            "EMAIL;TYPE=INTERNET:jane@example.com",
+            "UID:ABC-123",
+            "NOTE:Messages: 2 (1 sent\\, 1 received)\\nFirst message: May 17\\, 2022  5:29:",
+            " 42 PM\\nLast message: May 17\\, 2022  5:29:42 PM",
+            "X-IMESSAGE-FIRST-MESSAGE:2022-05-17T17:29:42-07:00",
+            "X-IMESSAGE-LAST-MESSAGE:2022-05-17T17:29:42-07:00",
+            "X-IMESSAGE-MESSAGE-COUNT:2",
+            "END:VCARD",
+            "",
+        ]
+        .join("\r\n");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_card_without_messages() {
+        let options = fake_options_for(ExportType::Contacts);
+        let config = fake_config(options);
+        let exporter = Contacts::new(&config).unwrap();
+
+        let handles = vec![handle(7, "person@example.com", None)];
+
+        let actual = exporter.format_card(&handles, None);
+        let expected = [
+            "BEGIN:VCARD",
+            "VERSION:3.0",
+            "FN:Unknown",
+            "N:;;;;",
+            "EMAIL;TYPE=INTERNET:person@example.com",
+            "END:VCARD",
+            "",
+        ]
+        .join("\r\n");
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,722 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::exporter::{Exporter, Writer},
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, Tapback, URLOverride, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{Table, ME, ORPHANED, YOU},
+    },
+    util::{dates::get_local_time, plist::parse_plist},
+};
+
+/// Name of the file written when all conversations are exported together
+const COMBINED: &str = "messages";
+
+/// Kinds of reactions that are counted for each message, in column order
+const TAPBACK_KINDS: [&str; 8] = [
+    "loved",
+    "liked",
+    "disliked",
+    "laughed",
+    "emphasized",
+    "questioned",
+    "emoji",
+    "sticker",
+];
+
+/// Column names written to the first row of every file
+const HEADER: [&str; 24] = [
+    "rowid",
+    "guid",
+    "chat_id",
+    "chat_name",
+    "sender",
+    "is_from_me",
+    "date",
+    "date_delivered",
+    "date_read",
+    "date_edited",
+    "service",
+    "variant",
+    "text",
+    "edited",
+    "attachments",
+    "thread_originator_guid",
+    "loved",
+    "liked",
+    "disliked",
+    "laughed",
+    "emphasized",
+    "questioned",
+    "emoji",
+    "sticker",
+];
+
+pub struct CSV<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages, or for every message when chats are combined
+    pub orphaned: BufWriter<File>,
+}
+
+impl<'a> Exporter<'a> for CSV<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        if config.options.combine_chats {
+            orphaned.push(COMBINED);
+        } else {
+            orphaned.push(ORPHANED);
+        }
+        orphaned.set_extension("csv");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(CSV {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as csv...",
+            self.config.options.export_path.display()
+        );
+
+        // Write orphaned file headers
+        CSV::write_headers(&mut self.orphaned)?;
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                CSV::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Tapbacks are counted on the message they react to, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                CSV::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        if self.config.options.combine_chats {
+            return Ok(&mut self.orphaned);
+        }
+
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(self.config.filename(chatroom));
+                        path.set_extension("csv");
+
+                        // If the file already exists, don't write the headers again
+                        // This can happen if multiple chats use the same group name
+                        let file_exists = path.exists();
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        let mut buf = BufWriter::new(file);
+
+                        // Write headers if the file does not exist
+                        if !file_exists {
+                            CSV::write_headers(&mut buf)?;
+                        }
+
+                        Ok(entry.insert(buf))
+                    }
+                }
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Writer<'a> for CSV<'a> {
+    fn format_message(&self, message: &Message, _: usize) -> Result<String, TableError> {
+        // Build the text column, falling back to a description for messages without text
+        let text = if message.is_shareplay() {
+            self.format_shareplay().to_string()
+        } else if message.started_sharing_location() || message.stopped_sharing_location() {
+            self.format_shared_location(message).to_string()
+        } else if matches!(message.variant(), Variant::App(_)) {
+            match self.format_app(message, &mut vec![], "") {
+                Ok(app) => app,
+                Err(_) => message.text.clone().unwrap_or_default(),
+            }
+        } else {
+            message.text.clone().unwrap_or_default()
+        };
+
+        // Resolve the path for every attachment
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let attachment_paths: Vec<String> = attachments
+            .iter_mut()
+            .map(
+                |attachment| match self.format_attachment(attachment, message) {
+                    Ok(path) => path,
+                    Err(path) => path.to_string(),
+                },
+            )
+            .collect();
+
+        // Count the reactions this message received
+        let counts = self.count_tapbacks(message)?;
+
+        // Describe the previous versions of every edited or unsent part
+        let edited: Vec<String> = match &message.edited_parts {
+            Some(edited_parts) => (0..edited_parts.items())
+                .filter_map(|idx| self.format_edited(message, edited_parts, idx, ""))
+                .collect(),
+            None => vec![],
+        };
+
+        let mut row = self.base_row(message);
+        row.push(CSV::variant(message).to_string());
+        row.push(text);
+        row.push(edited.join("; "));
+        row.push(attachment_paths.join("; "));
+        row.push(message.thread_originator_guid.clone().unwrap_or_default());
+        row.extend(counts.iter().map(|count| count.to_string()));
+
+        Ok(CSV::build_row(&row))
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        // Copy the file, if requested
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .ok_or(attachment.filename())?;
+
+        // Build a relative filepath from the fully qualified one on the `Attachment`
+        Ok(self.config.message_attachment_path(attachment))
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        match self.format_attachment(sticker, message) {
+            Ok(path_to_sticker) => path_to_sticker,
+            Err(path) => path.to_string(),
+        }
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        _: &mut Vec<Attachment>,
+        _: &str,
+    ) -> Result<String, PlistParseError> {
+        if let Variant::App(_) = message.variant() {
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                let parsed = parse_plist(&payload)?;
+                // URL messages are described by their link, everything else by its most specific caption
+                let description = if message.is_url() {
+                    match URLMessage::get_url_message_override(&parsed)? {
+                        URLOverride::Normal(balloon) => {
+                            balloon.get_url().or(balloon.title).map(str::to_string)
+                        }
+                        URLOverride::AppleMusic(balloon) => balloon.url.map(str::to_string),
+                        URLOverride::Collaboration(balloon) => {
+                            balloon.get_url().map(str::to_string)
+                        }
+                        URLOverride::AppStore(balloon) => balloon.url.map(str::to_string),
+                        URLOverride::SharedPlacemark(balloon) => {
+                            balloon.get_url().map(str::to_string)
+                        }
+                    }
+                } else {
+                    let balloon = AppMessage::from_map(&parsed)?;
+                    balloon
+                        .ldtext
+                        .or(balloon.title)
+                        .or(balloon.caption)
+                        .or(balloon.app_name)
+                        .map(str::to_string)
+                };
+                return Ok(description.or(message.text.clone()).unwrap_or_default());
+            }
+            // Sometimes, URL messages are missing their payloads
+            if message.is_url() {
+                if let Some(text) = &message.text {
+                    return Ok(text.to_string());
+                }
+            }
+            return Err(PlistParseError::NoPayload);
+        }
+        Err(PlistParseError::WrongMessageType)
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        Ok(match msg.variant() {
+            Variant::Tapback(_, _, tapback) => match tapback {
+                Tapback::Loved => "loved",
+                Tapback::Liked => "liked",
+                Tapback::Disliked => "disliked",
+                Tapback::Laughed => "laughed",
+                Tapback::Emphasized => "emphasized",
+                Tapback::Questioned => "questioned",
+                Tapback::Emoji(_) => "emoji",
+            },
+            Variant::Sticker(_) => "sticker",
+            _ => unreachable!(),
+        }
+        .to_string())
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+
+        let text = match msg.get_announcement() {
+            Some(announcement) => match announcement {
+                Announcement::NameChange(name) => {
+                    format!("{who} renamed the conversation to {name}")
+                }
+                Announcement::PhotoChange => format!("{who} changed the group photo."),
+                Announcement::Unknown(num) => format!("{who} performed unknown action {num}."),
+                Announcement::FullyUnsent => format!("{who} unsent a message!"),
+            },
+            None => String::from("Unable to format announcement!"),
+        };
+
+        let mut row = self.base_row(msg);
+        row.push(String::from("announcement"));
+        row.push(text);
+        // Announcements have no edits, attachments, replies, or tapbacks
+        row.extend((0..3 + TAPBACK_KINDS.len()).map(|_| String::new()));
+
+        CSV::build_row(&row)
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "SharePlay Message Ended"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "Stopped sharing location!";
+        }
+        "Shared location!"
+    }
+
+    fn format_edited(
+        &self,
+        _: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        // The text column already holds the most recent version, so only the original is kept here
+        let edited_message_part = edited_message.part(message_part_idx)?;
+        match edited_message_part.status {
+            EditStatus::Edited => edited_message_part
+                .edit_history
+                .first()
+                .map(|event| event.text.clone()),
+            EditStatus::Unsent => Some(String::from("unsent")),
+            EditStatus::Original => None,
+        }
+    }
+
+    fn format_attributed(&'a self, msg: &'a str, _: &'a TextEffect) -> Cow<'a, str> {
+        // Spreadsheets have no way to represent formatted text
+        Cow::Borrowed(msg)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> CSV<'a> {
+    /// Write the column names to a new file
+    fn write_headers(file: &mut BufWriter<File>) -> Result<(), RuntimeError> {
+        CSV::write_to_file(file, &CSV::build_row(&HEADER))
+    }
+
+    /// Serialize a list of fields into a single line, quoting and escaping as needed
+    fn build_row<T: AsRef<[u8]>>(fields: &[T]) -> String {
+        let mut writer = ::csv::WriterBuilder::new().from_writer(vec![]);
+        // Writing to a `Vec` cannot fail
+        let _ = writer.write_record(fields);
+        writer
+            .into_inner()
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .unwrap_or_default()
+    }
+
+    /// Convert an iMessage timestamp to an ISO 8601 string, leaving unset timestamps empty
+    fn date(&self, stamp: &i64) -> String {
+        if *stamp == 0 {
+            return String::new();
+        }
+        get_local_time(stamp, &self.config.offset)
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_default()
+    }
+
+    /// Get a short name for the type of message
+    fn variant(message: &Message) -> &'static str {
+        match message.variant() {
+            Variant::App(_) => "app",
+            Variant::Edited => "edited",
+            Variant::SharePlay => "shareplay",
+            Variant::Sticker(_) => "sticker",
+            Variant::Tapback(..) => "tapback",
+            Variant::Normal => "normal",
+            Variant::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Build the columns shared by every row, up to and including the service
+    fn base_row(&self, message: &Message) -> Vec<String> {
+        let (chat_id, chat_name) = match self.config.conversation(message) {
+            Some((chatroom, id)) => (id.to_string(), chatroom.name().to_string()),
+            None => (String::new(), String::new()),
+        };
+
+        vec![
+            message.rowid.to_string(),
+            message.guid.clone(),
+            chat_id,
+            chat_name,
+            self.config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            message.is_from_me().to_string(),
+            self.date(&message.date),
+            self.date(&message.date_delivered),
+            self.date(&message.date_read),
+            self.date(&message.date_edited),
+            message.service.clone().unwrap_or_default(),
+        ]
+    }
+
+    /// Count the reactions on every part of a message, in the order of [`TAPBACK_KINDS`]
+    ///
+    /// Only the most recent reaction of each kind from each sender on each part is counted,
+    /// so reactions that were later removed are not included.
+    fn count_tapbacks(&self, message: &Message) -> Result<[usize; 8], TableError> {
+        let mut counts = [0; TAPBACK_KINDS.len()];
+
+        if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+            let mut current: HashMap<(usize, Option<i32>, bool, String), bool> = HashMap::new();
+            for (idx, tapbacks) in tapbacks_map {
+                for tapback in tapbacks {
+                    let kind = self.format_tapback(tapback)?;
+                    let added = match tapback.variant() {
+                        Variant::Tapback(_, added, _) => added,
+                        _ => true,
+                    };
+                    current.insert((*idx, tapback.handle_id, tapback.is_from_me(), kind), added);
+                }
+            }
+
+            for ((_, _, _, kind), added) in current {
+                if added {
+                    if let Some(position) = TAPBACK_KINDS.iter().position(|k| *k == kind) {
+                        counts[position] += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::Writer,
+        Exporter, CSV,
+    };
+    use imessage_database::{
+        message_types::edited::{EditStatus, EditedEvent, EditedMessage, EditedMessagePart},
+        tables::table::ME,
+    };
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Csv);
+        let config = fake_config(options);
+        let exporter = CSV::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_build_row() {
+        assert_eq!(CSV::build_row(&["a", "b", "c"]), "a,b,c\n");
+    }
+
+    #[test]
+    fn can_build_row_escaped() {
+        assert_eq!(
+            CSV::build_row(&["hello, world", "say \"hi\"", "two\nlines"]),
+            "\"hello, world\",\"say \"\"hi\"\"\",\"two\nlines\"\n"
+        );
+    }
+
+    #[test]
+    fn can_format_csv_from_me_normal() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Csv);
+        let config = fake_config(options);
+        let exporter = CSV::new(&config).unwrap();
+
+        let mut message = blank();
+        message.guid = "guid".to_string();
+        message.text = Some("Hello, world".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "0,guid,,,Me,true,,,,,iMessage,normal,\"Hello, world\",,,,0,0,0,0,0,0,0,0\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_csv_edited() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Csv);
+        let config = fake_config(options);
+        let exporter = CSV::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("Family lunch?".to_string());
+        message.edited_parts = Some(EditedMessage {
+            parts: vec![
+                EditedMessagePart {
+                    status: EditStatus::Edited,
+                    edit_history: vec![
+                        EditedEvent {
+                            date: 0,
+                            text: "Family dinner?".to_string(),
+                            guid: None,
+                        },
+                        EditedEvent {
+                            date: 0,
+                            text: "Family lunch?".to_string(),
+                            guid: None,
+                        },
+                    ],
+                },
+                EditedMessagePart {
+                    status: EditStatus::Original,
+                    edit_history: vec![],
+                },
+                EditedMessagePart {
+                    status: EditStatus::Unsent,
+                    edit_history: vec![],
+                },
+            ],
+        });
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "0,,,,Unknown,false,,,,,iMessage,normal,Family lunch?,Family dinner?; unsent,,,0,0,0,0,0,0,0,0\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_csv_shareplay() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Csv);
+        let config = fake_config(options);
+        let exporter = CSV::new(&config).unwrap();
+
+        let mut message = blank();
+        message.item_type = 6;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected =
+            "0,,,,Unknown,false,,,,,iMessage,normal,SharePlay Message Ended,,,,0,0,0,0,0,0,0,0\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_csv_announcement() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Csv);
+        let config = fake_config(options);
+        let exporter = CSV::new(&config).unwrap();
+
+        let mut message = blank();
+        message.group_title = Some("Hello world".to_string());
+
+        let actual = exporter.format_announcement(&message);
+        let expected =
+            "0,,,,Unknown,false,,,,,iMessage,announcement,Unknown renamed the conversation to Hello world,,,,,,,,,,,\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_count_tapbacks() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Csv);
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let mut loved = blank();
+        loved.associated_message_type = Some(2000);
+        loved.associated_message_guid = Some("fake_guid".to_string());
+
+        let mut liked = blank();
+        liked.handle_id = Some(1);
+        liked.associated_message_type = Some(2001);
+        liked.associated_message_guid = Some("fake_guid".to_string());
+
+        let mut removed_like = blank();
+        removed_like.handle_id = Some(1);
+        removed_like.associated_message_type = Some(3001);
+        removed_like.associated_message_guid = Some("fake_guid".to_string());
+
+        let mut part_tapbacks = HashMap::new();
+        part_tapbacks.insert(0, vec![loved, liked, removed_like]);
+        config
+            .tapbacks
+            .insert("fake_guid".to_string(), part_tapbacks);
+
+        let exporter = CSV::new(&config).unwrap();
+
+        let mut message = blank();
+        message.guid = "fake_guid".to_string();
+
+        assert_eq!(
+            exporter.count_tapbacks(&message).unwrap(),
+            [1, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+}
@@ -1,19 +1,35 @@
 use std::{
     borrow::Cow,
+    cell::RefCell,
     collections::{
         hash_map::Entry::{Occupied, Vacant},
-        HashMap,
+        BTreeSet, HashMap,
     },
     fs::File,
     io::{BufWriter, Write},
+    path::Path,
 };
 
+use base64::{engine::general_purpose::STANDARD, Engine};
+use minijinja::Value;
+use serde_json::json;
+
 use crate::{
     app::{
-        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
-        sanitizers::sanitize_html,
+        error::RuntimeError,
+        pagination::Pagination,
+        progress::build_progress_bar_export,
+        runtime::Config,
+        sanitizers::{sanitize_html, sanitize_url_path},
+    },
+    exporters::{
+        exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
+        templates::{
+            announcement_context, app_context, app_store_context, attachment_context,
+            collaboration_context, handwriting_context, music_context, part_context,
+            placemark_context, tapback_context, thread_context, url_context, Templates,
+        },
     },
-    exporters::exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
 };
 
 use imessage_database::{
@@ -33,6 +49,7 @@ use imessage_database::{
     },
     tables::{
         attachment::{Attachment, MediaType},
+        chat::Chat,
         messages::{models::BubbleComponent, Message},
         table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
     },
@@ -45,6 +62,190 @@ use imessage_database::{
 const HEADER: &str = "<html>\n<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">";
 const FOOTER: &str = "</body></html>";
 const STYLE: &str = include_str!("resources/style.css");
+const SEARCH_SCRIPT: &str = include_str!("resources/search.js");
+const EXPRESSIVE_SCRIPT: &str = include_str!("resources/expressives.js");
+/// Name of the landing page that links to every exported conversation
+const INDEX: &str = "index";
+/// Title of the index page entry for messages that do not belong to a conversation
+const ORPHANED_TITLE: &str = "Orphaned messages";
+/// Title of the table of contents link that points back to the index page
+const INDEX_TITLE: &str = "All conversations";
+/// Name of the page that searches every exported conversation
+const SEARCH: &str = "search";
+/// Name of the script that holds the search page's index of messages
+const SEARCH_INDEX: &str = "search_index";
+/// Suffix of the name of each conversation's media gallery page
+const GALLERY: &str = "media";
+/// Number of seconds between messages on the same day before the time is shown between them
+const TIME_GAP: i64 = 60 * 60;
+
+/// Data about an exported conversation that is listed on the index page
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChatSummary {
+    /// The display name of the conversation, or a list of its participants
+    pub title: String,
+    /// IDs of the chats written to the conversation's file
+    pub chat_ids: BTreeSet<i32>,
+    /// Names of the people in the conversation
+    pub members: BTreeSet<String>,
+    /// Number of messages written to the conversation's file
+    pub messages: u64,
+    /// Date of the earliest message written to the conversation's file
+    pub first: Option<i64>,
+    /// Date of the latest message written to the conversation's file
+    pub last: Option<i64>,
+}
+
+impl ChatSummary {
+    /// Include a message sent at the given date
+    fn add(&mut self, date: i64, is_message: bool) {
+        if is_message {
+            self.messages += 1;
+        }
+        self.first = Some(self.first.map_or(date, |first| first.min(date)));
+        self.last = Some(self.last.map_or(date, |last| last.max(date)));
+    }
+}
+
+/// Data about one page of a conversation that is split across multiple files
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PageSummary {
+    /// Name of the page's file, without the extension
+    pub name: String,
+    /// The name of the page in the conversation's table of contents
+    pub label: String,
+    /// The month or year the page holds, if the conversation is split by date
+    pub period: Option<String>,
+    /// Number of messages written to the page
+    pub messages: u64,
+    /// Date of the earliest message written to the page
+    pub first: Option<i64>,
+    /// Date of the latest message written to the page
+    pub last: Option<i64>,
+}
+
+impl PageSummary {
+    /// Include a message sent at the given date
+    fn add(&mut self, date: i64, is_message: bool) {
+        if is_message {
+            self.messages += 1;
+        }
+        self.first = Some(self.first.map_or(date, |first| first.min(date)));
+        self.last = Some(self.last.map_or(date, |last| last.max(date)));
+    }
+}
+
+/// A conversation that is split across multiple files
+pub struct PagedChat {
+    /// Data about every page written so far, in order
+    pub pages: Vec<PageSummary>,
+    /// Writer for the latest page
+    pub file: BufWriter<File>,
+}
+
+/// The latest run of consecutive messages from one sender in a conversation's file
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MessageRun {
+    /// Name of the sender of the run, or `None` if the latest item written was an announcement
+    pub sender: Option<String>,
+    /// Date of the latest item written to the file
+    pub date: i64,
+    /// Receipt for the latest message in the run, written once the run ends
+    pub receipt: Option<String>,
+}
+
+/// Where a top-level message falls in the run of messages from its sender
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// The message is rendered on its own, with its receipt in its header
+    Standalone,
+    /// The message starts a run, so its header names the sender
+    First,
+    /// The message continues a run, so its header is hidden
+    Continued,
+}
+
+/// Kinds of media listed on a conversation's gallery page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Video,
+    Sticker,
+    Handwriting,
+}
+
+/// An image, video, sticker, or handwritten message listed on a conversation's gallery page
+#[derive(Debug, PartialEq, Eq)]
+pub struct MediaItem {
+    /// GUID of the message the media was sent with
+    pub guid: String,
+    /// Date the message was sent
+    pub date: i64,
+    /// The kind of media
+    pub kind: MediaKind,
+    /// Path to the media, or the markup for a handwritten message
+    pub source: String,
+}
+
+/// A compact index of message text that the search page uses to find messages in every conversation
+///
+/// Messages are streamed to the index as they are exported, referring to their file and sender by position
+/// so each name is only written once, in the tables at the end of the index.
+pub struct SearchIndex {
+    /// Writer for the script that holds the index
+    pub file: BufWriter<File>,
+    /// Link to and title of every file that contains an indexed message
+    pub files: Vec<(String, String)>,
+    /// Map of a file's link to its position in `files`
+    pub file_ids: HashMap<String, usize>,
+    /// Name of every sender of an indexed message
+    pub senders: Vec<String>,
+    /// Map of a sender's name to its position in `senders`
+    pub sender_ids: HashMap<String, usize>,
+}
+
+impl SearchIndex {
+    fn new(file: BufWriter<File>) -> Self {
+        SearchIndex {
+            file,
+            files: vec![],
+            file_ids: HashMap::new(),
+            senders: vec![],
+            sender_ids: HashMap::new(),
+        }
+    }
+
+    /// Get the position of a file in the index, adding it if necessary
+    fn file_id(&mut self, href: String, title: &str) -> usize {
+        match self.file_ids.entry(href) {
+            Occupied(entry) => *entry.get(),
+            Vacant(entry) => {
+                self.files.push((entry.key().clone(), title.to_string()));
+                *entry.insert(self.files.len() - 1)
+            }
+        }
+    }
+
+    /// Get the position of a sender in the index, adding them if necessary
+    fn sender_id(&mut self, sender: &str) -> usize {
+        if let Some(id) = self.sender_ids.get(sender) {
+            return *id;
+        }
+        self.senders.push(sender.to_string());
+        self.sender_ids
+            .insert(sender.to_string(), self.senders.len() - 1);
+        self.senders.len() - 1
+    }
+
+    /// Close the list of messages and write the tables they refer to
+    fn format_tables(&self) -> String {
+        format!(
+            "];\nconst SEARCH_FILES = {};\nconst SEARCH_SENDERS = {};\n",
+            json!(self.files),
+            json!(self.senders)
+        )
+    }
+}
 
 pub struct HTML<'a> {
     /// Data that is setup from the application's runtime
@@ -54,6 +255,20 @@ pub struct HTML<'a> {
     pub files: HashMap<String, BufWriter<File>>,
     /// Writer instance for orphaned messages
     pub orphaned: BufWriter<File>,
+    /// Map of resolved chatroom file location to the data listed for it on the index page
+    pub chats: HashMap<String, ChatSummary>,
+    /// Map of resolved chatroom file location to the pages it is split across, if pagination is enabled
+    pub pages: HashMap<String, PagedChat>,
+    /// Index of every exported message for the search page
+    pub search: SearchIndex,
+    /// User-supplied templates that replace the default rendering, if any
+    pub templates: Option<Templates>,
+    /// Map of resolved chatroom file location to the latest run of messages written to it
+    pub runs: HashMap<String, MessageRun>,
+    /// Media rendered by the latest call to `format_message`, added to a gallery once the message is written
+    pub media: RefCell<Vec<MediaItem>>,
+    /// Map of resolved chatroom file location to the media sent in it, with a link to the page each item was written to
+    pub galleries: HashMap<String, Vec<(String, MediaItem)>>,
 }
 
 impl<'a> Exporter<'a> for HTML<'a> {
@@ -67,10 +282,29 @@ impl<'a> Exporter<'a> for HTML<'a> {
             .open(&orphaned)
             .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
 
+        // The search index only covers the current export, so it replaces any previous index
+        let mut search_index = config.options.export_path.clone();
+        search_index.push(SEARCH_INDEX);
+        search_index.set_extension("js");
+        let search_file = File::create(&search_index)
+            .map_err(|err| RuntimeError::CreateError(err, search_index))?;
+
+        let templates = match &config.options.template_dir {
+            Some(dir) => Some(Templates::load(dir, "html")?),
+            None => None,
+        };
+
         Ok(HTML {
             config,
             files: HashMap::new(),
             orphaned: BufWriter::new(file),
+            chats: HashMap::new(),
+            pages: HashMap::new(),
+            search: SearchIndex::new(BufWriter::new(search_file)),
+            templates,
+            runs: HashMap::new(),
+            media: RefCell::new(vec![]),
+            galleries: HashMap::new(),
         })
     }
 
@@ -82,7 +316,10 @@ impl<'a> Exporter<'a> for HTML<'a> {
         );
 
         // Write orphaned file headers
-        HTML::write_headers(&mut self.orphaned)?;
+        HTML::write_headers(&mut self.orphaned, HTML::style(self.templates.as_ref()))?;
+
+        // Open the list of indexed messages
+        HTML::write_to_file(&mut self.search.file, "const SEARCH_MESSAGES = [\n")?;
 
         // Keep track of current message ROWID
         let mut current_message_row = -1;
@@ -119,14 +356,26 @@ impl<'a> Exporter<'a> for HTML<'a> {
             // Render the announcement in-line
             if msg.is_announcement() {
                 let announcement = self.format_announcement(&msg);
-                HTML::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+                let (markers, _) = self.advance_run(&msg, false)?;
+                let file = self.get_or_create_file(&msg)?;
+                HTML::write_to_file(file, &markers)?;
+                HTML::write_to_file(file, &announcement)?;
+                self.summarize(&msg, false);
             }
             // Message replies and tapbacks are rendered in context, so no need to render them separately
             else if !msg.is_tapback() {
+                let (markers, placement) = self.advance_run(&msg, true)?;
                 let message = self
-                    .format_message(&msg, 0)
+                    .format_message_in_run(&msg, 0, placement)
                     .map_err(RuntimeError::DatabaseError)?;
-                HTML::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+                let file = self.get_or_create_file(&msg)?;
+                HTML::write_to_file(file, &markers)?;
+                HTML::write_to_file(file, &message)?;
+                self.summarize(&msg, true);
+                self.add_to_gallery(&msg);
+                if let Some(entry) = self.format_search_entry(&msg) {
+                    HTML::write_to_file(&mut self.search.file, &entry)?;
+                }
             }
             current_message += 1;
             if current_message % 99 == 0 {
@@ -136,11 +385,31 @@ impl<'a> Exporter<'a> for HTML<'a> {
         pb.finish();
 
         eprintln!("Writing HTML footers...");
+        self.finish_runs()?;
         for (_, buf) in self.files.iter_mut() {
             HTML::write_to_file(buf, FOOTER)?;
         }
+        for (filename, chat) in self.pages.iter_mut() {
+            HTML::finish_page(filename, chat, None)?;
+        }
         HTML::write_to_file(&mut self.orphaned, FOOTER)?;
 
+        eprintln!("Writing HTML galleries...");
+        for filename in self.galleries.keys() {
+            self.write_gallery(filename)?;
+        }
+
+        eprintln!("Writing HTML index...");
+        self.write_index()?;
+        for filename in self.pages.keys() {
+            self.write_contents(filename)?;
+        }
+
+        eprintln!("Writing HTML search index...");
+        let tables = self.search.format_tables();
+        HTML::write_to_file(&mut self.search.file, &tables)?;
+        self.write_search_page()?;
+
         Ok(())
     }
 
@@ -153,6 +422,11 @@ impl<'a> Exporter<'a> for HTML<'a> {
             Some((chatroom, _)) => {
                 let filename = self.config.filename(chatroom);
 
+                // Split the conversation across multiple pages, if enabled
+                if let Some(pagination) = &self.config.options.paginate {
+                    return self.get_or_create_page(filename, message, pagination);
+                }
+
                 return match self.files.entry(filename) {
                     Occupied(entry) => Ok(entry.into_mut()),
                     Vacant(entry) => {
@@ -174,7 +448,8 @@ impl<'a> Exporter<'a> for HTML<'a> {
 
                         // Write headers if the file does not exist
                         if !file_exists {
-                            let _ = HTML::write_headers(&mut buf);
+                            let _ =
+                                HTML::write_headers(&mut buf, HTML::style(self.templates.as_ref()));
                         }
 
                         Ok(entry.insert(buf))
@@ -188,132 +463,1071 @@ impl<'a> Exporter<'a> for HTML<'a> {
 
 impl<'a> Writer<'a> for HTML<'a> {
     fn format_message(&self, message: &Message, indent_size: usize) -> Result<String, TableError> {
-        // Data we want to write to a file
-        let mut formatted_message = String::new();
-
-        // Message div
-        if message.is_reply() && indent_size == 0 {
-            // Add an ID for any top-level message so we can link to them in threads
-            self.add_line(
-                &mut formatted_message,
-                &format!("<div class=\"message\", id=\"r-{}\">", message.guid),
-                "",
-                "",
-            );
-        } else {
-            // No ID needed if the message has no replies
-            self.add_line(&mut formatted_message, "<div class=\"message\">", "", "");
-        }
-
-        // Start message div
-        if message.is_from_me() {
-            self.add_line(
-                &mut formatted_message,
-                &format!("<div class=\"sent {:?}\">", message.service()),
-                "",
-                "",
-            );
-        } else {
-            self.add_line(&mut formatted_message, "<div class=\"received\">", "", "");
-        }
+        self.format_message_in_run(message, indent_size, Placement::Standalone)
+    }
 
-        // Add message date
-        self.add_line(
-            &mut formatted_message,
-            &self.get_time(message),
-            "<p><span class=\"timestamp\">",
-            "</span>",
-        );
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        self.render_attachment(attachment, message)
+            .map(|(rendered, _)| rendered)
+    }
 
-        // Add reply anchor if necessary
-        if message.is_reply() {
-            if indent_size > 0 {
-                // If we are indented it means we are rendering in a thread
-                self.add_line(
-                    &mut formatted_message,
-                    &format!("<a href=\"#r-{}\">⇲</a>", message.guid),
-                    "<span class=\"reply_anchor\">",
-                    "</span>",
-                );
-            } else {
-                // If there is no ident we are rendering a top-level message
-                self.add_line(
-                    &mut formatted_message,
-                    &format!("<a href=\"#{}\">⇱</a>", message.guid),
-                    "<span class=\"reply_anchor\">",
-                    "</span>",
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        match self.render_attachment(sticker, message) {
+            Ok((sticker_embed, path)) => {
+                let sticker_effect = sticker.get_sticker_effect(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
                 );
+                let sticker_effect = sticker_effect
+                    .ok()
+                    .flatten()
+                    .map(|effect| effect.to_string());
+                let rendered = match &sticker_effect {
+                    Some(effect) => format!("{sticker_embed}\n<div class=\"sticker_effect\">Sent with {effect} effect</div>"),
+                    None => sticker_embed,
+                };
+                self.apply_template("sticker", rendered, || {
+                    attachment_context(self.config, message, sticker, &path, sticker_effect)
+                })
             }
+            Err(embed) => embed.to_string(),
         }
+    }
 
-        // Add message sender
-        self.add_line(
-            &mut formatted_message,
-            self.config.who(
-                message.handle_id,
-                message.is_from_me(),
-                &message.destination_caller_id,
-            ),
-            "<span class=\"sender\">",
-            "</span></p>",
-        );
+    fn format_app(
+        &self,
+        message: &'a Message,
+        attachments: &mut Vec<Attachment>,
+        _: &str,
+    ) -> Result<String, PlistParseError> {
+        if let Variant::App(balloon) = message.variant() {
+            let mut app_bubble = String::new();
 
-        // If message was deleted (not unsent), annotate it
-        if message.is_deleted() {
-            self.add_line(
-                &mut formatted_message,
-                "This message was deleted from the conversation!",
-                "<span class=\"deleted\">",
-                "</span></p>",
-            );
-        }
+            // Handwritten messages use a different payload type, so check that first
+            if message.is_handwriting() {
+                if let Some(payload) = message.raw_payload_data(&self.config.db) {
+                    return match HandwrittenMessage::from_payload(&payload) {
+                        Ok(bubble) => Ok(self.apply_template(
+                            "handwriting",
+                            self.format_handwriting(message, &bubble, message),
+                            || handwriting_context(self.config, message, &bubble),
+                        )),
+                        Err(why) => Err(PlistParseError::HandwritingError(why)),
+                    };
+                }
+            }
 
-        // Useful message metadata
-        let message_parts = message.body();
-        let mut attachments = Attachment::from_message(&self.config.db, message)?;
-        let mut replies = message.get_replies(&self.config.db)?;
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                let res = if message.is_url() {
+                    let parsed = parse_plist(&payload)?;
+                    let bubble = URLMessage::get_url_message_override(&parsed)?;
+                    match bubble {
+                        URLOverride::Normal(balloon) => self.apply_template(
+                            "url",
+                            self.format_url(message, &balloon, message),
+                            || url_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::AppleMusic(balloon) => self.apply_template(
+                            "music",
+                            self.format_music(&balloon, message),
+                            || music_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::Collaboration(balloon) => self.apply_template(
+                            "collaboration",
+                            self.format_collaboration(&balloon, message),
+                            || collaboration_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::AppStore(balloon) => self.apply_template(
+                            "app_store",
+                            self.format_app_store(&balloon, message),
+                            || app_store_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::SharedPlacemark(balloon) => self.apply_template(
+                            "placemark",
+                            self.format_placemark(&balloon, message),
+                            || placemark_context(self.config, message, &balloon),
+                        ),
+                    }
+                } else {
+                    let parsed = parse_plist(&payload)?;
+                    match AppMessage::from_map(&parsed) {
+                        Ok(bubble) => {
+                            let (name, rendered) = match balloon {
+                                CustomBalloon::Application(bundle_id) => (
+                                    "generic_app",
+                                    self.format_generic_app(
+                                        &bubble,
+                                        bundle_id,
+                                        attachments,
+                                        message,
+                                    ),
+                                ),
+                                CustomBalloon::ApplePay => {
+                                    ("apple_pay", self.format_apple_pay(&bubble, message))
+                                }
+                                CustomBalloon::Fitness => {
+                                    ("fitness", self.format_fitness(&bubble, message))
+                                }
+                                CustomBalloon::Slideshow => {
+                                    ("slideshow", self.format_slideshow(&bubble, message))
+                                }
+                                CustomBalloon::CheckIn => {
+                                    ("check_in", self.format_check_in(&bubble, message))
+                                }
+                                CustomBalloon::FindMy => {
+                                    ("find_my", self.format_find_my(&bubble, message))
+                                }
+                                CustomBalloon::Handwriting => unreachable!(),
+                                CustomBalloon::URL => unreachable!(),
+                            };
+                            let bundle_id = match balloon {
+                                CustomBalloon::Application(bundle_id) => Some(bundle_id),
+                                _ => None,
+                            };
+                            self.apply_template(name, rendered, || {
+                                app_context(self.config, message, &bubble, bundle_id)
+                            })
+                        }
+                        Err(why) => return Err(why),
+                    }
+                };
+                app_bubble.push_str(&res);
+            } else {
+                // Sometimes, URL messages are missing their payloads
+                if message.is_url() {
+                    if let Some(text) = &message.text {
+                        let mut out_s = String::new();
+                        out_s.push_str("<a href=\"");
+                        out_s.push_str(text);
+                        out_s.push_str("\">");
 
-        // Index of where we are in the attachment Vector
-        let mut attachment_index: usize = 0;
+                        out_s.push_str("<div class=\"app_header\"><div class=\"name\">");
+                        out_s.push_str(text);
+                        out_s.push_str("</div></div>");
 
-        // Add message subject
-        if let Some(subject) = &message.subject {
-            // Add message sender
-            self.add_line(
-                &mut formatted_message,
-                &sanitize_html(subject),
-                "<p>Subject: <span class=\"subject\">",
-                "</span></p>",
-            );
-        }
+                        out_s.push_str("<div class=\"app_footer\"><div class=\"caption\">");
+                        out_s.push_str(text);
+                        out_s.push_str("</div></div></a>");
 
-        // Handle SharePlay
-        if message.is_shareplay() {
-            self.add_line(
-                &mut formatted_message,
-                self.format_shareplay(),
-                "<span class=\"shareplay\">",
-                "</span>",
-            );
+                        return Ok(out_s);
+                    }
+                }
+                return Err(PlistParseError::NoPayload);
+            }
+            Ok(app_bubble)
+        } else {
+            Err(PlistParseError::WrongMessageType)
         }
+    }
 
-        // Handle Shared Location
-        if message.started_sharing_location() || message.stopped_sharing_location() {
-            self.add_line(
-                &mut formatted_message,
-                self.format_shared_location(message),
-                "<span class=\"shared_location\">",
-                "</span>",
-            );
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        match msg.variant() {
+            Variant::Tapback(_, added, tapback) => {
+                if !added {
+                    return Ok(String::new());
+                }
+                let rendered = format!(
+                    "<span class=\"tapback\"><b>{}</b> by {}</span>",
+                    tapback,
+                    self.config
+                        .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id),
+                );
+                Ok(self.apply_template("tapback", rendered, || tapback_context(self.config, msg)))
+            }
+            Variant::Sticker(_) => {
+                let mut paths = Attachment::from_message(&self.config.db, msg)?;
+                let who =
+                    self.config
+                        .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+                // Sticker messages have only one attachment, the sticker image
+                let rendered = match paths.get_mut(0) {
+                    Some(sticker) => format!(
+                        "{} <div class=\"sticker_tapback\">&nbsp;by {who}</div>",
+                        self.format_sticker(sticker, msg)
+                    ),
+                    None => {
+                        format!("<span class=\"tapback\">Sticker from {who} not found!</span>")
+                    }
+                };
+                Ok(self.apply_template("tapback", rendered, || tapback_context(self.config, msg)))
+            }
+            _ => unreachable!(),
         }
+    }
 
-        // Generate the message body from it's components
-        for (idx, message_part) in message_parts.iter().enumerate() {
-            // Write the part div start
-            self.add_line(
-                &mut formatted_message,
-                "<hr><div class=\"message_part\">",
-                "",
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or("You");
+        }
+        let timestamp = format(&msg.date(&self.config.offset));
+
+        let rendered = match msg.get_announcement() {
+            Some(announcement) => match announcement {
+                Announcement::NameChange(name) => {
+                    let clean_name = sanitize_html(name);
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} named the conversation <b>{clean_name}</b></p></div>\n"
+                    )
+                }
+                Announcement::PhotoChange => {
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} changed the group photo.</p></div>\n"
+                    )
+                }
+                Announcement::Unknown(num) => {
+                    format!(
+                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} performed unknown action {num}</p></div>\n"
+                    )
+                }
+                Announcement::FullyUnsent => {
+                    format!(
+                        "<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} unsent a message.</p></div>"
+                    )
+                }
+            },
+            None => String::from(
+                "\n<div class =\"announcement\"><p>Unable to format announcement!</p></div>\n",
+            ),
+        };
+
+        self.apply_template("announcement", rendered, || {
+            announcement_context(self.config, msg)
+        })
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "<hr>SharePlay Message Ended"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "<hr>Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "<hr>Stopped sharing location!";
+        }
+        "<hr>Shared location!"
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        if let Some(edited_message_part) = edited_message.part(message_part_idx) {
+            let mut out_s = String::new();
+            let mut previous_timestamp: Option<&i64> = None;
+
+            match edited_message_part.status {
+                EditStatus::Edited => {
+                    out_s.push_str("<table>");
+
+                    for (idx, event) in edited_message_part.edit_history.iter().enumerate() {
+                        let last = idx == edited_message_part.edit_history.len() - 1;
+                        let clean_text = sanitize_html(&event.text);
+                        match previous_timestamp {
+                            None => out_s.push_str(&self.edited_to_html("", &clean_text, last)),
+                            Some(prev_timestamp) => {
+                                let end = get_local_time(&event.date, &self.config.offset);
+                                let start = get_local_time(prev_timestamp, &self.config.offset);
+
+                                let diff = readable_diff(start, end).unwrap_or_default();
+                                out_s.push_str(&self.edited_to_html(
+                                    &format!("Edited {diff} later"),
+                                    &clean_text,
+                                    last,
+                                ));
+                            }
+                        }
+
+                        // Update the previous timestamp for the next loop
+                        previous_timestamp = Some(&event.date);
+                    }
+
+                    out_s.push_str("</table>");
+                }
+                EditStatus::Unsent => {
+                    let who = if msg.is_from_me() {
+                        self.config.options.custom_name.as_deref().unwrap_or(YOU)
+                    } else {
+                        self.config
+                            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id)
+                    };
+
+                    match readable_diff(
+                        msg.date(&self.config.offset),
+                        msg.date_edited(&self.config.offset),
+                    ) {
+                        Some(diff) => {
+                            out_s.push_str(&format!(
+                                "<span class=\"unsent\">{who} unsent this message part {diff} after sending!</span>"
+                            ))
+                        },
+                        None => {
+                            out_s.push_str(&format!(
+                                "<span class=\"unsent\">{who} unsent this message part!</span>"
+                            ))
+                        },
+                    }
+                }
+                EditStatus::Original => {
+                    return None;
+                }
+            }
+            return Some(out_s);
+        }
+        None
+    }
+
+    fn format_attributed(&'a self, text: &'a str, attribute: &'a TextEffect) -> Cow<str> {
+        match attribute {
+            TextEffect::Default => Cow::Borrowed(text),
+            TextEffect::Mention(mentioned) => Cow::Owned(self.format_mention(text, mentioned)),
+            TextEffect::Link(url) => Cow::Owned(self.format_link(text, url)),
+            TextEffect::OTP => Cow::Owned(self.format_otp(text)),
+            TextEffect::Styles(styles) => Cow::Owned(self.format_styles(text, styles)),
+            TextEffect::Animated(animation) => Cow::Owned(self.format_animated(text, animation)),
+            TextEffect::Conversion(unit) => Cow::Owned(self.format_conversion(text, unit)),
+        }
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
+    fn format_url(&self, msg: &Message, balloon: &URLMessage, _: &Message) -> String {
+        let mut out_s = String::new();
+
+        // Make the whole bubble clickable
+        let mut close_url = false;
+        if let Some(url) = balloon.get_url() {
+            out_s.push_str("<a href=\"");
+            out_s.push_str(url);
+            out_s.push_str("\">");
+            close_url = true;
+        } else if let Some(text) = &msg.text {
+            // Fallback if the balloon data does not contain the URL
+            out_s.push_str("<a href=\"");
+            out_s.push_str(text);
+            out_s.push_str("\">");
+            close_url = true;
+        }
+
+        // Header section
+        out_s.push_str("<div class=\"app_header\">");
+
+        // Add preview images
+        balloon.images.iter().for_each(|image| {
+            out_s.push_str("<img src=\"");
+            out_s.push_str(image);
+            if self.config.options.no_lazy {
+                out_s.push_str("\" onerror=\"this.style.display='none'\">");
+            } else {
+                out_s.push_str("\" loading=\"lazy\", onerror=\"this.style.display='none'\">");
+            }
+        });
+
+        if let Some(site_name) = balloon.site_name {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(site_name);
+            out_s.push_str("</div>");
+        } else if let Some(url) = balloon.get_url() {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(url);
+            out_s.push_str("</div>");
+        } else if let Some(text) = &msg.text {
+            // Fallback if the balloon data does not contain the URL
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(text);
+            out_s.push_str("</div>");
+        }
+
+        // Header end
+        out_s.push_str("</div>");
+
+        // Only write the footer if there is data to write
+        if balloon.title.is_some() || balloon.summary.is_some() {
+            out_s.push_str("<div class=\"app_footer\">");
+
+            // Title
+            if let Some(title) = balloon.title {
+                out_s.push_str("<div class=\"caption\">");
+                out_s.push_str(&sanitize_html(title));
+                out_s.push_str("</div>");
+            }
+
+            // Subtitle
+            if let Some(summary) = balloon.summary {
+                out_s.push_str("<div class=\"subcaption\">");
+                out_s.push_str(&sanitize_html(summary));
+                out_s.push_str("</div>");
+            }
+
+            // End footer
+            out_s.push_str("</div>");
+        }
+
+        // End the link
+        if close_url {
+            out_s.push_str("</a>");
+        }
+        out_s
+    }
+
+    fn format_music(&self, balloon: &MusicMessage, _: &Message) -> String {
+        let mut out_s = String::new();
+
+        // Header section
+        out_s.push_str("<div class=\"app_header\">");
+
+        // Name
+        if let Some(track_name) = balloon.track_name {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(track_name);
+            out_s.push_str("</div>");
+        }
+
+        // Add preview section
+        if let Some(preview) = balloon.preview {
+            out_s.push_str("<audio controls src=\"");
+            out_s.push_str(preview);
+            out_s.push_str("\" </audio>");
+        }
+
+        // Header end
+        out_s.push_str("</div>");
+
+        // Make the footer clickable so we can interact with the preview
+        if let Some(url) = balloon.url {
+            out_s.push_str("<a href=\"");
+            out_s.push_str(url);
+            out_s.push_str("\">");
+        }
+
+        // Only write the footer if there is data to write
+        if balloon.artist.is_some() || balloon.album.is_some() {
+            out_s.push_str("<div class=\"app_footer\">");
+
+            // artist
+            if let Some(artist) = balloon.artist {
+                out_s.push_str("<div class=\"caption\">");
+                out_s.push_str(artist);
+                out_s.push_str("</div>");
+            }
+
+            // Subtitle
+            if let Some(album) = balloon.album {
+                out_s.push_str("<div class=\"subcaption\">");
+                out_s.push_str(album);
+                out_s.push_str("</div>");
+            }
+
+            // End footer
+            out_s.push_str("</div>");
+        }
+
+        // End the link
+        if balloon.url.is_some() {
+            out_s.push_str("</a>");
+        }
+        out_s
+    }
+
+    fn format_collaboration(&self, balloon: &CollaborationMessage, _: &Message) -> String {
+        let mut out_s = String::new();
+
+        // Header section
+        out_s.push_str("<div class=\"app_header\">");
+
+        // Name
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(app_name);
+            out_s.push_str("</div>");
+        } else if let Some(bundle_id) = balloon.bundle_id {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(bundle_id);
+            out_s.push_str("</div>");
+        }
+
+        // Header end
+        out_s.push_str("</div>");
+
+        // Make the footer clickable so we can interact with the preview
+        if let Some(url) = balloon.url {
+            out_s.push_str("<a href=\"");
+            out_s.push_str(url);
+            out_s.push_str("\">");
+        }
+
+        // Only write the footer if there is data to write
+        if balloon.title.is_some() || balloon.get_url().is_some() {
+            out_s.push_str("<div class=\"app_footer\">");
+
+            // artist
+            if let Some(title) = balloon.title {
+                out_s.push_str("<div class=\"caption\">");
+                out_s.push_str(title);
+                out_s.push_str("</div>");
+            }
+
+            // Subtitle
+            if let Some(url) = balloon.get_url() {
+                out_s.push_str("<div class=\"subcaption\">");
+                out_s.push_str(url);
+                out_s.push_str("</div>");
+            }
+
+            // End footer
+            out_s.push_str("</div>");
+        }
+
+        // End the link
+        if balloon.url.is_some() {
+            out_s.push_str("</a>");
+        }
+
+        out_s
+    }
+
+    fn format_app_store(&self, balloon: &AppStoreMessage, _: &'a Message) -> String {
+        let mut out_s = String::new();
+
+        // Header section
+        out_s.push_str("<div class=\"app_header\">");
+
+        // App name
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(app_name);
+            out_s.push_str("</div>");
+        }
+
+        // Header end
+        out_s.push_str("</div>");
+
+        // Make the footer clickable so we can interact with the preview
+        if let Some(url) = balloon.url {
+            out_s.push_str("<a href=\"");
+            out_s.push_str(url);
+            out_s.push_str("\">");
+        }
+
+        // Only write the footer if there is data to write
+        if balloon.description.is_some() || balloon.genre.is_some() {
+            out_s.push_str("<div class=\"app_footer\">");
+
+            // App description
+            if let Some(description) = balloon.description {
+                out_s.push_str("<div class=\"caption\">");
+                out_s.push_str(description);
+                out_s.push_str("</div>");
+            }
+
+            // App platform
+            if let Some(platform) = balloon.platform {
+                out_s.push_str("<div class=\"subcaption\">");
+                out_s.push_str(platform);
+                out_s.push_str("</div>");
+            }
+
+            // App genre
+            if let Some(genre) = balloon.genre {
+                out_s.push_str("<div class=\"trailing_subcaption\">");
+                out_s.push_str(genre);
+                out_s.push_str("</div>");
+            }
+
+            // End footer
+            out_s.push_str("</div>");
+        }
+
+        // End the link
+        if balloon.url.is_some() {
+            out_s.push_str("</a>");
+        }
+        out_s
+    }
+
+    fn format_placemark(&self, balloon: &PlacemarkMessage, _: &'a Message) -> String {
+        let mut out_s = String::new();
+
+        // Make the whole bubble clickable
+        if let Some(url) = balloon.get_url() {
+            out_s.push_str("<a href=\"");
+            out_s.push_str(url);
+            out_s.push_str("\">");
+        }
+
+        // Header section
+        out_s.push_str("<div class=\"app_header\">");
+
+        if let Some(place_name) = balloon.place_name {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(place_name);
+            out_s.push_str("</div>");
+        } else if let Some(url) = balloon.get_url() {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(url);
+            out_s.push_str("</div>");
+        }
+
+        // Header end
+        out_s.push_str("</div>");
+
+        // Only write the footer if there is data to write
+        if balloon.placemark.address.is_some()
+            || balloon.placemark.postal_code.is_some()
+            || balloon.placemark.country.is_some()
+            || balloon.placemark.sub_administrative_area.is_some()
+        {
+            out_s.push_str("<div class=\"app_footer\">");
+
+            // Address
+            if let Some(address) = balloon.placemark.address {
+                out_s.push_str("<div class=\"caption\">");
+                out_s.push_str(address);
+                out_s.push_str("</div>");
+            }
+
+            // Postal Code
+            if let Some(postal_code) = balloon.placemark.postal_code {
+                out_s.push_str("<div class=\"trailing_caption\">");
+                out_s.push_str(postal_code);
+                out_s.push_str("</div>");
+            }
+
+            // Country
+            if let Some(country) = balloon.placemark.country {
+                out_s.push_str("<div class=\"subcaption\">");
+                out_s.push_str(country);
+                out_s.push_str("</div>");
+            }
+
+            // Administrative Area
+            if let Some(area) = balloon.placemark.sub_administrative_area {
+                out_s.push_str("<div class=\"trailing_subcaption\">");
+                out_s.push_str(area);
+                out_s.push_str("</div>");
+            }
+
+            // End footer
+            out_s.push_str("</div>");
+        }
+
+        // End the link
+        if balloon.get_url().is_some() {
+            out_s.push_str("</a>");
+        }
+        out_s
+    }
+
+    fn format_handwriting(
+        &self,
+        msg: &Message,
+        balloon: &HandwrittenMessage,
+        _: &Message,
+    ) -> String {
+        // svg can be embedded directly into the html
+        let svg = balloon.render_svg();
+        self.media.borrow_mut().push(MediaItem {
+            guid: msg.guid.clone(),
+            date: msg.date,
+            kind: MediaKind::Handwriting,
+            source: svg.clone(),
+        });
+        svg
+    }
+
+    fn format_apple_pay(&self, balloon: &AppMessage, _: &Message) -> String {
+        let mut out_s = String::new();
+
+        out_s.push_str("<div class=\"app_header\">");
+
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(app_name);
+            out_s.push_str("</div>");
+        }
+
+        // Header end, footer begin
+        out_s.push_str("</div>");
+        out_s.push_str("<div class=\"app_footer\">");
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str("<div class=\"caption\">");
+            out_s.push_str(ldtext);
+            out_s.push_str("</div>");
+        }
+
+        // End footer
+        out_s.push_str("</div>");
+
+        out_s
+    }
+
+    fn format_fitness(&self, balloon: &AppMessage, message: &Message) -> String {
+        self.balloon_to_html(balloon, "Fitness", &mut [], message)
+    }
+
+    fn format_slideshow(&self, balloon: &AppMessage, message: &Message) -> String {
+        self.balloon_to_html(balloon, "Slideshow", &mut [], message)
+    }
+
+    fn format_find_my(&self, balloon: &AppMessage, _: &'a Message) -> String {
+        let mut out_s = String::new();
+
+        out_s.push_str("<div class=\"app_header\">");
+
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str("<div class=\"name\">");
+            out_s.push_str(app_name);
+            out_s.push_str("</div>");
+        }
+
+        // Header end, footer begin
+        out_s.push_str("</div>");
+        out_s.push_str("<div class=\"app_footer\">");
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str("<div class=\"caption\">");
+            out_s.push_str(ldtext);
+            out_s.push_str("</div>");
+        }
+
+        // End footer
+        out_s.push_str("</div>");
+
+        out_s
+    }
+
+    fn format_check_in(&self, balloon: &AppMessage, _: &Message) -> String {
+        let mut out_s = String::new();
+
+        out_s.push_str("<div class=\"app_header\">");
+
+        // Name
+        out_s.push_str("<div class=\"name\">");
+        out_s.push_str(balloon.app_name.unwrap_or("Check In"));
+        out_s.push_str("</div>");
+
+        // ldtext
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str("<div class=\"ldtext\">");
+            out_s.push_str(ldtext);
+            out_s.push_str("</div>");
+        }
+
+        // Header end, footer begin
+        out_s.push_str("</div>");
+
+        // Only write the footer if there is data to write
+        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+
+        // Before manual check-in
+        if let Some(date_str) = metadata.get("estimatedEndTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str("<div class=\"app_footer\">");
+
+            out_s.push_str("<div class=\"caption\">Expected around ");
+            out_s.push_str(&date_string);
+            out_s.push_str("</div>");
+
+            out_s.push_str("</div>");
+        }
+        // Expired check-in
+        else if let Some(date_str) = metadata.get("triggerTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str("<div class=\"app_footer\">");
+
+            out_s.push_str("<div class=\"caption\">Was expected around ");
+            out_s.push_str(&date_string);
+            out_s.push_str("</div>");
+
+            out_s.push_str("</div>");
+        }
+        // Accepted check-in
+        else if let Some(date_str) = metadata.get("sendDate") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            out_s.push_str("<div class=\"app_footer\">");
+
+            out_s.push_str("<div class=\"caption\">Checked in at ");
+            out_s.push_str(&date_string);
+            out_s.push_str("</div>");
+
+            out_s.push_str("</div>");
+        }
+
+        out_s
+    }
+
+    fn format_generic_app(
+        &self,
+        balloon: &AppMessage,
+        bundle_id: &str,
+        attachments: &mut Vec<Attachment>,
+        message: &Message,
+    ) -> String {
+        self.balloon_to_html(balloon, bundle_id, attachments, message)
+    }
+}
+
+impl<'a> TextEffectFormatter for HTML<'a> {
+    fn format_mention(&self, text: &str, mentioned: &str) -> String {
+        format!("<span title=\"{mentioned}\"><b>{text}</b></span>")
+    }
+
+    fn format_link(&self, text: &str, url: &str) -> String {
+        format!("<a href=\"{url}\">{text}</a>")
+    }
+
+    fn format_otp(&self, text: &str) -> String {
+        format!("<u>{text}</u>")
+    }
+
+    fn format_conversion(&self, text: &str, _: &Unit) -> String {
+        format!("<u>{text}</u>")
+    }
+
+    fn format_styles(&self, text: &str, styles: &[Style]) -> String {
+        let (prefix, suffix): (String, String) = styles.iter().rev().fold(
+            (String::new(), String::new()),
+            |(mut prefix, mut suffix), style| {
+                let (open, close) = match style {
+                    Style::Bold => ("<b>", "</b>"),
+                    Style::Italic => ("<i>", "</i>"),
+                    Style::Strikethrough => ("<s>", "</s>"),
+                    Style::Underline => ("<u>", "</u>"),
+                };
+                prefix.push_str(open);
+                suffix.insert_str(0, close);
+                (prefix, suffix)
+            },
+        );
+
+        format!("{prefix}{text}{suffix}")
+    }
+
+    fn format_animated(&self, text: &str, animation: &Animation) -> String {
+        format!("<span class=\"animation{animation:?}\">{text}</span>")
+    }
+}
+
+impl<'a> HTML<'a> {
+    /// Render a message, hiding its header if it continues a run of messages from the same sender
+    ///
+    /// Messages in a run leave their receipt to the end of the run instead of their header.
+    fn format_message_in_run(
+        &self,
+        message: &Message,
+        indent_size: usize,
+        placement: Placement,
+    ) -> Result<String, TableError> {
+        // Data we want to write to a file
+        let mut formatted_message = String::new();
+
+        // Message div
+        if indent_size == 0 {
+            // Add an ID for any top-level message so we can link to them in threads and search results
+            let class = match placement {
+                Placement::Continued => "message continued",
+                _ => "message",
+            };
+            self.add_line(
+                &mut formatted_message,
+                &format!("<div class=\"{class}\", id=\"r-{}\">", message.guid),
+                "",
+                "",
+            );
+        } else {
+            // Messages rendered in a thread are wrapped in a div with their ID
+            self.add_line(&mut formatted_message, "<div class=\"message\">", "", "");
+        }
+
+        // Start message div, with the classes that animate its expressive, if any
+        let effect = self
+            .format_effect(message)
+            .map(|effect| format!(" effect effect_{effect}"))
+            .unwrap_or_default();
+        if message.is_from_me() {
+            self.add_line(
+                &mut formatted_message,
+                &format!("<div class=\"sent {:?}{effect}\">", message.service()),
+                "",
+                "",
+            );
+        } else {
+            self.add_line(
+                &mut formatted_message,
+                &format!("<div class=\"received{effect}\">"),
+                "",
+                "",
+            );
+        }
+
+        // Add message date, leaving the receipt to the end of the run if the message is in one
+        let timestamp = match placement {
+            Placement::Standalone => self.get_time(message),
+            _ => format(&message.date(&self.config.offset)),
+        };
+        self.add_line(
+            &mut formatted_message,
+            &timestamp,
+            "<p><span class=\"timestamp\">",
+            "</span>",
+        );
+
+        // Add reply anchor if necessary
+        if message.is_reply() {
+            if indent_size > 0 {
+                // If we are indented it means we are rendering in a thread
+                self.add_line(
+                    &mut formatted_message,
+                    &format!("<a href=\"#r-{}\">⇲</a>", message.guid),
+                    "<span class=\"reply_anchor\">",
+                    "</span>",
+                );
+            } else {
+                // If there is no ident we are rendering a top-level message
+                self.add_line(
+                    &mut formatted_message,
+                    &format!("<a href=\"#{}\">⇱</a>", message.guid),
+                    "<span class=\"reply_anchor\">",
+                    "</span>",
+                );
+            }
+        }
+
+        // Add message sender, unless the first message of the run already named them
+        if placement == Placement::Continued {
+            self.add_line(&mut formatted_message, "</p>", "", "");
+        } else {
+            self.add_line(
+                &mut formatted_message,
+                self.config.who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                ),
+                "<span class=\"sender\">",
+                "</span></p>",
+            );
+        }
+
+        // If message was deleted (not unsent), annotate it
+        if message.is_deleted() {
+            self.add_line(
+                &mut formatted_message,
+                "This message was deleted from the conversation!",
+                "<span class=\"deleted\">",
+                "</span></p>",
+            );
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut replies = message.get_replies(&self.config.db)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Rendered message parts, collected if a template renders the message
+        let template = self.message_template(indent_size);
+        let mut parts: Vec<Value> = vec![];
+
+        // Add message subject
+        if let Some(subject) = &message.subject {
+            // Add message sender
+            self.add_line(
+                &mut formatted_message,
+                &sanitize_html(subject),
+                "<p>Subject: <span class=\"subject\">",
+                "</span></p>",
+            );
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            self.add_line(
+                &mut formatted_message,
+                self.format_shareplay(),
+                "<span class=\"shareplay\">",
+                "</span>",
+            );
+        }
+
+        // Handle Shared Location
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            self.add_line(
+                &mut formatted_message,
+                self.format_shared_location(message),
+                "<span class=\"shared_location\">",
+                "</span>",
+            );
+        }
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            let part_start = formatted_message.len();
+            let mut part_tapbacks: Vec<String> = vec![];
+            let mut part_replies: Vec<String> = vec![];
+
+            // Write the part div start
+            self.add_line(
+                &mut formatted_message,
+                "<hr><div class=\"message_part\">",
+                "",
                 "",
             );
 
@@ -450,6 +1664,7 @@ impl<'a> Writer<'a> for HTML<'a> {
 
             // Write the part div end
             self.add_line(&mut formatted_message, "</div>", "", "");
+            let part_end = formatted_message.len();
 
             // Handle expressives
             if message.expressive_send_style_id.is_some() {
@@ -473,10 +1688,11 @@ impl<'a> Writer<'a> for HTML<'a> {
                             if !formatted.is_empty() {
                                 self.add_line(
                                     &mut formatted_tapbacks,
-                                    &self.format_tapback(tapback)?,
+                                    &formatted,
                                     "<div class=\"tapback\">",
                                     "</div>",
                                 );
+                                part_tapbacks.push(formatted);
                             }
                             Ok(())
                         })?;
@@ -503,17 +1719,27 @@ impl<'a> Writer<'a> for HTML<'a> {
                         let _ = reply.generate_text(&self.config.db);
                         if !reply.is_tapback() {
                             // Set indent to 1 so we know this is a recursive call
+                            let formatted = self.format_message(reply, 1)?;
                             self.add_line(
                                 &mut formatted_message,
-                                &self.format_message(reply, 1)?,
+                                &formatted,
                                 &format!("<div class=\"reply\" id=\"{}\">", reply.guid),
                                 "</div>",
                             );
+                            part_replies.push(formatted);
                         }
                         Ok(())
                     })?;
                 self.add_line(&mut formatted_message, "</div>", "", "");
             }
+
+            if template.is_some() {
+                parts.push(part_context(
+                    &formatted_message[part_start..part_end],
+                    &part_tapbacks,
+                    &part_replies,
+                ));
+            }
         }
 
         // Add a note if the message is a reply and not rendered in a thread
@@ -532,14 +1758,192 @@ impl<'a> Writer<'a> for HTML<'a> {
         // End message div
         self.add_line(&mut formatted_message, "</div>", "", "");
 
-        Ok(formatted_message)
+        Ok(match template {
+            Some(name) => self.apply_template(name, formatted_message, || {
+                thread_context(self.config, message, indent_size, parts)
+            }),
+            None => formatted_message,
+        })
+    }
+
+    /// Name of the effect `expressives.js` plays for a message sent with an expressive, if it is known
+    fn format_effect(&self, message: &Message) -> Option<&'static str> {
+        match message.get_expressive() {
+            Expressive::Screen(effect) => Some(match effect {
+                ScreenEffect::Confetti => "confetti",
+                ScreenEffect::Echo => "echo",
+                ScreenEffect::Fireworks => "fireworks",
+                ScreenEffect::Balloons => "balloons",
+                ScreenEffect::Heart => "heart",
+                ScreenEffect::Lasers => "lasers",
+                ScreenEffect::ShootingStar => "shooting_star",
+                ScreenEffect::Sparkles => "sparkles",
+                ScreenEffect::Spotlight => "spotlight",
+            }),
+            Expressive::Bubble(effect) => Some(match effect {
+                BubbleEffect::Slam => "slam",
+                BubbleEffect::Loud => "loud",
+                BubbleEffect::Gentle => "gentle",
+                BubbleEffect::InvisibleInk => "invisible_ink",
+            }),
+            Expressive::Unknown(_) | Expressive::None => None,
+        }
+    }
+
+    fn get_time(&self, message: &Message) -> String {
+        let mut date = format(&message.date(&self.config.offset));
+        if let Some(receipt) = self.get_receipt(message) {
+            date.push_str(&format!(" ({receipt})"));
+        }
+        date
+    }
+
+    /// Describe when a message was read, or that it was delivered if it was sent by the database owner
+    fn get_receipt(&self, message: &Message) -> Option<String> {
+        match message.time_until_read(&self.config.offset) {
+            Some(time) if !time.is_empty() => {
+                let who = if message.is_from_me() {
+                    "them"
+                } else {
+                    self.config.options.custom_name.as_deref().unwrap_or("you")
+                };
+                Some(format!("Read by {who} after {time}"))
+            }
+            _ if message.is_from_me() && message.date_delivered != 0 => {
+                Some(String::from("Delivered"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Place a message or announcement in the run of messages written to its file
+    ///
+    /// Returns the markup to write before it: the receipt that ends the previous run, followed by
+    /// a day separator or time gap marker if enough time has passed since the previous message.
+    fn advance_run(
+        &mut self,
+        message: &Message,
+        is_message: bool,
+    ) -> Result<(String, Placement), RuntimeError> {
+        // Runs do not continue across pages, so start the message's page before placing it
+        self.get_or_create_file(message)?;
+
+        let config = self.config;
+        let (filename, _) = self.resolve_filename(message);
+        let sender = is_message.then(|| {
+            config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string()
+        });
+        let receipt = if is_message {
+            self.get_receipt(message).map(|receipt| {
+                let class = if message.is_from_me() {
+                    "sent"
+                } else {
+                    "received"
+                };
+                format!("<div class=\"receipt {class}\">{receipt}</div>\n")
+            })
+        } else {
+            None
+        };
+
+        let previous = self.runs.insert(
+            filename,
+            MessageRun {
+                sender: sender.clone(),
+                date: message.date,
+                receipt,
+            },
+        );
+
+        let separator = self.format_run_break(previous.as_ref().map(|run| run.date), message);
+        let continued = separator.is_empty()
+            && sender.is_some()
+            && previous.as_ref().map(|run| &run.sender) == Some(&sender);
+
+        let mut markup = String::new();
+        if !continued {
+            if let Some(receipt) = previous.and_then(|run| run.receipt) {
+                markup.push_str(&receipt);
+            }
+        }
+        markup.push_str(&separator);
+
+        let placement = if continued {
+            Placement::Continued
+        } else {
+            Placement::First
+        };
+        Ok((markup, placement))
+    }
+
+    /// Build the marker that separates a message from the message written before it, if any
+    ///
+    /// Messages sent on a new day get a day separator, while messages sent long after the previous
+    /// message on the same day get the time they were sent.
+    fn format_run_break(&self, previous: Option<i64>, message: &Message) -> String {
+        let date = match message.date(&self.config.offset) {
+            Ok(date) => date,
+            Err(_) => return String::new(),
+        };
+        let previous_date = previous.and_then(|previous| {
+            get_local_time(&previous, &self.config.offset)
+                .ok()
+                .map(|previous_date| (previous, previous_date))
+        });
+
+        match previous_date {
+            Some((previous, previous_date)) if previous_date.date_naive() == date.date_naive() => {
+                if (message.date - previous) / TIMESTAMP_FACTOR >= TIME_GAP {
+                    format!(
+                        "<div class=\"time_gap\">{}</div>\n",
+                        date.format("%-I:%M %p")
+                    )
+                } else {
+                    String::new()
+                }
+            }
+            _ => format!(
+                "<div class=\"day_separator\">{}</div>\n",
+                date.format("%A, %B %-d, %Y")
+            ),
+        }
+    }
+
+    /// Write the receipt that ends the latest run of messages in every file
+    fn finish_runs(&mut self) -> Result<(), RuntimeError> {
+        for (filename, run) in self.runs.drain() {
+            if let Some(receipt) = run.receipt {
+                let file = match self.pages.get_mut(&filename) {
+                    Some(chat) => &mut chat.file,
+                    None => self.files.get_mut(&filename).unwrap_or(&mut self.orphaned),
+                };
+                HTML::write_to_file(file, &receipt)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_line(&self, string: &mut String, part: &str, pre: &str, post: &str) {
+        if !part.is_empty() {
+            string.push_str(pre);
+            string.push_str(part);
+            string.push_str(post);
+            string.push('\n');
+        }
     }
 
-    fn format_attachment(
+    /// Render an attachment, along with the path or inlined `data:` URI its markup points to
+    fn render_attachment(
         &self,
         attachment: &'a mut Attachment,
         message: &Message,
-    ) -> Result<String, &'a str> {
+    ) -> Result<(String, String), &'a str> {
         // Copy the file, if requested
         self.config
             .options
@@ -547,10 +1951,30 @@ impl<'a> Writer<'a> for HTML<'a> {
             .handle_attachment(message, attachment, self.config)
             .ok_or(attachment.filename())?;
 
-        // Build a relative filepath from the fully qualified one on the `Attachment`
-        let embed_path = self.config.message_attachment_path(attachment);
+        // Inline the file if requested, otherwise build a relative filepath from the fully qualified one on the `Attachment`
+        let embed_path = match self.inline_attachment(attachment) {
+            Some(data_uri) => data_uri,
+            None => self.config.message_attachment_path(attachment),
+        };
+
+        // Collect images and videos for the conversation's gallery
+        let kind = match attachment.mime_type() {
+            MediaType::Image(_) if attachment.is_sticker => Some(MediaKind::Sticker),
+            MediaType::Image(_) => Some(MediaKind::Image),
+            MediaType::Video(_) => Some(MediaKind::Video),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            // Link to the file instead of repeating inlined data, which would keep every attachment in memory
+            self.media.borrow_mut().push(MediaItem {
+                guid: message.guid.clone(),
+                date: message.date,
+                kind,
+                source: self.config.message_attachment_path(attachment),
+            });
+        }
 
-        return Ok(match attachment.mime_type() {
+        let rendered = match attachment.mime_type() {
             MediaType::Image(_) => {
                 if self.config.options.no_lazy {
                     format!("<img src=\"{embed_path}\">")
@@ -583,1179 +2007,1448 @@ impl<'a> Writer<'a> for HTML<'a> {
             MediaType::Other(media_type) => {
                 format!("<p>Unable to embed {media_type} attachments: {embed_path}</p>")
             }
-        });
-    }
+        };
 
-    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
-        match self.format_attachment(sticker, message) {
-            Ok(sticker_embed) => {
-                let sticker_effect = sticker.get_sticker_effect(
-                    &self.config.options.platform,
-                    &self.config.options.db_path,
-                    self.config.options.attachment_root.as_deref(),
-                );
-                if let Ok(Some(sticker_effect)) = sticker_effect {
-                    return format!("{sticker_embed}\n<div class=\"sticker_effect\">Sent with {sticker_effect} effect</div>");
-                }
-                sticker_embed
-            }
-            Err(embed) => embed.to_string(),
-        }
+        let rendered = self.apply_template("attachment", rendered, || {
+            attachment_context(self.config, message, attachment, &embed_path, None)
+        });
+        Ok((rendered, embed_path))
     }
 
-    fn format_app(
-        &self,
-        message: &'a Message,
-        attachments: &mut Vec<Attachment>,
-        _: &str,
-    ) -> Result<String, PlistParseError> {
-        if let Variant::App(balloon) = message.variant() {
-            let mut app_bubble = String::new();
-
-            // Handwritten messages use a different payload type, so check that first
-            if message.is_handwriting() {
-                if let Some(payload) = message.raw_payload_data(&self.config.db) {
-                    return match HandwrittenMessage::from_payload(&payload) {
-                        Ok(bubble) => Ok(self.format_handwriting(message, &bubble, message)),
-                        Err(why) => Err(PlistParseError::HandwritingError(why)),
-                    };
-                }
-            }
-
-            if let Some(payload) = message.payload_data(&self.config.db) {
-                let res = if message.is_url() {
-                    let parsed = parse_plist(&payload)?;
-                    let bubble = URLMessage::get_url_message_override(&parsed)?;
-                    match bubble {
-                        URLOverride::Normal(balloon) => self.format_url(message, &balloon, message),
-                        URLOverride::AppleMusic(balloon) => self.format_music(&balloon, message),
-                        URLOverride::Collaboration(balloon) => {
-                            self.format_collaboration(&balloon, message)
-                        }
-                        URLOverride::AppStore(balloon) => self.format_app_store(&balloon, message),
-                        URLOverride::SharedPlacemark(balloon) => {
-                            self.format_placemark(&balloon, message)
-                        }
-                    }
-                } else {
-                    let parsed = parse_plist(&payload)?;
-                    match AppMessage::from_map(&parsed) {
-                        Ok(bubble) => match balloon {
-                            CustomBalloon::Application(bundle_id) => {
-                                self.format_generic_app(&bubble, bundle_id, attachments, message)
-                            }
-                            CustomBalloon::ApplePay => self.format_apple_pay(&bubble, message),
-                            CustomBalloon::Fitness => self.format_fitness(&bubble, message),
-                            CustomBalloon::Slideshow => self.format_slideshow(&bubble, message),
-                            CustomBalloon::CheckIn => self.format_check_in(&bubble, message),
-                            CustomBalloon::FindMy => self.format_find_my(&bubble, message),
-                            CustomBalloon::Handwriting => unreachable!(),
-                            CustomBalloon::URL => unreachable!(),
-                        },
-                        Err(why) => return Err(why),
-                    }
-                };
-                app_bubble.push_str(&res);
-            } else {
-                // Sometimes, URL messages are missing their payloads
-                if message.is_url() {
-                    if let Some(text) = &message.text {
-                        let mut out_s = String::new();
-                        out_s.push_str("<a href=\"");
-                        out_s.push_str(text);
-                        out_s.push_str("\">");
-
-                        out_s.push_str("<div class=\"app_header\"><div class=\"name\">");
-                        out_s.push_str(text);
-                        out_s.push_str("</div></div>");
-
-                        out_s.push_str("<div class=\"app_footer\"><div class=\"caption\">");
-                        out_s.push_str(text);
-                        out_s.push_str("</div></div></a>");
-
-                        return Ok(out_s);
-                    }
-                }
-                return Err(PlistParseError::NoPayload);
-            }
-            Ok(app_bubble)
-        } else {
-            Err(PlistParseError::WrongMessageType)
+    /// Build a `data:` URI for an attachment if it should be embedded in the page
+    ///
+    /// Images are always embedded, but audio and video are only embedded if they are smaller than the configured limit.
+    fn inline_attachment(&self, attachment: &Attachment) -> Option<String> {
+        if !self.config.options.embed_attachments {
+            return None;
         }
-    }
 
-    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
-        match msg.variant() {
-            Variant::Tapback(_, added, tapback) => {
-                if !added {
-                    return Ok(String::new());
+        match attachment.mime_type() {
+            MediaType::Image(_) => {}
+            MediaType::Video(_) | MediaType::Audio(_) => {
+                let limit = self.config.options.embed_media_limit?;
+                if self.config.message_attachment_size(attachment) > limit {
+                    return None;
                 }
-                Ok(format!(
-                    "<span class=\"tapback\"><b>{}</b> by {}</span>",
-                    tapback,
-                    self.config
-                        .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id),
-                ))
-            }
-            Variant::Sticker(_) => {
-                let mut paths = Attachment::from_message(&self.config.db, msg)?;
-                let who =
-                    self.config
-                        .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
-                // Sticker messages have only one attachment, the sticker image
-                Ok(match paths.get_mut(0) {
-                    Some(sticker) => format!(
-                        "{} <div class=\"sticker_tapback\">&nbsp;by {who}</div>",
-                        self.format_sticker(sticker, msg)
-                    ),
-                    None => {
-                        format!("<span class=\"tapback\">Sticker from {who} not found!</span>")
-                    }
-                })
             }
-            _ => unreachable!(),
-        }
-    }
-
-    fn format_expressive(&self, msg: &'a Message) -> &'a str {
-        match msg.get_expressive() {
-            Expressive::Screen(effect) => match effect {
-                ScreenEffect::Confetti => "Sent with Confetti",
-                ScreenEffect::Echo => "Sent with Echo",
-                ScreenEffect::Fireworks => "Sent with Fireworks",
-                ScreenEffect::Balloons => "Sent with Balloons",
-                ScreenEffect::Heart => "Sent with Heart",
-                ScreenEffect::Lasers => "Sent with Lasers",
-                ScreenEffect::ShootingStar => "Sent with Shooting Star",
-                ScreenEffect::Sparkles => "Sent with Sparkles",
-                ScreenEffect::Spotlight => "Sent with Spotlight",
-            },
-            Expressive::Bubble(effect) => match effect {
-                BubbleEffect::Slam => "Sent with Slam",
-                BubbleEffect::Loud => "Sent with Loud",
-                BubbleEffect::Gentle => "Sent with Gentle",
-                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
-            },
-            Expressive::Unknown(effect) => effect,
-            Expressive::None => "",
-        }
-    }
-
-    fn format_announcement(&self, msg: &'a Message) -> String {
-        let mut who = self
-            .config
-            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
-        // Rename yourself so we render the proper grammar here
-        if who == ME {
-            who = self.config.options.custom_name.as_deref().unwrap_or("You");
+            _ => return None,
         }
-        let timestamp = format(&msg.date(&self.config.offset));
-
-        return match msg.get_announcement() {
-            Some(announcement) => match announcement {
-                Announcement::NameChange(name) => {
-                    let clean_name = sanitize_html(name);
-                    format!(
-                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} named the conversation <b>{clean_name}</b></p></div>\n"
-                    )
-                }
-                Announcement::PhotoChange => {
-                    format!(
-                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} changed the group photo.</p></div>\n"
-                    )
-                }
-                Announcement::Unknown(num) => {
-                    format!(
-                        "\n<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} performed unknown action {num}</p></div>\n"
-                    )
-                }
-                Announcement::FullyUnsent => {
-                    format!(
-                        "<div class =\"announcement\"><p><span class=\"timestamp\">{timestamp}</span> {who} unsent a message.</p></div>"
-                    )
-                }
-            },
-            None => String::from(
-                "\n<div class =\"announcement\"><p>Unable to format announcement!</p></div>\n",
-            ),
-        };
-    }
-
-    fn format_shareplay(&self) -> &str {
-        "<hr>SharePlay Message Ended"
-    }
 
-    fn format_shared_location(&self, msg: &'a Message) -> &str {
-        // Handle Shared Location
-        if msg.started_sharing_location() {
-            return "<hr>Started sharing location!";
-        } else if msg.stopped_sharing_location() {
-            return "<hr>Stopped sharing location!";
-        }
-        "<hr>Shared location!"
+        let bytes = self.config.message_attachment_bytes(attachment)?;
+        Some(format!(
+            "data:{};base64,{}",
+            self.config.message_attachment_content_type(attachment),
+            STANDARD.encode(bytes)
+        ))
     }
-
-    fn format_edited(
-        &self,
-        msg: &'a Message,
-        edited_message: &'a EditedMessage,
-        message_part_idx: usize,
-        _: &str,
-    ) -> Option<String> {
-        if let Some(edited_message_part) = edited_message.part(message_part_idx) {
-            let mut out_s = String::new();
-            let mut previous_timestamp: Option<&i64> = None;
-
-            match edited_message_part.status {
-                EditStatus::Edited => {
-                    out_s.push_str("<table>");
-
-                    for (idx, event) in edited_message_part.edit_history.iter().enumerate() {
-                        let last = idx == edited_message_part.edit_history.len() - 1;
-                        let clean_text = sanitize_html(&event.text);
-                        match previous_timestamp {
-                            None => out_s.push_str(&self.edited_to_html("", &clean_text, last)),
-                            Some(prev_timestamp) => {
-                                let end = get_local_time(&event.date, &self.config.offset);
-                                let start = get_local_time(prev_timestamp, &self.config.offset);
-
-                                let diff = readable_diff(start, end).unwrap_or_default();
-                                out_s.push_str(&self.edited_to_html(
-                                    &format!("Edited {diff} later"),
-                                    &clean_text,
-                                    last,
-                                ));
-                            }
-                        }
-
-                        // Update the previous timestamp for the next loop
-                        previous_timestamp = Some(&event.date);
-                    }
-
-                    out_s.push_str("</table>");
-                }
-                EditStatus::Unsent => {
-                    let who = if msg.is_from_me() {
-                        self.config.options.custom_name.as_deref().unwrap_or(YOU)
-                    } else {
-                        self.config
-                            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id)
-                    };
-
-                    match readable_diff(
-                        msg.date(&self.config.offset),
-                        msg.date_edited(&self.config.offset),
-                    ) {
-                        Some(diff) => {
-                            out_s.push_str(&format!(
-                                "<span class=\"unsent\">{who} unsent this message part {diff} after sending!</span>"
-                            ))
-                        },
-                        None => {
-                            out_s.push_str(&format!(
-                                "<span class=\"unsent\">{who} unsent this message part!</span>"
-                            ))
-                        },
-                    }
-                }
-                EditStatus::Original => {
-                    return None;
+
+    /// Resolve the name of the file a message is written to, and the chat it belongs to, if any
+    fn resolve_filename(&self, message: &Message) -> (String, Option<&'a Chat>) {
+        let config = self.config;
+        match config.conversation(message) {
+            Some((chatroom, _)) => (config.filename(chatroom), Some(chatroom)),
+            None => (ORPHANED.to_string(), None),
+        }
+    }
+
+    /// Record a message written to a conversation's file so it can be listed on the index page
+    fn summarize(&mut self, message: &Message, is_message: bool) {
+        let config = self.config;
+        let (filename, chatroom) = self.resolve_filename(message);
+
+        // Record the message on the page it was written to, if the conversation is paginated
+        if let Some(page) = self
+            .pages
+            .get_mut(&filename)
+            .and_then(|chat| chat.pages.last_mut())
+        {
+            page.add(message.date, is_message);
+        }
+
+        let summary = self
+            .chats
+            .entry(filename)
+            .or_insert_with_key(|filename| ChatSummary {
+                title: match chatroom {
+                    Some(chatroom) => chatroom
+                        .display_name()
+                        .map_or_else(|| filename.to_string(), str::to_string),
+                    None => String::from(ORPHANED_TITLE),
+                },
+                ..Default::default()
+            });
+
+        // Multiple chats can share a file if they use the same group name
+        if let Some(chatroom) = chatroom {
+            if summary.chat_ids.insert(chatroom.rowid) {
+                if let Some(participants) = config.chatroom_participants.get(&chatroom.rowid) {
+                    summary
+                        .members
+                        .extend(participants.iter().map(|participant| {
+                            config.who(Some(*participant), false, &None).to_string()
+                        }));
                 }
             }
-            return Some(out_s);
         }
-        None
+        summary.add(message.date, is_message);
     }
 
-    fn format_attributed(&'a self, text: &'a str, attribute: &'a TextEffect) -> Cow<str> {
-        match attribute {
-            TextEffect::Default => Cow::Borrowed(text),
-            TextEffect::Mention(mentioned) => Cow::Owned(self.format_mention(text, mentioned)),
-            TextEffect::Link(url) => Cow::Owned(self.format_link(text, url)),
-            TextEffect::OTP => Cow::Owned(self.format_otp(text)),
-            TextEffect::Styles(styles) => Cow::Owned(self.format_styles(text, styles)),
-            TextEffect::Animated(animation) => Cow::Owned(self.format_animated(text, animation)),
-            TextEffect::Conversion(unit) => Cow::Owned(self.format_conversion(text, unit)),
+    /// Build the landing page that links to every exported conversation
+    ///
+    /// Conversations are listed with the most recent first, followed by orphaned messages.
+    fn format_index(&self) -> String {
+        let mut chats: Vec<(&String, &ChatSummary)> = self
+            .chats
+            .iter()
+            .filter(|(filename, _)| filename.as_str() != ORPHANED)
+            .collect();
+        chats.sort_by(|(a_name, a), (b_name, b)| b.last.cmp(&a.last).then(a_name.cmp(b_name)));
+
+        let orphaned = ChatSummary {
+            title: String::from(ORPHANED_TITLE),
+            ..Default::default()
+        };
+        let orphaned_name = ORPHANED.to_string();
+        chats.push((
+            &orphaned_name,
+            self.chats.get(ORPHANED).unwrap_or(&orphaned),
+        ));
+
+        let mut out_s = format!(
+            "<div class=\"index\">\n<h1>Conversations</h1>\n<p><a href=\"{}\">Search messages</a></p>\n<table>\n",
+            HTML::chat_href(SEARCH)
+        );
+        out_s.push_str("<thead><tr><th>Conversation</th><th>Participants</th><th>Messages</th><th>Media</th><th>First message</th><th>Last message</th></tr></thead>\n<tbody>\n");
+        for (filename, summary) in chats {
+            let media = match self.galleries.get(filename.as_str()) {
+                Some(gallery) => format!(
+                    "<a href=\"{}\">{}</a>",
+                    HTML::gallery_href(filename),
+                    gallery.len()
+                ),
+                None => String::new(),
+            };
+            let members = summary
+                .members
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+                .join(", ");
+            out_s.push_str(&format!(
+                "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{media}</td><td>{}</td><td>{}</td></tr>\n",
+                HTML::chat_href(filename),
+                sanitize_html(&summary.title),
+                sanitize_html(&members),
+                summary.messages,
+                self.format_index_date(summary.first),
+                self.format_index_date(summary.last),
+            ));
         }
+        out_s.push_str("</tbody>\n</table>\n</div>\n");
+        out_s
     }
 
-    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
-        file.write_all(text.as_bytes())
-            .map_err(RuntimeError::DiskError)
+    /// Add a message to the search index, returning the entry to write if the message has any text
+    ///
+    /// Entries link to the page the message was written to, so this must be called after the message is written.
+    fn format_search_entry(&mut self, message: &Message) -> Option<String> {
+        let text = message
+            .text
+            .as_deref()?
+            .replace(['\u{FFFC}', '\u{FFFD}'], "");
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+
+        let (filename, _) = self.resolve_filename(message);
+        let href = self.message_href(&filename);
+        let title = self
+            .chats
+            .get(&filename)
+            .map_or(filename.as_str(), |summary| summary.title.as_str());
+        let file = self.search.file_id(href, title);
+
+        let sender = self.search.sender_id(self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        ));
+        let timestamp = message
+            .date(&self.config.offset)
+            .map(|date| date.timestamp())
+            .unwrap_or_default();
+
+        Some(format!(
+            "{},\n",
+            json!([file, sender, timestamp, text, message.guid])
+        ))
     }
-}
 
-impl<'a> BalloonFormatter<&'a Message> for HTML<'a> {
-    fn format_url(&self, msg: &Message, balloon: &URLMessage, _: &Message) -> String {
-        let mut out_s = String::new();
+    /// Build a relative link to the page the latest message in a file was written to
+    fn message_href(&self, filename: &str) -> String {
+        match self.pages.get(filename).and_then(|chat| chat.pages.last()) {
+            Some(page) => HTML::page_href(page),
+            None => HTML::chat_href(filename),
+        }
+    }
 
-        // Make the whole bubble clickable
-        let mut close_url = false;
-        if let Some(url) = balloon.get_url() {
-            out_s.push_str("<a href=\"");
-            out_s.push_str(url);
-            out_s.push_str("\">");
-            close_url = true;
-        } else if let Some(text) = &msg.text {
-            // Fallback if the balloon data does not contain the URL
-            out_s.push_str("<a href=\"");
-            out_s.push_str(text);
-            out_s.push_str("\">");
-            close_url = true;
+    /// Add the media rendered with a message to its conversation's gallery
+    ///
+    /// Media rendered in threads belongs to replies that are also written on their own, so only media from the
+    /// message itself is kept. Items link to the page the message was written to, so this must be called after
+    /// the message is written.
+    fn add_to_gallery(&mut self, message: &Message) {
+        let media = self.media.take();
+        if media.is_empty() {
+            return;
         }
 
-        // Header section
-        out_s.push_str("<div class=\"app_header\">");
+        let (filename, _) = self.resolve_filename(message);
+        let href = self.message_href(&filename);
+        let gallery = self.galleries.entry(filename).or_default();
+        for item in media {
+            if item.guid == message.guid {
+                gallery.push((href.clone(), item));
+            }
+        }
+    }
 
-        // Add preview images
-        balloon.images.iter().for_each(|image| {
-            out_s.push_str("<img src=\"");
-            out_s.push_str(image);
-            if self.config.options.no_lazy {
-                out_s.push_str("\" onerror=\"this.style.display='none'\">");
-            } else {
-                out_s.push_str("\" loading=\"lazy\", onerror=\"this.style.display='none'\">");
+    /// Build the gallery page for a conversation, grouping its media by month with the newest first
+    fn format_gallery(&self, filename: &str) -> String {
+        let title = self
+            .chats
+            .get(filename)
+            .map_or(filename, |summary| summary.title.as_str());
+
+        let mut out_s = format!(
+            "<div class=\"gallery\">\n<h1>{}</h1>\n<p><a href=\"{}\">{INDEX_TITLE}</a> | <a href=\"{}\">Conversation</a></p>\n",
+            sanitize_html(title),
+            HTML::chat_href(INDEX),
+            HTML::chat_href(filename),
+        );
+
+        let mut month: Option<String> = None;
+        for (href, item) in self.galleries.get(filename).into_iter().flatten().rev() {
+            let date = get_local_time(&item.date, &self.config.offset);
+            let label = match &date {
+                Ok(date) => date.format("%B %Y").to_string(),
+                Err(_) => String::from("Unknown date"),
+            };
+            if month.as_ref() != Some(&label) {
+                if month.is_some() {
+                    out_s.push_str("</div>\n");
+                }
+                out_s.push_str(&format!("<h2>{label}</h2>\n<div class=\"gallery_grid\">\n"));
+                month = Some(label);
             }
-        });
 
-        if let Some(site_name) = balloon.site_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(site_name);
-            out_s.push_str("</div>");
-        } else if let Some(url) = balloon.get_url() {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(url);
-            out_s.push_str("</div>");
-        } else if let Some(text) = &msg.text {
-            // Fallback if the balloon data does not contain the URL
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(text);
-            out_s.push_str("</div>");
+            let media = match item.kind {
+                MediaKind::Image | MediaKind::Sticker => {
+                    format!("<img src=\"{}\" loading=\"lazy\">", item.source)
+                }
+                MediaKind::Video => {
+                    format!(
+                        "<video src=\"{}\" preload=\"metadata\" muted></video>",
+                        item.source
+                    )
+                }
+                MediaKind::Handwriting => item.source.clone(),
+            };
+            out_s.push_str(&format!(
+                "<a class=\"gallery_item\" href=\"{href}#r-{}\" title=\"{}\">{media}</a>\n",
+                item.guid,
+                format(&date),
+            ));
+        }
+        if month.is_some() {
+            out_s.push_str("</div>\n");
         }
 
-        // Header end
-        out_s.push_str("</div>");
+        out_s.push_str("</div>\n");
+        out_s
+    }
 
-        // Only write the footer if there is data to write
-        if balloon.title.is_some() || balloon.summary.is_some() {
-            out_s.push_str("<div class=\"app_footer\">");
+    /// Write a conversation's gallery page, replacing any page left by a previous export
+    fn write_gallery(&self, filename: &str) -> Result<(), RuntimeError> {
+        let mut path = self.config.options.export_path.clone();
+        path.push(format!("{filename} - {GALLERY}.html"));
 
-            // Title
-            if let Some(title) = balloon.title {
-                out_s.push_str("<div class=\"caption\">");
-                out_s.push_str(&sanitize_html(title));
-                out_s.push_str("</div>");
-            }
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut buf = BufWriter::new(file);
+        HTML::write_headers(&mut buf, HTML::style(self.templates.as_ref()))?;
+        HTML::write_to_file(&mut buf, &self.format_gallery(filename))?;
+        HTML::write_to_file(&mut buf, FOOTER)?;
+        Ok(())
+    }
 
-            // Subtitle
-            if let Some(summary) = balloon.summary {
-                out_s.push_str("<div class=\"subcaption\">");
-                out_s.push_str(&sanitize_html(summary));
-                out_s.push_str("</div>");
-            }
+    /// Build a relative link to a conversation's gallery page
+    fn gallery_href(filename: &str) -> String {
+        sanitize_url_path(&format!("{filename} - {GALLERY}.html")).to_string()
+    }
 
-            // End footer
-            out_s.push_str("</div>");
+    /// Build the page that searches every exported conversation
+    fn format_search_page(&self) -> String {
+        format!(
+            "<div class=\"search\">\n<h1>Search</h1>\n<p><a href=\"{}\">{INDEX_TITLE}</a></p>\n<input id=\"query\" type=\"search\" placeholder=\"Search messages, senders, and conversations\" autofocus>\n<p id=\"status\"></p>\n<ol id=\"results\"></ol>\n</div>\n<script src=\"{}\"></script>\n<script>\n{SEARCH_SCRIPT}</script>\n",
+            HTML::chat_href(INDEX),
+            sanitize_url_path(&format!("{SEARCH_INDEX}.js")),
+        )
+    }
+
+    /// Write the search page, replacing any page left by a previous export
+    fn write_search_page(&self) -> Result<(), RuntimeError> {
+        let mut path = self.config.options.export_path.clone();
+        path.push(SEARCH);
+        path.set_extension("html");
+
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut buf = BufWriter::new(file);
+        HTML::write_headers(&mut buf, HTML::style(self.templates.as_ref()))?;
+        HTML::write_to_file(&mut buf, &self.format_search_page())?;
+        HTML::write_to_file(&mut buf, FOOTER)?;
+        Ok(())
+    }
+
+    /// Build a relative link to a conversation's file, or to its table of contents if it is paginated
+    fn chat_href(filename: &str) -> String {
+        // Resolve the name the same way the conversation's file was created
+        let path = Path::new(filename).with_extension("html");
+        sanitize_url_path(&path.to_string_lossy()).to_string()
+    }
+
+    /// Build a relative link to a page of a paginated conversation
+    fn page_href(page: &PageSummary) -> String {
+        sanitize_url_path(&format!("{}.html", page.name)).to_string()
+    }
+
+    fn format_index_date(&self, date: Option<i64>) -> String {
+        date.map(|date| format(&get_local_time(&date, &self.config.offset)))
+            .unwrap_or_default()
+    }
+
+    /// Write the landing page, replacing any index left by a previous export
+    fn write_index(&self) -> Result<(), RuntimeError> {
+        let mut path = self.config.options.export_path.clone();
+        path.push(INDEX);
+        path.set_extension("html");
+
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut buf = BufWriter::new(file);
+        HTML::write_headers(&mut buf, HTML::style(self.templates.as_ref()))?;
+        HTML::write_to_file(&mut buf, &self.format_index())?;
+        HTML::write_to_file(&mut buf, FOOTER)?;
+        Ok(())
+    }
+
+    /// Get the page a message should be written to, starting a new page if the message does not belong on the latest one
+    fn get_or_create_page(
+        &mut self,
+        filename: String,
+        message: &Message,
+        pagination: &Pagination,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        let config = self.config;
+        let style = HTML::style(self.templates.as_ref());
+
+        // Pages split by date hold a single month or year
+        let period = match pagination {
+            Pagination::Month => Some(("%Y-%m", "%B %Y")),
+            Pagination::Year => Some(("%Y", "%Y")),
+            Pagination::Messages(_) => None,
         }
+        .map(|(key, label)| match message.date(&config.offset) {
+            Ok(date) => (date.format(key).to_string(), date.format(label).to_string()),
+            Err(_) => (String::from("unknown"), String::from("Unknown date")),
+        });
 
-        // End the link
-        if close_url {
-            out_s.push_str("</a>");
+        match self.pages.entry(filename) {
+            Occupied(entry) => {
+                let filename = entry.key().clone();
+                let chat = entry.into_mut();
+                let is_full = match chat.pages.last() {
+                    Some(page) => match pagination {
+                        Pagination::Messages(count) => page.messages >= *count as u64,
+                        _ => page.period.as_ref() != period.as_ref().map(|(key, _)| key),
+                    },
+                    None => true,
+                };
+
+                if is_full {
+                    // Runs do not continue across pages, so end the latest run on the page it started on
+                    if let Some(receipt) = self.runs.remove(&filename).and_then(|run| run.receipt) {
+                        HTML::write_to_file(&mut chat.file, &receipt)?;
+                    }
+                    let (page, file) =
+                        HTML::create_page(config, &filename, chat.pages.len() + 1, period, style)?;
+                    HTML::finish_page(&filename, chat, Some(&page))?;
+                    chat.file = file;
+                    chat.pages.push(page);
+                    HTML::write_to_file(
+                        &mut chat.file,
+                        &HTML::format_page_nav(&filename, chat.pages.iter().rev().nth(1), None),
+                    )?;
+                }
+                Ok(&mut chat.file)
+            }
+            Vacant(entry) => {
+                let (page, mut file) = HTML::create_page(config, entry.key(), 1, period, style)?;
+                HTML::write_to_file(&mut file, &HTML::format_page_nav(entry.key(), None, None))?;
+                Ok(&mut entry
+                    .insert(PagedChat {
+                        pages: vec![page],
+                        file,
+                    })
+                    .file)
+            }
         }
-        out_s
     }
 
-    fn format_music(&self, balloon: &MusicMessage, _: &Message) -> String {
-        let mut out_s = String::new();
+    /// Open the file for a page of a paginated conversation
+    ///
+    /// Pages split by date are named for the period they hold, otherwise they are numbered.
+    fn create_page(
+        config: &Config,
+        filename: &str,
+        number: usize,
+        period: Option<(String, String)>,
+        style: &str,
+    ) -> Result<(PageSummary, BufWriter<File>), RuntimeError> {
+        let page = match period {
+            Some((key, label)) => PageSummary {
+                name: format!("{filename} - {key}"),
+                label,
+                period: Some(key),
+                ..Default::default()
+            },
+            None => PageSummary {
+                name: format!("{filename} - page {number}"),
+                label: format!("Page {number}"),
+                ..Default::default()
+            },
+        };
 
-        // Header section
-        out_s.push_str("<div class=\"app_header\">");
+        // Page names may contain periods, so the extension is appended instead of set
+        let mut path = config.options.export_path.clone();
+        path.push(format!("{}.html", page.name));
 
-        // Name
-        if let Some(track_name) = balloon.track_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(track_name);
-            out_s.push_str("</div>");
-        }
+        // If the file already exists, don't write the headers again
+        let file_exists = path.exists();
 
-        // Add preview section
-        if let Some(preview) = balloon.preview {
-            out_s.push_str("<audio controls src=\"");
-            out_s.push_str(preview);
-            out_s.push_str("\" </audio>");
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        let mut buf = BufWriter::new(file);
+
+        if !file_exists {
+            HTML::write_headers(&mut buf, style)?;
         }
 
-        // Header end
-        out_s.push_str("</div>");
+        Ok((page, buf))
+    }
 
-        // Make the footer clickable so we can interact with the preview
-        if let Some(url) = balloon.url {
-            out_s.push_str("<a href=\"");
-            out_s.push_str(url);
-            out_s.push_str("\">");
+    /// Close the latest page of a paginated conversation, linking to the page that follows it
+    fn finish_page(
+        filename: &str,
+        chat: &mut PagedChat,
+        next: Option<&PageSummary>,
+    ) -> Result<(), RuntimeError> {
+        let previous = chat.pages.iter().rev().nth(1);
+        HTML::write_to_file(
+            &mut chat.file,
+            &HTML::format_page_nav(filename, previous, next),
+        )?;
+        HTML::write_to_file(&mut chat.file, FOOTER)?;
+        chat.file.flush().map_err(RuntimeError::DiskError)
+    }
+
+    /// Build the links between a page of a paginated conversation and its neighbors
+    fn format_page_nav(
+        filename: &str,
+        previous: Option<&PageSummary>,
+        next: Option<&PageSummary>,
+    ) -> String {
+        let mut out_s = String::from("<nav class=\"pagination\">");
+        match previous {
+            Some(page) => out_s.push_str(&format!(
+                "<a href=\"{}\">← {}</a>",
+                HTML::page_href(page),
+                sanitize_html(&page.label)
+            )),
+            None => out_s.push_str("<span></span>"),
+        }
+        out_s.push_str(&format!(
+            "<a href=\"{}\">Contents</a>",
+            HTML::chat_href(filename)
+        ));
+        match next {
+            Some(page) => out_s.push_str(&format!(
+                "<a href=\"{}\">{} →</a>",
+                HTML::page_href(page),
+                sanitize_html(&page.label)
+            )),
+            None => out_s.push_str("<span></span>"),
         }
+        out_s.push_str("</nav>\n");
+        out_s
+    }
 
-        // Only write the footer if there is data to write
-        if balloon.artist.is_some() || balloon.album.is_some() {
-            out_s.push_str("<div class=\"app_footer\">");
+    /// Build the table of contents that links to every page of a paginated conversation
+    fn format_contents(&self, filename: &str) -> String {
+        let title = self
+            .chats
+            .get(filename)
+            .map_or(filename, |summary| summary.title.as_str());
 
-            // artist
-            if let Some(artist) = balloon.artist {
-                out_s.push_str("<div class=\"caption\">");
-                out_s.push_str(artist);
-                out_s.push_str("</div>");
+        let gallery = if self.galleries.contains_key(filename) {
+            format!(" | <a href=\"{}\">Media</a>", HTML::gallery_href(filename))
+        } else {
+            String::new()
+        };
+        let mut out_s = format!(
+            "<div class=\"index\">\n<h1>{}</h1>\n<p><a href=\"{}\">{INDEX_TITLE}</a>{gallery}</p>\n<table>\n",
+            sanitize_html(title),
+            HTML::chat_href(INDEX),
+        );
+        out_s.push_str("<thead><tr><th>Page</th><th>Messages</th><th>First message</th><th>Last message</th></tr></thead>\n<tbody>\n");
+        if let Some(chat) = self.pages.get(filename) {
+            for page in &chat.pages {
+                out_s.push_str(&format!(
+                    "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    HTML::page_href(page),
+                    sanitize_html(&page.label),
+                    page.messages,
+                    self.format_index_date(page.first),
+                    self.format_index_date(page.last),
+                ));
             }
+        }
+        out_s.push_str("</tbody>\n</table>\n</div>\n");
+        out_s
+    }
 
-            // Subtitle
-            if let Some(album) = balloon.album {
-                out_s.push_str("<div class=\"subcaption\">");
-                out_s.push_str(album);
-                out_s.push_str("</div>");
-            }
+    /// Write the table of contents for a paginated conversation where its single file would otherwise be
+    fn write_contents(&self, filename: &str) -> Result<(), RuntimeError> {
+        let mut path = self.config.options.export_path.clone();
+        path.push(filename);
+        path.set_extension("html");
+
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut buf = BufWriter::new(file);
+        HTML::write_headers(&mut buf, HTML::style(self.templates.as_ref()))?;
+        HTML::write_to_file(&mut buf, &self.format_contents(filename))?;
+        HTML::write_to_file(&mut buf, FOOTER)?;
+        Ok(())
+    }
 
-            // End footer
-            out_s.push_str("</div>");
+    /// Get the stylesheet for every page, which may be replaced by a user-supplied template
+    fn style(templates: Option<&Templates>) -> &str {
+        templates
+            .and_then(|templates| templates.stylesheet.as_deref())
+            .unwrap_or(STYLE)
+    }
+
+    /// Render a user-supplied template in place of the default output, if one was supplied
+    fn apply_template(
+        &self,
+        name: &str,
+        default: String,
+        context: impl FnOnce() -> Value,
+    ) -> String {
+        match &self.templates {
+            Some(templates) => templates.render(name, default, context),
+            None => default,
         }
+    }
 
-        // End the link
-        if balloon.url.is_some() {
-            out_s.push_str("</a>");
+    /// Get the name of the template that renders a message, if one was supplied
+    ///
+    /// Messages rendered in a thread use the `reply` template if there is one, otherwise every message uses the `message` template.
+    fn message_template(&self, indent_size: usize) -> Option<&'static str> {
+        let templates = self.templates.as_ref()?;
+        if indent_size > 0 && templates.has("reply") {
+            Some("reply")
+        } else if templates.has("message") {
+            Some("message")
+        } else {
+            None
         }
-        out_s
     }
 
-    fn format_collaboration(&self, balloon: &CollaborationMessage, _: &Message) -> String {
+    fn write_headers(file: &mut BufWriter<File>, style: &str) -> Result<(), RuntimeError> {
+        // Write file header
+        HTML::write_to_file(file, HEADER)?;
+
+        // Write CSS
+        HTML::write_to_file(file, "<style>\n")?;
+        HTML::write_to_file(file, style)?;
+        HTML::write_to_file(file, "\n</style>")?;
+
+        // Write the script that plays expressives
+        HTML::write_to_file(file, "\n<script>\n")?;
+        HTML::write_to_file(file, EXPRESSIVE_SCRIPT)?;
+        HTML::write_to_file(file, "\n</script>")?;
+        HTML::write_to_file(file, "\n</head>\n<body>\n")?;
+        Ok(())
+    }
+
+    fn edited_to_html(&self, timestamp: &str, text: &str, last: bool) -> String {
+        let tag = if last { "tfoot" } else { "tbody" };
+        format!("<{tag}><tr><td><span class=\"timestamp\">{timestamp}</span></td><td>{text}</td></tr></{tag}>")
+    }
+
+    fn balloon_to_html(
+        &self,
+        balloon: &AppMessage,
+        bundle_id: &str,
+        attachments: &mut [Attachment],
+        message: &Message,
+    ) -> String {
         let mut out_s = String::new();
+        if let Some(url) = balloon.url {
+            out_s.push_str("<a href=\"");
+            out_s.push_str(url);
+            out_s.push_str("\">");
+        }
+        out_s.push_str("<div class=\"app_header\">");
+
+        // Image
+        if let Some(image) = balloon.image {
+            out_s.push_str("<img src=\"");
+            out_s.push_str(image);
+            out_s.push_str("\">");
+        } else if let Some(attachment) = attachments.get_mut(0) {
+            out_s.push_str(
+                &self
+                    .format_attachment(attachment, message)
+                    .unwrap_or_default(),
+            );
+        }
+
+        // Name
+        out_s.push_str("<div class=\"name\">");
+        out_s.push_str(balloon.app_name.unwrap_or(bundle_id));
+        out_s.push_str("</div>");
 
-        // Header section
-        out_s.push_str("<div class=\"app_header\">");
+        // Title
+        if let Some(title) = balloon.title {
+            out_s.push_str("<div class=\"image_title\">");
+            out_s.push_str(title);
+            out_s.push_str("</div>");
+        }
 
-        // Name
-        if let Some(app_name) = balloon.app_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(app_name);
+        // Subtitle
+        if let Some(subtitle) = balloon.subtitle {
+            out_s.push_str("<div class=\"image_subtitle\">");
+            out_s.push_str(subtitle);
             out_s.push_str("</div>");
-        } else if let Some(bundle_id) = balloon.bundle_id {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(bundle_id);
+        }
+
+        // ldtext
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str("<div class=\"ldtext\">");
+            out_s.push_str(ldtext);
             out_s.push_str("</div>");
         }
 
-        // Header end
+        // Header end, footer begin
         out_s.push_str("</div>");
 
-        // Make the footer clickable so we can interact with the preview
-        if let Some(url) = balloon.url {
-            out_s.push_str("<a href=\"");
-            out_s.push_str(url);
-            out_s.push_str("\">");
-        }
-
         // Only write the footer if there is data to write
-        if balloon.title.is_some() || balloon.get_url().is_some() {
+        if balloon.caption.is_some()
+            || balloon.subcaption.is_some()
+            || balloon.trailing_caption.is_some()
+            || balloon.trailing_subcaption.is_some()
+        {
             out_s.push_str("<div class=\"app_footer\">");
 
-            // artist
-            if let Some(title) = balloon.title {
+            // Caption
+            if let Some(caption) = balloon.caption {
                 out_s.push_str("<div class=\"caption\">");
-                out_s.push_str(title);
+                out_s.push_str(caption);
                 out_s.push_str("</div>");
             }
 
-            // Subtitle
-            if let Some(url) = balloon.get_url() {
+            // Subcaption
+            if let Some(subcaption) = balloon.subcaption {
                 out_s.push_str("<div class=\"subcaption\">");
-                out_s.push_str(url);
+                out_s.push_str(subcaption);
+                out_s.push_str("</div>");
+            }
+
+            // Trailing Caption
+            if let Some(trailing_caption) = balloon.trailing_caption {
+                out_s.push_str("<div class=\"trailing_caption\">");
+                out_s.push_str(trailing_caption);
+                out_s.push_str("</div>");
+            }
+
+            // Trailing Subcaption
+            if let Some(trailing_subcaption) = balloon.trailing_subcaption {
+                out_s.push_str("<div class=\"trailing_subcaption\">");
+                out_s.push_str(trailing_subcaption);
                 out_s.push_str("</div>");
             }
 
-            // End footer
             out_s.push_str("</div>");
         }
-
-        // End the link
         if balloon.url.is_some() {
             out_s.push_str("</a>");
         }
-
         out_s
     }
+}
 
-    fn format_app_store(&self, balloon: &AppStoreMessage, _: &'a Message) -> String {
-        let mut out_s = String::new();
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::{current_dir, set_var},
+        fs::{create_dir_all, remove_dir_all, write, File},
+        io::BufWriter,
+        path::PathBuf,
+    };
 
-        // Header section
-        out_s.push_str("<div class=\"app_header\">");
+    use crate::{
+        app::{
+            fixtures::{blank, fake_config, fake_options},
+            pagination::Pagination,
+        },
+        exporters::{
+            exporter::Writer,
+            html::{ChatSummary, MediaItem, MediaKind, PageSummary, PagedChat, Placement},
+        },
+        Exporter, HTML,
+    };
+    use imessage_database::{
+        tables::{
+            attachment::Attachment,
+            chat::Chat,
+            table::{ME, ORPHANED},
+        },
+        util::platform::Platform,
+    };
 
-        // App name
-        if let Some(app_name) = balloon.app_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(app_name);
-            out_s.push_str("</div>");
+    fn fake_chat() -> Chat {
+        Chat {
+            rowid: 0,
+            chat_identifier: "Default".to_string(),
+            service_name: Some(String::new()),
+            display_name: None,
         }
+    }
 
-        // Header end
-        out_s.push_str("</div>");
-
-        // Make the footer clickable so we can interact with the preview
-        if let Some(url) = balloon.url {
-            out_s.push_str("<a href=\"");
-            out_s.push_str(url);
-            out_s.push_str("\">");
+    pub(super) fn fake_attachment() -> Attachment {
+        Attachment {
+            rowid: 0,
+            filename: Some("a/b/c/d.jpg".to_string()),
+            uti: Some("public.png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            transfer_name: Some("d.jpg".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
         }
+    }
 
-        // Only write the footer if there is data to write
-        if balloon.description.is_some() || balloon.genre.is_some() {
-            out_s.push_str("<div class=\"app_footer\">");
+    #[test]
+    fn can_create() {
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
 
-            // App description
-            if let Some(description) = balloon.description {
-                out_s.push_str("<div class=\"caption\">");
-                out_s.push_str(description);
-                out_s.push_str("</div>");
-            }
+    #[test]
+    fn can_get_time_valid() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-            // App platform
-            if let Some(platform) = balloon.platform {
-                out_s.push_str("<div class=\"subcaption\">");
-                out_s.push_str(platform);
-                out_s.push_str("</div>");
-            }
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        // let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-            // App genre
-            if let Some(genre) = balloon.genre {
-                out_s.push_str("<div class=\"trailing_subcaption\">");
-                out_s.push_str(genre);
-                out_s.push_str("</div>");
-            }
+        // Create fake message
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // May 17, 2022  8:29:42 PM
+        message.date_delivered = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
 
-            // End footer
-            out_s.push_str("</div>");
-        }
+        assert_eq!(
+            exporter.get_time(&message),
+            "May 17, 2022  5:29:42 PM (Read by you after 1 hour, 49 seconds)"
+        );
+    }
 
-        // End the link
-        if balloon.url.is_some() {
-            out_s.push_str("</a>");
-        }
-        out_s
+    #[test]
+    fn can_get_time_invalid() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        // Create fake message
+        let mut message = blank();
+        // May 17, 2022  9:30:31 PM
+        message.date = 674530231992568192;
+        // May 17, 2022  9:30:31 PM
+        message.date_delivered = 674530231992568192;
+        // Wed May 18 2022 02:36:24 GMT+0000
+        message.date_read = 674526582885055488;
+        assert_eq!(exporter.get_time(&message), "May 17, 2022  6:30:31 PM");
     }
 
-    fn format_placemark(&self, balloon: &PlacemarkMessage, _: &'a Message) -> String {
-        let mut out_s = String::new();
+    #[test]
+    fn can_add_line_no_indent() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        // Make the whole bubble clickable
-        if let Some(url) = balloon.get_url() {
-            out_s.push_str("<a href=\"");
-            out_s.push_str(url);
-            out_s.push_str("\">");
-        }
+        // Create sample data
+        let mut s = String::new();
+        exporter.add_line(&mut s, "hello world", "", "");
 
-        // Header section
-        out_s.push_str("<div class=\"app_header\">");
+        assert_eq!(s, "hello world\n".to_string());
+    }
 
-        if let Some(place_name) = balloon.place_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(place_name);
-            out_s.push_str("</div>");
-        } else if let Some(url) = balloon.get_url() {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(url);
-            out_s.push_str("</div>");
-        }
+    #[test]
+    fn can_add_line() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        // Header end
-        out_s.push_str("</div>");
+        // Create sample data
+        let mut s = String::new();
+        exporter.add_line(&mut s, "hello world", "  ", "");
 
-        // Only write the footer if there is data to write
-        if balloon.placemark.address.is_some()
-            || balloon.placemark.postal_code.is_some()
-            || balloon.placemark.country.is_some()
-            || balloon.placemark.sub_administrative_area.is_some()
-        {
-            out_s.push_str("<div class=\"app_footer\">");
+        assert_eq!(s, "  hello world\n".to_string());
+    }
 
-            // Address
-            if let Some(address) = balloon.placemark.address {
-                out_s.push_str("<div class=\"caption\">");
-                out_s.push_str(address);
-                out_s.push_str("</div>");
-            }
+    #[test]
+    fn can_add_line_pre_post() {
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-            // Postal Code
-            if let Some(postal_code) = balloon.placemark.postal_code {
-                out_s.push_str("<div class=\"trailing_caption\">");
-                out_s.push_str(postal_code);
-                out_s.push_str("</div>");
-            }
+        // Create sample data
+        let mut s = String::new();
+        exporter.add_line(&mut s, "hello world", "<div>", "</div>");
 
-            // Country
-            if let Some(country) = balloon.placemark.country {
-                out_s.push_str("<div class=\"subcaption\">");
-                out_s.push_str(country);
-                out_s.push_str("</div>");
-            }
+        assert_eq!(s, "<div>hello world</div>\n".to_string());
+    }
 
-            // Administrative Area
-            if let Some(area) = balloon.placemark.sub_administrative_area {
-                out_s.push_str("<div class=\"trailing_subcaption\">");
-                out_s.push_str(area);
-                out_s.push_str("</div>");
-            }
+    #[test]
+    fn can_format_html_from_me_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-            // End footer
-            out_s.push_str("</div>");
-        }
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        // End the link
-        if balloon.get_url().is_some() {
-            out_s.push_str("</a>");
-        }
-        out_s
-    }
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
 
-    fn format_handwriting(&self, _: &Message, balloon: &HandwrittenMessage, _: &Message) -> String {
-        // svg can be embedded directly into the html
-        balloon.render_svg()
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
     }
 
-    fn format_apple_pay(&self, balloon: &AppMessage, _: &Message) -> String {
-        let mut out_s = String::new();
+    #[test]
+    fn can_format_html_from_me_normal_template() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        out_s.push_str("<div class=\"app_header\">");
+        let template_dir = PathBuf::from("/tmp/imessage-exporter-html-template");
+        create_dir_all(&template_dir).unwrap();
+        write(
+            template_dir.join("message.html"),
+            "<article title=\"{{ message.sender }} at {{ message.date }}\">{% for part in parts %}{{ part.content }}{% endfor %}</article>",
+        )
+        .unwrap();
+        write(template_dir.join("style.css"), "body { color: red; }").unwrap();
 
-        if let Some(app_name) = balloon.app_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(app_name);
-            out_s.push_str("</div>");
-        }
+        // Create exporter
+        let mut options = fake_options();
+        options.template_dir = Some(template_dir.clone());
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        // Header end, footer begin
-        out_s.push_str("</div>");
-        out_s.push_str("<div class=\"app_footer\">");
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
 
-        if let Some(ldtext) = balloon.ldtext {
-            out_s.push_str("<div class=\"caption\">");
-            out_s.push_str(ldtext);
-            out_s.push_str("</div>");
-        }
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<article title=\"Me at May 17, 2022  5:29:42 PM\"><hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</article>";
 
-        // End footer
-        out_s.push_str("</div>");
+        assert_eq!(actual, expected);
+        assert_eq!(
+            HTML::style(exporter.templates.as_ref()),
+            "body { color: red; }"
+        );
 
-        out_s
+        remove_dir_all(&template_dir).unwrap();
     }
 
-    fn format_fitness(&self, balloon: &AppMessage, message: &Message) -> String {
-        self.balloon_to_html(balloon, "Fitness", &mut [], message)
-    }
+    #[test]
+    fn can_format_html_message_with_html() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-    fn format_slideshow(&self, balloon: &AppMessage, message: &Message) -> String {
-        self.balloon_to_html(balloon, "Slideshow", &mut [], message)
-    }
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-    fn format_find_my(&self, balloon: &AppMessage, _: &'a Message) -> String {
-        let mut out_s = String::new();
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("<table></table>".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
 
-        out_s.push_str("<div class=\"app_header\">");
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">&lt;table&gt;&lt;/table&gt;</span>\n</div>\n</div>\n</div>\n";
 
-        if let Some(app_name) = balloon.app_name {
-            out_s.push_str("<div class=\"name\">");
-            out_s.push_str(app_name);
-            out_s.push_str("</div>");
-        }
+        assert_eq!(actual, expected);
+    }
 
-        // Header end, footer begin
-        out_s.push_str("</div>");
-        out_s.push_str("<div class=\"app_footer\">");
+    #[test]
+    fn can_format_html_from_me_normal_deleted() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        if let Some(ldtext) = balloon.ldtext {
-            out_s.push_str("<div class=\"caption\">");
-            out_s.push_str(ldtext);
-            out_s.push_str("</div>");
-        }
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        // End footer
-        out_s.push_str("</div>");
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.text = Some("Hello world".to_string());
+        message.date = 674526582885055488;
+        message.is_from_me = true;
+        message.deleted_from = Some(0);
 
-        out_s
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"deleted\">This message was deleted from the conversation!</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
     }
 
-    fn format_check_in(&self, balloon: &AppMessage, _: &Message) -> String {
-        let mut out_s = String::new();
+    #[test]
+    fn can_format_html_from_me_normal_read() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        out_s.push_str("<div class=\"app_header\">");
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        // Name
-        out_s.push_str("<div class=\"name\">");
-        out_s.push_str(balloon.app_name.unwrap_or("Check In"));
-        out_s.push_str("</div>");
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_delivered = 674530231992568192;
+        message.is_from_me = true;
 
-        // ldtext
-        if let Some(ldtext) = balloon.ldtext {
-            out_s.push_str("<div class=\"ldtext\">");
-            out_s.push_str(ldtext);
-            out_s.push_str("</div>");
-        }
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected =
+            "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM (Read by them after 1 hour, 49 seconds)</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
 
-        // Header end, footer begin
-        out_s.push_str("</div>");
+        assert_eq!(actual, expected);
+    }
 
-        // Only write the footer if there is data to write
-        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+    #[test]
+    fn can_format_html_from_them_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        // Before manual check-in
-        if let Some(date_str) = metadata.get("estimatedEndTime") {
-            // Parse the estimated end time from the message's query string
-            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
-            let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = HTML::new(&config).unwrap();
 
-            out_s.push_str("<div class=\"app_footer\">");
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.handle_id = Some(999999);
 
-            out_s.push_str("<div class=\"caption\">Expected around ");
-            out_s.push_str(&date_string);
-            out_s.push_str("</div>");
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Sample Contact</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
 
-            out_s.push_str("</div>");
-        }
-        // Expired check-in
-        else if let Some(date_str) = metadata.get("triggerTime") {
-            // Parse the estimated end time from the message's query string
-            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
-            let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+        assert_eq!(actual, expected);
+    }
 
-            out_s.push_str("<div class=\"app_footer\">");
+    #[test]
+    fn can_format_html_from_them_normal_read() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-            out_s.push_str("<div class=\"caption\">Was expected around ");
-            out_s.push_str(&date_string);
-            out_s.push_str("</div>");
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = HTML::new(&config).unwrap();
 
-            out_s.push_str("</div>");
-        }
-        // Accepted check-in
-        else if let Some(date_str) = metadata.get("sendDate") {
-            // Parse the estimated end time from the message's query string
-            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
-            let date_time = get_local_time(&date_stamp, &0);
-            let date_string = format(&date_time);
+        let mut message = blank();
+        message.handle_id = Some(999999);
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date_delivered = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected =
+            "<div class=\"message\", id=\"r-\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM (Read by you after 1 hour, 49 seconds)</span>\n<span class=\"sender\">Sample Contact</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
+    }
 
-            out_s.push_str("<div class=\"app_footer\">");
+    #[test]
+    fn can_format_html_from_them_custom_name_read() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-            out_s.push_str("<div class=\"caption\">Checked in at ");
-            out_s.push_str(&date_string);
-            out_s.push_str("</div>");
+        // Create exporter
+        let mut options = fake_options();
+        options.custom_name = Some("Name".to_string());
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = HTML::new(&config).unwrap();
 
-            out_s.push_str("</div>");
-        }
+        let mut message = blank();
+        message.handle_id = Some(999999);
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date_delivered = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
 
-        out_s
-    }
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected =
+            "<div class=\"message\", id=\"r-\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM (Read by Name after 1 hour, 49 seconds)</span>\n<span class=\"sender\">Sample Contact</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
 
-    fn format_generic_app(
-        &self,
-        balloon: &AppMessage,
-        bundle_id: &str,
-        attachments: &mut Vec<Attachment>,
-        message: &Message,
-    ) -> String {
-        self.balloon_to_html(balloon, bundle_id, attachments, message)
+        assert_eq!(actual, expected);
     }
-}
 
-impl<'a> TextEffectFormatter for HTML<'a> {
-    fn format_mention(&self, text: &str, mentioned: &str) -> String {
-        format!("<span title=\"{mentioned}\"><b>{text}</b></span>")
-    }
+    #[test]
+    fn can_format_html_first_in_run() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-    fn format_link(&self, text: &str, url: &str) -> String {
-        format!("<a href=\"{url}\">{text}</a>")
-    }
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-    fn format_otp(&self, text: &str) -> String {
-        format!("<u>{text}</u>")
-    }
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_delivered = 674530231992568192;
+        message.is_from_me = true;
 
-    fn format_conversion(&self, text: &str, _: &Unit) -> String {
-        format!("<u>{text}</u>")
+        let actual = exporter
+            .format_message_in_run(&message, 0, Placement::First)
+            .unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
     }
 
-    fn format_styles(&self, text: &str, styles: &[Style]) -> String {
-        let (prefix, suffix): (String, String) = styles.iter().rev().fold(
-            (String::new(), String::new()),
-            |(mut prefix, mut suffix), style| {
-                let (open, close) = match style {
-                    Style::Bold => ("<b>", "</b>"),
-                    Style::Italic => ("<i>", "</i>"),
-                    Style::Strikethrough => ("<s>", "</s>"),
-                    Style::Underline => ("<u>", "</u>"),
-                };
-                prefix.push_str(open);
-                suffix.insert_str(0, close);
-                (prefix, suffix)
-            },
-        );
+    #[test]
+    fn can_format_html_continued_in_run() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        format!("{prefix}{text}{suffix}")
-    }
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-    fn format_animated(&self, text: &str, animation: &Animation) -> String {
-        format!("<span class=\"animation{animation:?}\">{text}</span>")
-    }
-}
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.is_from_me = true;
 
-impl<'a> HTML<'a> {
-    fn get_time(&self, message: &Message) -> String {
-        let mut date = format(&message.date(&self.config.offset));
-        let read_after = message.time_until_read(&self.config.offset);
-        if let Some(time) = read_after {
-            if !time.is_empty() {
-                let who = if message.is_from_me() {
-                    "them"
-                } else {
-                    self.config.options.custom_name.as_deref().unwrap_or("you")
-                };
-                date.push_str(&format!(" (Read by {who} after {time})"));
-            }
-        }
-        date
-    }
+        let actual = exporter
+            .format_message_in_run(&message, 0, Placement::Continued)
+            .unwrap();
+        let expected = "<div class=\"message continued\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n</p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
 
-    fn add_line(&self, string: &mut String, part: &str, pre: &str, post: &str) {
-        if !part.is_empty() {
-            string.push_str(pre);
-            string.push_str(part);
-            string.push_str(post);
-            string.push('\n');
-        }
+        assert_eq!(actual, expected);
     }
 
-    fn write_headers(file: &mut BufWriter<File>) -> Result<(), RuntimeError> {
-        // Write file header
-        HTML::write_to_file(file, HEADER)?;
+    #[test]
+    fn can_group_messages_into_runs() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        // Write CSS
-        HTML::write_to_file(file, "<style>\n")?;
-        HTML::write_to_file(file, STYLE)?;
-        HTML::write_to_file(file, "\n</style>")?;
-        HTML::write_to_file(file, "\n</head>\n<body>\n")?;
-        Ok(())
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let mut exporter = HTML::new(&config).unwrap();
+
+        // The first message in a file starts a run on a new day
+        let mut first = blank();
+        first.handle_id = Some(999999);
+        // May 17, 2022  5:29:42 PM
+        first.date = 674526582885055488;
+        let (markup, placement) = exporter.advance_run(&first, true).unwrap();
+        assert_eq!(
+            markup,
+            "<div class=\"day_separator\">Tuesday, May 17, 2022</div>\n"
+        );
+        assert_eq!(placement, Placement::First);
+
+        // A message from the same sender a minute later continues the run
+        let mut second = blank();
+        second.handle_id = Some(999999);
+        second.date = first.date + 60_000_000_000;
+        // Read an hour after it was sent
+        second.date_read = second.date + 3_600_000_000_000;
+        let (markup, placement) = exporter.advance_run(&second, true).unwrap();
+        assert_eq!(markup, "");
+        assert_eq!(placement, Placement::Continued);
+
+        // A reply from someone else ends the run, so the receipt for the last message in the run is written first
+        let mut third = blank();
+        third.is_from_me = true;
+        third.date = second.date + 60_000_000_000;
+        let (markup, placement) = exporter.advance_run(&third, true).unwrap();
+        assert_eq!(
+            markup,
+            "<div class=\"receipt received\">Read by you after 1 hour</div>\n"
+        );
+        assert_eq!(placement, Placement::First);
+
+        // A message sent hours later on the same day gets a time gap marker
+        let mut fourth = blank();
+        fourth.is_from_me = true;
+        fourth.date = third.date + 7_200_000_000_000;
+        let (markup, placement) = exporter.advance_run(&fourth, true).unwrap();
+        assert_eq!(markup, "<div class=\"time_gap\">7:31 PM</div>\n");
+        assert_eq!(placement, Placement::First);
+
+        // Announcements end the run
+        let mut announcement = blank();
+        announcement.date = fourth.date + 60_000_000_000;
+        let (_, placement) = exporter.advance_run(&announcement, false).unwrap();
+        assert_eq!(placement, Placement::First);
+
+        let mut fifth = blank();
+        fifth.is_from_me = true;
+        fifth.date = announcement.date + 60_000_000_000;
+        let (_, placement) = exporter.advance_run(&fifth, true).unwrap();
+        assert_eq!(placement, Placement::First);
     }
 
-    fn edited_to_html(&self, timestamp: &str, text: &str, last: bool) -> String {
-        let tag = if last { "tfoot" } else { "tbody" };
-        format!("<{tag}><tr><td><span class=\"timestamp\">{timestamp}</span></td><td>{text}</td></tr></{tag}>")
-    }
+    #[test]
+    fn can_format_day_separator() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-    fn balloon_to_html(
-        &self,
-        balloon: &AppMessage,
-        bundle_id: &str,
-        attachments: &mut [Attachment],
-        message: &Message,
-    ) -> String {
-        let mut out_s = String::new();
-        if let Some(url) = balloon.url {
-            out_s.push_str("<a href=\"");
-            out_s.push_str(url);
-            out_s.push_str("\">");
-        }
-        out_s.push_str("<div class=\"app_header\">");
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        // Image
-        if let Some(image) = balloon.image {
-            out_s.push_str("<img src=\"");
-            out_s.push_str(image);
-            out_s.push_str("\">");
-        } else if let Some(attachment) = attachments.get_mut(0) {
-            out_s.push_str(
-                &self
-                    .format_attachment(attachment, message)
-                    .unwrap_or_default(),
-            );
-        }
+        let mut message = blank();
+        // May 18, 2022  5:29:42 PM
+        message.date = 674526582885055488 + 86_400_000_000_000;
 
-        // Name
-        out_s.push_str("<div class=\"name\">");
-        out_s.push_str(balloon.app_name.unwrap_or(bundle_id));
-        out_s.push_str("</div>");
+        assert_eq!(
+            exporter.format_run_break(Some(674526582885055488), &message),
+            "<div class=\"day_separator\">Wednesday, May 18, 2022</div>\n"
+        );
+    }
 
-        // Title
-        if let Some(title) = balloon.title {
-            out_s.push_str("<div class=\"image_title\">");
-            out_s.push_str(title);
-            out_s.push_str("</div>");
-        }
+    #[test]
+    fn can_format_html_shareplay() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        // Subtitle
-        if let Some(subtitle) = balloon.subtitle {
-            out_s.push_str("<div class=\"image_subtitle\">");
-            out_s.push_str(subtitle);
-            out_s.push_str("</div>");
-        }
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
 
-        // ldtext
-        if let Some(ldtext) = balloon.ldtext {
-            out_s.push_str("<div class=\"ldtext\">");
-            out_s.push_str(ldtext);
-            out_s.push_str("</div>");
-        }
+        let exporter = HTML::new(&config).unwrap();
 
-        // Header end, footer begin
-        out_s.push_str("</div>");
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.item_type = 6;
 
-        // Only write the footer if there is data to write
-        if balloon.caption.is_some()
-            || balloon.subcaption.is_some()
-            || balloon.trailing_caption.is_some()
-            || balloon.trailing_subcaption.is_some()
-        {
-            out_s.push_str("<div class=\"app_footer\">");
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"shareplay\"><hr>SharePlay Message Ended</span>\n</div>\n</div>\n";
 
-            // Caption
-            if let Some(caption) = balloon.caption {
-                out_s.push_str("<div class=\"caption\">");
-                out_s.push_str(caption);
-                out_s.push_str("</div>");
-            }
+        assert_eq!(actual, expected);
+    }
 
-            // Subcaption
-            if let Some(subcaption) = balloon.subcaption {
-                out_s.push_str("<div class=\"subcaption\">");
-                out_s.push_str(subcaption);
-                out_s.push_str("</div>");
-            }
+    #[test]
+    fn can_format_html_bubble_effect() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-            // Trailing Caption
-            if let Some(trailing_caption) = balloon.trailing_caption {
-                out_s.push_str("<div class=\"trailing_caption\">");
-                out_s.push_str(trailing_caption);
-                out_s.push_str("</div>");
-            }
+        // Create exporter
+        let options = fake_options();
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-            // Trailing Subcaption
-            if let Some(trailing_subcaption) = balloon.trailing_subcaption {
-                out_s.push_str("<div class=\"trailing_subcaption\">");
-                out_s.push_str(trailing_subcaption);
-                out_s.push_str("</div>");
-            }
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.is_from_me = true;
+        message.expressive_send_style_id =
+            Some("com.apple.MobileSMS.expressivesend.invisibleink".to_string());
 
-            out_s.push_str("</div>");
-        }
-        if balloon.url.is_some() {
-            out_s.push_str("</a>");
-        }
-        out_s
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage effect effect_invisible_ink\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n<span class=\"expressive\">Sent with Invisible Ink</span>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        collections::HashMap,
-        env::{current_dir, set_var},
-        path::PathBuf,
-    };
+    #[test]
+    fn can_format_html_screen_effect() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-    use crate::{
-        app::attachment_manager::AttachmentManager, exporters::exporter::Writer, Config, Exporter,
-        Options, HTML,
-    };
-    use imessage_database::{
-        tables::{
-            attachment::Attachment,
-            messages::Message,
-            table::{get_connection, ME},
-        },
-        util::{
-            dates::get_offset, dirs::default_db_path, platform::Platform,
-            query_context::QueryContext,
-        },
-    };
+        // Create exporter
+        let options = fake_options();
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = HTML::new(&config).unwrap();
 
-    pub(super) fn blank() -> Message {
-        Message {
-            rowid: i32::default(),
-            guid: String::default(),
-            text: None,
-            service: Some("iMessage".to_string()),
-            handle_id: Some(i32::default()),
-            destination_caller_id: None,
-            subject: None,
-            date: i64::default(),
-            date_read: i64::default(),
-            date_delivered: i64::default(),
-            is_from_me: false,
-            is_read: false,
-            item_type: 0,
-            other_handle: 0,
-            share_status: false,
-            share_direction: false,
-            group_title: None,
-            group_action_type: 0,
-            associated_message_guid: None,
-            associated_message_type: Some(i32::default()),
-            balloon_bundle_id: None,
-            expressive_send_style_id: None,
-            thread_originator_guid: None,
-            thread_originator_part: None,
-            date_edited: 0,
-            chat_id: None,
-            associated_message_emoji: None,
-            num_attachments: 0,
-            deleted_from: None,
-            num_replies: 0,
-            components: None,
-            edited_parts: None,
-        }
-    }
-
-    pub(super) fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::from("/tmp"),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
-
-    pub(super) fn fake_config(options: Options) -> Config {
-        let db = get_connection(&options.get_db_path()).unwrap();
-        Config {
-            chatrooms: HashMap::new(),
-            real_chatrooms: HashMap::new(),
-            chatroom_participants: HashMap::new(),
-            participants: HashMap::new(),
-            real_participants: HashMap::new(),
-            tapbacks: HashMap::new(),
-            options,
-            offset: get_offset(),
-            db,
-            converter: None,
-        }
-    }
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.handle_id = Some(999999);
+        message.expressive_send_style_id =
+            Some("com.apple.messages.effect.CKShootingStarEffect".to_string());
 
-    pub(super) fn fake_attachment() -> Attachment {
-        Attachment {
-            rowid: 0,
-            filename: Some("a/b/c/d.jpg".to_string()),
-            uti: Some("public.png".to_string()),
-            mime_type: Some("image/png".to_string()),
-            transfer_name: Some("d.jpg".to_string()),
-            total_bytes: 100,
-            is_sticker: false,
-            hide_attachment: 0,
-            copied_path: None,
-        }
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"received effect effect_shooting_star\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Sample Contact</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n<span class=\"expressive\">Sent with Shooting Star</span>\n</div>\n</div>\n";
+
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_create() {
+    fn cant_format_html_unknown_effect() {
+        // Create exporter
         let options = fake_options();
         let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
-        assert_eq!(exporter.files.len(), 0);
+
+        let mut message = blank();
+        message.expressive_send_style_id = Some("com.apple.messages.effect.Unknown".to_string());
+
+        assert_eq!(exporter.format_effect(&message), None);
     }
 
     #[test]
-    fn can_get_time_valid() {
+    fn can_format_html_announcement() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
         // Create exporter
         let options = fake_options();
-        let config = fake_config(options);
-        // let config = fake_config(options);
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
         let exporter = HTML::new(&config).unwrap();
 
-        // Create fake message
         let mut message = blank();
         // May 17, 2022  8:29:42 PM
         message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_announcement(&message);
+        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> You named the conversation <b>Hello world</b></p></div>\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_html_announcement_custom_name() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let mut options = fake_options();
+        options.custom_name = Some("Name".to_string());
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut message = blank();
         // May 17, 2022  8:29:42 PM
-        message.date_delivered = 674526582885055488;
-        // May 17, 2022  9:30:31 PM
-        message.date_read = 674530231992568192;
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
 
-        assert_eq!(
-            exporter.get_time(&message),
-            "May 17, 2022  5:29:42 PM (Read by you after 1 hour, 49 seconds)"
-        );
+        let actual = exporter.format_announcement(&message);
+        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> Name named the conversation <b>Hello world</b></p></div>\n";
+
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_get_time_invalid() {
+    fn can_format_html_tapback_me() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
         // Create exporter
         let options = fake_options();
-        let config = fake_config(options);
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
         let exporter = HTML::new(&config).unwrap();
 
-        // Create fake message
         let mut message = blank();
-        // May 17, 2022  9:30:31 PM
-        message.date = 674530231992568192;
-        // May 17, 2022  9:30:31 PM
-        message.date_delivered = 674530231992568192;
-        // Wed May 18 2022 02:36:24 GMT+0000
-        message.date_read = 674526582885055488;
-        assert_eq!(exporter.get_time(&message), "May 17, 2022  6:30:31 PM");
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+
+        let actual = exporter.format_tapback(&message).unwrap();
+        let expected = "<span class=\"tapback\"><b>Loved</b> by Me</span>";
+
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_add_line_no_indent() {
+    fn can_format_html_tapback_them() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
         // Create exporter
         let options = fake_options();
-        let config = fake_config(options);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
         let exporter = HTML::new(&config).unwrap();
 
-        // Create sample data
-        let mut s = String::new();
-        exporter.add_line(&mut s, "hello world", "", "");
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+        message.handle_id = Some(999999);
 
-        assert_eq!(s, "hello world\n".to_string());
+        let actual = exporter.format_tapback(&message).unwrap();
+        let expected = "<span class=\"tapback\"><b>Loved</b> by Sample Contact</span>";
+
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_add_line() {
+    fn can_format_html_tapback_custom_emoji() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
         // Create exporter
         let options = fake_options();
-        let config = fake_config(options);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
         let exporter = HTML::new(&config).unwrap();
 
-        // Create sample data
-        let mut s = String::new();
-        exporter.add_line(&mut s, "hello world", "  ", "");
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2006);
+        message.associated_message_guid = Some("fake_guid".to_string());
+        message.handle_id = Some(999999);
+        message.associated_message_emoji = Some("☕️".to_string());
 
-        assert_eq!(s, "  hello world\n".to_string());
+        let actual = exporter.format_tapback(&message).unwrap();
+        // The result contains `&nbsp;`
+        let expected = "<span class=\"tapback\"><b>☕\u{fe0f}</b> by Sample Contact</span>";
+
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_add_line_pre_post() {
+    fn can_format_html_tapback_custom_sticker() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
         // Create exporter
         let options = fake_options();
-        let config = fake_config(options);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
         let exporter = HTML::new(&config).unwrap();
 
-        // Create sample data
-        let mut s = String::new();
-        exporter.add_line(&mut s, "hello world", "<div>", "</div>");
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2007);
+        message.associated_message_guid = Some("fake_guid".to_string());
+        message.handle_id = Some(999999);
+        message.associated_message_emoji = Some("☕️".to_string());
 
-        assert_eq!(s, "<div>hello world</div>\n".to_string());
+        let actual = exporter.format_tapback(&message).unwrap();
+        let expected = "<span class=\"tapback\">Sticker from Sample Contact not found!</span>";
+
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_format_html_from_me_normal() {
+    fn can_format_html_started_sharing_location_me() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
@@ -1765,20 +3458,20 @@ mod tests {
         let exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.text = Some("Hello world".to_string());
-        message.is_from_me = true;
-        message.chat_id = Some(0);
+        message.is_from_me = false;
+        message.other_handle = 2;
+        message.share_status = false;
+        message.share_direction = false;
+        message.item_type = 4;
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"shared_location\"><hr>Started sharing location!</span>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_format_html_message_with_html() {
+    fn can_format_html_stopped_sharing_location_me() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
@@ -1788,20 +3481,20 @@ mod tests {
         let exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.text = Some("<table></table>".to_string());
-        message.is_from_me = true;
-        message.chat_id = Some(0);
+        message.is_from_me = false;
+        message.other_handle = 2;
+        message.share_status = true;
+        message.share_direction = false;
+        message.item_type = 4;
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">&lt;table&gt;&lt;/table&gt;</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"shared_location\"><hr>Stopped sharing location!</span>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_format_html_from_me_normal_deleted() {
+    fn can_format_html_started_sharing_location_them() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
@@ -1811,20 +3504,21 @@ mod tests {
         let exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.text = Some("Hello world".to_string());
-        message.date = 674526582885055488;
-        message.is_from_me = true;
-        message.deleted_from = Some(0);
+        message.handle_id = None;
+        message.is_from_me = false;
+        message.other_handle = 0;
+        message.share_status = false;
+        message.share_direction = false;
+        message.item_type = 4;
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"deleted\">This message was deleted from the conversation!</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"received\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Unknown</span></p>\n<span class=\"shared_location\"><hr>Started sharing location!</span>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_format_html_from_me_normal_read() {
+    fn can_format_html_stopped_sharing_location_them() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
@@ -1834,490 +3528,720 @@ mod tests {
         let exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        message.text = Some("Hello world".to_string());
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        // May 17, 2022  9:30:31 PM
-        message.date_delivered = 674530231992568192;
-        message.is_from_me = true;
+        message.handle_id = None;
+        message.is_from_me = false;
+        message.other_handle = 0;
+        message.share_status = true;
+        message.share_direction = false;
+        message.item_type = 4;
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected =
-            "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM (Read by them after 1 hour, 49 seconds)</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"received\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Unknown</span></p>\n<span class=\"shared_location\"><hr>Stopped sharing location!</span>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_format_html_from_them_normal() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
+    fn can_format_html_attachment_macos() {
         // Create exporter
         let options = fake_options();
-        let mut config = fake_config(options);
-        config
-            .participants
-            .insert(999999, "Sample Contact".to_string());
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
-        let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.text = Some("Hello world".to_string());
-        message.handle_id = Some(999999);
+        let message = blank();
 
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Sample Contact</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+        let mut attachment = fake_attachment();
 
-        assert_eq!(actual, expected);
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(actual, "<img src=\"a/b/c/d.jpg\" loading=\"lazy\">");
     }
 
     #[test]
-    fn can_format_html_from_them_normal_read() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
+    fn can_format_html_attachment_macos_invalid() {
         // Create exporter
         let options = fake_options();
-        let mut config = fake_config(options);
-        config
-            .participants
-            .insert(999999, "Sample Contact".to_string());
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
-        let mut message = blank();
-        message.handle_id = Some(999999);
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.text = Some("Hello world".to_string());
-        // May 17, 2022  8:29:42 PM
-        message.date_delivered = 674526582885055488;
-        // May 17, 2022  9:30:31 PM
-        message.date_read = 674530231992568192;
+        let message = blank();
 
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected =
-            "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM (Read by you after 1 hour, 49 seconds)</span>\n<span class=\"sender\">Sample Contact</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+        let mut attachment = fake_attachment();
+        attachment.filename = None;
 
-        assert_eq!(actual, expected);
+        let actual = exporter.format_attachment(&mut attachment, &message);
+
+        assert_eq!(actual, Err("d.jpg"));
     }
 
     #[test]
-    fn can_format_html_from_them_custom_name_read() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
+    fn can_format_html_attachment_ios() {
         // Create exporter
-        let mut options = fake_options();
-        options.custom_name = Some("Name".to_string());
+        let options = fake_options();
         let mut config = fake_config(options);
-        config
-            .participants
-            .insert(999999, "Sample Contact".to_string());
+        config.options.no_lazy = true;
+        config.options.platform = Platform::iOS;
         let exporter = HTML::new(&config).unwrap();
+        let message = blank();
 
-        let mut message = blank();
-        message.handle_id = Some(999999);
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.text = Some("Hello world".to_string());
-        // May 17, 2022  8:29:42 PM
-        message.date_delivered = 674526582885055488;
-        // May 17, 2022  9:30:31 PM
-        message.date_read = 674530231992568192;
+        let mut attachment = fake_attachment();
 
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected =
-            "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM (Read by Name after 1 hour, 49 seconds)</span>\n<span class=\"sender\">Sample Contact</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hello world</span>\n</div>\n</div>\n</div>\n";
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
 
-        assert_eq!(actual, expected);
+        assert!(actual.ends_with("33/33c81da8ae3194fc5a0ea993ef6ffe0b048baedb\">"));
     }
 
     #[test]
-    fn can_format_html_shareplay() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
+    fn can_format_html_attachment_ios_invalid() {
         // Create exporter
         let options = fake_options();
-        let mut config = fake_config(options);
-        config.participants.insert(0, ME.to_string());
-
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
-        let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.item_type = 6;
+        let message = blank();
 
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"shareplay\"><hr>SharePlay Message Ended</span>\n</div>\n</div>\n";
+        let mut attachment = fake_attachment();
+        attachment.filename = None;
 
-        assert_eq!(actual, expected);
+        let actual = exporter.format_attachment(&mut attachment, &message);
+
+        assert_eq!(actual, Err("d.jpg"));
     }
 
     #[test]
-    fn can_format_html_announcement() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
+    fn can_format_html_attachment_sticker() {
         // Create exporter
-        let options = fake_options();
-        let mut config = fake_config(options);
-        config.participants.insert(0, ME.to_string());
+        let mut options = fake_options();
+        options.export_path = current_dir().unwrap().parent().unwrap().to_path_buf();
 
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.group_title = Some("Hello world".to_string());
-        message.is_from_me = true;
+        // Set message to sticker variant
+        message.associated_message_type = Some(1000);
 
-        let actual = exporter.format_announcement(&message);
-        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> You named the conversation <b>Hello world</b></p></div>\n";
+        let mut attachment = fake_attachment();
+        attachment.is_sticker = true;
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.filename = Some(sticker_path.to_string_lossy().to_string());
+        attachment.copied_path = Some(PathBuf::from(sticker_path.to_string_lossy().to_string()));
 
-        assert_eq!(actual, expected);
+        let actual = exporter.format_sticker(&mut attachment, &message);
+
+        assert_eq!(actual, "<img src=\"imessage-database/test_data/stickers/outline.heic\" loading=\"lazy\">\n<div class=\"sticker_effect\">Sent with Outline effect</div>");
+
+        // Remove the files created by the constructor for this test
+        let export_path = current_dir().unwrap().parent().unwrap().to_path_buf();
+        std::fs::remove_file(export_path.join("orphaned.html")).unwrap();
+        std::fs::remove_file(export_path.join("search_index.js")).unwrap();
     }
 
     #[test]
-    fn can_format_html_announcement_custom_name() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
+    fn can_format_html_attachment_sticker_template_inlined() {
+        let template_dir = PathBuf::from("/tmp/imessage-exporter-html-sticker-template");
+        create_dir_all(&template_dir).unwrap();
+        write(
+            template_dir.join("sticker.html"),
+            "<figure data-src=\"{{ attachment.path }}\"></figure>",
+        )
+        .unwrap();
 
         // Create exporter
         let mut options = fake_options();
-        options.custom_name = Some("Name".to_string());
-        let mut config = fake_config(options);
-        config.participants.insert(0, ME.to_string());
-
+        options.embed_attachments = true;
+        options.template_dir = Some(template_dir.clone());
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.group_title = Some("Hello world".to_string());
+        // Set message to sticker variant
+        message.associated_message_type = Some(1000);
 
-        let actual = exporter.format_announcement(&message);
-        let expected = "\n<div class =\"announcement\"><p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span> Name named the conversation <b>Hello world</b></p></div>\n";
+        let mut attachment = fake_attachment();
+        attachment.is_sticker = true;
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.filename = Some(sticker_path.to_string_lossy().to_string());
+        attachment.copied_path = Some(sticker_path);
 
-        assert_eq!(actual, expected);
+        let actual = exporter.format_sticker(&mut attachment, &message);
+
+        assert!(actual.starts_with("<figure data-src=\"data:image"));
+
+        remove_dir_all(&template_dir).unwrap();
     }
 
     #[test]
-    fn can_format_html_tapback_me() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
+    fn can_inline_image() {
+        // Create exporter
+        let mut options = fake_options();
+        options.embed_attachments = true;
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut attachment = fake_attachment();
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.copied_path = Some(sticker_path);
+
+        let actual = exporter.inline_attachment(&attachment).unwrap();
+        assert!(actual.starts_with("data:image/png;base64,"));
+    }
 
+    #[test]
+    fn cant_inline_when_disabled() {
         // Create exporter
         let options = fake_options();
-        let mut config = fake_config(options);
-        config.participants.insert(0, ME.to_string());
-
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
-        let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.associated_message_type = Some(2000);
-        message.associated_message_guid = Some("fake_guid".to_string());
-
-        let actual = exporter.format_tapback(&message).unwrap();
-        let expected = "<span class=\"tapback\"><b>Loved</b> by Me</span>";
+        let mut attachment = fake_attachment();
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.copied_path = Some(sticker_path);
 
-        assert_eq!(actual, expected);
+        assert!(exporter.inline_attachment(&attachment).is_none());
     }
 
     #[test]
-    fn can_format_html_tapback_them() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
+    fn cant_inline_video_without_limit() {
+        // Create exporter
+        let mut options = fake_options();
+        options.embed_attachments = true;
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("video/mp4".to_string());
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.copied_path = Some(sticker_path);
+
+        assert!(exporter.inline_attachment(&attachment).is_none());
+    }
 
+    #[test]
+    fn can_inline_video_under_limit() {
         // Create exporter
-        let options = fake_options();
-        let mut config = fake_config(options);
-        config
-            .participants
-            .insert(999999, "Sample Contact".to_string());
+        let mut options = fake_options();
+        options.embed_attachments = true;
+        options.embed_media_limit = Some(1024 * 1024);
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
-        let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.associated_message_type = Some(2000);
-        message.associated_message_guid = Some("fake_guid".to_string());
-        message.handle_id = Some(999999);
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("video/mp4".to_string());
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.copied_path = Some(sticker_path);
+
+        let actual = exporter.inline_attachment(&attachment).unwrap();
+        assert!(actual.starts_with("data:video/mp4;base64,"));
+    }
+
+    #[test]
+    fn cant_inline_video_over_limit() {
+        // Create exporter
+        let mut options = fake_options();
+        options.embed_attachments = true;
+        options.embed_media_limit = Some(1);
+        let config = fake_config(options);
+        let exporter = HTML::new(&config).unwrap();
 
-        let actual = exporter.format_tapback(&message).unwrap();
-        let expected = "<span class=\"tapback\"><b>Loved</b> by Sample Contact</span>";
+        let mut attachment = fake_attachment();
+        attachment.mime_type = Some("video/mp4".to_string());
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.copied_path = Some(sticker_path);
 
-        assert_eq!(actual, expected);
+        assert!(exporter.inline_attachment(&attachment).is_none());
     }
 
     #[test]
-    fn can_format_html_tapback_custom_emoji() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
+    fn cant_inline_gallery_item() {
         // Create exporter
-        let options = fake_options();
-        let mut config = fake_config(options);
-        config
-            .participants
-            .insert(999999, "Sample Contact".to_string());
+        let mut options = fake_options();
+        options.embed_attachments = true;
+        let config = fake_config(options);
         let exporter = HTML::new(&config).unwrap();
 
-        let mut message = blank();
-        // May 17, 2022  8:29:42 PM
-        message.date = 674526582885055488;
-        message.associated_message_type = Some(2006);
-        message.associated_message_guid = Some("fake_guid".to_string());
-        message.handle_id = Some(999999);
-        message.associated_message_emoji = Some("☕️".to_string());
+        let message = blank();
+        let mut attachment = fake_attachment();
+        let sticker_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/stickers/outline.heic");
+        attachment.copied_path = Some(sticker_path);
 
-        let actual = exporter.format_tapback(&message).unwrap();
-        // The result contains `&nbsp;`
-        let expected = "<span class=\"tapback\"><b>☕\u{fe0f}</b> by Sample Contact</span>";
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+        assert!(actual.contains("data:image/png;base64,"));
 
-        assert_eq!(actual, expected);
+        let media = exporter.media.borrow();
+        assert_eq!(media.len(), 1);
+        assert!(!media[0].source.starts_with("data:"));
     }
 
     #[test]
-    fn can_format_html_tapback_custom_sticker() {
+    fn can_summarize_chat() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
-        // Create exporter
         let options = fake_options();
         let mut config = fake_config(options);
+        let mut chat = fake_chat();
+        chat.display_name = Some("Family".to_string());
+        config.chatrooms.insert(0, chat);
+        config.real_chatrooms.insert(0, 0);
         config
-            .participants
-            .insert(999999, "Sample Contact".to_string());
-        let exporter = HTML::new(&config).unwrap();
+            .chatroom_participants
+            .insert(0, [1, 2].into_iter().collect());
+        config.participants.insert(1, "Sample Contact".to_string());
+        config.participants.insert(2, "Other Contact".to_string());
+        let mut exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
+        message.chat_id = Some(0);
         // May 17, 2022  8:29:42 PM
         message.date = 674526582885055488;
-        message.associated_message_type = Some(2007);
-        message.associated_message_guid = Some("fake_guid".to_string());
-        message.handle_id = Some(999999);
-        message.associated_message_emoji = Some("☕️".to_string());
-
-        let actual = exporter.format_tapback(&message).unwrap();
-        let expected = "<span class=\"tapback\">Sticker from Sample Contact not found!</span>";
-
-        assert_eq!(actual, expected);
+        exporter.summarize(&message, true);
+        // May 17, 2022  8:31:22 PM
+        message.date = 674526682885055488;
+        exporter.summarize(&message, true);
+        exporter.summarize(&message, false);
+
+        let expected = ChatSummary {
+            title: "Family".to_string(),
+            chat_ids: [0].into_iter().collect(),
+            members: ["Other Contact".to_string(), "Sample Contact".to_string()]
+                .into_iter()
+                .collect(),
+            messages: 2,
+            first: Some(674526582885055488),
+            last: Some(674526682885055488),
+        };
+        assert_eq!(exporter.chats.get("Family - 0"), Some(&expected));
     }
 
     #[test]
-    fn can_format_html_started_sharing_location_me() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
-        // Create exporter
+    fn can_summarize_orphaned() {
         let options = fake_options();
         let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
-
-        let mut message = blank();
-        message.is_from_me = false;
-        message.other_handle = 2;
-        message.share_status = false;
-        message.share_direction = false;
-        message.item_type = 4;
+        let mut exporter = HTML::new(&config).unwrap();
 
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"shared_location\"><hr>Started sharing location!</span>\n</div>\n</div>\n";
+        let message = blank();
+        exporter.summarize(&message, true);
 
-        assert_eq!(actual, expected);
+        let summary = exporter.chats.get(ORPHANED).unwrap();
+        assert_eq!(summary.title, "Orphaned messages");
+        assert_eq!(summary.messages, 1);
+        assert!(summary.members.is_empty());
     }
 
     #[test]
-    fn can_format_html_stopped_sharing_location_me() {
+    fn can_format_index() {
         // Set timezone to PST for consistent Local time
         set_var("TZ", "PST");
 
-        // Create exporter
         let options = fake_options();
         let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
-
-        let mut message = blank();
-        message.is_from_me = false;
-        message.other_handle = 2;
-        message.share_status = true;
-        message.share_direction = false;
-        message.item_type = 4;
+        let mut exporter = HTML::new(&config).unwrap();
+        exporter.chats.insert(
+            "Older Chat".to_string(),
+            ChatSummary {
+                title: "Older Chat".to_string(),
+                messages: 3,
+                first: Some(674526582885055488),
+                last: Some(674526582885055488),
+                ..Default::default()
+            },
+        );
+        exporter.chats.insert(
+            "friend@example.com".to_string(),
+            ChatSummary {
+                title: "friend@example.com".to_string(),
+                members: ["friend@example.com".to_string()].into_iter().collect(),
+                messages: 1,
+                first: Some(674526682885055488),
+                last: Some(674526682885055488),
+                ..Default::default()
+            },
+        );
 
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Me</span></p>\n<span class=\"shared_location\"><hr>Stopped sharing location!</span>\n</div>\n</div>\n";
+        let actual = exporter.format_index();
+        let expected = [
+            "<div class=\"index\">",
+            "<h1>Conversations</h1>",
+            "<p><a href=\"search.html\">Search messages</a></p>",
+            "<table>",
+            "<thead><tr><th>Conversation</th><th>Participants</th><th>Messages</th><th>Media</th><th>First message</th><th>Last message</th></tr></thead>",
+            "<tbody>",
+            "<tr><td><a href=\"friend%40example.html\">friend@example.com</a></td><td>friend@example.com</td><td>1</td><td></td><td>May 17, 2022  5:31:22 PM</td><td>May 17, 2022  5:31:22 PM</td></tr>",
+            "<tr><td><a href=\"Older%20Chat.html\">Older Chat</a></td><td></td><td>3</td><td></td><td>May 17, 2022  5:29:42 PM</td><td>May 17, 2022  5:29:42 PM</td></tr>",
+            "<tr><td><a href=\"orphaned.html\">Orphaned messages</a></td><td></td><td>0</td><td></td><td></td><td></td></tr>",
+            "</tbody>",
+            "</table>",
+            "</div>",
+            "",
+        ]
+        .join("\n");
 
         assert_eq!(actual, expected);
     }
 
     #[test]
-    fn can_format_html_started_sharing_location_them() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
-        // Create exporter
+    fn can_format_search_entry() {
         let options = fake_options();
-        let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
+        let mut config = fake_config(options);
+        let mut chat = fake_chat();
+        chat.display_name = Some("Family".to_string());
+        config.chatrooms.insert(0, chat);
+        config.real_chatrooms.insert(0, 0);
+        config.participants.insert(0, "Sample Contact".to_string());
+        let mut exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        message.handle_id = None;
-        message.is_from_me = false;
-        message.other_handle = 0;
-        message.share_status = false;
-        message.share_direction = false;
-        message.item_type = 4;
+        message.guid = "abc-123".to_string();
+        message.chat_id = Some(0);
+        message.text = Some("\u{FFFC}Dinner at \"7\"?".to_string());
+        // May 17, 2022  5:29:42 PM
+        message.date = 674526582885055488;
+        exporter.summarize(&message, true);
 
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Unknown</span></p>\n<span class=\"shared_location\"><hr>Started sharing location!</span>\n</div>\n</div>\n";
+        let actual = exporter.format_search_entry(&message);
+        let expected = "[0,0,1652833782,\"Dinner at \\\"7\\\"?\",\"abc-123\"],\n";
+        assert_eq!(actual.as_deref(), Some(expected));
 
-        assert_eq!(actual, expected);
+        // The same file and sender are not added to the index twice
+        message.is_from_me = true;
+        exporter.format_search_entry(&message);
+        assert_eq!(
+            exporter.search.files,
+            vec![("Family%20-%200.html".to_string(), "Family".to_string())]
+        );
+        assert_eq!(
+            exporter.search.senders,
+            vec!["Sample Contact".to_string(), ME.to_string()]
+        );
     }
 
     #[test]
-    fn can_format_html_stopped_sharing_location_them() {
-        // Set timezone to PST for consistent Local time
-        set_var("TZ", "PST");
-
-        // Create exporter
+    fn can_skip_search_entry_without_text() {
         let options = fake_options();
         let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
+        let mut exporter = HTML::new(&config).unwrap();
 
         let mut message = blank();
-        message.handle_id = None;
-        message.is_from_me = false;
-        message.other_handle = 0;
-        message.share_status = true;
-        message.share_direction = false;
-        message.item_type = 4;
-
-        let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"received\">\n<p><span class=\"timestamp\">Dec 31, 2000  4:00:00 PM</span>\n<span class=\"sender\">Unknown</span></p>\n<span class=\"shared_location\"><hr>Stopped sharing location!</span>\n</div>\n</div>\n";
+        assert!(exporter.format_search_entry(&message).is_none());
 
-        assert_eq!(actual, expected);
+        message.text = Some("\u{FFFC}".to_string());
+        assert!(exporter.format_search_entry(&message).is_none());
+        assert!(exporter.search.files.is_empty());
     }
 
     #[test]
-    fn can_format_html_attachment_macos() {
-        // Create exporter
+    fn can_format_search_tables() {
         let options = fake_options();
         let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
+        let mut exporter = HTML::new(&config).unwrap();
+        exporter
+            .search
+            .file_id("orphaned.html".to_string(), "Orphaned messages");
+        exporter.search.sender_id(ME);
+
+        let actual = exporter.search.format_tables();
+        let expected = "];\nconst SEARCH_FILES = [[\"orphaned.html\",\"Orphaned messages\"]];\nconst SEARCH_SENDERS = [\"Me\"];\n";
+        assert_eq!(actual, expected);
+    }
 
-        let message = blank();
+    #[test]
+    fn can_paginate_by_message_count() {
+        let mut options = fake_options();
+        options.export_path = PathBuf::from("/tmp/imessage-exporter-paginate-count");
+        options.paginate = Some(Pagination::Messages(2));
+        create_dir_all(&options.export_path).unwrap();
 
-        let mut attachment = fake_attachment();
+        let mut config = fake_config(options);
+        config.chatrooms.insert(0, fake_chat());
+        config.real_chatrooms.insert(0, 0);
+        let mut exporter = HTML::new(&config).unwrap();
 
-        let actual = exporter
-            .format_attachment(&mut attachment, &message)
-            .unwrap();
+        let mut message = blank();
+        message.chat_id = Some(0);
+        for _ in 0..3 {
+            exporter.get_or_create_file(&message).unwrap();
+            exporter.summarize(&message, true);
+        }
 
-        assert_eq!(actual, "<img src=\"a/b/c/d.jpg\" loading=\"lazy\">");
+        let chat = exporter.pages.get("Default").unwrap();
+        let names: Vec<&str> = chat.pages.iter().map(|page| page.name.as_str()).collect();
+        assert_eq!(names, vec!["Default - page 1", "Default - page 2"]);
+        assert_eq!(chat.pages[0].messages, 2);
+        assert_eq!(chat.pages[1].messages, 1);
+        assert!(exporter.files.is_empty());
+
+        remove_dir_all(&config.options.export_path).unwrap();
     }
 
     #[test]
-    fn can_format_html_attachment_macos_invalid() {
-        // Create exporter
-        let options = fake_options();
-        let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
+    fn can_paginate_by_month() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        let message = blank();
+        let mut options = fake_options();
+        options.export_path = PathBuf::from("/tmp/imessage-exporter-paginate-month");
+        options.paginate = Some(Pagination::Month);
+        create_dir_all(&options.export_path).unwrap();
 
-        let mut attachment = fake_attachment();
-        attachment.filename = None;
+        let mut config = fake_config(options);
+        config.chatrooms.insert(0, fake_chat());
+        config.real_chatrooms.insert(0, 0);
+        let mut exporter = HTML::new(&config).unwrap();
 
-        let actual = exporter.format_attachment(&mut attachment, &message);
+        let mut message = blank();
+        message.chat_id = Some(0);
+        // May 17, 2022  5:29:42 PM
+        message.date = 674526582885055488;
+        exporter.get_or_create_file(&message).unwrap();
+        exporter.summarize(&message, true);
+        // May 17, 2022  5:31:22 PM
+        message.date = 674526682885055488;
+        exporter.get_or_create_file(&message).unwrap();
+        exporter.summarize(&message, true);
+
+        let chat = exporter.pages.get("Default").unwrap();
+        assert_eq!(
+            chat.pages,
+            vec![PageSummary {
+                name: "Default - 2022-05".to_string(),
+                label: "May 2022".to_string(),
+                period: Some("2022-05".to_string()),
+                messages: 2,
+                first: Some(674526582885055488),
+                last: Some(674526682885055488),
+            }]
+        );
+        assert!(config
+            .options
+            .export_path
+            .join("Default - 2022-05.html")
+            .exists());
 
-        assert_eq!(actual, Err("d.jpg"));
+        remove_dir_all(&config.options.export_path).unwrap();
     }
 
     #[test]
-    fn can_format_html_attachment_ios() {
-        // Create exporter
-        let options = fake_options();
-        let mut config = fake_config(options);
-        config.options.no_lazy = true;
-        config.options.platform = Platform::iOS;
-        let exporter = HTML::new(&config).unwrap();
-        let message = blank();
-
-        let mut attachment = fake_attachment();
-
-        let actual = exporter
-            .format_attachment(&mut attachment, &message)
-            .unwrap();
+    fn can_format_page_nav() {
+        let previous = PageSummary {
+            name: "friend@example.com - 2022-04".to_string(),
+            label: "April 2022".to_string(),
+            ..Default::default()
+        };
+        let next = PageSummary {
+            name: "friend@example.com - 2022-06".to_string(),
+            label: "June 2022".to_string(),
+            ..Default::default()
+        };
 
-        assert!(actual.ends_with("33/33c81da8ae3194fc5a0ea993ef6ffe0b048baedb\">"));
+        assert_eq!(
+            HTML::format_page_nav("friend@example.com", Some(&previous), Some(&next)),
+            "<nav class=\"pagination\"><a href=\"friend%40example.com%20-%202022-04.html\">← April 2022</a><a href=\"friend%40example.html\">Contents</a><a href=\"friend%40example.com%20-%202022-06.html\">June 2022 →</a></nav>\n"
+        );
+        assert_eq!(
+            HTML::format_page_nav("friend@example.com", None, None),
+            "<nav class=\"pagination\"><span></span><a href=\"friend%40example.html\">Contents</a><span></span></nav>\n"
+        );
     }
 
     #[test]
-    fn can_format_html_attachment_ios_invalid() {
-        // Create exporter
+    fn can_add_to_gallery() {
         let options = fake_options();
         let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
-
-        let message = blank();
-
-        let mut attachment = fake_attachment();
-        attachment.filename = None;
+        let mut exporter = HTML::new(&config).unwrap();
 
-        let actual = exporter.format_attachment(&mut attachment, &message);
+        let mut message = blank();
+        message.guid = "message".to_string();
+
+        // Media from a reply rendered in the message's thread is not kept
+        exporter.media.borrow_mut().extend([
+            MediaItem {
+                guid: "message".to_string(),
+                date: 674526582885055488,
+                kind: MediaKind::Image,
+                source: "attachments/1.jpg".to_string(),
+            },
+            MediaItem {
+                guid: "reply".to_string(),
+                date: 674526682885055488,
+                kind: MediaKind::Video,
+                source: "attachments/2.mov".to_string(),
+            },
+        ]);
+        exporter.add_to_gallery(&message);
 
-        assert_eq!(actual, Err("d.jpg"));
+        assert!(exporter.media.borrow().is_empty());
+        assert_eq!(
+            exporter.galleries.get(ORPHANED),
+            Some(&vec![(
+                "orphaned.html".to_string(),
+                MediaItem {
+                    guid: "message".to_string(),
+                    date: 674526582885055488,
+                    kind: MediaKind::Image,
+                    source: "attachments/1.jpg".to_string(),
+                }
+            )])
+        );
     }
 
     #[test]
-    fn can_format_html_attachment_sticker() {
-        // Create exporter
-        let mut options = fake_options();
-        options.export_path = current_dir().unwrap().parent().unwrap().to_path_buf();
+    fn can_format_gallery() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
+        let options = fake_options();
         let config = fake_config(options);
-        let exporter = HTML::new(&config).unwrap();
+        let mut exporter = HTML::new(&config).unwrap();
+        exporter.chats.insert(
+            "Chat".to_string(),
+            ChatSummary {
+                title: "Chat".to_string(),
+                ..Default::default()
+            },
+        );
+        exporter.galleries.insert(
+            "Chat".to_string(),
+            vec![
+                (
+                    "Chat.html".to_string(),
+                    MediaItem {
+                        guid: "a".to_string(),
+                        // May 17, 2022  5:29:42 PM
+                        date: 674526582885055488,
+                        kind: MediaKind::Image,
+                        source: "attachments/1.jpg".to_string(),
+                    },
+                ),
+                (
+                    "Chat.html".to_string(),
+                    MediaItem {
+                        guid: "b".to_string(),
+                        // June 16, 2022  5:29:42 PM
+                        date: 674526582885055488 + 2_592_000_000_000_000,
+                        kind: MediaKind::Video,
+                        source: "attachments/2.mov".to_string(),
+                    },
+                ),
+            ],
+        );
 
-        let mut message = blank();
-        // Set message to sticker variant
-        message.associated_message_type = Some(1000);
+        let actual = exporter.format_gallery("Chat");
+        let expected = [
+            "<div class=\"gallery\">",
+            "<h1>Chat</h1>",
+            "<p><a href=\"index.html\">All conversations</a> | <a href=\"Chat.html\">Conversation</a></p>",
+            "<h2>June 2022</h2>",
+            "<div class=\"gallery_grid\">",
+            "<a class=\"gallery_item\" href=\"Chat.html#r-b\" title=\"Jun 16, 2022  5:29:42 PM\"><video src=\"attachments/2.mov\" preload=\"metadata\" muted></video></a>",
+            "</div>",
+            "<h2>May 2022</h2>",
+            "<div class=\"gallery_grid\">",
+            "<a class=\"gallery_item\" href=\"Chat.html#r-a\" title=\"May 17, 2022  5:29:42 PM\"><img src=\"attachments/1.jpg\" loading=\"lazy\"></a>",
+            "</div>",
+            "</div>",
+            "",
+        ]
+        .join("\n");
 
-        let mut attachment = fake_attachment();
-        attachment.is_sticker = true;
-        let sticker_path = current_dir()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join("imessage-database/test_data/stickers/outline.heic");
-        attachment.filename = Some(sticker_path.to_string_lossy().to_string());
-        attachment.copied_path = Some(PathBuf::from(sticker_path.to_string_lossy().to_string()));
+        assert_eq!(actual, expected);
+    }
 
-        let actual = exporter.format_sticker(&mut attachment, &message);
+    #[test]
+    fn can_format_contents() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
 
-        assert_eq!(actual, "<img src=\"imessage-database/test_data/stickers/outline.heic\" loading=\"lazy\">\n<div class=\"sticker_effect\">Sent with Outline effect</div>");
+        let options = fake_options();
+        let config = fake_config(options);
+        let mut exporter = HTML::new(&config).unwrap();
+        exporter.chats.insert(
+            "Family - 0".to_string(),
+            ChatSummary {
+                title: "Family".to_string(),
+                ..Default::default()
+            },
+        );
+        exporter.pages.insert(
+            "Family - 0".to_string(),
+            PagedChat {
+                pages: vec![PageSummary {
+                    name: "Family - 0 - page 1".to_string(),
+                    label: "Page 1".to_string(),
+                    period: None,
+                    messages: 2,
+                    first: Some(674526582885055488),
+                    last: Some(674526682885055488),
+                }],
+                file: BufWriter::new(File::open("/dev/null").unwrap()),
+            },
+        );
 
-        // Remove the file created by the constructor for this test
-        let orphaned_path = current_dir()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join("orphaned.html");
-        std::fs::remove_file(orphaned_path).unwrap();
+        let actual = exporter.format_contents("Family - 0");
+        let expected = [
+            "<div class=\"index\">",
+            "<h1>Family</h1>",
+            "<p><a href=\"index.html\">All conversations</a></p>",
+            "<table>",
+            "<thead><tr><th>Page</th><th>Messages</th><th>First message</th><th>Last message</th></tr></thead>",
+            "<tbody>",
+            "<tr><td><a href=\"Family%20-%200%20-%20page%201.html\">Page 1</a></td><td>2</td><td>May 17, 2022  5:29:42 PM</td><td>May 17, 2022  5:31:22 PM</td></tr>",
+            "</tbody>",
+            "</table>",
+            "</div>",
+            "",
+        ]
+        .join("\n");
+
+        assert_eq!(actual, expected);
     }
 }
 
 #[cfg(test)]
 mod balloon_format_tests {
-    use std::env::set_var;
-
-    use super::tests::{blank, fake_config, fake_options};
-    use crate::{exporters::exporter::BalloonFormatter, Exporter, HTML};
+    use crate::{
+        app::fixtures::{blank, fake_config, fake_options},
+        exporters::exporter::BalloonFormatter,
+        Exporter, HTML,
+    };
     use imessage_database::message_types::{
         app::AppMessage,
         app_store::AppStoreMessage,
@@ -2326,6 +4250,7 @@ mod balloon_format_tests {
         placemark::{Placemark, PlacemarkMessage},
         url::URLMessage,
     };
+    use std::env::set_var;
 
     #[test]
     fn can_format_html_url() {
@@ -2694,8 +4619,8 @@ mod balloon_format_tests {
 
 #[cfg(test)]
 mod text_effect_tests {
-    use super::tests::{blank, fake_config, fake_options};
     use crate::{
+        app::fixtures::{blank, fake_config, fake_options},
         exporters::exporter::{TextEffectFormatter, Writer},
         Exporter, HTML,
     };
@@ -2851,7 +4776,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Test <span title=\"+15558675309\"><b>Dad</b></span> </span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Test <span title=\"+15558675309\"><b>Dad</b></span> </span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -2886,7 +4811,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><u>000123</u> is your security code. Don&apos;t share your code.</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><u>000123</u> is your security code. Don&apos;t share your code.</span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -2921,7 +4846,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><a href=\"https://twitter.com/xxxxxxxxx/status/0000223300009216128\">https://twitter.com/xxxxxxxxx/status/0000223300009216128</a></span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><a href=\"https://twitter.com/xxxxxxxxx/status/0000223300009216128\">https://twitter.com/xxxxxxxxx/status/0000223300009216128</a></span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -2956,7 +4881,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hi. Right now or <u>tomorrow</u>?</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">Hi. Right now or <u>tomorrow</u>?</span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -2991,7 +4916,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><span class=\"animationBig\">Big</span> <span class=\"animationSmall\">small </span><span class=\"animationShake\">shake</span> <span class=\"animationNod\">nod</span> <span class=\"animationExplode\">explode </span><span class=\"animationRipple\">ripple</span> <span class=\"animationBloom\">bloom</span> <span class=\"animationJitter\">jitter</span></span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><span class=\"animationBig\">Big</span> <span class=\"animationSmall\">small </span><span class=\"animationShake\">shake</span> <span class=\"animationNod\">nod</span> <span class=\"animationExplode\">explode </span><span class=\"animationRipple\">ripple</span> <span class=\"animationBloom\">bloom</span> <span class=\"animationJitter\">jitter</span></span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -3026,7 +4951,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><b>Bold</b> <u>underline</u> <i>italic</i> <s>strikethrough</s> all <i><u><s><b>four</b></s></u></i></span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><b>Bold</b> <u>underline</u> <i>italic</i> <s>strikethrough</s> all <i><u><s><b>four</b></s></u></i></span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -3061,7 +4986,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><i><u><s><b>Everything</b></s></u></i></span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><i><u><s><b>Everything</b></s></u></i></span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -3096,7 +5021,7 @@ mod text_effect_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><u>Underline</u> normal <span class=\"animationJitter\">jitter</span> normal</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\"><u>Underline</u> normal <span class=\"animationJitter\">jitter</span> normal</span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -3110,9 +5035,11 @@ mod edited_tests {
         io::Read,
     };
 
-    use super::tests::{blank, fake_config, fake_options};
-
-    use crate::{exporters::exporter::Writer, Exporter, HTML};
+    use crate::{
+        app::fixtures::{blank, fake_config, fake_options},
+        exporters::exporter::Writer,
+        Exporter, HTML,
+    };
     use imessage_database::{
         message_types::edited::{EditStatus, EditedMessage, EditedMessagePart},
         util::typedstream::parser::TypedStreamReader,
@@ -3171,7 +5098,7 @@ mod edited_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">From arbitrary byte stream:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"attachment_error\">Attachment does not exist!</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">To native Rust data structures:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"unsent\"><span class=\"unsent\">You unsent this message part 1 hour, 49 seconds after sending!</span></span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">From arbitrary byte stream:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"attachment_error\">Attachment does not exist!</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">To native Rust data structures:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"unsent\"><span class=\"unsent\">You unsent this message part 1 hour, 49 seconds after sending!</span></span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
@@ -3208,7 +5135,7 @@ mod edited_tests {
         message.components = parser.parse().ok();
 
         let actual = exporter.format_message(&message, 0).unwrap();
-        let expected = "<div class=\"message\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">From arbitrary byte stream:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"attachment_error\">Attachment does not exist!</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">To native Rust data structures:\r</span>\n</div>\n</div>\n</div>\n";
+        let expected = "<div class=\"message\", id=\"r-\">\n<div class=\"sent iMessage\">\n<p><span class=\"timestamp\">May 17, 2022  5:29:42 PM</span>\n<span class=\"sender\">Me</span></p>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">From arbitrary byte stream:\r</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"attachment_error\">Attachment does not exist!</span>\n</div>\n<hr><div class=\"message_part\">\n<span class=\"bubble\">To native Rust data structures:\r</span>\n</div>\n</div>\n</div>\n";
 
         assert_eq!(actual, expected);
     }
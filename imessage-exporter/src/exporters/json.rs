@@ -0,0 +1,1171 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap, HashSet,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    app::{
+        error::RuntimeError, export_type::ExportType, progress::build_progress_bar_export,
+        runtime::Config,
+    },
+    exporters::exporter::{BalloonFormatter, Exporter, Writer},
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        app_store::AppStoreMessage,
+        collaboration::CollaborationMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        handwriting::HandwrittenMessage,
+        music::MusicMessage,
+        placemark::PlacemarkMessage,
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        messages::{models::BubbleComponent, Message},
+        table::{Table, ORPHANED},
+    },
+    util::{
+        dates::{get_local_time, TIMESTAMP_FACTOR},
+        plist::parse_plist,
+    },
+};
+
+use serde_json::{json, Map, Value};
+
+pub struct JSON<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+    /// If true, write one record per line instead of a JSON array per file
+    pub line_delimited: bool,
+    /// Files that have already received at least one record
+    started: HashSet<String>,
+}
+
+impl<'a> Exporter<'a> for JSON<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let line_delimited = matches!(config.options.export_type, Some(ExportType::Ndjson));
+
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension(JSON::extension(line_delimited));
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(JSON {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+            line_delimited,
+            started: HashSet::new(),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as {}...",
+            self.config.options.export_path.display(),
+            JSON::extension(self.line_delimited)
+        );
+
+        // Open the orphaned array
+        if !self.line_delimited {
+            JSON::write_to_file(&mut self.orphaned, "[")?;
+        }
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                self.write_record(&msg, &announcement)?;
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                self.write_record(&msg, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Close the arrays
+        if !self.line_delimited {
+            for (_, buf) in self.files.iter_mut() {
+                JSON::write_to_file(buf, "\n]\n")?;
+            }
+            JSON::write_to_file(&mut self.orphaned, "\n]\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(self.config.filename(chatroom));
+                        path.set_extension(JSON::extension(self.line_delimited));
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        let mut buf = BufWriter::new(file);
+
+                        // Open the array for this conversation
+                        if !self.line_delimited {
+                            JSON::write_to_file(&mut buf, "[")?;
+                        }
+
+                        Ok(entry.insert(buf))
+                    }
+                }
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Writer<'a> for JSON<'a> {
+    fn format_message(&self, message: &Message, _: usize) -> Result<String, TableError> {
+        Ok(self.message_record(message)?.to_string())
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        // Copy the file, if requested
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .ok_or(attachment.filename())?;
+
+        // Build a relative filepath from the fully qualified one on the `Attachment`
+        Ok(self.config.message_attachment_path(attachment))
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        self.attachment_record(sticker, message).to_string()
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        _: &mut Vec<Attachment>,
+        _: &str,
+    ) -> Result<String, PlistParseError> {
+        Ok(self.app_record(message)?.to_string())
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        Ok(self.tapback_record(msg)?.to_string())
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Confetti",
+                ScreenEffect::Echo => "Echo",
+                ScreenEffect::Fireworks => "Fireworks",
+                ScreenEffect::Balloons => "Balloons",
+                ScreenEffect::Heart => "Heart",
+                ScreenEffect::Lasers => "Lasers",
+                ScreenEffect::ShootingStar => "ShootingStar",
+                ScreenEffect::Sparkles => "Sparkles",
+                ScreenEffect::Spotlight => "Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Slam",
+                BubbleEffect::Loud => "Loud",
+                BubbleEffect::Gentle => "Gentle",
+                BubbleEffect::InvisibleInk => "InvisibleInk",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut record = self.base_record(msg);
+
+        let announcement = match msg.get_announcement() {
+            Some(Announcement::NameChange(name)) => json!({"type": "name_change", "name": name}),
+            Some(Announcement::PhotoChange) => json!({"type": "photo_change"}),
+            Some(Announcement::FullyUnsent) => json!({"type": "fully_unsent"}),
+            Some(Announcement::Unknown(num)) => json!({"type": "unknown", "action": num}),
+            None => Value::Null,
+        };
+        record.insert("announcement".to_string(), announcement);
+
+        Value::Object(record).to_string()
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "shareplay"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        if msg.started_sharing_location() {
+            return "started";
+        } else if msg.stopped_sharing_location() {
+            return "stopped";
+        }
+        "shared"
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        self.edited_record(msg, edited_message, message_part_idx)
+            .map(|record| record.to_string())
+    }
+
+    fn format_attributed(&'a self, msg: &'a str, _: &'a TextEffect) -> Cow<'a, str> {
+        // Effects are exported as ranges alongside the text, so the text itself is unchanged
+        Cow::Borrowed(msg)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> BalloonFormatter<&'a str> for JSON<'a> {
+    fn format_url(&self, msg: &Message, balloon: &URLMessage, _: &str) -> String {
+        self.url_record(msg, balloon).to_string()
+    }
+
+    fn format_music(&self, balloon: &MusicMessage, _: &str) -> String {
+        self.music_record(balloon).to_string()
+    }
+
+    fn format_collaboration(&self, balloon: &CollaborationMessage, _: &str) -> String {
+        self.collaboration_record(balloon).to_string()
+    }
+
+    fn format_app_store(&self, balloon: &AppStoreMessage, _: &'a str) -> String {
+        self.app_store_record(balloon).to_string()
+    }
+
+    fn format_placemark(&self, balloon: &PlacemarkMessage, _: &'a str) -> String {
+        self.placemark_record(balloon).to_string()
+    }
+
+    fn format_handwriting(&self, msg: &Message, balloon: &HandwrittenMessage, _: &str) -> String {
+        self.handwriting_record(msg, balloon).to_string()
+    }
+
+    fn format_apple_pay(&self, balloon: &AppMessage, _: &str) -> String {
+        self.generic_app_record("apple_pay", balloon).to_string()
+    }
+
+    fn format_fitness(&self, balloon: &AppMessage, _: &str) -> String {
+        self.generic_app_record("fitness", balloon).to_string()
+    }
+
+    fn format_slideshow(&self, balloon: &AppMessage, _: &str) -> String {
+        self.generic_app_record("slideshow", balloon).to_string()
+    }
+
+    fn format_find_my(&self, balloon: &AppMessage, _: &'a str) -> String {
+        self.generic_app_record("find_my", balloon).to_string()
+    }
+
+    fn format_check_in(&self, balloon: &AppMessage, _: &'a str) -> String {
+        self.check_in_record(balloon).to_string()
+    }
+
+    fn format_generic_app(
+        &self,
+        balloon: &AppMessage,
+        bundle_id: &str,
+        _: &mut Vec<Attachment>,
+        _: &str,
+    ) -> String {
+        let mut record = self.generic_app_record("app", balloon);
+        if let Value::Object(map) = &mut record {
+            map.insert("bundle_id".to_string(), json!(bundle_id));
+        }
+        record.to_string()
+    }
+}
+
+impl<'a> JSON<'a> {
+    /// Get the file extension used for the selected output mode
+    fn extension(line_delimited: bool) -> &'static str {
+        if line_delimited {
+            "ndjson"
+        } else {
+            "json"
+        }
+    }
+
+    /// Write a single record to the message's file, separating it from any previous records
+    fn write_record(&mut self, message: &Message, record: &str) -> Result<(), RuntimeError> {
+        let (separator, terminator) = if self.line_delimited {
+            ("", "\n")
+        } else {
+            let key = match self.config.conversation(message) {
+                Some((chatroom, _)) => self.config.filename(chatroom),
+                None => ORPHANED.to_string(),
+            };
+            if self.started.insert(key) {
+                ("\n", "")
+            } else {
+                (",\n", "")
+            }
+        };
+
+        let file = self.get_or_create_file(message)?;
+        JSON::write_to_file(file, separator)?;
+        JSON::write_to_file(file, record)?;
+        JSON::write_to_file(file, terminator)
+    }
+
+    /// Convert an iMessage timestamp to an RFC 3339 string, omitting unset timestamps
+    fn date(&self, stamp: &i64) -> Value {
+        if *stamp == 0 {
+            return Value::Null;
+        }
+        match get_local_time(stamp, &self.config.offset) {
+            Ok(date) => json!(date.to_rfc3339()),
+            Err(_) => Value::Null,
+        }
+    }
+
+    /// Fields shared by every record: identifiers, sender, conversation, and dates
+    fn base_record(&self, message: &Message) -> Map<String, Value> {
+        let chat = match self.config.conversation(message) {
+            Some((chatroom, id)) => json!({
+                "id": id,
+                "identifier": chatroom.chat_identifier,
+                "name": chatroom.display_name(),
+                "service": chatroom.service_name,
+            }),
+            None => Value::Null,
+        };
+
+        let mut record = Map::new();
+        record.insert("rowid".to_string(), json!(message.rowid));
+        record.insert("guid".to_string(), json!(message.guid));
+        record.insert("chat".to_string(), chat);
+        record.insert(
+            "sender".to_string(),
+            json!(self.config.who(
+                message.handle_id,
+                message.is_from_me(),
+                &message.destination_caller_id,
+            )),
+        );
+        record.insert("handle_id".to_string(), json!(message.handle_id));
+        record.insert("is_from_me".to_string(), json!(message.is_from_me()));
+        record.insert("date".to_string(), self.date(&message.date));
+        record.insert(
+            "date_delivered".to_string(),
+            self.date(&message.date_delivered),
+        );
+        record.insert("date_read".to_string(), self.date(&message.date_read));
+        record.insert("date_edited".to_string(), self.date(&message.date_edited));
+        record.insert("service".to_string(), json!(message.service));
+        record
+    }
+
+    /// Build the full structured record for a message, including its tapbacks and replies
+    fn message_record(&self, message: &Message) -> Result<Value, TableError> {
+        let mut record = self.base_record(message);
+
+        let variant = match message.variant() {
+            Variant::App(_) => "app",
+            Variant::Edited => "edited",
+            Variant::SharePlay => "shareplay",
+            Variant::Sticker(_) => "sticker",
+            Variant::Tapback(..) => "tapback",
+            Variant::Normal => "normal",
+            Variant::Unknown(_) => "unknown",
+        };
+        record.insert("variant".to_string(), json!(variant));
+        record.insert("subject".to_string(), json!(message.subject));
+        record.insert("text".to_string(), json!(message.text));
+        record.insert("is_deleted".to_string(), json!(message.is_deleted()));
+
+        let expressive = self.format_expressive(message);
+        record.insert(
+            "expressive".to_string(),
+            if expressive.is_empty() {
+                Value::Null
+            } else {
+                json!(expressive)
+            },
+        );
+
+        if message.is_shareplay() {
+            record.insert("shareplay".to_string(), json!(self.format_shareplay()));
+        }
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            record.insert(
+                "shared_location".to_string(),
+                json!(self.format_shared_location(message)),
+            );
+        }
+
+        record.insert(
+            "thread_originator_guid".to_string(),
+            json!(message.thread_originator_guid),
+        );
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut replies = message.get_replies(&self.config.db)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        let mut components = Vec::with_capacity(message_parts.len());
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            let mut component = Map::new();
+            component.insert("index".to_string(), json!(idx));
+
+            match message_part {
+                BubbleComponent::Text(text_attrs) => {
+                    component.insert("type".to_string(), json!("text"));
+
+                    let ranges: Vec<Value> = text_attrs
+                        .iter()
+                        .map(|text_attr| {
+                            json!({
+                                "start": text_attr.start,
+                                "end": text_attr.end,
+                                "text": message.text.as_ref().and_then(|text| text.get(text_attr.start..text_attr.end)),
+                                "effect": JSON::effect_record(&text_attr.effect),
+                            })
+                        })
+                        .collect();
+                    component.insert("ranges".to_string(), Value::Array(ranges));
+
+                    // Render edited message content, if applicable
+                    if message.is_part_edited(idx) {
+                        if let Some(edited_parts) = &message.edited_parts {
+                            if let Some(edited) = self.edited_record(message, edited_parts, idx) {
+                                component.insert("edited".to_string(), edited);
+                            }
+                        }
+                    }
+                }
+                BubbleComponent::Attachment(_) => {
+                    component.insert("type".to_string(), json!("attachment"));
+                    match attachments.get_mut(attachment_index) {
+                        Some(attachment) => {
+                            let record = self.attachment_record(attachment, message);
+                            // Stickers do not advance the attachment index
+                            if !attachment.is_sticker {
+                                attachment_index += 1;
+                            }
+                            component.insert("attachment".to_string(), record);
+                        }
+                        // Attachment does not exist in attachments table
+                        None => {
+                            component.insert("attachment".to_string(), Value::Null);
+                        }
+                    }
+                }
+                BubbleComponent::App => {
+                    component.insert("type".to_string(), json!("app"));
+                    match self.app_record(message) {
+                        Ok(balloon) => {
+                            component.insert("balloon".to_string(), balloon);
+                        }
+                        Err(why) => {
+                            component.insert("error".to_string(), json!(why.to_string()));
+                        }
+                    }
+                }
+                BubbleComponent::Retracted => {
+                    component.insert("type".to_string(), json!("retracted"));
+                    if let Some(edited_parts) = &message.edited_parts {
+                        if let Some(edited) = self.edited_record(message, edited_parts, idx) {
+                            component.insert("edited".to_string(), edited);
+                        }
+                    }
+                }
+            };
+
+            // Handle Tapbacks
+            if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+                if let Some(tapbacks) = tapbacks_map.get(&idx) {
+                    let tapbacks = tapbacks
+                        .iter()
+                        .map(|tapback| self.tapback_record(tapback))
+                        .collect::<Result<Vec<Value>, TableError>>()?;
+                    component.insert("tapbacks".to_string(), Value::Array(tapbacks));
+                }
+            }
+
+            // Handle Replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                let mut reply_records = vec![];
+                for reply in replies.iter_mut() {
+                    let _ = reply.generate_text(&self.config.db);
+                    if !reply.is_tapback() {
+                        reply_records.push(self.message_record(reply)?);
+                    }
+                }
+                component.insert("replies".to_string(), Value::Array(reply_records));
+            }
+
+            components.push(Value::Object(component));
+        }
+        record.insert("components".to_string(), Value::Array(components));
+
+        Ok(Value::Object(record))
+    }
+
+    /// Describe the effect applied to a range of text
+    fn effect_record(effect: &TextEffect) -> Value {
+        match effect {
+            TextEffect::Default => json!({"type": "default"}),
+            TextEffect::Mention(handle) => json!({"type": "mention", "handle": handle}),
+            TextEffect::Link(url) => json!({"type": "link", "url": url}),
+            TextEffect::OTP => json!({"type": "otp"}),
+            TextEffect::Styles(styles) => json!({
+                "type": "styles",
+                "styles": styles.iter().map(|style| format!("{style:?}")).collect::<Vec<_>>(),
+            }),
+            TextEffect::Animated(animation) => {
+                json!({"type": "animated", "animation": format!("{animation:?}")})
+            }
+            TextEffect::Conversion(unit) => {
+                json!({"type": "conversion", "unit": format!("{unit:?}")})
+            }
+        }
+    }
+
+    /// Describe an attachment, copying it if requested
+    fn attachment_record(&self, attachment: &mut Attachment, message: &Message) -> Value {
+        let sticker_effect = if attachment.is_sticker {
+            attachment
+                .get_sticker_effect(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                )
+                .ok()
+                .flatten()
+                .map(|effect| effect.to_string())
+        } else {
+            None
+        };
+
+        let path = self.format_attachment(attachment, message).ok();
+
+        json!({
+            "rowid": attachment.rowid,
+            "filename": attachment.filename,
+            "transfer_name": attachment.transfer_name,
+            "mime_type": attachment.mime_type,
+            "uti": attachment.uti,
+            "total_bytes": attachment.total_bytes,
+            "is_sticker": attachment.is_sticker,
+            "sticker_effect": sticker_effect,
+            "path": path,
+        })
+    }
+
+    /// Describe a tapback or sticker reaction
+    fn tapback_record(&self, msg: &Message) -> Result<Value, TableError> {
+        let who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        match msg.variant() {
+            Variant::Tapback(_, added, tapback) => Ok(json!({
+                "type": "tapback",
+                "tapback": tapback.to_string(),
+                "added": added,
+                "sender": who,
+                "date": self.date(&msg.date),
+            })),
+            Variant::Sticker(_) => {
+                let mut paths = Attachment::from_message(&self.config.db, msg)?;
+                // Sticker messages have only one attachment, the sticker image
+                let sticker = paths
+                    .get_mut(0)
+                    .map(|sticker| self.attachment_record(sticker, msg))
+                    .unwrap_or(Value::Null);
+                Ok(json!({
+                    "type": "sticker",
+                    "sticker": sticker,
+                    "sender": who,
+                    "date": self.date(&msg.date),
+                }))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Describe the edit history of a message part
+    fn edited_record(
+        &self,
+        msg: &Message,
+        edited_message: &EditedMessage,
+        message_part_idx: usize,
+    ) -> Option<Value> {
+        let edited_message_part = edited_message.part(message_part_idx)?;
+        match edited_message_part.status {
+            EditStatus::Edited => {
+                let history: Vec<Value> = edited_message_part
+                    .edit_history
+                    .iter()
+                    .map(|event| {
+                        json!({
+                            "date": self.date(&event.date),
+                            "text": event.text,
+                            "guid": event.guid,
+                        })
+                    })
+                    .collect();
+                Some(json!({"status": "edited", "history": history}))
+            }
+            EditStatus::Unsent => Some(json!({
+                "status": "unsent",
+                "date": self.date(&msg.date_edited),
+            })),
+            EditStatus::Original => None,
+        }
+    }
+
+    /// Parse an app message's payload into a structured balloon
+    fn app_record(&self, message: &Message) -> Result<Value, PlistParseError> {
+        if let Variant::App(balloon) = message.variant() {
+            // Handwritten messages use a different payload type, so check that first
+            if message.is_handwriting() {
+                if let Some(payload) = message.raw_payload_data(&self.config.db) {
+                    return match HandwrittenMessage::from_payload(&payload) {
+                        Ok(bubble) => Ok(self.handwriting_record(message, &bubble)),
+                        Err(why) => Err(PlistParseError::HandwritingError(why)),
+                    };
+                }
+            }
+
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                let parsed = parse_plist(&payload)?;
+                // Handle URL messages separately since they are a special case
+                if message.is_url() {
+                    let bubble = URLMessage::get_url_message_override(&parsed)?;
+                    Ok(match bubble {
+                        URLOverride::Normal(balloon) => self.url_record(message, &balloon),
+                        URLOverride::AppleMusic(balloon) => self.music_record(&balloon),
+                        URLOverride::Collaboration(balloon) => self.collaboration_record(&balloon),
+                        URLOverride::AppStore(balloon) => self.app_store_record(&balloon),
+                        URLOverride::SharedPlacemark(balloon) => self.placemark_record(&balloon),
+                    })
+                } else {
+                    let bubble = AppMessage::from_map(&parsed)?;
+                    Ok(match balloon {
+                        CustomBalloon::Application(bundle_id) => {
+                            let mut record = self.generic_app_record("app", &bubble);
+                            if let Value::Object(map) = &mut record {
+                                map.insert("bundle_id".to_string(), json!(bundle_id));
+                            }
+                            record
+                        }
+                        CustomBalloon::ApplePay => self.generic_app_record("apple_pay", &bubble),
+                        CustomBalloon::Fitness => self.generic_app_record("fitness", &bubble),
+                        CustomBalloon::Slideshow => self.generic_app_record("slideshow", &bubble),
+                        CustomBalloon::CheckIn => self.check_in_record(&bubble),
+                        CustomBalloon::FindMy => self.generic_app_record("find_my", &bubble),
+                        CustomBalloon::Handwriting => unreachable!(),
+                        CustomBalloon::URL => unreachable!(),
+                    })
+                }
+            } else {
+                // Sometimes, URL messages are missing their payloads
+                if message.is_url() {
+                    if let Some(text) = &message.text {
+                        return Ok(json!({"type": "url", "url": text}));
+                    }
+                }
+                Err(PlistParseError::NoPayload)
+            }
+        } else {
+            Err(PlistParseError::WrongMessageType)
+        }
+    }
+
+    fn url_record(&self, msg: &Message, balloon: &URLMessage) -> Value {
+        json!({
+            "type": "url",
+            "url": balloon.get_url().or(msg.text.as_deref()),
+            "title": balloon.title,
+            "summary": balloon.summary,
+            "site_name": balloon.site_name,
+            "images": balloon.images,
+            "icons": balloon.icons,
+        })
+    }
+
+    fn music_record(&self, balloon: &MusicMessage) -> Value {
+        json!({
+            "type": "music",
+            "url": balloon.url,
+            "preview": balloon.preview,
+            "track_name": balloon.track_name,
+            "album": balloon.album,
+            "artist": balloon.artist,
+        })
+    }
+
+    fn collaboration_record(&self, balloon: &CollaborationMessage) -> Value {
+        json!({
+            "type": "collaboration",
+            "url": balloon.get_url(),
+            "title": balloon.title,
+            "app_name": balloon.app_name,
+            "bundle_id": balloon.bundle_id,
+        })
+    }
+
+    fn app_store_record(&self, balloon: &AppStoreMessage) -> Value {
+        json!({
+            "type": "app_store",
+            "url": balloon.url,
+            "app_name": balloon.app_name,
+            "description": balloon.description,
+            "platform": balloon.platform,
+            "genre": balloon.genre,
+        })
+    }
+
+    fn placemark_record(&self, balloon: &PlacemarkMessage) -> Value {
+        json!({
+            "type": "placemark",
+            "url": balloon.get_url(),
+            "place_name": balloon.place_name,
+            "name": balloon.placemark.name,
+            "address": balloon.placemark.address,
+            "street": balloon.placemark.street,
+            "city": balloon.placemark.city,
+            "state": balloon.placemark.state,
+            "postal_code": balloon.placemark.postal_code,
+            "country": balloon.placemark.country,
+            "iso_country_code": balloon.placemark.iso_country_code,
+            "sub_administrative_area": balloon.placemark.sub_administrative_area,
+            "sub_locality": balloon.placemark.sub_locality,
+        })
+    }
+
+    fn handwriting_record(&self, msg: &Message, balloon: &HandwrittenMessage) -> Value {
+        let path = self
+            .config
+            .options
+            .attachment_manager
+            .handle_handwriting(msg, balloon, self.config)
+            .and_then(|filepath| self.config.relative_path(PathBuf::from(&filepath)));
+
+        json!({
+            "type": "handwriting",
+            "id": balloon.id,
+            "width": balloon.width,
+            "height": balloon.height,
+            "path": path,
+        })
+    }
+
+    fn check_in_record(&self, balloon: &AppMessage) -> Value {
+        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+
+        // Determine which stage of the check in the message represents
+        let (status, date_str) = if let Some(date_str) = metadata.get("estimatedEndTime") {
+            ("expected", Some(date_str))
+        } else if let Some(date_str) = metadata.get("triggerTime") {
+            ("expired", Some(date_str))
+        } else if let Some(date_str) = metadata.get("sendDate") {
+            ("checked_in", Some(date_str))
+        } else {
+            ("unknown", None)
+        };
+
+        let date = date_str
+            .map(|date_str| date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR)
+            .and_then(|date_stamp| get_local_time(&date_stamp, &0).ok())
+            .map(|date| date.to_rfc3339());
+
+        json!({
+            "type": "check_in",
+            "caption": balloon.caption,
+            "status": status,
+            "date": date,
+        })
+    }
+
+    fn generic_app_record(&self, balloon_type: &str, balloon: &AppMessage) -> Value {
+        json!({
+            "type": balloon_type,
+            "app_name": balloon.app_name,
+            "url": balloon.url,
+            "title": balloon.title,
+            "subtitle": balloon.subtitle,
+            "caption": balloon.caption,
+            "subcaption": balloon.subcaption,
+            "trailing_caption": balloon.trailing_caption,
+            "trailing_subcaption": balloon.trailing_subcaption,
+            "ldtext": balloon.ldtext,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::Writer,
+        Exporter, JSON,
+    };
+    use imessage_database::{
+        message_types::text_effects::{Style, TextEffect},
+        tables::table::ME,
+    };
+    use serde_json::{json, Value};
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+        assert!(!exporter.line_delimited);
+    }
+
+    #[test]
+    fn can_create_line_delimited() {
+        let mut options = fake_options_for(ExportType::Json);
+        options.export_type = Some(ExportType::Ndjson);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+        assert!(exporter.line_delimited);
+    }
+
+    #[test]
+    fn can_format_json_from_me_normal() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual: Value =
+            serde_json::from_str(&exporter.format_message(&message, 0).unwrap()).unwrap();
+
+        assert_eq!(actual["sender"], "Me");
+        assert_eq!(actual["text"], "Hello world");
+        assert_eq!(actual["variant"], "normal");
+        assert_eq!(actual["service"], "iMessage");
+        assert_eq!(actual["date_read"], Value::Null);
+        assert_eq!(actual["components"][0]["type"], "text");
+        assert_eq!(actual["components"][0]["ranges"][0]["text"], "Hello world");
+    }
+
+    #[test]
+    fn can_format_json_announcement() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello world".to_string());
+
+        let actual: Value = serde_json::from_str(&exporter.format_announcement(&message)).unwrap();
+
+        assert_eq!(
+            actual["announcement"],
+            json!({"type": "name_change", "name": "Hello world"})
+        );
+    }
+
+    #[test]
+    fn can_format_json_tapback_me() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = JSON::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+
+        let actual: Value =
+            serde_json::from_str(&exporter.format_tapback(&message).unwrap()).unwrap();
+
+        assert_eq!(actual["type"], "tapback");
+        assert_eq!(actual["tapback"], "Loved");
+        assert_eq!(actual["added"], true);
+        assert_eq!(actual["sender"], "Me");
+    }
+
+    #[test]
+    fn can_format_json_started_sharing_location() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+
+        let mut message = blank();
+        message.item_type = 4;
+        message.group_action_type = 0;
+        message.share_status = false;
+        message.share_direction = false;
+
+        let actual: Value =
+            serde_json::from_str(&exporter.format_message(&message, 0).unwrap()).unwrap();
+
+        assert_eq!(actual["shared_location"], "started");
+    }
+
+    #[test]
+    fn can_describe_effects() {
+        assert_eq!(
+            JSON::effect_record(&TextEffect::Default),
+            json!({"type": "default"})
+        );
+        assert_eq!(
+            JSON::effect_record(&TextEffect::Link("https://example.com")),
+            json!({"type": "link", "url": "https://example.com"})
+        );
+        assert_eq!(
+            JSON::effect_record(&TextEffect::Mention("+15558675309")),
+            json!({"type": "mention", "handle": "+15558675309"})
+        );
+        assert_eq!(
+            JSON::effect_record(&TextEffect::Styles(vec![Style::Bold, Style::Italic])),
+            json!({"type": "styles", "styles": ["Bold", "Italic"]})
+        );
+    }
+}
+
+#[cfg(test)]
+mod balloon_format_tests {
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::BalloonFormatter,
+        Exporter, JSON,
+    };
+    use imessage_database::message_types::{
+        app::AppMessage,
+        music::MusicMessage,
+        placemark::{Placemark, PlacemarkMessage},
+        url::URLMessage,
+    };
+    use serde_json::Value;
+
+    #[test]
+    fn can_format_json_url() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+
+        let balloon = URLMessage {
+            title: Some("title"),
+            summary: Some("summary"),
+            url: Some("url"),
+            original_url: Some("original_url"),
+            item_type: Some("item_type"),
+            images: vec!["images"],
+            icons: vec!["icons"],
+            site_name: Some("site_name"),
+            placeholder: false,
+        };
+
+        let actual: Value =
+            serde_json::from_str(&exporter.format_url(&blank(), &balloon, "")).unwrap();
+
+        assert_eq!(actual["type"], "url");
+        assert_eq!(actual["url"], "url");
+        assert_eq!(actual["title"], "title");
+        assert_eq!(actual["summary"], "summary");
+        assert_eq!(actual["images"][0], "images");
+    }
+
+    #[test]
+    fn can_format_json_music() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+
+        let balloon = MusicMessage {
+            url: Some("url"),
+            preview: None,
+            artist: Some("artist"),
+            album: Some("album"),
+            track_name: Some("track_name"),
+        };
+
+        let actual: Value = serde_json::from_str(&exporter.format_music(&balloon, "")).unwrap();
+
+        assert_eq!(actual["type"], "music");
+        assert_eq!(actual["track_name"], "track_name");
+        assert_eq!(actual["preview"], Value::Null);
+    }
+
+    #[test]
+    fn can_format_json_placemark() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+
+        let balloon = PlacemarkMessage {
+            url: Some("url"),
+            original_url: Some("original_url"),
+            place_name: Some("Name"),
+            placemark: Placemark {
+                name: Some("name"),
+                address: Some("address"),
+                state: Some("state"),
+                city: Some("city"),
+                iso_country_code: Some("iso_country_code"),
+                postal_code: Some("postal_code"),
+                country: Some("country"),
+                street: Some("street"),
+                sub_administrative_area: Some("sub_administrative_area"),
+                sub_locality: Some("sub_locality"),
+            },
+        };
+
+        let actual: Value = serde_json::from_str(&exporter.format_placemark(&balloon, "")).unwrap();
+
+        assert_eq!(actual["type"], "placemark");
+        assert_eq!(actual["place_name"], "Name");
+        assert_eq!(actual["city"], "city");
+    }
+
+    #[test]
+    fn can_format_json_generic_app() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Json);
+        let config = fake_config(options);
+        let exporter = JSON::new(&config).unwrap();
+
+        let balloon = AppMessage {
+            image: None,
+            url: None,
+            title: Some("title"),
+            subtitle: Some("subtitle"),
+            caption: Some("caption"),
+            subcaption: Some("subcaption"),
+            trailing_caption: Some("trailing_caption"),
+            trailing_subcaption: Some("trailing_subcaption"),
+            app_name: Some("app_name"),
+            ldtext: Some("ldtext"),
+        };
+
+        let actual: Value = serde_json::from_str(&exporter.format_generic_app(
+            &balloon,
+            "bundle_id",
+            &mut vec![],
+            "",
+        ))
+        .unwrap();
+
+        assert_eq!(actual["type"], "app");
+        assert_eq!(actual["bundle_id"], "bundle_id");
+        assert_eq!(actual["app_name"], "app_name");
+    }
+}
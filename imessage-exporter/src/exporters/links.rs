@@ -0,0 +1,631 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        progress::build_progress_bar_export,
+        runtime::Config,
+        sanitizers::{sanitize_html, sanitize_url_path, sanitize_xml},
+    },
+    exporters::exporter::Exporter,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        messages::{models::BubbleComponent, Message},
+        table::{Table, ORPHANED},
+    },
+    util::{
+        dates::{format, get_local_time},
+        plist::parse_plist,
+    },
+};
+
+/// Name of the files every link is written to
+const LINKS: &str = "links";
+
+/// Title of the page that lists every link
+const LINKS_TITLE: &str = "All links";
+
+/// Title of the page that lists links that do not belong to a conversation
+const ORPHANED_TITLE: &str = "Orphaned links";
+
+const HEADER: &str = "<html>\n<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">";
+const FOOTER: &str = "</tbody>\n</table>\n</div>\n</body></html>";
+const STYLE: &str = include_str!("resources/style.css");
+
+/// Column names written to the first row of every CSV file
+const CSV_HEADER: [&str; 10] = [
+    "kind",
+    "guid",
+    "date",
+    "sender",
+    "chat_id",
+    "chat_name",
+    "title",
+    "summary",
+    "url",
+    "text",
+];
+
+/// The kind of message a link was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A rich link preview or a link in the text of a message
+    Link,
+    /// An Apple Music link
+    Music,
+    /// An App Store link
+    AppStore,
+    /// A shared document or collaboration invite
+    Collaboration,
+    /// A place shared from the Maps app
+    Placemark,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Link => "link",
+            Kind::Music => "music",
+            Kind::AppStore => "app_store",
+            Kind::Collaboration => "collaboration",
+            Kind::Placemark => "placemark",
+        }
+    }
+}
+
+/// A link shared in a conversation
+#[derive(Debug, PartialEq)]
+struct Link {
+    /// The kind of message the link was found in
+    kind: Kind,
+    /// The GUID of the message the link was found in
+    guid: String,
+    /// The date the message was sent
+    date: i64,
+    /// The name of the person who sent the message
+    sender: String,
+    /// The deduplicated ID of the conversation the message belongs to
+    chat_id: Option<i32>,
+    /// The name of the conversation the message belongs to
+    chat: Option<String>,
+    /// The title of the linked page or item
+    title: Option<String>,
+    /// A description of the linked page or item
+    summary: Option<String>,
+    /// The URL that was shared
+    url: String,
+    /// The text the link was attached to, for links in the text of a message
+    text: Option<String>,
+}
+
+/// Writers for the HTML page and CSV file that list a conversation's links
+pub struct LinkFiles {
+    /// Writer for the HTML page
+    pub html: BufWriter<File>,
+    /// Writer for the CSV file
+    pub csv: BufWriter<File>,
+}
+
+/// Exports every link shared in a conversation as an HTML page and a CSV file, and every link in the
+/// export as a combined HTML page and CSV file
+pub struct Links<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Map of resolved chatroom file location to the files its links are written to
+    pub files: HashMap<String, LinkFiles>,
+    /// Writers for the files every link is written to
+    pub combined: LinkFiles,
+}
+
+impl<'a> Exporter<'a> for Links<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let combined = Links::create_files(config, LINKS, LINKS_TITLE, true)?;
+        Ok(Links {
+            config,
+            files: HashMap::new(),
+            combined,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as links...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message, which holds the ranges of any links in it
+            let _ = msg.generate_text(&self.config.db);
+
+            for link in self.links(&msg) {
+                let row = self.format_csv(&link);
+                let html = self.format_html(&link, true);
+                Links::write_to_file(&mut self.combined.html, &html)?;
+                Links::write_to_file(&mut self.combined.csv, &row)?;
+
+                let html = self.format_html(&link, false);
+                let files = self.get_or_create_files(&msg)?;
+                Links::write_to_file(&mut files.html, &html)?;
+                Links::write_to_file(&mut files.csv, &row)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        for files in self.files.values_mut() {
+            Links::write_to_file(&mut files.html, FOOTER)?;
+        }
+        Links::write_to_file(&mut self.combined.html, FOOTER)?;
+
+        Ok(())
+    }
+
+    /// Every link is written to the combined files, see [`Links::get_or_create_files`] for the conversation's files
+    fn get_or_create_file(&mut self, _: &Message) -> Result<&mut BufWriter<File>, RuntimeError> {
+        Ok(&mut self.combined.csv)
+    }
+}
+
+impl<'a> Links<'a> {
+    /// Create the files for the given chat, caching them so we don't need to build them later
+    fn get_or_create_files(&mut self, message: &Message) -> Result<&mut LinkFiles, RuntimeError> {
+        let (filename, title) = match self.config.conversation(message) {
+            Some((chatroom, _)) => (self.config.filename(chatroom), chatroom.name().to_string()),
+            None => (ORPHANED.to_string(), ORPHANED_TITLE.to_string()),
+        };
+
+        match self.files.entry(filename) {
+            Occupied(entry) => Ok(entry.into_mut()),
+            Vacant(entry) => {
+                let files = Links::create_files(self.config, entry.key(), &title, false)?;
+                Ok(entry.insert(files))
+            }
+        }
+    }
+
+    /// Open the HTML page and CSV file with the given name, writing their headers if they are new
+    fn create_files(
+        config: &Config,
+        name: &str,
+        title: &str,
+        combined: bool,
+    ) -> Result<LinkFiles, RuntimeError> {
+        let open = |extension: &str| -> Result<(BufWriter<File>, bool), RuntimeError> {
+            // Names may contain periods, so the extension is appended instead of set
+            let mut path = config.options.export_path.clone();
+            path.push(format!("{name}.{extension}"));
+
+            // If the file already exists, don't write the headers again
+            // This can happen if multiple chats use the same group name
+            let file_exists = path.exists();
+
+            let file = File::options()
+                .append(true)
+                .create(true)
+                .open(&path)
+                .map_err(|err| RuntimeError::CreateError(err, path))?;
+            Ok((BufWriter::new(file), file_exists))
+        };
+
+        let (mut html, html_exists) = open("html")?;
+        if !html_exists {
+            Links::write_to_file(&mut html, &Links::format_header(title, combined))?;
+        }
+
+        let (mut csv, csv_exists) = open("csv")?;
+        if !csv_exists {
+            Links::write_to_file(&mut csv, &Links::build_row(&CSV_HEADER))?;
+        }
+
+        Ok(LinkFiles { html, csv })
+    }
+
+    /// Collect every link shared in a message
+    fn links(&self, message: &Message) -> Vec<Link> {
+        let mut links = vec![];
+
+        if let Variant::App(CustomBalloon::URL) = message.variant() {
+            match self.balloon_link(message) {
+                Some(link) => links.push(link),
+                // Link previews that failed to load only have the URL in their text
+                None => {
+                    if let Some(url) = message.text.as_deref().map(str::trim) {
+                        if !url.is_empty() {
+                            links.push(self.blank_link(message, Kind::Link, url));
+                        }
+                    }
+                }
+            }
+        }
+
+        for link in self.text_links(message, &message.body()) {
+            // Link previews also attach their URL to the message text, so only keep one of them
+            if !links.iter().any(|existing| existing.url == link.url) {
+                links.push(link);
+            }
+        }
+
+        links
+    }
+
+    /// Build a link with the data every message has
+    fn blank_link(&self, message: &Message, kind: Kind, url: &str) -> Link {
+        let (chat_id, chat) = match self.config.conversation(message) {
+            Some((chatroom, id)) => (Some(*id), Some(chatroom.name().to_string())),
+            None => (None, None),
+        };
+
+        Link {
+            kind,
+            guid: message.guid.clone(),
+            date: message.date,
+            sender: self
+                .config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            chat_id,
+            chat,
+            title: None,
+            summary: None,
+            url: url.to_string(),
+            text: None,
+        }
+    }
+
+    /// Collect the data from a rich link, Apple Music, App Store, collaboration, or Maps message
+    fn balloon_link(&self, message: &Message) -> Option<Link> {
+        let payload = message.payload_data(&self.config.db)?;
+        let parsed = parse_plist(&payload).ok()?;
+
+        let (kind, url, title, summary) =
+            match URLMessage::get_url_message_override(&parsed).ok()? {
+                URLOverride::Normal(balloon) => (
+                    Kind::Link,
+                    balloon
+                        .get_url()
+                        .or(message.text.as_deref())
+                        .map(str::to_string),
+                    balloon.title.or(balloon.site_name),
+                    balloon.summary.map(str::to_string),
+                ),
+                URLOverride::AppleMusic(balloon) => {
+                    let artist = [balloon.artist, balloon.album]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<&str>>()
+                        .join(" - ");
+                    (
+                        Kind::Music,
+                        balloon.url.map(str::to_string),
+                        balloon.track_name.or(balloon.album),
+                        Some(artist).filter(|artist| !artist.is_empty()),
+                    )
+                }
+                URLOverride::AppStore(balloon) => (
+                    Kind::AppStore,
+                    balloon.url.map(str::to_string),
+                    balloon.app_name,
+                    balloon.description.or(balloon.genre).map(str::to_string),
+                ),
+                URLOverride::Collaboration(balloon) => (
+                    Kind::Collaboration,
+                    balloon.get_url().map(str::to_string),
+                    balloon.title,
+                    balloon.app_name.map(str::to_string),
+                ),
+                URLOverride::SharedPlacemark(balloon) => (
+                    Kind::Placemark,
+                    balloon.get_url().map(str::to_string),
+                    balloon.placemark.name.or(balloon.place_name),
+                    balloon
+                        .placemark
+                        .address
+                        .or(balloon.place_name)
+                        .map(str::to_string),
+                ),
+            };
+
+        let mut link = self.blank_link(message, kind, &url?);
+        link.title = title.map(str::to_string);
+        link.summary = summary;
+        Some(link)
+    }
+
+    /// Collect the links attached to ranges of a message's text
+    fn text_links(&self, message: &Message, body: &[BubbleComponent]) -> Vec<Link> {
+        let mut links: Vec<Link> = vec![];
+        let Some(text) = message.text.as_deref() else {
+            return links;
+        };
+
+        for component in body {
+            if let BubbleComponent::Text(attributes) = component {
+                for attribute in attributes {
+                    if let TextEffect::Link(url) = attribute.effect {
+                        if links.iter().any(|link| link.url == url) {
+                            continue;
+                        }
+                        let mut link = self.blank_link(message, Kind::Link, url);
+                        link.text = text.get(attribute.start..attribute.end).map(str::to_string);
+                        links.push(link);
+                    }
+                }
+            }
+        }
+
+        links
+    }
+
+    /// Build the start of a page that lists links, with a column for the conversation if it lists every link
+    fn format_header(title: &str, combined: bool) -> String {
+        let chat_column = if combined {
+            "<th>Conversation</th>"
+        } else {
+            ""
+        };
+        let nav = if combined {
+            String::new()
+        } else {
+            format!(
+                "<p><a href=\"{}\">{LINKS_TITLE}</a></p>\n",
+                sanitize_url_path(&format!("{LINKS}.html"))
+            )
+        };
+        format!(
+            "{HEADER}\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n<div class=\"index\">\n<h1>{}</h1>\n{nav}<table>\n<thead><tr><th>Date</th><th>Sender</th>{chat_column}<th>Kind</th><th>Link</th><th>Summary</th></tr></thead>\n<tbody>\n",
+            sanitize_html(title)
+        )
+    }
+
+    /// Build the table row for a link, with the conversation it was shared in if the page lists every link
+    fn format_html(&self, link: &Link, combined: bool) -> String {
+        let chat = if combined {
+            let name = link.chat.as_deref().unwrap_or(ORPHANED_TITLE);
+            let filename = self
+                .chat_filename(link)
+                .unwrap_or_else(|| ORPHANED.to_string());
+            format!(
+                "<td><a href=\"{}\">{}</a></td>",
+                sanitize_url_path(&format!("{filename}.html")),
+                sanitize_html(name)
+            )
+        } else {
+            String::new()
+        };
+
+        let title = link
+            .title
+            .as_deref()
+            .or(link.text.as_deref())
+            .unwrap_or(&link.url);
+
+        format!(
+            "<tr><td>{}</td><td>{}</td>{chat}<td>{}</td><td><a href=\"{}\">{}</a></td><td>{}</td></tr>\n",
+            format(&get_local_time(&link.date, &self.config.offset)),
+            sanitize_html(&link.sender),
+            link.kind.as_str(),
+            sanitize_xml(&link.url),
+            sanitize_html(title),
+            sanitize_html(link.summary.as_deref().unwrap_or_default()),
+        )
+    }
+
+    /// Resolve the name of the files a link's conversation is written to
+    fn chat_filename(&self, link: &Link) -> Option<String> {
+        let chatroom = self.config.chatrooms.get(&link.chat_id?)?;
+        Some(self.config.filename(chatroom))
+    }
+
+    /// Build the CSV row for a link
+    fn format_csv(&self, link: &Link) -> String {
+        Links::build_row(&[
+            link.kind.as_str(),
+            &link.guid,
+            &get_local_time(&link.date, &self.config.offset)
+                .map(|date| date.to_rfc3339())
+                .unwrap_or_default(),
+            &link.sender,
+            &link.chat_id.map(|id| id.to_string()).unwrap_or_default(),
+            link.chat.as_deref().unwrap_or_default(),
+            link.title.as_deref().unwrap_or_default(),
+            link.summary.as_deref().unwrap_or_default(),
+            &link.url,
+            link.text.as_deref().unwrap_or_default(),
+        ])
+    }
+
+    /// Serialize a list of fields into a single line, quoting and escaping as needed
+    fn build_row<T: AsRef<[u8]>>(fields: &[T]) -> String {
+        let mut writer = ::csv::WriterBuilder::new().from_writer(vec![]);
+        // Writing to a `Vec` cannot fail
+        let _ = writer.write_record(fields);
+        writer
+            .into_inner()
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .unwrap_or_default()
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_at},
+        },
+        exporters::links::{Kind, Link},
+        Exporter, Links,
+    };
+    use imessage_database::{
+        message_types::text_effects::TextEffect,
+        tables::messages::models::{BubbleComponent, TextAttributes},
+    };
+
+    /// Keep the combined files out of `/tmp`, where they look like existing HTML export data
+    const EXPORT_DIR: &str = "/tmp/imessage-exporter-links";
+
+    fn fake_link() -> Link {
+        Link {
+            kind: Kind::Music,
+            guid: "ABC-123".to_string(),
+            date: 674526582885055488,
+            sender: "Sample Contact".to_string(),
+            chat_id: None,
+            chat: None,
+            title: Some("Lovefool".to_string()),
+            summary: Some("The Cardigans - First Band on the Moon".to_string()),
+            url: "https://music.apple.com/us/album/lovefool/1?i=2&l=en".to_string(),
+            text: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_at(ExportType::Links, EXPORT_DIR);
+        let config = fake_config(options);
+        let exporter = Links::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_skip_message_without_links() {
+        let options = fake_options_at(ExportType::Links, EXPORT_DIR);
+        let config = fake_config(options);
+        let exporter = Links::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+
+        assert!(exporter.links(&message).is_empty());
+    }
+
+    #[test]
+    fn can_get_text_links() {
+        let options = fake_options_at(ExportType::Links, EXPORT_DIR);
+        let config = fake_config(options);
+        let exporter = Links::new(&config).unwrap();
+
+        let mut message = blank();
+        message.guid = "ABC-123".to_string();
+        message.text = Some("Read this and this".to_string());
+
+        let body = vec![BubbleComponent::Text(vec![
+            TextAttributes::new(0, 5, TextEffect::Default),
+            TextAttributes::new(5, 9, TextEffect::Link("https://example.com")),
+            TextAttributes::new(9, 14, TextEffect::Default),
+            TextAttributes::new(14, 18, TextEffect::Link("https://example.com")),
+        ])];
+
+        let links = exporter.text_links(&message, &body);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].kind, Kind::Link);
+        assert_eq!(links[0].guid, "ABC-123");
+        assert_eq!(links[0].url, "https://example.com");
+        assert_eq!(links[0].text.as_deref(), Some("this"));
+    }
+
+    #[test]
+    fn can_format_csv() {
+        std::env::set_var("TZ", "PST");
+        let options = fake_options_at(ExportType::Links, EXPORT_DIR);
+        let config = fake_config(options);
+        let exporter = Links::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_csv(&fake_link()),
+            "music,ABC-123,2022-05-17T17:29:42-07:00,Sample Contact,,,Lovefool,The Cardigans - First Band on the Moon,https://music.apple.com/us/album/lovefool/1?i=2&l=en,\n"
+        );
+    }
+
+    #[test]
+    fn can_format_html() {
+        std::env::set_var("TZ", "PST");
+        let options = fake_options_at(ExportType::Links, EXPORT_DIR);
+        let config = fake_config(options);
+        let exporter = Links::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_html(&fake_link(), false),
+            "<tr><td>May 17, 2022  5:29:42 PM</td><td>Sample Contact</td><td>music</td><td><a href=\"https://music.apple.com/us/album/lovefool/1?i=2&amp;l=en\">Lovefool</a></td><td>The Cardigans - First Band on the Moon</td></tr>\n"
+        );
+    }
+
+    #[test]
+    fn can_format_html_combined() {
+        std::env::set_var("TZ", "PST");
+        let options = fake_options_at(ExportType::Links, EXPORT_DIR);
+        let config = fake_config(options);
+        let exporter = Links::new(&config).unwrap();
+
+        let html = exporter.format_html(&fake_link(), true);
+        assert!(html.contains("<td><a href=\"orphaned.html\">Orphaned links</a></td>"));
+    }
+
+    #[test]
+    fn can_format_header() {
+        let header = Links::format_header("Trip & Friends", false);
+        assert!(header.contains("<h1>Trip &amp; Friends</h1>"));
+        assert!(header.contains("<a href=\"links.html\">All links</a>"));
+        assert!(!header.contains("<th>Conversation</th>"));
+
+        let header = Links::format_header("All links", true);
+        assert!(header.contains("<th>Conversation</th>"));
+        assert!(!header.contains("<a href=\"links.html\">"));
+    }
+}
@@ -0,0 +1,752 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::{
+    app::{
+        error::RuntimeError, export_type::ExportType, progress::build_progress_bar_export,
+        runtime::Config, sanitizers::sanitize_xml,
+    },
+    exporters::exporter::Exporter,
+};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        app::AppMessage,
+        url::URLMessage,
+        variants::{BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{messages::Message, table::Table},
+    util::{dates::get_local_time, plist::parse_plist},
+};
+use serde_json::{json, Map, Value};
+
+/// Name of the file every location is written to
+const LOCATIONS: &str = "locations";
+
+/// Name given to the document in formats that support one
+const DOCUMENT_NAME: &str = "iMessage Locations";
+
+/// Query string keys that may hold a `latitude,longitude` pair in a maps URL, in order of preference
+const COORDINATE_KEYS: [&str; 3] = ["ll", "sll", "q"];
+
+/// The kind of message a location was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// A place shared from the Maps app
+    Placemark,
+    /// A Find My message
+    FindMy,
+    /// A Check In message
+    CheckIn,
+    /// A message noting someone started or stopped sharing their location
+    SharedLocation,
+}
+
+impl Source {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Source::Placemark => "placemark",
+            Source::FindMy => "find_my",
+            Source::CheckIn => "check_in",
+            Source::SharedLocation => "shared_location",
+        }
+    }
+}
+
+/// A location shared in a conversation
+#[derive(Debug, PartialEq)]
+struct Location {
+    /// The kind of message the location was found in
+    source: Source,
+    /// The GUID of the message the location was found in
+    guid: String,
+    /// The date the message was sent, in RFC 3339 format
+    date: Option<String>,
+    /// The name of the person who sent the message
+    sender: String,
+    /// The deduplicated ID of the conversation the message belongs to
+    chat_id: Option<i32>,
+    /// The name of the conversation the message belongs to
+    chat: Option<String>,
+    /// The name of the place
+    name: Option<String>,
+    /// Text that describes the message, i.e. the status of a Check In
+    description: Option<String>,
+    /// The maps URL for the place
+    url: Option<String>,
+    /// Place data, like the city or country, as key-value pairs
+    details: Vec<(&'static str, String)>,
+    /// The latitude and longitude of the place, in degrees
+    coordinates: Option<(f64, f64)>,
+}
+
+impl Location {
+    /// The best available title for the location
+    fn title(&self) -> &str {
+        self.name
+            .as_deref()
+            .or(self.description.as_deref())
+            .unwrap_or(self.source.as_str())
+    }
+}
+
+/// Exports every location shared in a conversation as GeoJSON, KML, or GPX
+pub struct Locations<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Writer instance for the file every location is written to
+    pub file: BufWriter<File>,
+    /// Number of locations written to the file
+    written: usize,
+}
+
+impl<'a> Exporter<'a> for Locations<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut path = config.options.export_path.clone();
+        path.push(LOCATIONS);
+        if let Some(export_type) = &config.options.export_type {
+            path.set_extension(export_type.to_string());
+        }
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        Ok(Locations {
+            config,
+            file: BufWriter::new(file),
+            written: 0,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as {}...",
+            self.config.options.export_path.display(),
+            self.format()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        let header = self.format_header();
+        Locations::write_to_file(&mut self.file, &header)?;
+
+        for message in messages {
+            let msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            if let Some(location) = self.location(&msg) {
+                if let Some(feature) = self.format_location(&location) {
+                    Locations::write_to_file(&mut self.file, &feature)?;
+                    self.written += 1;
+                }
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        let footer = self.format_footer();
+        Locations::write_to_file(&mut self.file, &footer)?;
+
+        Ok(())
+    }
+
+    /// Every location is written to the same file
+    fn get_or_create_file(&mut self, _: &Message) -> Result<&mut BufWriter<File>, RuntimeError> {
+        Ok(&mut self.file)
+    }
+}
+
+impl<'a> Locations<'a> {
+    /// The format selected for the export
+    fn format(&self) -> &'static str {
+        match self.config.options.export_type {
+            Some(ExportType::Kml) => "kml",
+            Some(ExportType::Gpx) => "gpx",
+            _ => "geojson",
+        }
+    }
+
+    /// Collect the location data from a message, if it contains any
+    fn location(&self, message: &Message) -> Option<Location> {
+        let mut location =
+            if message.started_sharing_location() || message.stopped_sharing_location() {
+                let mut location = self.blank_location(message, Source::SharedLocation);
+                location.description = Some(
+                    if message.started_sharing_location() {
+                        "Started sharing location"
+                    } else {
+                        "Stopped sharing location"
+                    }
+                    .to_string(),
+                );
+                location
+            } else {
+                match message.variant() {
+                    Variant::App(CustomBalloon::URL) => self.placemark(message)?,
+                    Variant::App(CustomBalloon::FindMy) => {
+                        self.app_location(message, Source::FindMy)?
+                    }
+                    Variant::App(CustomBalloon::CheckIn) => {
+                        self.app_location(message, Source::CheckIn)?
+                    }
+                    _ => return None,
+                }
+            };
+
+        location.coordinates = location.url.as_deref().and_then(Locations::coordinates);
+        Some(location)
+    }
+
+    /// Build a location with the data every message has
+    fn blank_location(&self, message: &Message, source: Source) -> Location {
+        let (chat_id, chat) = match self.config.conversation(message) {
+            Some((chatroom, id)) => (Some(*id), Some(chatroom.name().to_string())),
+            None => (None, None),
+        };
+
+        Location {
+            source,
+            guid: message.guid.clone(),
+            date: get_local_time(&message.date, &self.config.offset)
+                .ok()
+                .map(|date| date.to_rfc3339()),
+            sender: self
+                .config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            chat_id,
+            chat,
+            name: None,
+            description: None,
+            url: None,
+            details: vec![],
+            coordinates: None,
+        }
+    }
+
+    /// Collect the place data from a Maps message
+    fn placemark(&self, message: &Message) -> Option<Location> {
+        let payload = message.payload_data(&self.config.db)?;
+        let parsed = parse_plist(&payload).ok()?;
+        let URLOverride::SharedPlacemark(balloon) =
+            URLMessage::get_url_message_override(&parsed).ok()?
+        else {
+            return None;
+        };
+
+        let mut location = self.blank_location(message, Source::Placemark);
+        location.name = balloon
+            .placemark
+            .name
+            .or(balloon.place_name)
+            .map(str::to_string);
+        location.description = balloon.place_name.map(str::to_string);
+        location.url = balloon.get_url().map(str::to_string);
+        location.details = [
+            ("address", balloon.placemark.address),
+            ("street", balloon.placemark.street),
+            ("sub_locality", balloon.placemark.sub_locality),
+            ("city", balloon.placemark.city),
+            (
+                "sub_administrative_area",
+                balloon.placemark.sub_administrative_area,
+            ),
+            ("state", balloon.placemark.state),
+            ("postal_code", balloon.placemark.postal_code),
+            ("country", balloon.placemark.country),
+            ("iso_country_code", balloon.placemark.iso_country_code),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value?.to_string())))
+        .collect();
+
+        Some(location)
+    }
+
+    /// Collect the data from a Find My or Check In message
+    fn app_location(&self, message: &Message, source: Source) -> Option<Location> {
+        let mut location = self.blank_location(message, source);
+
+        if let Some(payload) = message.payload_data(&self.config.db) {
+            let parsed = parse_plist(&payload).ok()?;
+            let balloon = AppMessage::from_map(&parsed).ok()?;
+            location.description = balloon
+                .ldtext
+                .or(balloon.caption)
+                .or(balloon.app_name)
+                .map(str::to_string);
+            location.url = balloon.url.map(str::to_string);
+        }
+
+        Some(location)
+    }
+
+    /// Parse the latitude and longitude from a maps URL
+    ///
+    /// Apple Maps links store the coordinates of a place as `ll=latitude,longitude`.
+    fn coordinates(url: &str) -> Option<(f64, f64)> {
+        let (_, query) = url.split_once('?')?;
+        let params: Vec<(&str, &str)> = query
+            .split('&')
+            .filter_map(|param| param.split_once('='))
+            .collect();
+
+        COORDINATE_KEYS.iter().find_map(|key| {
+            let (_, value) = params.iter().find(|(name, _)| name == key)?;
+            let value = value.replace("%2C", ",").replace("%2c", ",");
+            let (latitude, longitude) = value.split_once(',')?;
+            let latitude: f64 = latitude.trim().parse().ok()?;
+            let longitude: f64 = longitude.trim().parse().ok()?;
+            ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+                .then_some((latitude, longitude))
+        })
+    }
+
+    /// Build the text written before any locations
+    fn format_header(&self) -> String {
+        match self.config.options.export_type {
+            Some(ExportType::Kml) => format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n<name>{DOCUMENT_NAME}</name>\n"
+            ),
+            Some(ExportType::Gpx) => format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gpx version=\"1.1\" creator=\"imessage-exporter\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n<metadata><name>{DOCUMENT_NAME}</name></metadata>\n"
+            ),
+            _ => String::from("{\"type\":\"FeatureCollection\",\"features\":[\n"),
+        }
+    }
+
+    /// Build the text written after every location
+    fn format_footer(&self) -> String {
+        match self.config.options.export_type {
+            Some(ExportType::Kml) => String::from("</Document>\n</kml>\n"),
+            Some(ExportType::Gpx) => String::from("</gpx>\n"),
+            _ => String::from("\n]}\n"),
+        }
+    }
+
+    /// Build the text for a single location in the selected format
+    ///
+    /// GPX waypoints require coordinates, so locations without any are skipped in that format.
+    fn format_location(&self, location: &Location) -> Option<String> {
+        match self.config.options.export_type {
+            Some(ExportType::Kml) => Some(Locations::format_kml(location)),
+            Some(ExportType::Gpx) => Locations::format_gpx(location),
+            _ => {
+                let feature = Locations::format_geojson(location).to_string();
+                // Features after the first are separated from the previous one
+                Some(if self.written == 0 {
+                    feature
+                } else {
+                    format!(",\n{feature}")
+                })
+            }
+        }
+    }
+
+    /// Build the properties shared by every format, in display order
+    fn properties(location: &Location) -> Vec<(&'static str, String)> {
+        let mut properties = vec![("source", location.source.as_str().to_string())];
+        properties.push(("guid", location.guid.clone()));
+        if let Some(date) = &location.date {
+            properties.push(("date", date.clone()));
+        }
+        properties.push(("sender", location.sender.clone()));
+        if let Some(chat_id) = location.chat_id {
+            properties.push(("chat_id", chat_id.to_string()));
+        }
+        if let Some(chat) = &location.chat {
+            properties.push(("chat", chat.clone()));
+        }
+        if let Some(name) = &location.name {
+            properties.push(("name", name.clone()));
+        }
+        if let Some(description) = &location.description {
+            properties.push(("description", description.clone()));
+        }
+        if let Some(url) = &location.url {
+            properties.push(("url", url.clone()));
+        }
+        properties.extend(location.details.iter().cloned());
+        properties
+    }
+
+    /// Build a GeoJSON feature, with a `null` geometry if the location has no coordinates
+    fn format_geojson(location: &Location) -> Value {
+        let mut properties = Map::new();
+        for (key, value) in Locations::properties(location) {
+            properties.insert(key.to_string(), json!(value));
+        }
+        // Keep the deduplicated chat ID numeric
+        if let Some(chat_id) = location.chat_id {
+            properties.insert("chat_id".to_string(), json!(chat_id));
+        }
+
+        // GeoJSON positions are longitude first
+        let geometry = match location.coordinates {
+            Some((latitude, longitude)) => json!({
+                "type": "Point",
+                "coordinates": [longitude, latitude],
+            }),
+            None => Value::Null,
+        };
+
+        json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": properties,
+        })
+    }
+
+    /// Build a KML placemark, without a point if the location has no coordinates
+    fn format_kml(location: &Location) -> String {
+        let mut out_s = String::from("<Placemark>\n");
+        out_s.push_str(&format!(
+            "<name>{}</name>\n",
+            sanitize_xml(location.title())
+        ));
+        if let Some(description) = &location.description {
+            out_s.push_str(&format!(
+                "<description>{}</description>\n",
+                sanitize_xml(description)
+            ));
+        }
+        if let Some(date) = &location.date {
+            out_s.push_str(&format!("<TimeStamp><when>{date}</when></TimeStamp>\n"));
+        }
+
+        out_s.push_str("<ExtendedData>\n");
+        for (key, value) in Locations::properties(location) {
+            out_s.push_str(&format!(
+                "<Data name=\"{key}\"><value>{}</value></Data>\n",
+                sanitize_xml(&value)
+            ));
+        }
+        out_s.push_str("</ExtendedData>\n");
+
+        // KML coordinates are longitude first
+        if let Some((latitude, longitude)) = location.coordinates {
+            out_s.push_str(&format!(
+                "<Point><coordinates>{longitude},{latitude}</coordinates></Point>\n"
+            ));
+        }
+        out_s.push_str("</Placemark>\n");
+        out_s
+    }
+
+    /// Build a GPX waypoint, or `None` if the location has no coordinates
+    fn format_gpx(location: &Location) -> Option<String> {
+        let (latitude, longitude) = location.coordinates?;
+
+        let mut out_s = format!("<wpt lat=\"{latitude}\" lon=\"{longitude}\">\n");
+        if let Some(date) = &location.date {
+            out_s.push_str(&format!("<time>{date}</time>\n"));
+        }
+        out_s.push_str(&format!(
+            "<name>{}</name>\n",
+            sanitize_xml(location.title())
+        ));
+
+        // GPX has no place for arbitrary properties, so the rest are written to the comment
+        let comment = Locations::properties(location)
+            .into_iter()
+            .map(|(key, value)| format!("{key}: {value}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        out_s.push_str(&format!("<cmt>{}</cmt>\n", sanitize_xml(&comment)));
+        if let Some(description) = &location.description {
+            out_s.push_str(&format!("<desc>{}</desc>\n", sanitize_xml(description)));
+        }
+        out_s.push_str(&format!("<src>{}</src>\n", sanitize_xml(&location.sender)));
+        if let Some(url) = &location.url {
+            out_s.push_str(&format!("<link href=\"{}\"/>\n", sanitize_xml(url)));
+        }
+        out_s.push_str(&format!("<type>{}</type>\n", location.source.as_str()));
+        out_s.push_str("</wpt>\n");
+        Some(out_s)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::locations::{Location, Source},
+        Exporter, Locations,
+    };
+
+    use serde_json::json;
+
+    fn fake_location() -> Location {
+        Location {
+            source: Source::Placemark,
+            guid: "ABC-123".to_string(),
+            date: Some("2022-05-17T17:29:42-07:00".to_string()),
+            sender: "Sample Contact".to_string(),
+            chat_id: Some(2),
+            chat: Some("Trip & Friends".to_string()),
+            name: Some("Cherry Cove".to_string()),
+            description: None,
+            url: Some(
+                "https://maps.apple.com/?ll=33.450858,-118.508212&q=Cherry%20Cove".to_string(),
+            ),
+            details: vec![("city", "Avalon".to_string())],
+            coordinates: Some((33.450858, -118.508212)),
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Geojson);
+        let config = fake_config(options);
+        let exporter = Locations::new(&config).unwrap();
+        assert_eq!(exporter.written, 0);
+    }
+
+    #[test]
+    fn can_parse_coordinates() {
+        assert_eq!(
+            Locations::coordinates("https://maps.apple.com/?address=Cherry%20Cove&ll=33.450858,-118.508212&q=Cherry%20Cove&t=m"),
+            Some((33.450858, -118.508212))
+        );
+    }
+
+    #[test]
+    fn can_parse_encoded_coordinates() {
+        assert_eq!(
+            Locations::coordinates("https://maps.apple.com/?q=33.450858%2C-118.508212"),
+            Some((33.450858, -118.508212))
+        );
+    }
+
+    #[test]
+    fn cant_parse_place_name_as_coordinates() {
+        assert_eq!(
+            Locations::coordinates("https://maps.apple.com/?q=Cherry%20Cove,%20Avalon"),
+            None
+        );
+    }
+
+    #[test]
+    fn cant_parse_invalid_coordinates() {
+        assert_eq!(
+            Locations::coordinates("https://maps.apple.com/?ll=133.4,-218.5"),
+            None
+        );
+    }
+
+    #[test]
+    fn cant_parse_coordinates_from_query_string() {
+        assert_eq!(
+            Locations::coordinates(
+                "?FindMyMessagePayloadVersionKey=v0&FindMyMessagePayloadZippedDataKey=FAKEDATA"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn can_get_started_sharing_location() {
+        let options = fake_options_for(ExportType::Geojson);
+        let config = fake_config(options);
+        let exporter = Locations::new(&config).unwrap();
+
+        let mut message = blank();
+        message.item_type = 4;
+
+        let location = exporter.location(&message).unwrap();
+        assert_eq!(location.source, Source::SharedLocation);
+        assert_eq!(
+            location.description.as_deref(),
+            Some("Started sharing location")
+        );
+        assert_eq!(location.coordinates, None);
+    }
+
+    #[test]
+    fn can_get_stopped_sharing_location() {
+        let options = fake_options_for(ExportType::Geojson);
+        let config = fake_config(options);
+        let exporter = Locations::new(&config).unwrap();
+
+        let mut message = blank();
+        message.item_type = 4;
+        message.share_status = true;
+
+        let location = exporter.location(&message).unwrap();
+        assert_eq!(
+            location.description.as_deref(),
+            Some("Stopped sharing location")
+        );
+    }
+
+    #[test]
+    fn cant_get_location_from_normal_message() {
+        let options = fake_options_for(ExportType::Geojson);
+        let config = fake_config(options);
+        let exporter = Locations::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("Meet at the cove".to_string());
+
+        assert!(exporter.location(&message).is_none());
+    }
+
+    #[test]
+    fn can_format_geojson() {
+        let actual = Locations::format_geojson(&fake_location());
+        let expected = json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [-118.508212, 33.450858],
+            },
+            "properties": {
+                "source": "placemark",
+                "guid": "ABC-123",
+                "date": "2022-05-17T17:29:42-07:00",
+                "sender": "Sample Contact",
+                "chat_id": 2,
+                "chat": "Trip & Friends",
+                "name": "Cherry Cove",
+                "url": "https://maps.apple.com/?ll=33.450858,-118.508212&q=Cherry%20Cove",
+                "city": "Avalon",
+            },
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_geojson_without_coordinates() {
+        let mut location = fake_location();
+        location.coordinates = None;
+
+        let actual = Locations::format_geojson(&location);
+        assert_eq!(actual["geometry"], json!(null));
+    }
+
+    #[test]
+    fn can_separate_geojson_features() {
+        let options = fake_options_for(ExportType::Geojson);
+        let config = fake_config(options);
+        let mut exporter = Locations::new(&config).unwrap();
+
+        let first = exporter.format_location(&fake_location()).unwrap();
+        assert!(first.starts_with('{'));
+
+        exporter.written = 1;
+        let second = exporter.format_location(&fake_location()).unwrap();
+        assert!(second.starts_with(",\n{"));
+    }
+
+    #[test]
+    fn can_format_kml() {
+        let actual = Locations::format_kml(&fake_location());
+        let expected = [
+            "<Placemark>",
+            "<name>Cherry Cove</name>",
+            "<TimeStamp><when>2022-05-17T17:29:42-07:00</when></TimeStamp>",
+            "<ExtendedData>",
+            "<Data name=\"source\"><value>placemark</value></Data>",
+            "<Data name=\"guid\"><value>ABC-123</value></Data>",
+            "<Data name=\"date\"><value>2022-05-17T17:29:42-07:00</value></Data>",
+            "<Data name=\"sender\"><value>Sample Contact</value></Data>",
+            "<Data name=\"chat_id\"><value>2</value></Data>",
+            "<Data name=\"chat\"><value>Trip &amp; Friends</value></Data>",
+            "<Data name=\"name\"><value>Cherry Cove</value></Data>",
+            "<Data name=\"url\"><value>https://maps.apple.com/?ll=33.450858,-118.508212&amp;q=Cherry%20Cove</value></Data>",
+            "<Data name=\"city\"><value>Avalon</value></Data>",
+            "</ExtendedData>",
+            "<Point><coordinates>-118.508212,33.450858</coordinates></Point>",
+            "</Placemark>",
+            "",
+        ]
+        .join("\n");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_kml_without_coordinates() {
+        let mut location = fake_location();
+        location.coordinates = None;
+
+        let actual = Locations::format_kml(&location);
+        assert!(!actual.contains("<Point>"));
+    }
+
+    #[test]
+    fn can_format_gpx() {
+        let mut location = fake_location();
+        location.details = vec![];
+
+        let actual = Locations::format_gpx(&location).unwrap();
+        let expected = [
+            "<wpt lat=\"33.450858\" lon=\"-118.508212\">",
+            "<time>2022-05-17T17:29:42-07:00</time>",
+            "<name>Cherry Cove</name>",
+            "<cmt>source: placemark&#10;guid: ABC-123&#10;date: 2022-05-17T17:29:42-07:00&#10;sender: Sample Contact&#10;chat_id: 2&#10;chat: Trip &amp; Friends&#10;name: Cherry Cove&#10;url: https://maps.apple.com/?ll=33.450858,-118.508212&amp;q=Cherry%20Cove</cmt>",
+            "<src>Sample Contact</src>",
+            "<link href=\"https://maps.apple.com/?ll=33.450858,-118.508212&amp;q=Cherry%20Cove\"/>",
+            "<type>placemark</type>",
+            "</wpt>",
+            "",
+        ]
+        .join("\n");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cant_format_gpx_without_coordinates() {
+        let mut location = fake_location();
+        location.coordinates = None;
+
+        assert!(Locations::format_gpx(&location).is_none());
+    }
+}
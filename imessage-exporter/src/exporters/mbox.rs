@@ -0,0 +1,857 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::exporter::{Exporter, Writer},
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, URLOverride, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        handle::Handle,
+        messages::Message,
+        table::{Table, ME, ORPHANED, UNKNOWN, YOU},
+    },
+    util::plist::parse_plist,
+};
+
+/// Name of the file written when all conversations are exported together
+const COMBINED: &str = "messages";
+
+/// Domain used for addresses that are not already email addresses, i.e. phone numbers
+///
+/// The `.invalid` top level domain is reserved, so these addresses can never reach a real mailbox.
+const ADDRESS_DOMAIN: &str = "imessage.invalid";
+
+/// Local part of the database owner's address when their caller ID is not known
+const SELF_ADDRESS: &str = "me";
+
+/// Maximum length of a line of base64 encoded data, per RFC 2045
+const BASE64_LINE_LENGTH: usize = 76;
+
+/// Exports messages as RFC 5322 emails in `mboxrd` files
+///
+/// Each message becomes an email from its sender to everyone else in the conversation. Replies
+/// reference the message they reply to, so mail clients can thread them.
+pub struct MBOX<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages, or for every message when chats are combined
+    pub orphaned: BufWriter<File>,
+    /// Map of handle ID to the phone number or email address it represents
+    handles: HashMap<i32, String>,
+}
+
+impl<'a> Exporter<'a> for MBOX<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        if config.options.combine_chats {
+            orphaned.push(COMBINED);
+        } else {
+            orphaned.push(ORPHANED);
+        }
+        orphaned.set_extension("mbox");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        // The participant cache merges handles that belong to the same person, but addresses need the original values
+        let mut handles = HashMap::new();
+        let mut statement = Handle::get(&config.db).map_err(RuntimeError::DatabaseError)?;
+        let rows = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+        for row in rows {
+            let handle = Handle::extract(row).map_err(RuntimeError::DatabaseError)?;
+            handles.insert(handle.rowid, handle.id);
+        }
+
+        Ok(MBOX {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+            handles,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as mbox...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are listed in the body of the message they react to
+            if !msg.is_tapback() {
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                MBOX::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        if self.config.options.combine_chats {
+            return Ok(&mut self.orphaned);
+        }
+
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(self.config.filename(chatroom));
+                        path.set_extension("mbox");
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        Ok(entry.insert(BufWriter::new(file)))
+                    }
+                }
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Writer<'a> for MBOX<'a> {
+    fn format_message(&self, message: &Message, _: usize) -> Result<String, TableError> {
+        let date = message.date(&self.config.offset).ok();
+        let sender = self.sender(message);
+
+        let mut headers = vec![
+            format!("Message-ID: {}", MBOX::message_id(&message.guid)),
+            format!(
+                "Date: {}",
+                date.map(|date| date.to_rfc2822()).unwrap_or_default()
+            ),
+            format!(
+                "From: {}",
+                MBOX::format_mailbox(
+                    self.config.who(
+                        message.handle_id,
+                        message.is_from_me(),
+                        &message.destination_caller_id
+                    ),
+                    sender
+                )
+            ),
+        ];
+
+        let recipients = self.recipients(message, sender);
+        if !recipients.is_empty() {
+            headers.push(format!("To: {}", recipients.join(", ")));
+        }
+        headers.push(format!(
+            "Subject: {}",
+            MBOX::encode_header(&self.subject(message))
+        ));
+
+        // Replies point at the message they reply to so mail clients can thread them
+        if let Some(parent) = &message.thread_originator_guid {
+            let parent = MBOX::message_id(parent);
+            headers.push(format!("In-Reply-To: {parent}"));
+            headers.push(format!("References: {parent}"));
+        }
+        if let Some(service) = &message.service {
+            headers.push(format!(
+                "X-iMessage-Service: {}",
+                MBOX::encode_header(service)
+            ));
+        }
+        headers.push("MIME-Version: 1.0".to_string());
+
+        let body = self.body(message)?;
+
+        let mut attachments = if message.is_announcement() {
+            vec![]
+        } else {
+            Attachment::from_message(&self.config.db, message)?
+        };
+        let parts: Vec<String> = attachments
+            .iter_mut()
+            .filter_map(|attachment| self.format_attachment(attachment, message).ok())
+            .collect();
+
+        let mut out_s = format!(
+            "From {} {}\n",
+            MBOX::address(sender),
+            date.map(|date| date.format("%a %b %e %H:%M:%S %Y").to_string())
+                .unwrap_or_default()
+        );
+        headers.iter().for_each(|header| {
+            out_s.push_str(header);
+            out_s.push('\n');
+        });
+
+        if parts.is_empty() {
+            out_s.push_str("Content-Type: text/plain; charset=utf-8\n");
+            out_s.push_str("Content-Transfer-Encoding: 8bit\n\n");
+            out_s.push_str(&MBOX::escape_from(&body));
+        } else {
+            let boundary = format!("imessage-exporter-{}", message.guid);
+            out_s.push_str(&format!(
+                "Content-Type: multipart/mixed; boundary=\"{boundary}\"\n\n"
+            ));
+            out_s.push_str(&format!("--{boundary}\n"));
+            out_s.push_str("Content-Type: text/plain; charset=utf-8\n");
+            out_s.push_str("Content-Transfer-Encoding: 8bit\n\n");
+            out_s.push_str(&MBOX::escape_from(&body));
+            out_s.push('\n');
+            for part in parts {
+                out_s.push_str(&format!("--{boundary}\n"));
+                out_s.push_str(&part);
+            }
+            out_s.push_str(&format!("--{boundary}--\n"));
+        }
+
+        // Messages are separated by a blank line
+        out_s.push('\n');
+        Ok(out_s)
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        // Copy the file, if requested, so converted images are attached instead of the originals
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .ok_or(attachment.filename())?;
+
+        let bytes = self
+            .config
+            .message_attachment_bytes(attachment)
+            .ok_or(attachment.filename())?;
+
+        let name = attachment
+            .copied_path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or(attachment.filename());
+        // Converted files no longer match the MIME type stored in the database
+        let content_type = self.config.message_attachment_content_type(attachment);
+        let name = MBOX::encode_header(name);
+
+        Ok(format!(
+            "Content-Type: {content_type}; name=\"{name}\"\nContent-Transfer-Encoding: base64\nContent-Disposition: attachment; filename=\"{name}\"\n\n{}\n",
+            MBOX::wrap_base64(&STANDARD.encode(bytes))
+        ))
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        // Stickers are sent as regular image attachments
+        self.format_attachment(sticker, message).unwrap_or_default()
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        _: &mut Vec<Attachment>,
+        _: &str,
+    ) -> Result<String, PlistParseError> {
+        if let Variant::App(_) = message.variant() {
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                let parsed = parse_plist(&payload)?;
+                // URL messages are described by their link, everything else by its most specific caption
+                let description = if message.is_url() {
+                    match URLMessage::get_url_message_override(&parsed)? {
+                        URLOverride::Normal(balloon) => {
+                            balloon.get_url().or(balloon.title).map(str::to_string)
+                        }
+                        URLOverride::AppleMusic(balloon) => balloon.url.map(str::to_string),
+                        URLOverride::Collaboration(balloon) => {
+                            balloon.get_url().map(str::to_string)
+                        }
+                        URLOverride::AppStore(balloon) => balloon.url.map(str::to_string),
+                        URLOverride::SharedPlacemark(balloon) => {
+                            balloon.get_url().map(str::to_string)
+                        }
+                    }
+                } else {
+                    let balloon = AppMessage::from_map(&parsed)?;
+                    balloon
+                        .ldtext
+                        .or(balloon.title)
+                        .or(balloon.caption)
+                        .or(balloon.app_name)
+                        .map(str::to_string)
+                };
+                return Ok(description.or(message.text.clone()).unwrap_or_default());
+            }
+            // Sometimes, URL messages are missing their payloads
+            if message.is_url() {
+                if let Some(text) = &message.text {
+                    return Ok(text.to_string());
+                }
+            }
+            return Err(PlistParseError::NoPayload);
+        }
+        Err(PlistParseError::WrongMessageType)
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        let who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        match msg.variant() {
+            Variant::Tapback(_, added, tapback) => {
+                if !added {
+                    return Ok(String::new());
+                }
+                Ok(format!("{tapback} by {who}"))
+            }
+            Variant::Sticker(_) => Ok(format!("Sticker from {who}")),
+            _ => unreachable!(),
+        }
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+
+        match msg.get_announcement() {
+            Some(announcement) => match announcement {
+                Announcement::NameChange(name) => {
+                    format!("{who} renamed the conversation to {name}")
+                }
+                Announcement::PhotoChange => format!("{who} changed the group photo."),
+                Announcement::Unknown(num) => format!("{who} performed unknown action {num}."),
+                Announcement::FullyUnsent => format!("{who} unsent a message!"),
+            },
+            None => String::from("Unable to format announcement!"),
+        }
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "SharePlay Message Ended"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "Stopped sharing location!";
+        }
+        "Shared location!"
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        let edited_message_part = edited_message.part(message_part_idx)?;
+        match edited_message_part.status {
+            EditStatus::Edited => {
+                let mut out_s = String::new();
+                for (idx, event) in edited_message_part.edit_history.iter().enumerate() {
+                    if idx == 0 {
+                        out_s.push_str(&event.text);
+                    } else {
+                        out_s.push_str(&format!("\n[Edited] {}", event.text));
+                    }
+                }
+                Some(out_s)
+            }
+            EditStatus::Unsent => {
+                let who = if msg.is_from_me() {
+                    self.config.options.custom_name.as_deref().unwrap_or(YOU)
+                } else {
+                    "They"
+                };
+                Some(format!("{who} unsent this message part!"))
+            }
+            EditStatus::Original => None,
+        }
+    }
+
+    fn format_attributed(&'a self, msg: &'a str, _: &'a TextEffect) -> Cow<'a, str> {
+        // Plain text emails have no way to represent formatted text
+        Cow::Borrowed(msg)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> MBOX<'a> {
+    /// Build a globally unique `Message-ID` from a message's GUID
+    fn message_id(guid: &str) -> String {
+        format!("<{guid}@{ADDRESS_DOMAIN}>")
+    }
+
+    /// Get the phone number or email address that sent a message
+    fn sender<'b>(&'b self, message: &'b Message) -> &'b str {
+        if message.is_from_me() {
+            return message
+                .destination_caller_id
+                .as_deref()
+                .unwrap_or(SELF_ADDRESS);
+        }
+        message
+            .handle_id
+            .and_then(|handle_id| self.handles.get(&handle_id))
+            .map(String::as_str)
+            .unwrap_or(UNKNOWN)
+    }
+
+    /// Build an email address for a handle, since phone numbers are not valid addresses on their own
+    fn address(handle: &str) -> String {
+        if handle.contains('@') {
+            return handle.to_string();
+        }
+        // Spaces and other characters are not allowed in an unquoted local part
+        let local: String = handle
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '_'))
+            .collect();
+        if local.is_empty() {
+            return format!("{SELF_ADDRESS}@{ADDRESS_DOMAIN}");
+        }
+        format!("{local}@{ADDRESS_DOMAIN}")
+    }
+
+    /// Build a mailbox from a display name and a handle
+    fn format_mailbox(name: &str, handle: &str) -> String {
+        let address = MBOX::address(handle);
+        if name.is_empty() || name == handle {
+            return format!("<{address}>");
+        }
+        if name.is_ascii() {
+            return format!(
+                "\"{}\" <{address}>",
+                name.replace('\\', "\\\\").replace('"', "\\\"")
+            );
+        }
+        format!("{} <{address}>", MBOX::encode_header(name))
+    }
+
+    /// Get everyone who received a message, including the database owner if they did not send it
+    fn recipients(&self, message: &Message, sender: &str) -> Vec<String> {
+        let mut recipients: Vec<String> = message
+            .chat_id
+            .and_then(|chat_id| self.config.chatroom_participants.get(&chat_id))
+            .map(|participants| {
+                participants
+                    .iter()
+                    .filter_map(|handle_id| {
+                        self.handles
+                            .get(handle_id)
+                            .filter(|handle| handle.as_str() != sender)
+                            .map(|handle| {
+                                MBOX::format_mailbox(
+                                    self.config.who(Some(*handle_id), false, &None),
+                                    handle,
+                                )
+                            })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Received messages were also sent to the database owner
+        if !message.is_from_me() {
+            recipients.push(MBOX::format_mailbox(
+                self.config.who(None, true, &message.destination_caller_id),
+                message
+                    .destination_caller_id
+                    .as_deref()
+                    .unwrap_or(SELF_ADDRESS),
+            ));
+        }
+        recipients
+    }
+
+    /// Get the subject of a message, falling back to the name of its conversation so mail clients group them
+    fn subject(&self, message: &Message) -> String {
+        if let Some(subject) = &message.subject {
+            return subject.to_string();
+        }
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => match chatroom.display_name() {
+                Some(name) => name.to_string(),
+                None => format!("Conversation with {}", self.config.filename(chatroom)),
+            },
+            None => {
+                // Without a chat, name the conversation after the other people in it
+                let mut names: Vec<&str> = message
+                    .chat_id
+                    .and_then(|chat_id| self.config.chatroom_participants.get(&chat_id))
+                    .map(|participants| {
+                        participants
+                            .iter()
+                            .map(|handle_id| self.config.who(Some(*handle_id), false, &None))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                if names.is_empty() && !message.is_from_me() {
+                    names.push(self.config.who(message.handle_id, false, &None));
+                }
+                if names.is_empty() {
+                    return String::from("Conversation");
+                }
+                format!("Conversation with {}", names.join(", "))
+            }
+        }
+    }
+
+    /// Build the plain text body of a message, including its reactions
+    fn body(&self, message: &Message) -> Result<String, TableError> {
+        if message.is_announcement() {
+            return Ok(self.format_announcement(message));
+        }
+
+        let mut body = if message.is_shareplay() {
+            self.format_shareplay().to_string()
+        } else if message.started_sharing_location() || message.stopped_sharing_location() {
+            self.format_shared_location(message).to_string()
+        } else if let Some(edited_parts) = message
+            .edited_parts
+            .as_ref()
+            .filter(|_| message.is_edited())
+        {
+            (0..edited_parts.parts.len())
+                .filter_map(|idx| self.format_edited(message, edited_parts, idx, ""))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else if matches!(message.variant(), Variant::App(_)) {
+            self.format_app(message, &mut vec![], "")
+                .unwrap_or_else(|_| message.text.clone().unwrap_or_default())
+        } else {
+            message
+                .text
+                .as_deref()
+                .unwrap_or_default()
+                // Attachments are exported as their own parts, so drop their placeholders
+                .replace('\u{FFFC}', "")
+                .trim()
+                .to_string()
+        };
+
+        if message.is_deleted() {
+            body.push_str("\n\nThis message was deleted from the conversation!");
+        }
+
+        let expressive = self.format_expressive(message);
+        if !expressive.is_empty() {
+            body.push_str(&format!("\n\n({expressive})"));
+        }
+
+        // Tapbacks have no equivalent in email, so list them after the text
+        if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+            let mut parts: Vec<_> = tapbacks_map.iter().collect();
+            parts.sort_by_key(|(idx, _)| **idx);
+
+            let mut tapbacks = vec![];
+            for (_, reactions) in parts {
+                for tapback in reactions {
+                    let formatted = self.format_tapback(tapback)?;
+                    if !formatted.is_empty() {
+                        tapbacks.push(formatted);
+                    }
+                }
+            }
+            if !tapbacks.is_empty() {
+                body.push_str("\n\nTapbacks:");
+                tapbacks.iter().for_each(|tapback| {
+                    body.push_str("\n- ");
+                    body.push_str(tapback);
+                });
+            }
+        }
+
+        body.push('\n');
+        Ok(body)
+    }
+
+    /// Encode a header value as an RFC 2047 encoded word if it cannot be written as plain ASCII
+    fn encode_header(value: &str) -> Cow<'_, str> {
+        if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+            return Cow::Borrowed(value);
+        }
+        let clean: String = value.chars().filter(|c| !c.is_control()).collect();
+        Cow::Owned(format!("=?UTF-8?B?{}?=", STANDARD.encode(clean)))
+    }
+
+    /// Quote lines that look like mbox separators, per the `mboxrd` format
+    fn escape_from(body: &str) -> Cow<'_, str> {
+        let needs_escape = |line: &str| line.trim_start_matches('>').starts_with("From ");
+        if !body.lines().any(needs_escape) {
+            return Cow::Borrowed(body);
+        }
+
+        let mut out_s = String::with_capacity(body.len() + 1);
+        for line in body.split_inclusive('\n') {
+            if needs_escape(line) {
+                out_s.push('>');
+            }
+            out_s.push_str(line);
+        }
+        Cow::Owned(out_s)
+    }
+
+    /// Split base64 data into lines of the maximum allowed length
+    fn wrap_base64(data: &str) -> String {
+        data.as_bytes()
+            .chunks(BASE64_LINE_LENGTH)
+            // Base64 output is always ASCII
+            .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, env::set_var};
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::Writer,
+        Exporter, MBOX,
+    };
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Mbox);
+        let config = fake_config(options);
+        let exporter = MBOX::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_format_mbox_from_me() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Mbox);
+        let mut config = fake_config(options);
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([999999]));
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let mut exporter = MBOX::new(&config).unwrap();
+        exporter.handles.insert(999999, "+15558675309".to_string());
+
+        let mut message = blank();
+        message.guid = "ABC-123".to_string();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(1);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "From me@imessage.invalid Tue May 17 17:29:42 2022\nMessage-ID: <ABC-123@imessage.invalid>\nDate: Tue, 17 May 2022 17:29:42 -0700\nFrom: \"Me\" <me@imessage.invalid>\nTo: \"Sample Contact\" <+15558675309@imessage.invalid>\nSubject: Conversation with Sample Contact\nX-iMessage-Service: iMessage\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_mbox_reply_from_them() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Mbox);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let mut exporter = MBOX::new(&config).unwrap();
+        exporter
+            .handles
+            .insert(999999, "person@example.com".to_string());
+
+        let mut message = blank();
+        message.guid = "DEF-456".to_string();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("From here on\nout".to_string());
+        message.handle_id = Some(999999);
+        message.subject = Some("Plans".to_string());
+        message.thread_originator_guid = Some("ABC-123".to_string());
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "From person@example.com Tue May 17 17:29:42 2022\nMessage-ID: <DEF-456@imessage.invalid>\nDate: Tue, 17 May 2022 17:29:42 -0700\nFrom: \"Sample Contact\" <person@example.com>\nTo: \"Me\" <me@imessage.invalid>\nSubject: Plans\nIn-Reply-To: <ABC-123@imessage.invalid>\nReferences: <ABC-123@imessage.invalid>\nX-iMessage-Service: iMessage\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n>From here on\nout\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_address() {
+        assert_eq!(
+            MBOX::address("+1 (555) 867-5309"),
+            "+15558675309@imessage.invalid"
+        );
+        assert_eq!(MBOX::address("person@example.com"), "person@example.com");
+        assert_eq!(MBOX::address(""), "me@imessage.invalid");
+    }
+
+    #[test]
+    fn can_format_mailbox() {
+        assert_eq!(
+            MBOX::format_mailbox("Sample \"Nick\" Contact", "+15558675309"),
+            "\"Sample \\\"Nick\\\" Contact\" <+15558675309@imessage.invalid>"
+        );
+        assert_eq!(
+            MBOX::format_mailbox("Zoë", "zoe@example.com"),
+            "=?UTF-8?B?Wm/Dqw==?= <zoe@example.com>"
+        );
+        assert_eq!(
+            MBOX::format_mailbox("+15558675309", "+15558675309"),
+            "<+15558675309@imessage.invalid>"
+        );
+    }
+
+    #[test]
+    fn can_encode_header() {
+        assert_eq!(MBOX::encode_header("Plain subject"), "Plain subject");
+        assert_eq!(MBOX::encode_header("🎉"), "=?UTF-8?B?8J+OiQ==?=");
+        assert_eq!(
+            MBOX::encode_header("Line\nbreak"),
+            "=?UTF-8?B?TGluZWJyZWFr?="
+        );
+    }
+
+    #[test]
+    fn can_escape_from_lines() {
+        assert_eq!(MBOX::escape_from("Hello\nworld\n"), "Hello\nworld\n");
+        assert_eq!(
+            MBOX::escape_from("From me\n>From you\nFrom\n"),
+            ">From me\n>>From you\nFrom\n"
+        );
+    }
+
+    #[test]
+    fn can_wrap_base64() {
+        let data = "A".repeat(160);
+        let wrapped = MBOX::wrap_base64(&data);
+        let lines: Vec<&str> = wrapped.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 76);
+        assert_eq!(lines[1].len(), 76);
+        assert_eq!(lines[2].len(), 8);
+    }
+}
@@ -0,0 +1,1760 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    app::{
+        attachment_manager::AttachmentManager,
+        error::RuntimeError,
+        progress::build_progress_bar_export,
+        runtime::Config,
+        sanitizers::{sanitize_markdown, sanitize_markdown_lines},
+    },
+    exporters::exporter::{BalloonFormatter, Exporter, TextEffectFormatter, Writer},
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        app_store::AppStoreMessage,
+        collaboration::CollaborationMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        handwriting::HandwrittenMessage,
+        music::MusicMessage,
+        placemark::PlacemarkMessage,
+        text_effects::{Animation, Style, TextEffect, Unit},
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::{models::BubbleComponent, Message},
+        table::{Table, FITNESS_RECEIVER, ME, ORPHANED, YOU},
+    },
+    util::{
+        dates::{format, get_local_time, readable_diff, TIMESTAMP_FACTOR},
+        plist::parse_plist,
+    },
+};
+
+pub struct MD<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+}
+
+impl<'a> Exporter<'a> for MD<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("md");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        Ok(MD {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as md...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Render the announcement in-line
+            if msg.is_announcement() {
+                let announcement = self.format_announcement(&msg);
+                MD::write_to_file(self.get_or_create_file(&msg)?, &announcement)?;
+            }
+            // Message replies and tapbacks are rendered in context, so no need to render them separately
+            else if !msg.is_tapback() {
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                MD::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(self.config.filename(chatroom));
+                        path.set_extension("md");
+
+                        let file = File::options()
+                            .append(true)
+                            .create(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        Ok(entry.insert(BufWriter::new(file)))
+                    }
+                }
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Writer<'a> for MD<'a> {
+    fn format_message(&self, message: &Message, indent_size: usize) -> Result<String, TableError> {
+        // Data we want to write to a file
+        let mut formatted_message = String::new();
+
+        // Add message sender and date
+        self.add_line(
+            &mut formatted_message,
+            &format!(
+                "**{}** · {}",
+                sanitize_markdown(self.config.who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )),
+                self.get_time(message)
+            ),
+        );
+
+        // If message was deleted, annotate it
+        if message.is_deleted() {
+            self.add_line(
+                &mut formatted_message,
+                "*This message was deleted from the conversation!*",
+            );
+        }
+
+        // Useful message metadata
+        let message_parts = message.body();
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut replies = message.get_replies(&self.config.db)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        // Render subject
+        if let Some(subject) = &message.subject {
+            self.add_line(
+                &mut formatted_message,
+                &format!("**{}**", sanitize_markdown(subject)),
+            );
+        }
+
+        // Handle SharePlay
+        if message.is_shareplay() {
+            self.add_line(&mut formatted_message, self.format_shareplay());
+        }
+
+        // Handle Shared Location
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            self.add_line(&mut formatted_message, self.format_shared_location(message));
+        }
+
+        // Generate the message body from it's components
+        for (idx, message_part) in message_parts.iter().enumerate() {
+            match message_part {
+                // Fitness messages have a prefix that we need to replace with the opposite if who sent the message
+                BubbleComponent::Text(text_attrs) => {
+                    if let Some(text) = &message.text {
+                        // Render edited message content, if applicable
+                        if message.is_part_edited(idx) {
+                            if let Some(edited_parts) = &message.edited_parts {
+                                if let Some(edited) =
+                                    self.format_edited(message, edited_parts, idx, "")
+                                {
+                                    self.add_line(&mut formatted_message, &edited);
+                                };
+                            }
+                        } else {
+                            let mut formatted_text = String::with_capacity(text.len());
+
+                            for text_attr in text_attrs {
+                                if let Some(message_content) =
+                                    text.get(text_attr.start..text_attr.end)
+                                {
+                                    formatted_text.push_str(
+                                        &self.format_attributed(message_content, &text_attr.effect),
+                                    )
+                                }
+                            }
+
+                            // If we failed to parse any text above, use the original text
+                            if formatted_text.is_empty() {
+                                formatted_text.push_str(&sanitize_markdown(text));
+                            }
+
+                            if formatted_text.starts_with(FITNESS_RECEIVER) {
+                                self.add_line(
+                                    &mut formatted_message,
+                                    &sanitize_markdown_lines(
+                                        &formatted_text.replace(FITNESS_RECEIVER, YOU),
+                                    ),
+                                );
+                            } else {
+                                self.add_line(
+                                    &mut formatted_message,
+                                    &sanitize_markdown_lines(&formatted_text),
+                                );
+                            }
+                        }
+                    }
+                }
+                BubbleComponent::Attachment(_) => match attachments.get_mut(attachment_index) {
+                    Some(attachment) => {
+                        if attachment.is_sticker {
+                            let result = self.format_sticker(attachment, message);
+                            self.add_line(&mut formatted_message, &result);
+                        } else {
+                            match self.format_attachment(attachment, message) {
+                                Ok(result) => {
+                                    attachment_index += 1;
+                                    self.add_line(&mut formatted_message, &result);
+                                }
+                                Err(result) => {
+                                    self.add_line(
+                                        &mut formatted_message,
+                                        &sanitize_markdown(result),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                    // Attachment does not exist in attachments table
+                    None => self.add_line(&mut formatted_message, "*Attachment missing!*"),
+                },
+                BubbleComponent::App => match self.format_app(message, &mut attachments, "") {
+                    Ok(ok_bubble) => self.add_line(&mut formatted_message, &ok_bubble),
+                    Err(why) => self.add_line(
+                        &mut formatted_message,
+                        &format!("*Unable to format app message: {why}*"),
+                    ),
+                },
+                BubbleComponent::Retracted => {
+                    if let Some(edited_parts) = &message.edited_parts {
+                        if let Some(edited) = self.format_edited(message, edited_parts, idx, "") {
+                            self.add_line(&mut formatted_message, &edited);
+                        };
+                    }
+                }
+            };
+
+            // Handle expressives
+            if message.expressive_send_style_id.is_some() {
+                let expressive = self.format_expressive(message);
+                if !expressive.is_empty() {
+                    self.add_line(&mut formatted_message, &format!("*{expressive}*"));
+                }
+            }
+
+            // Handle Tapbacks
+            if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+                if let Some(tapbacks) = tapbacks_map.get(&idx) {
+                    let mut formatted_tapbacks = String::new();
+                    tapbacks
+                        .iter()
+                        .try_for_each(|tapback| -> Result<(), TableError> {
+                            let formatted = self.format_tapback(tapback)?;
+                            if !formatted.is_empty() {
+                                formatted_tapbacks.push_str("- ");
+                                formatted_tapbacks.push_str(&formatted);
+                                formatted_tapbacks.push('\n');
+                            }
+                            Ok(())
+                        })?;
+
+                    if !formatted_tapbacks.is_empty() {
+                        self.add_line(&mut formatted_message, "Tapbacks:");
+                        self.add_line(&mut formatted_message, formatted_tapbacks.trim_end());
+                    }
+                }
+            }
+
+            // Handle Replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                replies
+                    .iter_mut()
+                    .try_for_each(|reply| -> Result<(), TableError> {
+                        let _ = reply.generate_text(&self.config.db);
+                        if !reply.is_tapback() {
+                            self.add_line(
+                                &mut formatted_message,
+                                &self.quote(&self.format_message(reply, indent_size + 1)?),
+                            );
+                        }
+                        Ok(())
+                    })?;
+            }
+        }
+
+        // Add a note if the message is a reply
+        if message.is_reply() && indent_size == 0 {
+            self.add_line(
+                &mut formatted_message,
+                "*This message responded to an earlier message.*",
+            );
+        }
+
+        Ok(formatted_message)
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        // Copy the file, if requested
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .ok_or(attachment.filename())?;
+
+        // Build a relative filepath from the fully qualified one on the `Attachment`
+        let embed_path = self.config.message_attachment_path(attachment);
+        let name = sanitize_markdown(attachment.filename());
+
+        Ok(match attachment.mime_type() {
+            MediaType::Image(_) => format!("![{name}]({})", MD::link_destination(&embed_path)),
+            _ => format!("[{name}]({})", MD::link_destination(&embed_path)),
+        })
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        let who = sanitize_markdown(self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        ));
+        match self.format_attachment(sticker, message) {
+            Ok(_) => {
+                let path_to_sticker = self.config.message_attachment_path(sticker);
+                let sticker_effect = sticker.get_sticker_effect(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                );
+                let destination = MD::link_destination(&path_to_sticker);
+                if let Ok(Some(sticker_effect)) = sticker_effect {
+                    return format!("![{sticker_effect} Sticker from {who}]({destination})");
+                }
+                format!("![Sticker from {who}]({destination})")
+            }
+            Err(path) => format!("Sticker from {who}: {}", sanitize_markdown(path)),
+        }
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        attachments: &mut Vec<Attachment>,
+        indent: &str,
+    ) -> Result<String, PlistParseError> {
+        if let Variant::App(balloon) = message.variant() {
+            let mut app_bubble = String::new();
+
+            // Handwritten messages use a different payload type, so check that first
+            if message.is_handwriting() {
+                if let Some(payload) = message.raw_payload_data(&self.config.db) {
+                    return match HandwrittenMessage::from_payload(&payload) {
+                        Ok(bubble) => Ok(self.format_handwriting(message, &bubble, indent)),
+                        Err(why) => Err(PlistParseError::HandwritingError(why)),
+                    };
+                }
+            }
+
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                // Handle URL messages separately since they are a special case
+                let res = if message.is_url() {
+                    let parsed = parse_plist(&payload)?;
+                    let bubble = URLMessage::get_url_message_override(&parsed)?;
+                    match bubble {
+                        URLOverride::Normal(balloon) => self.format_url(message, &balloon, indent),
+                        URLOverride::AppleMusic(balloon) => self.format_music(&balloon, indent),
+                        URLOverride::Collaboration(balloon) => {
+                            self.format_collaboration(&balloon, indent)
+                        }
+                        URLOverride::AppStore(balloon) => self.format_app_store(&balloon, indent),
+                        URLOverride::SharedPlacemark(balloon) => {
+                            self.format_placemark(&balloon, indent)
+                        }
+                    }
+                // Handwriting uses a different payload type than the rest of the branches
+                } else {
+                    // Handle the app case
+                    let parsed = parse_plist(&payload)?;
+                    match AppMessage::from_map(&parsed) {
+                        Ok(bubble) => match balloon {
+                            CustomBalloon::Application(bundle_id) => {
+                                self.format_generic_app(&bubble, bundle_id, attachments, indent)
+                            }
+                            CustomBalloon::ApplePay => self.format_apple_pay(&bubble, indent),
+                            CustomBalloon::Fitness => self.format_fitness(&bubble, indent),
+                            CustomBalloon::Slideshow => self.format_slideshow(&bubble, indent),
+                            CustomBalloon::CheckIn => self.format_check_in(&bubble, indent),
+                            CustomBalloon::FindMy => self.format_find_my(&bubble, indent),
+                            CustomBalloon::Handwriting => unreachable!(),
+                            CustomBalloon::URL => unreachable!(),
+                        },
+                        Err(why) => return Err(why),
+                    }
+                };
+                app_bubble.push_str(&res);
+            } else {
+                // Sometimes, URL messages are missing their payloads
+                if message.is_url() {
+                    if let Some(text) = &message.text {
+                        return Ok(sanitize_markdown(text).to_string());
+                    }
+                }
+                return Err(PlistParseError::NoPayload);
+            };
+            Ok(app_bubble)
+        } else {
+            Err(PlistParseError::WrongMessageType)
+        }
+    }
+
+    fn format_tapback(&self, msg: &Message) -> Result<String, TableError> {
+        match msg.variant() {
+            Variant::Tapback(_, added, tapback) => {
+                if !added {
+                    return Ok(String::new());
+                }
+                Ok(format!(
+                    "{} by {}",
+                    tapback,
+                    sanitize_markdown(self.config.who(
+                        msg.handle_id,
+                        msg.is_from_me(),
+                        &msg.destination_caller_id
+                    )),
+                ))
+            }
+            Variant::Sticker(_) => {
+                let mut paths = Attachment::from_message(&self.config.db, msg)?;
+                // Sticker messages have only one attachment, the sticker image
+                Ok(if let Some(sticker) = paths.get_mut(0) {
+                    self.format_sticker(sticker, msg)
+                } else {
+                    let who = self.config.who(
+                        msg.handle_id,
+                        msg.is_from_me(),
+                        &msg.destination_caller_id,
+                    );
+                    format!("Sticker from {} not found!", sanitize_markdown(who))
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, msg: &'a Message) -> String {
+        let mut who = self
+            .config
+            .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
+        // Rename yourself so we render the proper grammar here
+        if who == ME {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+        let who = sanitize_markdown(who);
+
+        let timestamp = format(&msg.date(&self.config.offset));
+
+        match msg.get_announcement() {
+            Some(announcement) => match announcement {
+                Announcement::NameChange(name) => {
+                    let name = sanitize_markdown(name);
+                    format!("*{timestamp} {who} renamed the conversation to {name}*\n\n")
+                }
+                Announcement::PhotoChange => {
+                    format!("*{timestamp} {who} changed the group photo.*\n\n")
+                }
+                Announcement::Unknown(num) => {
+                    format!("*{timestamp} {who} performed unknown action {num}.*\n\n")
+                }
+                Announcement::FullyUnsent => format!("*{timestamp} {who} unsent a message!*\n\n"),
+            },
+            None => String::from("*Unable to format announcement!*\n\n"),
+        }
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "*SharePlay Message Ended*"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "*Started sharing location!*";
+        } else if msg.stopped_sharing_location() {
+            return "*Stopped sharing location!*";
+        }
+        "*Shared location!*"
+    }
+
+    fn format_edited(
+        &self,
+        msg: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        if let Some(edited_message_part) = edited_message.part(message_part_idx) {
+            let mut out_s = String::new();
+            let mut previous_timestamp: Option<&i64> = None;
+
+            match edited_message_part.status {
+                EditStatus::Edited => {
+                    // Each version of the message is a list item, oldest first
+                    for event in &edited_message_part.edit_history {
+                        out_s.push_str("- ");
+                        match previous_timestamp {
+                            // Original message get an absolute timestamp
+                            None => {
+                                let parsed_timestamp =
+                                    format(&get_local_time(&event.date, &self.config.offset));
+                                out_s.push_str(&parsed_timestamp);
+                                out_s.push_str(": ");
+                            }
+                            // Subsequent edits get a relative timestamp
+                            Some(prev_timestamp) => {
+                                let end = get_local_time(&event.date, &self.config.offset);
+                                let start = get_local_time(prev_timestamp, &self.config.offset);
+                                if let Some(diff) = readable_diff(start, end) {
+                                    out_s.push_str("Edited ");
+                                    out_s.push_str(&diff);
+                                    out_s.push_str(" later: ");
+                                }
+                            }
+                        };
+
+                        // Update the previous timestamp for the next loop
+                        previous_timestamp = Some(&event.date);
+
+                        // Render the message text
+                        out_s.push_str(&sanitize_markdown_lines(&sanitize_markdown(&event.text)));
+                        out_s.push('\n');
+                    }
+                }
+                EditStatus::Unsent => {
+                    let who = if msg.is_from_me() {
+                        self.config.options.custom_name.as_deref().unwrap_or(YOU)
+                    } else {
+                        "They"
+                    };
+
+                    out_s.push('*');
+                    out_s.push_str(&sanitize_markdown(who));
+                    match readable_diff(
+                        msg.date(&self.config.offset),
+                        msg.date_edited(&self.config.offset),
+                    ) {
+                        Some(diff) => {
+                            out_s.push_str(" unsent this message part ");
+                            out_s.push_str(&diff);
+                            out_s.push_str(" after sending!");
+                        }
+                        None => {
+                            out_s.push_str(" unsent this message part!");
+                        }
+                    }
+                    out_s.push('*');
+                }
+                EditStatus::Original => {
+                    return None;
+                }
+            }
+
+            return Some(out_s.trim_end().to_string());
+        }
+        None
+    }
+
+    fn format_attributed(&'a self, msg: &'a str, attribute: &'a TextEffect) -> Cow<'a, str> {
+        match attribute {
+            TextEffect::Default => sanitize_markdown(msg),
+            TextEffect::Mention(mentioned) => {
+                Cow::Owned(self.format_mention(&sanitize_markdown(msg), mentioned))
+            }
+            TextEffect::Link(url) => Cow::Owned(self.format_link(&sanitize_markdown(msg), url)),
+            // Code spans render their contents literally, so this text is not escaped
+            TextEffect::OTP => Cow::Owned(self.format_otp(msg)),
+            TextEffect::Styles(styles) => {
+                Cow::Owned(self.format_styles(&sanitize_markdown(msg), styles))
+            }
+            TextEffect::Animated(animation) => {
+                Cow::Owned(self.format_animated(&sanitize_markdown(msg), animation))
+            }
+            TextEffect::Conversion(unit) => {
+                Cow::Owned(self.format_conversion(&sanitize_markdown(msg), unit))
+            }
+        }
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> BalloonFormatter<&'a str> for MD<'a> {
+    fn format_url(&self, msg: &Message, balloon: &URLMessage, _: &str) -> String {
+        let mut out_s = String::new();
+
+        match balloon.get_url() {
+            Some(url) => {
+                let title = sanitize_markdown(balloon.title.unwrap_or(url));
+                self.push_line(&mut out_s, &self.format_link(&title, url));
+            }
+            None => {
+                if let Some(text) = &msg.text {
+                    self.push_line(&mut out_s, &sanitize_markdown(text));
+                }
+                if let Some(title) = balloon.title {
+                    self.push_line(&mut out_s, &sanitize_markdown(title));
+                }
+            }
+        }
+
+        if let Some(summary) = balloon.summary {
+            self.push_line(&mut out_s, &sanitize_markdown(summary));
+        }
+
+        out_s
+    }
+
+    fn format_music(&self, balloon: &MusicMessage, _: &str) -> String {
+        let mut out_s = String::new();
+
+        match (balloon.track_name, balloon.url) {
+            (Some(track_name), Some(url)) => {
+                self.push_line(
+                    &mut out_s,
+                    &self.format_link(&sanitize_markdown(track_name), url),
+                );
+            }
+            (Some(track_name), None) => self.push_line(&mut out_s, &sanitize_markdown(track_name)),
+            (None, Some(url)) => self.push_line(&mut out_s, &self.format_link(url, url)),
+            (None, None) => {}
+        }
+
+        if let Some(album) = balloon.album {
+            self.push_line(&mut out_s, &sanitize_markdown(album));
+        }
+
+        if let Some(artist) = balloon.artist {
+            self.push_line(&mut out_s, &sanitize_markdown(artist));
+        }
+
+        out_s
+    }
+
+    fn format_collaboration(&self, balloon: &CollaborationMessage, _: &str) -> String {
+        let mut out_s = String::new();
+
+        if let Some(name) = balloon.app_name.or(balloon.bundle_id) {
+            self.push_line(
+                &mut out_s,
+                &format!("**{}** message:", sanitize_markdown(name)),
+            );
+        }
+
+        match (balloon.title, balloon.get_url()) {
+            (Some(title), Some(url)) => {
+                self.push_line(
+                    &mut out_s,
+                    &self.format_link(&sanitize_markdown(title), url),
+                );
+            }
+            (Some(title), None) => self.push_line(&mut out_s, &sanitize_markdown(title)),
+            (None, Some(url)) => self.push_line(&mut out_s, &self.format_link(url, url)),
+            (None, None) => {}
+        }
+
+        out_s
+    }
+
+    fn format_app_store(&self, balloon: &AppStoreMessage, _: &'a str) -> String {
+        let mut out_s = String::new();
+
+        match (balloon.app_name, balloon.url) {
+            (Some(name), Some(url)) => {
+                self.push_line(&mut out_s, &self.format_link(&sanitize_markdown(name), url));
+            }
+            (Some(name), None) => self.push_line(&mut out_s, &sanitize_markdown(name)),
+            (None, Some(url)) => self.push_line(&mut out_s, &self.format_link(url, url)),
+            (None, None) => {}
+        }
+
+        if let Some(description) = balloon.description {
+            self.push_line(&mut out_s, &sanitize_markdown(description));
+        }
+
+        if let Some(platform) = balloon.platform {
+            self.push_line(&mut out_s, &sanitize_markdown(platform));
+        }
+
+        if let Some(genre) = balloon.genre {
+            self.push_line(&mut out_s, &sanitize_markdown(genre));
+        }
+
+        out_s
+    }
+
+    fn format_placemark(&self, balloon: &PlacemarkMessage, _: &'a str) -> String {
+        let mut out_s = String::new();
+
+        match (balloon.place_name, balloon.get_url()) {
+            (Some(name), Some(url)) => {
+                self.push_line(&mut out_s, &self.format_link(&sanitize_markdown(name), url));
+            }
+            (Some(name), None) => self.push_line(&mut out_s, &sanitize_markdown(name)),
+            (None, Some(url)) => self.push_line(&mut out_s, &self.format_link(url, url)),
+            (None, None) => {}
+        }
+
+        [
+            balloon.placemark.name,
+            balloon.placemark.address,
+            balloon.placemark.state,
+            balloon.placemark.city,
+            balloon.placemark.iso_country_code,
+            balloon.placemark.postal_code,
+            balloon.placemark.country,
+            balloon.placemark.street,
+            balloon.placemark.sub_administrative_area,
+            balloon.placemark.sub_locality,
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(|line| self.push_line(&mut out_s, &sanitize_markdown(line)));
+
+        out_s
+    }
+
+    fn format_handwriting(&self, msg: &Message, balloon: &HandwrittenMessage, _: &str) -> String {
+        // Without a file to link to, the drawing is rendered in a code block to preserve its whitespace
+        let ascii = || format!("```\n{}\n```", balloon.render_ascii(40).trim_end());
+        match self.config.options.attachment_manager {
+            AttachmentManager::Disabled => ascii(),
+            AttachmentManager::Compatible | AttachmentManager::Efficient => self
+                .config
+                .options
+                .attachment_manager
+                .handle_handwriting(msg, balloon, self.config)
+                .map(|filepath| {
+                    self.config
+                        .relative_path(PathBuf::from(&filepath))
+                        .unwrap_or(filepath.display().to_string())
+                })
+                .map(|filepath| {
+                    format!(
+                        "![Handwritten message]({})",
+                        MD::link_destination(&filepath)
+                    )
+                })
+                .unwrap_or_else(ascii),
+        }
+    }
+
+    fn format_apple_pay(&self, balloon: &AppMessage, _: &str) -> String {
+        let mut out_s = String::new();
+        if let Some(caption) = balloon.caption {
+            out_s.push_str(&sanitize_markdown(caption));
+            out_s.push_str(" transaction: ");
+        }
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(&format!("**{}**", sanitize_markdown(ldtext)));
+        } else {
+            out_s.push_str("unknown amount");
+        }
+
+        out_s
+    }
+
+    fn format_fitness(&self, balloon: &AppMessage, _: &str) -> String {
+        let mut out_s = String::new();
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str(&format!("**{}** message: ", sanitize_markdown(app_name)));
+        }
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(&sanitize_markdown(ldtext));
+        } else {
+            out_s.push_str("unknown workout");
+        }
+        out_s
+    }
+
+    fn format_slideshow(&self, balloon: &AppMessage, _: &str) -> String {
+        let mut out_s = String::new();
+        match (balloon.ldtext, balloon.url) {
+            (Some(ldtext), Some(url)) => {
+                out_s.push_str("Photo album: ");
+                out_s.push_str(&self.format_link(&sanitize_markdown(ldtext), url));
+            }
+            (Some(ldtext), None) => {
+                out_s.push_str("Photo album: ");
+                out_s.push_str(&sanitize_markdown(ldtext));
+            }
+            (None, Some(url)) => out_s.push_str(&self.format_link("Photo album", url)),
+            (None, None) => {}
+        }
+
+        out_s
+    }
+
+    fn format_find_my(&self, balloon: &AppMessage, _: &'a str) -> String {
+        let mut out_s = String::new();
+        if let Some(app_name) = balloon.app_name {
+            out_s.push_str(&format!("**{}**: ", sanitize_markdown(app_name)));
+        }
+
+        if let Some(ldtext) = balloon.ldtext {
+            out_s.push_str(&sanitize_markdown(ldtext));
+        }
+
+        out_s
+    }
+
+    fn format_check_in(&self, balloon: &AppMessage, _: &'a str) -> String {
+        let mut out_s = String::new();
+
+        self.push_line(
+            &mut out_s,
+            &format!(
+                "**{}**",
+                sanitize_markdown(balloon.caption.unwrap_or("Check In"))
+            ),
+        );
+
+        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+
+        // Before manual check-in
+        if let Some(date_str) = metadata.get("estimatedEndTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            self.push_line(&mut out_s, &format!("Expected at {date_string}"));
+        }
+        // Expired check-in
+        else if let Some(date_str) = metadata.get("triggerTime") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            self.push_line(&mut out_s, &format!("Was expected at {date_string}"));
+        }
+        // Accepted check-in
+        else if let Some(date_str) = metadata.get("sendDate") {
+            // Parse the estimated end time from the message's query string
+            let date_stamp = date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR;
+            let date_time = get_local_time(&date_stamp, &0);
+            let date_string = format(&date_time);
+
+            self.push_line(&mut out_s, &format!("Checked in at {date_string}"));
+        }
+
+        out_s
+    }
+
+    fn format_generic_app(
+        &self,
+        balloon: &AppMessage,
+        bundle_id: &str,
+        _: &mut Vec<Attachment>,
+        _: &str,
+    ) -> String {
+        let mut out_s = String::new();
+
+        self.push_line(
+            &mut out_s,
+            &format!(
+                "**{}** message:",
+                sanitize_markdown(balloon.app_name.unwrap_or(bundle_id))
+            ),
+        );
+
+        [
+            balloon.title,
+            balloon.subtitle,
+            balloon.caption,
+            balloon.subcaption,
+            balloon.trailing_caption,
+            balloon.trailing_subcaption,
+        ]
+        .into_iter()
+        .flatten()
+        .for_each(|line| self.push_line(&mut out_s, &sanitize_markdown(line)));
+
+        out_s
+    }
+}
+
+impl<'a> TextEffectFormatter for MD<'a> {
+    fn format_mention(&self, text: &str, _: &str) -> String {
+        MD::wrap(text, "**", "**")
+    }
+
+    fn format_link(&self, text: &str, url: &str) -> String {
+        format!("[{text}]({})", MD::link_destination(url))
+    }
+
+    fn format_otp(&self, text: &str) -> String {
+        format!("`{text}`")
+    }
+
+    fn format_conversion(&self, text: &str, _: &Unit) -> String {
+        MD::wrap(text, "<u>", "</u>")
+    }
+
+    fn format_styles(&self, text: &str, styles: &[Style]) -> String {
+        let (prefix, suffix): (String, String) = styles.iter().rev().fold(
+            (String::new(), String::new()),
+            |(mut prefix, mut suffix), style| {
+                let (open, close) = match style {
+                    Style::Bold => ("**", "**"),
+                    Style::Italic => ("*", "*"),
+                    Style::Strikethrough => ("~~", "~~"),
+                    // Markdown has no underline syntax, but inline HTML is part of the spec
+                    Style::Underline => ("<u>", "</u>"),
+                };
+                prefix.push_str(open);
+                suffix.insert_str(0, close);
+                (prefix, suffix)
+            },
+        );
+
+        MD::wrap(text, &prefix, &suffix)
+    }
+
+    fn format_animated(&self, text: &str, _: &Animation) -> String {
+        // There is no way to represent animations in Markdown
+        text.to_string()
+    }
+}
+
+impl<'a> MD<'a> {
+    fn get_time(&self, message: &Message) -> String {
+        let mut date = format(&message.date(&self.config.offset));
+        let read_after = message.time_until_read(&self.config.offset);
+        if let Some(time) = read_after {
+            if !time.is_empty() {
+                let who = if message.is_from_me() {
+                    "them"
+                } else {
+                    self.config.options.custom_name.as_deref().unwrap_or("you")
+                };
+                date.push_str(&format!(" (Read by {who} after {time})"));
+            }
+        }
+        date
+    }
+
+    /// Add a block to the message, separated from the next one by a blank line
+    fn add_line(&self, string: &mut String, part: &str) {
+        if !part.is_empty() {
+            string.push_str(part);
+            string.push_str("\n\n");
+        }
+    }
+
+    /// Add a line to a block, without separating it from the previous line
+    fn push_line(&self, string: &mut String, part: &str) {
+        if !part.is_empty() {
+            if !string.is_empty() {
+                string.push('\n');
+            }
+            string.push_str(part);
+        }
+    }
+
+    /// Nest a rendered message inside a blockquote
+    fn quote(&self, text: &str) -> String {
+        text.trim_end()
+            .lines()
+            .map(|line| {
+                if line.is_empty() {
+                    String::from(">")
+                } else {
+                    format!("> {line}")
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Surround text with delimiters, keeping leading and trailing whitespace outside of them
+    ///
+    /// Emphasis delimiters are not recognized if they are adjacent to whitespace on the inside.
+    fn wrap(text: &str, open: &str, close: &str) -> String {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            return text.to_string();
+        }
+        let start = text.len() - text.trim_start().len();
+        let end = start + trimmed.len();
+        format!("{}{open}{trimmed}{close}{}", &text[..start], &text[end..])
+    }
+
+    /// Build a link destination, using the angle bracket form if the target contains spaces or parentheses
+    fn link_destination(target: &str) -> Cow<'_, str> {
+        if target.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+            return Cow::Owned(format!(
+                "<{}>",
+                target.replace('<', "%3C").replace('>', "%3E")
+            ));
+        }
+        Cow::Borrowed(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var};
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::Writer,
+        Exporter, MD,
+    };
+    use imessage_database::tables::{attachment::Attachment, table::ME};
+
+    pub(super) fn fake_attachment() -> Attachment {
+        Attachment {
+            rowid: 0,
+            filename: Some("a/b/c/d.jpg".to_string()),
+            uti: Some("public.png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            transfer_name: Some("d.jpg".to_string()),
+            total_bytes: 100,
+            is_sticker: false,
+            hide_attachment: 0,
+            copied_path: None,
+        }
+    }
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_add_line() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        // Create sample data
+        let mut s = String::new();
+        exporter.add_line(&mut s, "hello world");
+        exporter.add_line(&mut s, "");
+
+        assert_eq!(s, "hello world\n\n".to_string());
+    }
+
+    #[test]
+    fn can_quote() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.quote("**Me** · date\n\nhello\n\n> nested\n\n"),
+            "> **Me** · date\n>\n> hello\n>\n> > nested"
+        );
+    }
+
+    #[test]
+    fn can_format_md_from_me_normal() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** · May 17, 2022  5:29:42 PM\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_me_escaped() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("*Hello* #world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** · May 17, 2022  5:29:42 PM\n\n\\*Hello\\* \\#world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_me_multiline() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Shopping list:\nEggs\nMilk".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** · May 17, 2022  5:29:42 PM\n\nShopping list:\\\nEggs\\\nMilk\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_me_block_markers() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("- eggs\n1. milk\n> bread\n| jam |".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** · May 17, 2022  5:29:42 PM\n\n\\- eggs\\\n1\\. milk\\\n\\> bread\\\n\\| jam |\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_me_normal_deleted() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.text = Some("Hello world".to_string());
+        message.date = 674526582885055488;
+        message.is_from_me = true;
+        message.deleted_from = Some(0);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** · May 17, 2022  5:29:42 PM\n\n*This message was deleted from the conversation!*\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_from_them_normal_read() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample_Contact".to_string());
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        message.handle_id = Some(999999);
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date_delivered = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Sample\\_Contact** · May 17, 2022  5:29:42 PM (Read by you after 1 hour, 49 seconds)\n\nHello world\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_shareplay() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.item_type = 6;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Unknown** · May 17, 2022  5:29:42 PM\n\n*SharePlay Message Ended*\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_announcement() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.group_title = Some("Hello *world*".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_announcement(&message);
+        let expected =
+            "*May 17, 2022  5:29:42 PM You renamed the conversation to Hello \\*world\\**\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_tapback_them() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.format_tapback(&message).unwrap();
+        let expected = "Loved by Sample Contact";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_tapbacks_list() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+
+        let mut tapback = blank();
+        tapback.associated_message_type = Some(2001);
+        tapback.associated_message_guid = Some("p:0/fake_guid".to_string());
+        tapback.handle_id = Some(999999);
+
+        let mut part_tapbacks = HashMap::new();
+        part_tapbacks.insert(0, vec![tapback]);
+        config
+            .tapbacks
+            .insert("fake_guid".to_string(), part_tapbacks);
+
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.guid = "fake_guid".to_string();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** · May 17, 2022  5:29:42 PM\n\nHello world\n\nTapbacks:\n\n- Liked by Sample Contact\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_started_sharing_location_me() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let mut message = blank();
+        message.is_from_me = false;
+        message.other_handle = 2;
+        message.share_status = false;
+        message.share_direction = false;
+        message.item_type = 4;
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "**Me** · Dec 31, 2000  4:00:00 PM\n\n*Started sharing location!*\n\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_md_attachment_image() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let message = blank();
+
+        let mut attachment = fake_attachment();
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(actual, "![d.jpg](a/b/c/d.jpg)");
+    }
+
+    #[test]
+    fn can_format_md_attachment_file() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let message = blank();
+
+        let mut attachment = fake_attachment();
+        attachment.filename = Some("a/b/c/my file.pdf".to_string());
+        attachment.transfer_name = Some("my_file.pdf".to_string());
+        attachment.mime_type = Some("application/pdf".to_string());
+
+        let actual = exporter
+            .format_attachment(&mut attachment, &message)
+            .unwrap();
+
+        assert_eq!(actual, "[my\\_file.pdf](<a/b/c/my file.pdf>)");
+    }
+
+    #[test]
+    fn can_format_md_attachment_invalid() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let message = blank();
+
+        let mut attachment = fake_attachment();
+        attachment.filename = None;
+
+        let actual = exporter.format_attachment(&mut attachment, &message);
+
+        assert_eq!(actual, Err("d.jpg"));
+    }
+}
+
+#[cfg(test)]
+mod text_effect_tests {
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{fake_config, fake_options_for},
+        },
+        exporters::exporter::{TextEffectFormatter, Writer},
+        Exporter, MD,
+    };
+    use imessage_database::message_types::text_effects::{Style, TextEffect, Unit};
+
+    #[test]
+    fn can_format_md_styles() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        assert_eq!(exporter.format_styles("text", &[Style::Bold]), "**text**");
+        assert_eq!(exporter.format_styles("text", &[Style::Italic]), "*text*");
+        assert_eq!(
+            exporter.format_styles("text", &[Style::Strikethrough]),
+            "~~text~~"
+        );
+        assert_eq!(
+            exporter.format_styles("text", &[Style::Underline]),
+            "<u>text</u>"
+        );
+    }
+
+    #[test]
+    fn can_format_md_styles_nested() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_styles("text", &[Style::Bold, Style::Strikethrough]),
+            "~~**text**~~"
+        );
+    }
+
+    #[test]
+    fn can_format_md_styles_whitespace() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_styles(" bold text ", &[Style::Bold]),
+            " **bold text** "
+        );
+        assert_eq!(exporter.format_styles("  ", &[Style::Bold]), "  ");
+    }
+
+    #[test]
+    fn can_format_md_link() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let effect = TextEffect::Link("https://example.com/a_(b)");
+        assert_eq!(
+            exporter.format_attributed("my_site", &effect),
+            "[my\\_site](<https://example.com/a_(b)>)"
+        );
+    }
+
+    #[test]
+    fn can_format_md_mention() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let effect = TextEffect::Mention("+15558675309");
+        assert_eq!(exporter.format_attributed("Jane", &effect), "**Jane**");
+    }
+
+    #[test]
+    fn can_format_md_otp() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_attributed("123_456", &TextEffect::OTP),
+            "`123_456`"
+        );
+    }
+
+    #[test]
+    fn can_format_md_conversion() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_attributed("5 miles", &TextEffect::Conversion(Unit::Distance)),
+            "<u>5 miles</u>"
+        );
+    }
+}
+
+#[cfg(test)]
+mod balloon_format_tests {
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::BalloonFormatter,
+        Exporter, MD,
+    };
+    use imessage_database::message_types::{
+        app::AppMessage,
+        music::MusicMessage,
+        placemark::{Placemark, PlacemarkMessage},
+        url::URLMessage,
+    };
+
+    #[test]
+    fn can_format_md_url() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let balloon = URLMessage {
+            title: Some("title"),
+            summary: Some("summary"),
+            url: Some("url"),
+            original_url: Some("original_url"),
+            item_type: Some("item_type"),
+            images: vec!["images"],
+            icons: vec!["icons"],
+            site_name: Some("site_name"),
+            placeholder: false,
+        };
+
+        let expected = exporter.format_url(&blank(), &balloon, "");
+        let actual = "[title](url)\nsummary";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_md_music() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let balloon = MusicMessage {
+            url: Some("url"),
+            preview: Some("preview"),
+            artist: Some("artist"),
+            album: Some("album"),
+            track_name: Some("track_name"),
+        };
+
+        let expected = exporter.format_music(&balloon, "");
+        let actual = "[track\\_name](url)\nalbum\nartist";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_md_apple_pay() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let balloon = AppMessage {
+            image: Some("image"),
+            url: Some("url"),
+            title: Some("title"),
+            subtitle: Some("subtitle"),
+            caption: Some("caption"),
+            subcaption: Some("subcaption"),
+            trailing_caption: Some("trailing_caption"),
+            trailing_subcaption: Some("trailing_subcaption"),
+            app_name: Some("app_name"),
+            ldtext: Some("ldtext"),
+        };
+
+        let expected = exporter.format_apple_pay(&balloon, "");
+        let actual = "caption transaction: **ldtext**";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_md_placemark() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let balloon = PlacemarkMessage {
+            url: Some("url"),
+            original_url: Some("original_url"),
+            place_name: Some("Name"),
+            placemark: Placemark {
+                name: Some("name"),
+                address: Some("address"),
+                state: Some("state"),
+                city: Some("city"),
+                iso_country_code: None,
+                postal_code: None,
+                country: None,
+                street: None,
+                sub_administrative_area: None,
+                sub_locality: None,
+            },
+        };
+
+        let expected = exporter.format_placemark(&balloon, "");
+        let actual = "[Name](url)\nname\naddress\nstate\ncity";
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn can_format_md_generic_app() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Markdown);
+        let config = fake_config(options);
+        let exporter = MD::new(&config).unwrap();
+
+        let balloon = AppMessage {
+            image: Some("image"),
+            url: Some("url"),
+            title: Some("title"),
+            subtitle: Some("subtitle"),
+            caption: None,
+            subcaption: None,
+            trailing_caption: None,
+            trailing_subcaption: None,
+            app_name: Some("app_name"),
+            ldtext: Some("ldtext"),
+        };
+
+        let expected = exporter.format_generic_app(&balloon, "bundle_id", &mut vec![], "");
+        let actual = "**app\\_name** message:\ntitle\nsubtitle";
+
+        assert_eq!(expected, actual);
+    }
+}
@@ -1,3 +1,16 @@
+pub mod contacts;
+pub mod csv;
 pub mod exporter;
 pub mod html;
+pub mod json;
+pub mod links;
+pub mod locations;
+pub mod mbox;
+pub mod md;
+pub mod parquet;
+pub mod slack;
+pub mod sqlite;
+pub mod telegram;
+pub mod templates;
 pub mod txt;
+pub mod xml;
@@ -0,0 +1,820 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use crate::app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config};
+
+use imessage_database::{
+    error::table::TableError,
+    message_types::{
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        variants::{Announcement, Variant},
+    },
+    tables::{attachment::Attachment, messages::Message, table::Table},
+    util::dates::get_local_time,
+};
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+    UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::{
+    arrow::ArrowWriter, basic::Compression, errors::ParquetError,
+    file::properties::WriterProperties,
+};
+
+/// Number of rows buffered in memory before they are written to disk as a row group
+const BATCH_SIZE: usize = 65_536;
+
+/// Time zone used for timestamp columns, since the stored instants do not depend on the local offset
+const TIMEZONE: &str = "UTC";
+
+/// Exports messages, attachments, and reactions to Apache Parquet files
+pub struct Parquet<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Writer for the `messages.parquet` file
+    messages: ParquetFile<MessageRow>,
+    /// Writer for the `attachments.parquet` file
+    attachments: ParquetFile<AttachmentRow>,
+    /// Writer for the `reactions.parquet` file
+    reactions: ParquetFile<ReactionRow>,
+}
+
+/// Defines the schema of a Parquet file and how to convert its rows to columns
+trait Columnar: Sized {
+    /// Name of the file, without an extension
+    const NAME: &'static str;
+    /// Columns written to the file
+    fn schema() -> Schema;
+    /// Build the columns for a set of rows, in the same order as the schema
+    fn columns(rows: &[Self]) -> Vec<ArrayRef>;
+}
+
+/// Buffers rows and writes them to a Parquet file in batches
+struct ParquetFile<T: Columnar> {
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    rows: Vec<T>,
+}
+
+impl<T: Columnar> ParquetFile<T> {
+    /// Create the file in the export directory
+    fn create(directory: &Path) -> Result<Self, RuntimeError> {
+        let mut path = directory.to_path_buf();
+        path.push(T::NAME);
+        path.set_extension("parquet");
+
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+
+        let schema = Arc::new(T::schema());
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(properties))
+            .map_err(RuntimeError::ParquetError)?;
+
+        Ok(ParquetFile {
+            writer,
+            schema,
+            rows: Vec::with_capacity(BATCH_SIZE),
+        })
+    }
+
+    /// Add a row, writing the buffered rows to disk if the buffer is full
+    fn push(&mut self, row: T) -> Result<(), RuntimeError> {
+        self.rows.push(row);
+        if self.rows.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows to disk
+    fn flush(&mut self) -> Result<(), RuntimeError> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let batch = RecordBatch::try_new(self.schema.clone(), T::columns(&self.rows))
+            .map_err(|err| RuntimeError::ParquetError(ParquetError::from(err)))?;
+        self.writer
+            .write(&batch)
+            .map_err(RuntimeError::ParquetError)?;
+        self.rows.clear();
+        Ok(())
+    }
+
+    /// Write any remaining rows and the file footer
+    fn finish(&mut self) -> Result<(), RuntimeError> {
+        self.flush()?;
+        self.writer.finish().map_err(RuntimeError::ParquetError)?;
+        Ok(())
+    }
+}
+
+/// Build a timestamp field in the shared time zone
+fn timestamp_field(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Microsecond, Some(TIMEZONE.into())),
+        true,
+    )
+}
+
+/// Build a timestamp column in the shared time zone
+fn timestamp_column<T>(rows: &[T], value: impl Fn(&T) -> Option<i64>) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from(rows.iter().map(value).collect::<Vec<_>>())
+            .with_timezone(TIMEZONE),
+    )
+}
+
+/// A row in `messages.parquet`
+#[derive(Debug, PartialEq)]
+struct MessageRow {
+    rowid: i32,
+    guid: String,
+    chat_id: Option<i32>,
+    conversation_id: Option<i32>,
+    chat_identifier: Option<String>,
+    chat_name: Option<String>,
+    handle_id: Option<i32>,
+    sender: String,
+    is_from_me: bool,
+    service: Option<String>,
+    variant: &'static str,
+    subject: Option<String>,
+    text: Option<String>,
+    date: Option<i64>,
+    date_delivered: Option<i64>,
+    date_read: Option<i64>,
+    date_edited: Option<i64>,
+    is_read: bool,
+    is_deleted: bool,
+    is_edited: bool,
+    expressive: Option<String>,
+    announcement: Option<&'static str>,
+    group_title: Option<String>,
+    thread_originator_guid: Option<String>,
+    num_attachments: i32,
+    num_replies: i32,
+}
+
+impl Columnar for MessageRow {
+    const NAME: &'static str = "messages";
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("rowid", DataType::Int32, false),
+            Field::new("guid", DataType::Utf8, false),
+            Field::new("chat_id", DataType::Int32, true),
+            Field::new("conversation_id", DataType::Int32, true),
+            Field::new("chat_identifier", DataType::Utf8, true),
+            Field::new("chat_name", DataType::Utf8, true),
+            Field::new("handle_id", DataType::Int32, true),
+            Field::new("sender", DataType::Utf8, false),
+            Field::new("is_from_me", DataType::Boolean, false),
+            Field::new("service", DataType::Utf8, true),
+            Field::new("variant", DataType::Utf8, false),
+            Field::new("subject", DataType::Utf8, true),
+            Field::new("text", DataType::Utf8, true),
+            timestamp_field("date"),
+            timestamp_field("date_delivered"),
+            timestamp_field("date_read"),
+            timestamp_field("date_edited"),
+            Field::new("is_read", DataType::Boolean, false),
+            Field::new("is_deleted", DataType::Boolean, false),
+            Field::new("is_edited", DataType::Boolean, false),
+            Field::new("expressive", DataType::Utf8, true),
+            Field::new("announcement", DataType::Utf8, true),
+            Field::new("group_title", DataType::Utf8, true),
+            Field::new("thread_originator_guid", DataType::Utf8, true),
+            Field::new("num_attachments", DataType::Int32, false),
+            Field::new("num_replies", DataType::Int32, false),
+        ])
+    }
+
+    fn columns(rows: &[Self]) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.rowid),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| &row.guid),
+            )),
+            Arc::new(Int32Array::from_iter(rows.iter().map(|row| row.chat_id))),
+            Arc::new(Int32Array::from_iter(
+                rows.iter().map(|row| row.conversation_id),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.chat_identifier.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.chat_name.as_deref()),
+            )),
+            Arc::new(Int32Array::from_iter(rows.iter().map(|row| row.handle_id))),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| &row.sender),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|row| Some(row.is_from_me)),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.service.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| row.variant),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.subject.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.text.as_deref()),
+            )),
+            timestamp_column(rows, |row| row.date),
+            timestamp_column(rows, |row| row.date_delivered),
+            timestamp_column(rows, |row| row.date_read),
+            timestamp_column(rows, |row| row.date_edited),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|row| Some(row.is_read)),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|row| Some(row.is_deleted)),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|row| Some(row.is_edited)),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.expressive.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.announcement),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.group_title.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.thread_originator_guid.as_deref()),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.num_attachments),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.num_replies),
+            )),
+        ]
+    }
+}
+
+/// A row in `attachments.parquet`
+#[derive(Debug, PartialEq)]
+struct AttachmentRow {
+    rowid: i32,
+    message_rowid: i32,
+    message_guid: String,
+    chat_id: Option<i32>,
+    date: Option<i64>,
+    filename: Option<String>,
+    transfer_name: Option<String>,
+    mime_type: Option<String>,
+    uti: Option<String>,
+    total_bytes: u64,
+    is_sticker: bool,
+    path: Option<String>,
+}
+
+impl Columnar for AttachmentRow {
+    const NAME: &'static str = "attachments";
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("rowid", DataType::Int32, false),
+            Field::new("message_rowid", DataType::Int32, false),
+            Field::new("message_guid", DataType::Utf8, false),
+            Field::new("chat_id", DataType::Int32, true),
+            timestamp_field("date"),
+            Field::new("filename", DataType::Utf8, true),
+            Field::new("transfer_name", DataType::Utf8, true),
+            Field::new("mime_type", DataType::Utf8, true),
+            Field::new("uti", DataType::Utf8, true),
+            Field::new("total_bytes", DataType::UInt64, false),
+            Field::new("is_sticker", DataType::Boolean, false),
+            Field::new("path", DataType::Utf8, true),
+        ])
+    }
+
+    fn columns(rows: &[Self]) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.rowid),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.message_rowid),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| &row.message_guid),
+            )),
+            Arc::new(Int32Array::from_iter(rows.iter().map(|row| row.chat_id))),
+            timestamp_column(rows, |row| row.date),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.filename.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.transfer_name.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.mime_type.as_deref()),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.uti.as_deref()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|row| row.total_bytes),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|row| Some(row.is_sticker)),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.path.as_deref()),
+            )),
+        ]
+    }
+}
+
+/// A row in `reactions.parquet`
+#[derive(Debug, PartialEq)]
+struct ReactionRow {
+    rowid: i32,
+    guid: String,
+    message_rowid: i32,
+    message_guid: String,
+    part_index: u32,
+    chat_id: Option<i32>,
+    handle_id: Option<i32>,
+    sender: String,
+    is_from_me: bool,
+    kind: &'static str,
+    reaction: Option<String>,
+    added: bool,
+    date: Option<i64>,
+}
+
+impl Columnar for ReactionRow {
+    const NAME: &'static str = "reactions";
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Field::new("rowid", DataType::Int32, false),
+            Field::new("guid", DataType::Utf8, false),
+            Field::new("message_rowid", DataType::Int32, false),
+            Field::new("message_guid", DataType::Utf8, false),
+            Field::new("part_index", DataType::UInt32, false),
+            Field::new("chat_id", DataType::Int32, true),
+            Field::new("handle_id", DataType::Int32, true),
+            Field::new("sender", DataType::Utf8, false),
+            Field::new("is_from_me", DataType::Boolean, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("reaction", DataType::Utf8, true),
+            Field::new("added", DataType::Boolean, false),
+            timestamp_field("date"),
+        ])
+    }
+
+    fn columns(rows: &[Self]) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.rowid),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| &row.guid),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|row| row.message_rowid),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| &row.message_guid),
+            )),
+            Arc::new(UInt32Array::from_iter_values(
+                rows.iter().map(|row| row.part_index),
+            )),
+            Arc::new(Int32Array::from_iter(rows.iter().map(|row| row.chat_id))),
+            Arc::new(Int32Array::from_iter(rows.iter().map(|row| row.handle_id))),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| &row.sender),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|row| Some(row.is_from_me)),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|row| row.kind),
+            )),
+            Arc::new(StringArray::from_iter(
+                rows.iter().map(|row| row.reaction.as_deref()),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|row| Some(row.added)),
+            )),
+            timestamp_column(rows, |row| row.date),
+        ]
+    }
+}
+
+impl<'a> Parquet<'a> {
+    /// Create a new exporter with references to the cached data
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let directory = &config.options.export_path;
+        Ok(Parquet {
+            config,
+            messages: ParquetFile::create(directory)?,
+            attachments: ParquetFile::create(directory)?,
+            reactions: ParquetFile::create(directory)?,
+        })
+    }
+
+    /// Begin iterating over the messages table
+    pub fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as parquet...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are written alongside the messages they react to
+            if !msg.is_tapback() {
+                self.write_message(&msg)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Parquet files are unreadable until their footers are written
+        self.messages.finish()?;
+        self.attachments.finish()?;
+        self.reactions.finish()
+    }
+
+    /// Convert an iMessage timestamp to microseconds since the Unix epoch, omitting unset timestamps
+    fn timestamp(&self, stamp: &i64) -> Option<i64> {
+        if *stamp == 0 {
+            return None;
+        }
+        get_local_time(stamp, &self.config.offset)
+            .ok()
+            .map(|date| date.timestamp_micros())
+    }
+
+    /// Name of the bubble or screen effect a message was sent with
+    fn expressive(message: &Message) -> Option<&str> {
+        match message.get_expressive() {
+            Expressive::Screen(effect) => Some(match effect {
+                ScreenEffect::Confetti => "Confetti",
+                ScreenEffect::Echo => "Echo",
+                ScreenEffect::Fireworks => "Fireworks",
+                ScreenEffect::Balloons => "Balloons",
+                ScreenEffect::Heart => "Heart",
+                ScreenEffect::Lasers => "Lasers",
+                ScreenEffect::ShootingStar => "ShootingStar",
+                ScreenEffect::Sparkles => "Sparkles",
+                ScreenEffect::Spotlight => "Spotlight",
+            }),
+            Expressive::Bubble(effect) => Some(match effect {
+                BubbleEffect::Slam => "Slam",
+                BubbleEffect::Loud => "Loud",
+                BubbleEffect::Gentle => "Gentle",
+                BubbleEffect::InvisibleInk => "InvisibleInk",
+            }),
+            Expressive::Unknown(effect) => Some(effect),
+            Expressive::None => None,
+        }
+    }
+
+    /// Name of the message's variant
+    fn variant(message: &Message) -> &'static str {
+        if message.is_announcement() {
+            return "announcement";
+        }
+        match message.variant() {
+            Variant::App(_) => "app",
+            Variant::Edited => "edited",
+            Variant::SharePlay => "shareplay",
+            Variant::Sticker(_) => "sticker",
+            Variant::Tapback(..) => "tapback",
+            Variant::Normal => "normal",
+            Variant::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Build the `messages.parquet` row for a message
+    fn message_row(&self, message: &Message) -> MessageRow {
+        let chat = self.config.conversation(message);
+        let (announcement, group_title) = match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => (Some("name_change"), Some(name.to_string())),
+            Some(Announcement::PhotoChange) => (Some("photo_change"), None),
+            Some(Announcement::FullyUnsent) => (Some("fully_unsent"), None),
+            Some(Announcement::Unknown(_)) => (Some("unknown"), None),
+            None => (None, None),
+        };
+
+        MessageRow {
+            rowid: message.rowid,
+            guid: message.guid.to_string(),
+            chat_id: message.chat_id.or(message.deleted_from),
+            conversation_id: chat.map(|(_, id)| *id),
+            chat_identifier: chat.map(|(chatroom, _)| chatroom.chat_identifier.to_string()),
+            chat_name: chat.and_then(|(chatroom, _)| chatroom.display_name().map(String::from)),
+            handle_id: message.handle_id,
+            sender: self
+                .config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            is_from_me: message.is_from_me(),
+            service: message.service.clone(),
+            variant: Parquet::variant(message),
+            subject: message.subject.clone(),
+            text: message.text.clone(),
+            date: self.timestamp(&message.date),
+            date_delivered: self.timestamp(&message.date_delivered),
+            date_read: self.timestamp(&message.date_read),
+            date_edited: self.timestamp(&message.date_edited),
+            is_read: message.is_read,
+            is_deleted: message.is_deleted(),
+            is_edited: message.is_edited(),
+            expressive: Parquet::expressive(message).map(String::from),
+            announcement,
+            group_title,
+            thread_originator_guid: message.thread_originator_guid.clone(),
+            num_attachments: message.num_attachments,
+            num_replies: message.num_replies,
+        }
+    }
+
+    /// Build the `attachments.parquet` row for an attachment, copying it if requested
+    fn attachment_row(&self, attachment: &mut Attachment, message: &Message) -> AttachmentRow {
+        let path = self
+            .config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .map(|_| self.config.message_attachment_path(attachment));
+
+        AttachmentRow {
+            rowid: attachment.rowid,
+            message_rowid: message.rowid,
+            message_guid: message.guid.to_string(),
+            chat_id: message.chat_id.or(message.deleted_from),
+            date: self.timestamp(&message.date),
+            filename: attachment.filename.clone(),
+            transfer_name: attachment.transfer_name.clone(),
+            mime_type: attachment.mime_type.clone(),
+            uti: attachment.uti.clone(),
+            total_bytes: attachment.total_bytes,
+            is_sticker: attachment.is_sticker,
+            path,
+        }
+    }
+
+    /// Build the `reactions.parquet` row for a tapback or sticker placed on a part of a message
+    fn reaction_row(
+        &self,
+        reaction: &Message,
+        message: &Message,
+        part_index: usize,
+    ) -> ReactionRow {
+        let (kind, name, added) = match reaction.variant() {
+            Variant::Tapback(_, added, tapback) => ("tapback", Some(tapback.to_string()), added),
+            Variant::Sticker(_) => ("sticker", None, true),
+            _ => unreachable!(),
+        };
+
+        ReactionRow {
+            rowid: reaction.rowid,
+            guid: reaction.guid.to_string(),
+            message_rowid: message.rowid,
+            message_guid: message.guid.to_string(),
+            part_index: part_index as u32,
+            chat_id: message.chat_id.or(message.deleted_from),
+            handle_id: reaction.handle_id,
+            sender: self
+                .config
+                .who(
+                    reaction.handle_id,
+                    reaction.is_from_me(),
+                    &reaction.destination_caller_id,
+                )
+                .to_string(),
+            is_from_me: reaction.is_from_me(),
+            kind,
+            reaction: name,
+            added,
+            date: self.timestamp(&reaction.date),
+        }
+    }
+
+    /// Buffer a message along with its attachments and reactions
+    fn write_message(&mut self, message: &Message) -> Result<(), RuntimeError> {
+        self.messages.push(self.message_row(message))?;
+
+        let mut attachments = Attachment::from_message(&self.config.db, message)
+            .map_err(RuntimeError::DatabaseError)?;
+        for attachment in attachments.iter_mut() {
+            let row = self.attachment_row(attachment, message);
+            self.attachments.push(row)?;
+        }
+
+        if let Some(reactions_map) = self.config.tapbacks.get(&message.guid) {
+            // Sort by part index so the output is deterministic
+            let mut parts: Vec<_> = reactions_map.iter().collect();
+            parts.sort_by_key(|(idx, _)| **idx);
+            for (idx, reactions) in parts {
+                for reaction in reactions {
+                    let row = self.reaction_row(reaction, message, *idx);
+                    self.reactions.push(row)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::set_var, fs::File, path::PathBuf};
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::parquet::{Columnar, MessageRow, ParquetFile, ReactionRow},
+        Parquet,
+    };
+    use arrow_array::{Array, Int32Array, StringArray, TimestampMicrosecondArray};
+
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn can_build_message_row() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let options = fake_options_for(ExportType::Parquet);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+
+        // Create exporter
+        let exporter = Parquet::new(&config).unwrap();
+
+        let mut message = blank();
+        message.rowid = 5;
+        message.guid = "guid".to_string();
+        message.handle_id = Some(999999);
+        message.text = Some("Hello world".to_string());
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+
+        let row = exporter.message_row(&message);
+        assert_eq!(row.rowid, 5);
+        assert_eq!(row.sender, "Sample Contact");
+        assert_eq!(row.variant, "normal");
+        assert_eq!(row.text.as_deref(), Some("Hello world"));
+        // 2022-05-18T00:29:42Z
+        assert_eq!(row.date, Some(1652833782000000));
+        assert_eq!(row.date_read, None);
+    }
+
+    #[test]
+    fn can_build_message_row_announcement() {
+        let options = fake_options_for(ExportType::Parquet);
+        let config = fake_config(options);
+        let exporter = Parquet::new(&config).unwrap();
+
+        let mut message = blank();
+        message.group_title = Some("Hello world".to_string());
+
+        let row = exporter.message_row(&message);
+        assert_eq!(row.variant, "announcement");
+        assert_eq!(row.announcement, Some("name_change"));
+        assert_eq!(row.group_title.as_deref(), Some("Hello world"));
+    }
+
+    #[test]
+    fn can_write_and_read_rows() {
+        let directory = PathBuf::from("/tmp/can_write_and_read_rows");
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut file = ParquetFile::<ReactionRow>::create(&directory).unwrap();
+        file.push(ReactionRow {
+            rowid: 2,
+            guid: "reaction".to_string(),
+            message_rowid: 1,
+            message_guid: "message".to_string(),
+            part_index: 0,
+            chat_id: Some(3),
+            handle_id: None,
+            sender: "Me".to_string(),
+            is_from_me: true,
+            kind: "tapback",
+            reaction: Some("Loved".to_string()),
+            added: true,
+            date: Some(1652833782000000),
+        })
+        .unwrap();
+        file.finish().unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(
+            File::open(directory.join("reactions.parquet")).unwrap(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        let batches: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 1);
+
+        let batch = &batches[0];
+        assert_eq!(batch.schema().fields(), ReactionRow::schema().fields());
+        assert_eq!(batch.num_rows(), 1);
+
+        let rowids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(rowids.value(0), 2);
+
+        let handle_ids = batch
+            .column(6)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert!(handle_ids.is_null(0));
+
+        let reactions = batch
+            .column(10)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(reactions.value(0), "Loved");
+
+        let dates = batch
+            .column(12)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(dates.value(0), 1652833782000000);
+        assert_eq!(dates.timezone(), Some("UTC"));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn schemas_match_columns() {
+        assert_eq!(
+            MessageRow::schema().fields().len(),
+            MessageRow::columns(&[]).len()
+        );
+        assert_eq!(
+            ReactionRow::schema().fields().len(),
+            ReactionRow::columns(&[]).len()
+        );
+    }
+}
@@ -0,0 +1,165 @@
+// Play the bubble and screen effects that messages were sent with
+// Messages with an effect have the `effect` class and a class naming the effect, i.e. `effect_slam`
+// Each effect plays the first time its message scrolls into view, and again when its label is clicked
+const SCREEN_EFFECT_DURATION = 3500;
+const EFFECT_COLORS = ["#ff3b30", "#ff9500", "#ffcc00", "#34c759", "#5ac8fa", "#007aff", "#af52de", "#ff2d55"];
+
+const reduceMotion = window.matchMedia("(prefers-reduced-motion: reduce)").matches;
+
+function random(min, max) {
+	return min + Math.random() * (max - min);
+}
+
+function randomColor() {
+	return EFFECT_COLORS[Math.floor(Math.random() * EFFECT_COLORS.length)];
+}
+
+// Add a particle to a screen effect, positioned by the CSS custom properties in `props`
+function particle(overlay, kind, text, props) {
+	const span = document.createElement("span");
+	span.className = `particle ${kind}`;
+	span.textContent = text;
+	for (const [key, value] of Object.entries(props)) {
+		span.style.setProperty(`--${key}`, value);
+	}
+	overlay.append(span);
+}
+
+// Functions that fill a full screen overlay with the particles for each screen effect
+const SCREEN_EFFECTS = {
+	confetti(overlay) {
+		for (let idx = 0; idx < 100; idx++) {
+			particle(overlay, "confetti", "", {
+				x: `${random(0, 100)}vw`,
+				delay: `${random(0, 1.2)}s`,
+				spin: `${random(-720, 720)}deg`,
+				color: randomColor(),
+			});
+		}
+	},
+	balloons(overlay) {
+		for (let idx = 0; idx < 20; idx++) {
+			particle(overlay, "balloon", "🎈", {
+				x: `${random(0, 95)}vw`,
+				delay: `${random(0, 1)}s`,
+				drift: `${random(-20, 20)}vw`,
+			});
+		}
+	},
+	echo(overlay, message) {
+		const text = message.querySelector(".bubble")?.textContent ?? "";
+		for (let idx = 0; idx < 20; idx++) {
+			particle(overlay, "echo", text, {
+				x: `${random(0, 80)}vw`,
+				delay: `${random(0, 1.5)}s`,
+				drift: `${random(-10, 10)}vw`,
+				color: randomColor(),
+			});
+		}
+	},
+	fireworks(overlay) {
+		for (let burst = 0; burst < 5; burst++) {
+			const x = random(15, 85);
+			const y = random(15, 60);
+			const color = randomColor();
+			for (let idx = 0; idx < 30; idx++) {
+				const angle = (idx / 30) * 2 * Math.PI;
+				const distance = random(80, 180);
+				particle(overlay, "firework", "", {
+					x: `${x}vw`,
+					y: `${y}vh`,
+					dx: `${Math.cos(angle) * distance}px`,
+					dy: `${Math.sin(angle) * distance}px`,
+					delay: `${burst * 0.5}s`,
+					color,
+				});
+			}
+		}
+	},
+	heart(overlay) {
+		particle(overlay, "heart", "❤️", {});
+	},
+	lasers(overlay) {
+		for (let idx = 0; idx < 8; idx++) {
+			particle(overlay, "laser", "", {
+				y: `${random(10, 90)}vh`,
+				delay: `${random(0, 1.5)}s`,
+				color: randomColor(),
+			});
+		}
+	},
+	shooting_star(overlay) {
+		particle(overlay, "shooting_star", "🌠", {});
+	},
+	sparkles(overlay) {
+		for (let idx = 0; idx < 50; idx++) {
+			particle(overlay, "sparkle", "✨", {
+				x: `${random(0, 95)}vw`,
+				y: `${random(0, 95)}vh`,
+				delay: `${random(0, 2.5)}s`,
+			});
+		}
+	},
+	spotlight(overlay, message) {
+		const rect = message.getBoundingClientRect();
+		overlay.classList.add("spotlight");
+		overlay.style.setProperty("--x", `${rect.left + rect.width / 2}px`);
+		overlay.style.setProperty("--y", `${rect.top + rect.height / 2}px`);
+	},
+};
+
+function effectName(message) {
+	const name = [...message.classList].find((name) => name.startsWith("effect_"));
+	return name?.slice("effect_".length);
+}
+
+function play(message) {
+	if (reduceMotion) {
+		return;
+	}
+
+	const name = effectName(message);
+	const screenEffect = SCREEN_EFFECTS[name];
+	if (screenEffect) {
+		const overlay = document.createElement("div");
+		overlay.className = "screen_effect";
+		screenEffect(overlay, message);
+		document.body.append(overlay);
+		setTimeout(() => overlay.remove(), SCREEN_EFFECT_DURATION);
+	} else {
+		// Restart the bubble's animation, even if it already played
+		message.classList.remove("playing");
+		void message.offsetWidth;
+		message.classList.add("playing");
+	}
+}
+
+// Reveal invisible ink when it is clicked, and replay an effect when its label is clicked
+document.addEventListener("click", (event) => {
+	const message = event.target.closest(".effect");
+	if (!message) {
+		return;
+	}
+	if (message.classList.contains("effect_invisible_ink")) {
+		message.classList.add("revealed");
+	} else if (event.target.closest("span.expressive")) {
+		play(message);
+	}
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+	const observer = new IntersectionObserver((entries) => {
+		for (const entry of entries) {
+			if (entry.isIntersecting) {
+				observer.unobserve(entry.target);
+				play(entry.target);
+			}
+		}
+	}, { threshold: 0.5 });
+
+	for (const message of document.querySelectorAll(".effect")) {
+		if (!message.classList.contains("effect_invisible_ink")) {
+			observer.observe(message);
+		}
+	}
+});
@@ -0,0 +1,176 @@
+-- Conversations from the `chat` table
+CREATE TABLE chats (
+    -- `ROWID` of the chat in the source database
+    id INTEGER PRIMARY KEY,
+    -- Phone number, email address, or group identifier of the chat
+    identifier TEXT NOT NULL,
+    -- Service the chat was held on, i.e. `iMessage` or `SMS`
+    service TEXT,
+    -- Name the chat was given, if any
+    display_name TEXT,
+    -- Chats with the same participants share a conversation ID
+    conversation_id INTEGER
+);
+
+-- People messages were sent to or received from, from the `handle` table
+CREATE TABLE participants (
+    -- `ROWID` of the handle in the source database
+    id INTEGER PRIMARY KEY,
+    -- Phone number or email address
+    handle TEXT NOT NULL,
+    -- Apple's identifier for the person the handle belongs to
+    person_centric_id TEXT,
+    -- Name used for the participant in other export formats
+    name TEXT,
+    -- Handles that belong to the same person share a contact ID
+    contact_id INTEGER
+);
+
+-- Members of each chat
+CREATE TABLE chat_participants (
+    chat_id INTEGER NOT NULL REFERENCES chats (id),
+    participant_id INTEGER NOT NULL REFERENCES participants (id),
+    PRIMARY KEY (chat_id, participant_id)
+);
+
+-- One row per message, excluding tapbacks
+CREATE TABLE messages (
+    -- `ROWID` of the message in the source database
+    id INTEGER PRIMARY KEY,
+    guid TEXT NOT NULL UNIQUE,
+    chat_id INTEGER REFERENCES chats (id),
+    -- `NULL` when the message was sent by the database owner
+    sender_id INTEGER REFERENCES participants (id),
+    is_from_me INTEGER NOT NULL,
+    -- Name of the sender as used in other export formats
+    sender TEXT NOT NULL,
+    service TEXT,
+    -- One of `normal`, `app`, `edited`, `shareplay`, `sticker`, `announcement`, or `unknown`
+    variant TEXT NOT NULL,
+    subject TEXT,
+    -- Full message text, with U+FFFC in place of each attachment
+    text TEXT,
+    -- Dates are RFC 3339 strings in the export's local time zone
+    date TEXT,
+    date_delivered TEXT,
+    date_read TEXT,
+    date_edited TEXT,
+    is_read INTEGER NOT NULL,
+    is_deleted INTEGER NOT NULL,
+    -- Name of the bubble or screen effect the message was sent with
+    expressive TEXT,
+    -- One of `name_change`, `photo_change`, `fully_unsent`, or `unknown`
+    announcement TEXT,
+    -- New chat name for `name_change` announcements
+    group_title TEXT,
+    -- One of `started` or `stopped` for location sharing messages
+    shared_location TEXT
+);
+
+-- Ordered components that make up the body of a message
+CREATE TABLE message_parts (
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    -- Zero-based position of the part in the message body
+    part_index INTEGER NOT NULL,
+    -- One of `text`, `attachment`, `app`, or `retracted`
+    type TEXT NOT NULL,
+    -- Text of `text` parts
+    text TEXT,
+    -- Attachment shown by `attachment` parts
+    attachment_id INTEGER REFERENCES attachments (id),
+    PRIMARY KEY (message_id, part_index)
+);
+
+-- Formatting applied to ranges of message text
+CREATE TABLE text_effects (
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    part_index INTEGER NOT NULL,
+    -- Byte offsets into `messages.text`
+    start INTEGER NOT NULL,
+    end INTEGER NOT NULL,
+    text TEXT,
+    -- One of `default`, `mention`, `link`, `otp`, `styles`, `animated`, or `conversion`
+    effect TEXT NOT NULL,
+    -- Handle for mentions, URL for links, comma-separated styles, animation name, or unit name
+    value TEXT
+);
+
+-- Files attached to messages, including stickers
+CREATE TABLE attachments (
+    -- `ROWID` of the attachment in the source database
+    id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    filename TEXT,
+    transfer_name TEXT,
+    mime_type TEXT,
+    uti TEXT,
+    total_bytes INTEGER NOT NULL,
+    is_sticker INTEGER NOT NULL,
+    sticker_effect TEXT,
+    -- Location of the file, relative to the export directory if it was copied
+    path TEXT
+);
+
+-- Tapbacks and stickers placed on messages
+CREATE TABLE tapbacks (
+    -- `ROWID` of the tapback message in the source database
+    id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    part_index INTEGER NOT NULL,
+    sender_id INTEGER REFERENCES participants (id),
+    is_from_me INTEGER NOT NULL,
+    sender TEXT NOT NULL,
+    -- One of `tapback` or `sticker`
+    type TEXT NOT NULL,
+    -- Tapback name or emoji, `NULL` for stickers
+    tapback TEXT,
+    -- `0` when a previous tapback was removed
+    added INTEGER NOT NULL,
+    -- Sticker image for `sticker` rows
+    attachment_id INTEGER REFERENCES attachments (id),
+    date TEXT
+);
+
+-- Threaded replies to message parts
+CREATE TABLE replies (
+    message_id INTEGER PRIMARY KEY REFERENCES messages (id),
+    -- Message that was replied to
+    parent_id INTEGER NOT NULL REFERENCES messages (id),
+    -- Part of the parent message that was replied to
+    part_index INTEGER NOT NULL
+);
+
+-- Previous versions of edited message parts
+CREATE TABLE edits (
+    message_id INTEGER NOT NULL REFERENCES messages (id),
+    part_index INTEGER NOT NULL,
+    -- Zero-based position in the part's edit history
+    revision INTEGER NOT NULL,
+    -- One of `edited` or `unsent`
+    status TEXT NOT NULL,
+    date TEXT,
+    -- Text of the revision, `NULL` for unsent parts
+    text TEXT,
+    guid TEXT,
+    PRIMARY KEY (message_id, part_index, revision)
+);
+
+-- Decoded payloads of app messages
+CREATE TABLE app_balloons (
+    message_id INTEGER PRIMARY KEY REFERENCES messages (id),
+    -- One of `url`, `music`, `collaboration`, `app_store`, `placemark`, `handwriting`, `apple_pay`, `fitness`, `slideshow`, `check_in`, `find_my`, or `app`
+    type TEXT NOT NULL,
+    bundle_id TEXT,
+    app_name TEXT,
+    url TEXT,
+    title TEXT,
+    subtitle TEXT,
+    caption TEXT,
+    -- JSON object with the remaining fields for the balloon type
+    details TEXT
+);
+
+CREATE INDEX messages_chat_date ON messages (chat_id, date);
+CREATE INDEX attachments_message ON attachments (message_id);
+CREATE INDEX tapbacks_message ON tapbacks (message_id);
+CREATE INDEX replies_parent ON replies (parent_id);
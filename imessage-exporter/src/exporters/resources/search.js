@@ -0,0 +1,82 @@
+// Search every exported conversation using the index loaded from `search_index.js`
+// Each message is stored as `[file, sender, timestamp, text, guid]`, where `file` and `sender`
+// are positions in `SEARCH_FILES` and `SEARCH_SENDERS`
+const MAX_RESULTS = 250;
+
+const query = document.getElementById("query");
+const status = document.getElementById("status");
+const results = document.getElementById("results");
+
+// Lowercase copies of the searchable text, built the first time the user searches
+let haystacks = null;
+
+function buildHaystacks() {
+	return SEARCH_MESSAGES.map(([file, sender, , text]) =>
+		[text, SEARCH_SENDERS[sender], SEARCH_FILES[file][1]].join("\n").toLowerCase()
+	);
+}
+
+function renderResult([file, sender, timestamp, text, guid]) {
+	const [href, title] = SEARCH_FILES[file];
+
+	const item = document.createElement("li");
+	const link = document.createElement("a");
+	link.href = `${href}#r-${guid}`;
+
+	const date = document.createElement("span");
+	date.className = "timestamp";
+	date.textContent = new Date(timestamp * 1000).toLocaleString();
+
+	const who = document.createElement("span");
+	who.className = "sender";
+	who.textContent = SEARCH_SENDERS[sender];
+
+	const chat = document.createElement("span");
+	chat.className = "chat";
+	chat.textContent = title;
+
+	const body = document.createElement("p");
+	body.textContent = text;
+
+	link.append(date, " ", who, " in ", chat);
+	item.append(link, body);
+	return item;
+}
+
+function search() {
+	const terms = query.value.toLowerCase().split(/\s+/).filter(Boolean);
+	results.replaceChildren();
+	history.replaceState(null, "", terms.length ? `#${encodeURIComponent(query.value)}` : "#");
+
+	if (!terms.length) {
+		status.textContent = `${SEARCH_MESSAGES.length} messages indexed`;
+		return;
+	}
+
+	haystacks ??= buildHaystacks();
+
+	// The index is in chronological order, so walk it backwards to list the newest messages first
+	let matches = 0;
+	for (let idx = SEARCH_MESSAGES.length - 1; idx >= 0; idx--) {
+		if (terms.every((term) => haystacks[idx].includes(term))) {
+			matches++;
+			if (matches <= MAX_RESULTS) {
+				results.append(renderResult(SEARCH_MESSAGES[idx]));
+			}
+		}
+	}
+
+	status.textContent = matches > MAX_RESULTS
+		? `Showing the newest ${MAX_RESULTS} of ${matches} matching messages`
+		: `${matches} matching message${matches === 1 ? "" : "s"}`;
+}
+
+let timer = null;
+query.addEventListener("input", () => {
+	clearTimeout(timer);
+	timer = setTimeout(search, 150);
+});
+
+// Restore the previous search when navigating back from a result
+query.value = decodeURIComponent(location.hash.slice(1));
+search();
@@ -0,0 +1,1020 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        BTreeMap, HashMap, HashSet,
+    },
+    fs::{create_dir_all, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::exporter::{Exporter, TextEffectFormatter},
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        edited::EditStatus,
+        text_effects::{Animation, Style, TextEffect, Unit},
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, Tapback, URLOverride, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        handle::Handle,
+        messages::{models::BubbleComponent, Message},
+        table::{Table, ORPHANED, YOU},
+    },
+    util::{
+        dates::{get_local_time, TIMESTAMP_FACTOR},
+        plist::parse_plist,
+    },
+};
+
+use serde_json::{json, Map, Value};
+
+/// Name of the file that lists every channel in the export
+pub(crate) const CHANNELS: &str = "channels.json";
+
+/// Name of the file that lists every user in the export
+pub(crate) const USERS: &str = "users.json";
+
+/// ID used for the database owner and for the channel that holds orphaned messages
+const SELF_ID: i32 = 0;
+
+/// Maximum length of a Slack channel name
+const MAX_CHANNEL_NAME_LENGTH: usize = 80;
+
+/// The file holding a channel's messages for the day currently being exported
+pub struct DayFile {
+    /// Local date of the messages in the file, i.e. `2022-05-17`
+    pub day: String,
+    /// Buffered writer for the file
+    pub file: BufWriter<File>,
+    /// Whether the file has received at least one message
+    started: bool,
+}
+
+/// Exports messages in the layout of a Slack workspace export so they can be browsed in Slack archive viewers
+///
+/// Each conversation becomes a channel with one file of messages per day. Replies are threaded under the message they
+/// reply to and tapbacks become reactions.
+pub struct Slack<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of channel ID to the file for the day currently being exported
+    pub files: HashMap<String, DayFile>,
+    /// Map of channel ID to the channel record, for each channel that has messages
+    channels: BTreeMap<String, Value>,
+    /// Channel names that are already in use, since each channel's directory is named after it
+    channel_names: HashSet<String>,
+    /// Timestamps of messages that have replies, so replies can be threaded under them
+    threads: HashMap<String, String>,
+    /// Map of handle ID to the phone number or email address it represents
+    handles: HashMap<i32, String>,
+}
+
+impl<'a> Exporter<'a> for Slack<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        // The participant cache merges handles that belong to the same person, but users need the original values
+        let mut handles = HashMap::new();
+        let mut statement = Handle::get(&config.db).map_err(RuntimeError::DatabaseError)?;
+        let rows = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+        for row in rows {
+            let handle = Handle::extract(row).map_err(RuntimeError::DatabaseError)?;
+            // Skip people who are excluded or only in chats that the filters leave out of the export
+            if config.is_exported_participant(&handle.rowid) {
+                handles.insert(handle.rowid, handle.id);
+            }
+        }
+
+        Ok(Slack {
+            config,
+            files: HashMap::new(),
+            channels: BTreeMap::new(),
+            channel_names: HashSet::new(),
+            threads: HashMap::new(),
+            handles,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as Slack JSON...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are written as reactions on the messages they react to
+            if !msg.is_tapback() {
+                let record = if msg.is_announcement() {
+                    self.announcement_record(&msg)
+                } else {
+                    self.message_record(&msg)
+                        .map_err(RuntimeError::DatabaseError)?
+                };
+                self.write_record(&msg, &record)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        // Close the arrays
+        for day_file in self.files.values_mut() {
+            Slack::write_to_file(&mut day_file.file, "\n]\n")?;
+        }
+
+        self.write_channels()?;
+        self.write_users()
+    }
+
+    /// Get the file for the day a message was sent in its channel
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        Ok(&mut self.day_file(message)?.file)
+    }
+}
+
+impl<'a> TextEffectFormatter for Slack<'a> {
+    fn format_mention(&self, text: &str, mentioned: &str) -> String {
+        // Slack renders mentions with the name from the user list
+        match self
+            .handles
+            .iter()
+            .find(|(_, handle)| handle.as_str() == mentioned)
+        {
+            Some((handle_id, _)) => format!("<@{}>", self.user_id(Some(*handle_id), false)),
+            None => text.to_string(),
+        }
+    }
+
+    fn format_link(&self, text: &str, url: &str) -> String {
+        if text == url {
+            return format!("<{url}>");
+        }
+        format!("<{url}|{text}>")
+    }
+
+    fn format_otp(&self, text: &str) -> String {
+        Slack::wrap(text, "`", "`")
+    }
+
+    fn format_conversion(&self, text: &str, _: &Unit) -> String {
+        // There is no way to represent unit conversions in Slack
+        text.to_string()
+    }
+
+    fn format_styles(&self, text: &str, styles: &[Style]) -> String {
+        let (prefix, suffix): (String, String) = styles.iter().fold(
+            (String::new(), String::new()),
+            |(mut prefix, mut suffix), style| {
+                let delimiter = match style {
+                    Style::Bold => "*",
+                    Style::Italic => "_",
+                    Style::Strikethrough => "~",
+                    // Slack has no underline syntax
+                    Style::Underline => "",
+                };
+                prefix.push_str(delimiter);
+                suffix.insert_str(0, delimiter);
+                (prefix, suffix)
+            },
+        );
+
+        Slack::wrap(text, &prefix, &suffix)
+    }
+
+    fn format_animated(&self, text: &str, _: &Animation) -> String {
+        // There is no way to represent animations in Slack
+        text.to_string()
+    }
+}
+
+impl<'a> Slack<'a> {
+    /// Get the file for the day a message was sent in its channel, closing the channel's previous day
+    ///
+    /// Messages are streamed in date order, so a channel never returns to a day after moving past it.
+    fn day_file(&mut self, message: &Message) -> Result<&mut DayFile, RuntimeError> {
+        let channel_id = self.channel_id(message);
+        let day = get_local_time(&message.date, &self.config.offset)
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+
+        if !self.channels.contains_key(&channel_id) {
+            let channel = self.channel_record(message);
+            self.channel_names.insert(Slack::channel_name(&channel));
+            self.channels.insert(channel_id.clone(), channel);
+        }
+        let mut path = self.config.options.export_path.clone();
+        path.push(Slack::channel_name(&self.channels[&channel_id]));
+
+        match self.files.entry(channel_id) {
+            Occupied(entry) => {
+                let day_file = entry.into_mut();
+                if day_file.day != day {
+                    Slack::write_to_file(&mut day_file.file, "\n]\n")?;
+                    *day_file = Slack::create_day_file(&path, day)?;
+                }
+                Ok(day_file)
+            }
+            Vacant(entry) => {
+                create_dir_all(&path)
+                    .map_err(|err| RuntimeError::CreateError(err, path.clone()))?;
+                Ok(entry.insert(Slack::create_day_file(&path, day)?))
+            }
+        }
+    }
+
+    /// Create the file for a channel's messages on a given day and open its array
+    fn create_day_file(directory: &Path, day: String) -> Result<DayFile, RuntimeError> {
+        let mut path = directory.to_path_buf();
+        path.push(&day);
+        path.set_extension("json");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut file = BufWriter::new(file);
+        Slack::write_to_file(&mut file, "[")?;
+
+        Ok(DayFile {
+            day,
+            file,
+            started: false,
+        })
+    }
+
+    /// Write a message record to its channel's file, separating it from any previous records
+    fn write_record(&mut self, message: &Message, record: &Value) -> Result<(), RuntimeError> {
+        let day_file = self.day_file(message)?;
+        let separator = if day_file.started { ",\n" } else { "\n" };
+        day_file.started = true;
+
+        Slack::write_to_file(&mut day_file.file, separator)?;
+        Slack::write_to_file(&mut day_file.file, &record.to_string())
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+
+    /// Write a pretty-printed JSON file to the root of the export
+    fn write_json(&self, filename: &str, value: &Value) -> Result<(), RuntimeError> {
+        let mut path = self.config.options.export_path.clone();
+        path.push(filename);
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut file = BufWriter::new(file);
+        Slack::write_to_file(&mut file, &format!("{value:#}\n"))?;
+        file.flush().map_err(RuntimeError::DiskError)
+    }
+
+    /// Write the list of channels that have messages
+    fn write_channels(&self) -> Result<(), RuntimeError> {
+        let channels: Vec<Value> = self.channels.values().cloned().collect();
+        self.write_json(CHANNELS, &Value::Array(channels))
+    }
+
+    /// Write the list of users, one per deduplicated participant plus the database owner
+    fn write_users(&self) -> Result<(), RuntimeError> {
+        let owner = self.config.who(None, true, &None);
+        let mut users = BTreeMap::new();
+        users.insert(
+            self.user_id(None, true),
+            Slack::user_record(&self.user_id(None, true), "me", owner),
+        );
+
+        let mut handle_ids: Vec<&i32> = self.handles.keys().collect();
+        handle_ids.sort();
+        for handle_id in handle_ids {
+            let id = self.user_id(Some(*handle_id), false);
+            users.entry(id.clone()).or_insert_with(|| {
+                Slack::user_record(
+                    &id,
+                    &self.handles[handle_id],
+                    self.config.who(Some(*handle_id), false, &None),
+                )
+            });
+        }
+
+        self.write_json(USERS, &Value::Array(users.into_values().collect()))
+    }
+
+    /// Build a user record
+    fn user_record(id: &str, name: &str, real_name: &str) -> Value {
+        json!({
+            "id": id,
+            "name": name,
+            "real_name": real_name,
+            "deleted": false,
+            "is_bot": false,
+            "profile": {
+                "real_name": real_name,
+                "display_name": real_name,
+            },
+        })
+    }
+
+    /// Build a Slack user ID for a participant, using the deduplicated handle so merged contacts share an ID
+    ///
+    /// Deduplicated IDs start at 0, so they are shifted to leave 0 for the database owner. Handles missing from the
+    /// handle table get IDs built from the raw handle, so they are never credited to the database owner.
+    fn user_id(&self, handle_id: Option<i32>, is_from_me: bool) -> String {
+        if is_from_me {
+            return format!("U{SELF_ID:08}");
+        }
+        match handle_id.and_then(|id| self.config.real_participants.get(&id)) {
+            Some(id) => format!("U{:08}", id + 1),
+            None => format!("UH{:07}", handle_id.unwrap_or_default()),
+        }
+    }
+
+    /// Build a Slack channel ID for the conversation a message belongs to
+    ///
+    /// Deduplicated IDs start at 0, so they are shifted to leave 0 for orphaned messages.
+    fn channel_id(&self, message: &Message) -> String {
+        let id = match self.config.conversation(message) {
+            Some((_, id)) => id + 1,
+            None => SELF_ID,
+        };
+        format!("C{id:08}")
+    }
+
+    /// Get the name of a channel record
+    fn channel_name(channel: &Value) -> String {
+        channel["name"].as_str().unwrap_or_default().to_string()
+    }
+
+    /// Build the channel record for the conversation a message belongs to
+    fn channel_record(&self, message: &Message) -> Value {
+        let (name, topic, purpose, members) = match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let mut members: Vec<String> = self
+                    .config
+                    .chatroom_participants
+                    .get(&chatroom.rowid)
+                    .map(|participants| {
+                        participants
+                            .iter()
+                            .filter(|handle_id| self.config.is_exported_participant(handle_id))
+                            .map(|handle_id| self.user_id(Some(*handle_id), false))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                members.push(self.user_id(None, true));
+                members.sort();
+                members.dedup();
+                (
+                    self.unique_channel_name(&self.config.filename(chatroom)),
+                    chatroom.display_name().unwrap_or_default().to_string(),
+                    chatroom.chat_identifier.clone(),
+                    members,
+                )
+            }
+            None => (
+                self.unique_channel_name(ORPHANED),
+                String::new(),
+                String::new(),
+                vec![self.user_id(None, true)],
+            ),
+        };
+
+        json!({
+            "id": self.channel_id(message),
+            "name": name,
+            "created": get_local_time(&message.date, &self.config.offset)
+                .map(|date| date.timestamp())
+                .unwrap_or_default(),
+            "creator": self.user_id(None, true),
+            "is_archived": false,
+            "is_general": false,
+            "members": members,
+            "topic": {"value": topic, "creator": "", "last_set": 0},
+            "purpose": {"value": purpose, "creator": "", "last_set": 0},
+        })
+    }
+
+    /// Build a channel name that follows Slack's rules and is not used by another channel
+    ///
+    /// Channel names are lowercase, contain no spaces or periods, and are at most 80 characters long.
+    fn unique_channel_name(&self, name: &str) -> String {
+        let mut slug = String::with_capacity(name.len());
+        for c in name.chars().flat_map(char::to_lowercase) {
+            if c.is_alphanumeric() || c == '_' {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        let mut slug: String = slug
+            .trim_end_matches('-')
+            .chars()
+            .take(MAX_CHANNEL_NAME_LENGTH)
+            .collect();
+        if slug.is_empty() {
+            slug = String::from("conversation");
+        }
+
+        let mut unique = slug.clone();
+        let mut suffix = 1;
+        while self.channel_names.contains(&unique) {
+            suffix += 1;
+            unique = format!("{slug}-{suffix}");
+        }
+        unique
+    }
+
+    /// Build a Slack message timestamp, which is the Unix time with microsecond precision
+    ///
+    /// Slack uses timestamps as message IDs, so the sub-second part keeps messages sent in the same second distinct.
+    fn ts(stamp: &i64, offset: &i64) -> Option<String> {
+        if *stamp == 0 {
+            return None;
+        }
+        get_local_time(stamp, offset).ok().map(|date| {
+            format!(
+                "{}.{:06}",
+                date.timestamp(),
+                stamp.rem_euclid(TIMESTAMP_FACTOR) / 1000
+            )
+        })
+    }
+
+    /// Fields shared by every record for a message
+    fn base_record(&self, message: &Message) -> Map<String, Value> {
+        let name = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+
+        let mut record = Map::new();
+        record.insert("type".to_string(), json!("message"));
+        record.insert(
+            "user".to_string(),
+            json!(self.user_id(message.handle_id, message.is_from_me())),
+        );
+        record.insert(
+            "user_profile".to_string(),
+            json!({"real_name": name, "display_name": name}),
+        );
+        record.insert(
+            "ts".to_string(),
+            json!(Slack::ts(&message.date, &self.config.offset)),
+        );
+        record.insert("client_msg_id".to_string(), json!(message.guid));
+        record
+    }
+
+    /// Build the record for a message, including its thread, files, and reactions
+    fn message_record(&mut self, message: &Message) -> Result<Value, TableError> {
+        let mut record = self.base_record(message);
+        record.insert("text".to_string(), json!(self.text(message)));
+
+        if message.is_edited() {
+            record.insert(
+                "edited".to_string(),
+                json!({
+                    "user": self.user_id(message.handle_id, message.is_from_me()),
+                    "ts": Slack::ts(&message.date_edited, &self.config.offset),
+                }),
+            );
+        }
+
+        // Replies are threaded under the message they reply to
+        if let Some(thread_ts) = message
+            .thread_originator_guid
+            .as_ref()
+            .and_then(|guid| self.threads.get(guid))
+        {
+            record.insert("thread_ts".to_string(), json!(thread_ts));
+        }
+        if message.has_replies() {
+            self.thread_record(message, &mut record)?;
+        }
+
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let files: Vec<Value> = attachments
+            .iter_mut()
+            .map(|attachment| self.file_record(attachment, message))
+            .collect();
+        if !files.is_empty() {
+            record.insert("files".to_string(), Value::Array(files));
+        }
+
+        let reactions = self.reactions(message);
+        if !reactions.is_empty() {
+            record.insert("reactions".to_string(), Value::Array(reactions));
+        }
+
+        Ok(Value::Object(record))
+    }
+
+    /// Add the fields Slack uses to describe the replies to the message that starts a thread
+    fn thread_record(
+        &mut self,
+        message: &Message,
+        record: &mut Map<String, Value>,
+    ) -> Result<(), TableError> {
+        let thread_ts = match Slack::ts(&message.date, &self.config.offset) {
+            Some(ts) => ts,
+            None => return Ok(()),
+        };
+
+        let mut replies: Vec<Message> = message
+            .get_replies(&self.config.db)?
+            .into_values()
+            .flatten()
+            .filter(|reply| !reply.is_tapback())
+            .collect();
+        replies.sort_by_key(|reply| reply.date);
+
+        let reply_records: Vec<Value> = replies
+            .iter()
+            .map(|reply| {
+                json!({
+                    "user": self.user_id(reply.handle_id, reply.is_from_me()),
+                    "ts": Slack::ts(&reply.date, &self.config.offset),
+                })
+            })
+            .collect();
+        let mut reply_users: Vec<String> = replies
+            .iter()
+            .map(|reply| self.user_id(reply.handle_id, reply.is_from_me()))
+            .collect();
+        reply_users.sort();
+        reply_users.dedup();
+
+        record.insert("thread_ts".to_string(), json!(thread_ts));
+        record.insert("reply_count".to_string(), json!(reply_records.len()));
+        record.insert("reply_users_count".to_string(), json!(reply_users.len()));
+        record.insert("reply_users".to_string(), json!(reply_users));
+        if let Some(latest) = reply_records.last() {
+            record.insert("latest_reply".to_string(), latest["ts"].clone());
+        }
+        record.insert("replies".to_string(), Value::Array(reply_records));
+
+        self.threads.insert(message.guid.clone(), thread_ts);
+        Ok(())
+    }
+
+    /// Build the record for a group announcement
+    fn announcement_record(&self, message: &Message) -> Value {
+        let mut record = self.base_record(message);
+        let mut who = self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id,
+        );
+        // Rename yourself so we render the proper grammar here
+        if message.is_from_me() {
+            who = self.config.options.custom_name.as_deref().unwrap_or(YOU);
+        }
+
+        let text = match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => {
+                record.insert("subtype".to_string(), json!("channel_name"));
+                record.insert("name".to_string(), json!(name));
+                format!("{who} renamed the conversation to {name}")
+            }
+            Some(Announcement::PhotoChange) => format!("{who} changed the group photo."),
+            Some(Announcement::Unknown(num)) => format!("{who} performed unknown action {num}."),
+            Some(Announcement::FullyUnsent) => format!("{who} unsent a message!"),
+            None => String::from("Unable to format announcement!"),
+        };
+        record.insert("text".to_string(), json!(Slack::escape(&text)));
+
+        Value::Object(record)
+    }
+
+    /// Describe an attachment as a Slack file, copying it if requested
+    fn file_record(&self, attachment: &mut Attachment, message: &Message) -> Value {
+        let path = self
+            .config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .map(|_| self.config.message_attachment_path(attachment));
+        let name = attachment
+            .transfer_name
+            .as_deref()
+            .unwrap_or(attachment.filename())
+            .to_string();
+
+        json!({
+            "id": format!("F{:08}", attachment.rowid),
+            "name": name,
+            "title": name,
+            "mimetype": attachment.mime_type,
+            "filetype": Path::new(&name).extension().and_then(|ext| ext.to_str()),
+            "size": attachment.total_bytes,
+            "url_private": path,
+        })
+    }
+
+    /// Build the text of a message in Slack's `mrkdwn` format
+    fn text(&self, message: &Message) -> String {
+        if message.is_shareplay() {
+            return String::from("SharePlay Message Ended");
+        }
+        if message.started_sharing_location() {
+            return String::from("Started sharing location!");
+        } else if message.stopped_sharing_location() {
+            return String::from("Stopped sharing location!");
+        }
+
+        let text = message.text.as_deref().unwrap_or_default();
+        let mut out_s = String::with_capacity(text.len());
+        for (idx, component) in message.body().iter().enumerate() {
+            match component {
+                BubbleComponent::Text(ranges) => {
+                    // Unsent parts are not shown in the conversation, so they have no text
+                    if message
+                        .edited_parts
+                        .as_ref()
+                        .and_then(|edited| edited.part(idx))
+                        .is_some_and(|part| matches!(part.status, EditStatus::Unsent))
+                    {
+                        continue;
+                    }
+                    for range in ranges {
+                        if let Some(part) = text.get(range.start..range.end) {
+                            // Attachments are exported as files, so drop their placeholders
+                            let part = Slack::escape(&part.replace('\u{FFFC}', ""));
+                            out_s.push_str(&self.format_effect(&part, &range.effect));
+                        }
+                    }
+                }
+                BubbleComponent::App => {
+                    let description = self.app_text(message).unwrap_or_else(|_| text.to_string());
+                    out_s.push_str(&Slack::escape(&description));
+                }
+                BubbleComponent::Attachment(_) | BubbleComponent::Retracted => {}
+            }
+        }
+        out_s.trim().to_string()
+    }
+
+    /// Format a range of escaped text with its effect
+    fn format_effect(&self, text: &str, effect: &TextEffect) -> String {
+        match effect {
+            TextEffect::Default => text.to_string(),
+            TextEffect::Mention(mentioned) => self.format_mention(text, mentioned),
+            TextEffect::Link(url) => self.format_link(text, url),
+            TextEffect::OTP => self.format_otp(text),
+            TextEffect::Styles(styles) => self.format_styles(text, styles),
+            TextEffect::Animated(animation) => self.format_animated(text, animation),
+            TextEffect::Conversion(unit) => self.format_conversion(text, unit),
+        }
+    }
+
+    /// Describe an app message with its most specific caption, since Slack has no equivalent
+    fn app_text(&self, message: &Message) -> Result<String, PlistParseError> {
+        let payload = match message.payload_data(&self.config.db) {
+            Some(payload) => payload,
+            // Sometimes, URL messages are missing their payloads
+            None => return Ok(message.text.clone().unwrap_or_default()),
+        };
+        let parsed = parse_plist(&payload)?;
+        let description = if message.is_url() {
+            match URLMessage::get_url_message_override(&parsed)? {
+                URLOverride::Normal(balloon) => {
+                    balloon.get_url().or(balloon.title).map(str::to_string)
+                }
+                URLOverride::AppleMusic(balloon) => balloon.url.map(str::to_string),
+                URLOverride::Collaboration(balloon) => balloon.get_url().map(str::to_string),
+                URLOverride::AppStore(balloon) => balloon.url.map(str::to_string),
+                URLOverride::SharedPlacemark(balloon) => balloon.get_url().map(str::to_string),
+            }
+        } else {
+            let balloon = AppMessage::from_map(&parsed)?;
+            balloon
+                .ldtext
+                .or(balloon.title)
+                .or(balloon.caption)
+                .or(balloon.app_name)
+                .map(str::to_string)
+        };
+        Ok(description.or(message.text.clone()).unwrap_or_default())
+    }
+
+    /// Build Slack reactions from the tapbacks on a message, dropping tapbacks that were later removed
+    fn reactions(&self, message: &Message) -> Vec<Value> {
+        let tapbacks_map = match self.config.tapbacks.get(&message.guid) {
+            Some(tapbacks_map) => tapbacks_map,
+            None => return vec![],
+        };
+        let mut parts: Vec<_> = tapbacks_map.iter().collect();
+        parts.sort_by_key(|(idx, _)| **idx);
+
+        // Reaction names and the users who reacted with them, in the order they were first used
+        let mut reactions: Vec<(String, Vec<String>)> = vec![];
+        for (_, tapbacks) in parts {
+            for tapback in tapbacks {
+                if let Variant::Tapback(_, added, kind) = tapback.variant() {
+                    let name = Slack::reaction_name(&kind);
+                    let user = self.user_id(tapback.handle_id, tapback.is_from_me());
+                    let position = reactions.iter().position(|(n, _)| *n == name);
+                    match (added, position) {
+                        (true, Some(position)) => {
+                            if !reactions[position].1.contains(&user) {
+                                reactions[position].1.push(user);
+                            }
+                        }
+                        (true, None) => reactions.push((name, vec![user])),
+                        (false, Some(position)) => reactions[position].1.retain(|u| *u != user),
+                        (false, None) => {}
+                    }
+                }
+            }
+        }
+
+        reactions
+            .into_iter()
+            .filter(|(_, users)| !users.is_empty())
+            .map(|(name, users)| {
+                json!({
+                    "name": name,
+                    "users": users,
+                    "count": users.len(),
+                })
+            })
+            .collect()
+    }
+
+    /// Get the name Slack uses for the emoji of a tapback
+    ///
+    /// Slack does not have short names for every emoji, so custom emoji tapbacks use the emoji itself.
+    fn reaction_name(tapback: &Tapback) -> String {
+        match tapback {
+            Tapback::Loved => "heart",
+            Tapback::Liked => "+1",
+            Tapback::Disliked => "-1",
+            Tapback::Laughed => "joy",
+            Tapback::Emphasized => "bangbang",
+            Tapback::Questioned => "question",
+            Tapback::Emoji(emoji) => emoji.unwrap_or("grey_question"),
+        }
+        .to_string()
+    }
+
+    /// Escape the characters Slack uses for control sequences
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    /// Surround text with delimiters, keeping leading and trailing whitespace outside of them
+    ///
+    /// Formatting delimiters are not recognized if they are adjacent to whitespace on the inside.
+    fn wrap(text: &str, open: &str, close: &str) -> String {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            return text.to_string();
+        }
+        let start = text.len() - text.trim_start().len();
+        let end = start + trimmed.len();
+        format!("{}{open}{trimmed}{close}{}", &text[..start], &text[end..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::set_var;
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::TextEffectFormatter,
+        Exporter, Slack,
+    };
+    use imessage_database::{
+        message_types::{text_effects::Style, variants::Tapback},
+        util::dates::get_offset,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Slack);
+        let config = fake_config(options);
+        let exporter = Slack::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_build_message_record() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Slack);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        config.real_participants.insert(999999, 4);
+        let mut exporter = Slack::new(&config).unwrap();
+
+        let mut message = blank();
+        message.guid = "ABC-123".to_string();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello <world> & you".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.message_record(&message).unwrap();
+        let expected = json!({
+            "type": "message",
+            "user": "U00000005",
+            "user_profile": {"real_name": "Sample Contact", "display_name": "Sample Contact"},
+            "ts": "1652833782.885055",
+            "client_msg_id": "ABC-123",
+            "text": "Hello &lt;world&gt; &amp; you",
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_thread_replies() {
+        let options = fake_options_for(ExportType::Slack);
+        let config = fake_config(options);
+        let mut exporter = Slack::new(&config).unwrap();
+        exporter
+            .threads
+            .insert("parent".to_string(), "1652833782.885055".to_string());
+
+        let mut reply = blank();
+        reply.date = 674526682885055488;
+        reply.text = Some("Reply".to_string());
+        reply.thread_originator_guid = Some("parent".to_string());
+
+        let actual = exporter.message_record(&reply).unwrap();
+
+        assert_eq!(actual["thread_ts"], json!("1652833782.885055"));
+        assert_eq!(actual["ts"], json!("1652833882.885055"));
+    }
+
+    #[test]
+    fn can_build_ts() {
+        let offset = get_offset();
+        assert_eq!(
+            Slack::ts(&674526582885055488, &offset),
+            Some("1652833782.885055".to_string())
+        );
+        assert_eq!(
+            Slack::ts(&674526582000000000, &offset),
+            Some("1652833782.000000".to_string())
+        );
+        assert_eq!(Slack::ts(&0, &offset), None);
+    }
+
+    #[test]
+    fn can_build_user_id() {
+        let options = fake_options_for(ExportType::Slack);
+        let mut config = fake_config(options);
+        config.real_participants.insert(10, 0);
+        config.real_participants.insert(11, 0);
+        let exporter = Slack::new(&config).unwrap();
+
+        assert_eq!(exporter.user_id(Some(10), false), "U00000001");
+        assert_eq!(exporter.user_id(Some(11), false), "U00000001");
+        assert_eq!(exporter.user_id(Some(0), true), "U00000000");
+        assert_eq!(exporter.user_id(Some(10), true), "U00000000");
+    }
+
+    #[test]
+    fn can_build_user_id_unknown_sender() {
+        let options = fake_options_for(ExportType::Slack);
+        let config = fake_config(options);
+        let exporter = Slack::new(&config).unwrap();
+
+        assert_eq!(exporter.user_id(Some(42), false), "UH0000042");
+        assert_eq!(exporter.user_id(None, false), "UH0000000");
+        assert_eq!(exporter.user_id(None, true), "U00000000");
+    }
+
+    #[test]
+    fn can_build_unique_channel_name() {
+        let options = fake_options_for(ExportType::Slack);
+        let config = fake_config(options);
+        let mut exporter = Slack::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.unique_channel_name("Family Chat - 12"),
+            "family-chat-12"
+        );
+        assert_eq!(
+            exporter.unique_channel_name("+1 (555) 867-5309"),
+            "1-555-867-5309"
+        );
+        assert_eq!(exporter.unique_channel_name("..."), "conversation");
+
+        exporter.channel_names.insert("family-chat".to_string());
+        assert_eq!(exporter.unique_channel_name("Family Chat"), "family-chat-2");
+    }
+
+    #[test]
+    fn can_format_styles() {
+        let options = fake_options_for(ExportType::Slack);
+        let config = fake_config(options);
+        let exporter = Slack::new(&config).unwrap();
+
+        assert_eq!(exporter.format_styles("Bold ", &[Style::Bold]), "*Bold* ");
+        assert_eq!(
+            exporter.format_styles("Both", &[Style::Bold, Style::Italic]),
+            "*_Both_*"
+        );
+        assert_eq!(
+            exporter.format_styles("Under", &[Style::Underline]),
+            "Under"
+        );
+    }
+
+    #[test]
+    fn can_format_link() {
+        let options = fake_options_for(ExportType::Slack);
+        let config = fake_config(options);
+        let exporter = Slack::new(&config).unwrap();
+
+        assert_eq!(
+            exporter.format_link("https://example.com", "https://example.com"),
+            "<https://example.com>"
+        );
+        assert_eq!(
+            exporter.format_link("here", "https://example.com"),
+            "<https://example.com|here>"
+        );
+    }
+
+    #[test]
+    fn can_format_mention() {
+        let options = fake_options_for(ExportType::Slack);
+        let mut config = fake_config(options);
+        config.real_participants.insert(7, 2);
+        let mut exporter = Slack::new(&config).unwrap();
+        exporter.handles.insert(7, "+15558675309".to_string());
+
+        assert_eq!(
+            exporter.format_mention("Sample", "+15558675309"),
+            "<@U00000003>"
+        );
+        assert_eq!(exporter.format_mention("Nobody", "+15550000000"), "Nobody");
+    }
+
+    #[test]
+    fn can_get_reaction_name() {
+        assert_eq!(Slack::reaction_name(&Tapback::Loved), "heart");
+        assert_eq!(Slack::reaction_name(&Tapback::Liked), "+1");
+        assert_eq!(Slack::reaction_name(&Tapback::Emoji(Some("🦀"))), "🦀");
+    }
+}
@@ -0,0 +1,1090 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        edited::EditStatus,
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        handwriting::HandwrittenMessage,
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        handle::Handle,
+        messages::{models::BubbleComponent, Message},
+        table::Table,
+    },
+    util::{
+        dates::{get_local_time, TIMESTAMP_FACTOR},
+        plist::parse_plist,
+    },
+};
+
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+
+/// Name of the export database file
+const DATABASE: &str = "messages";
+
+/// Statements that create the tables of the export database, see `resources/schema.sql` for column documentation
+const SCHEMA: &str = include_str!("resources/schema.sql");
+
+/// Exports messages to a normalized `SQLite` database with all binary fields decoded
+pub struct SQLite<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Connection to the export database
+    pub db: Connection,
+}
+
+/// Columns of a row in the `app_balloons` table
+#[derive(Default)]
+struct Balloon {
+    kind: &'static str,
+    bundle_id: Option<String>,
+    app_name: Option<String>,
+    url: Option<String>,
+    title: Option<String>,
+    subtitle: Option<String>,
+    caption: Option<String>,
+    details: Value,
+}
+
+impl<'a> SQLite<'a> {
+    /// Create a new exporter with references to the cached data
+    pub fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut path = config.options.export_path.clone();
+        path.push(DATABASE);
+        path.set_extension("sqlite");
+
+        let db = SQLite::create(&path)?;
+
+        Ok(SQLite { config, db })
+    }
+
+    /// Begin iterating over the messages table
+    pub fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as sqlite...",
+            self.config.options.export_path.display()
+        );
+
+        // Writing everything in a single transaction is much faster than committing each row
+        self.db
+            .execute_batch("BEGIN;")
+            .map_err(RuntimeError::ExportDatabaseError)?;
+
+        self.write_participants()?;
+        self.write_chats()?;
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are written alongside the messages they react to
+            if !msg.is_tapback() {
+                self.write_message(&msg)?;
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        self.db
+            .execute_batch("COMMIT;")
+            .map_err(RuntimeError::ExportDatabaseError)
+    }
+
+    /// Open the export database and create its tables
+    fn create(path: &Path) -> Result<Connection, RuntimeError> {
+        let db = Connection::open(path).map_err(RuntimeError::ExportDatabaseError)?;
+        // Filtered exports can reference rows that were not exported, i.e. replies to older messages
+        db.execute_batch("PRAGMA foreign_keys = OFF;")
+            .map_err(RuntimeError::ExportDatabaseError)?;
+        db.execute_batch(SCHEMA)
+            .map_err(RuntimeError::ExportDatabaseError)?;
+        Ok(db)
+    }
+
+    /// Convert an iMessage timestamp to an RFC 3339 string, omitting unset timestamps
+    fn date(&self, stamp: &i64) -> Option<String> {
+        if *stamp == 0 {
+            return None;
+        }
+        get_local_time(stamp, &self.config.offset)
+            .ok()
+            .map(|date| date.to_rfc3339())
+    }
+
+    /// Get the handle that sent a message, if it was not sent by the database owner
+    fn sender_id(message: &Message) -> Option<i32> {
+        match message.handle_id {
+            Some(0) | None => None,
+            Some(_) if message.is_from_me() => None,
+            handle_id => handle_id,
+        }
+    }
+
+    /// Name of the bubble or screen effect a message was sent with
+    fn expressive(message: &Message) -> Option<&str> {
+        match message.get_expressive() {
+            Expressive::Screen(effect) => Some(match effect {
+                ScreenEffect::Confetti => "Confetti",
+                ScreenEffect::Echo => "Echo",
+                ScreenEffect::Fireworks => "Fireworks",
+                ScreenEffect::Balloons => "Balloons",
+                ScreenEffect::Heart => "Heart",
+                ScreenEffect::Lasers => "Lasers",
+                ScreenEffect::ShootingStar => "ShootingStar",
+                ScreenEffect::Sparkles => "Sparkles",
+                ScreenEffect::Spotlight => "Spotlight",
+            }),
+            Expressive::Bubble(effect) => Some(match effect {
+                BubbleEffect::Slam => "Slam",
+                BubbleEffect::Loud => "Loud",
+                BubbleEffect::Gentle => "Gentle",
+                BubbleEffect::InvisibleInk => "InvisibleInk",
+            }),
+            Expressive::Unknown(effect) => Some(effect),
+            Expressive::None => None,
+        }
+    }
+
+    /// Name of the message's variant
+    fn variant(message: &Message) -> &'static str {
+        if message.is_announcement() {
+            return "announcement";
+        }
+        match message.variant() {
+            Variant::App(_) => "app",
+            Variant::Edited => "edited",
+            Variant::SharePlay => "shareplay",
+            Variant::Sticker(_) => "sticker",
+            Variant::Tapback(..) => "tapback",
+            Variant::Normal => "normal",
+            Variant::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Describe the type and value of an effect applied to a range of text
+    fn effect(effect: &TextEffect) -> (&'static str, Option<String>) {
+        match effect {
+            TextEffect::Default => ("default", None),
+            TextEffect::Mention(handle) => ("mention", Some(handle.to_string())),
+            TextEffect::Link(url) => ("link", Some(url.to_string())),
+            TextEffect::OTP => ("otp", None),
+            TextEffect::Styles(styles) => (
+                "styles",
+                Some(
+                    styles
+                        .iter()
+                        .map(|style| format!("{style:?}"))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+            ),
+            TextEffect::Animated(animation) => ("animated", Some(format!("{animation:?}"))),
+            TextEffect::Conversion(unit) => ("conversion", Some(format!("{unit:?}"))),
+        }
+    }
+
+    /// Write every exported chat and its members
+    fn write_chats(&self) -> Result<(), RuntimeError> {
+        let mut insert_chat = self
+            .db
+            .prepare(
+                "INSERT INTO chats (id, identifier, service, display_name, conversation_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(RuntimeError::ExportDatabaseError)?;
+        for (id, chat) in &self.config.chatrooms {
+            insert_chat
+                .execute(params![
+                    id,
+                    chat.chat_identifier,
+                    chat.service_name,
+                    chat.display_name(),
+                    self.config.real_chatrooms.get(id),
+                ])
+                .map_err(RuntimeError::ExportDatabaseError)?;
+        }
+
+        let mut insert_member = self
+            .db
+            .prepare("INSERT INTO chat_participants (chat_id, participant_id) VALUES (?1, ?2)")
+            .map_err(RuntimeError::ExportDatabaseError)?;
+        for (chat_id, members) in &self.config.chatroom_participants {
+            for participant_id in members
+                .iter()
+                .filter(|id| self.config.is_exported_participant(id))
+            {
+                insert_member
+                    .execute(params![chat_id, participant_id])
+                    .map_err(RuntimeError::ExportDatabaseError)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write every exported handle, including the raw values that the participant cache merges together
+    fn write_participants(&self) -> Result<(), RuntimeError> {
+        let mut insert = self
+            .db
+            .prepare(
+                "INSERT INTO participants (id, handle, person_centric_id, name, contact_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(RuntimeError::ExportDatabaseError)?;
+
+        let mut statement = Handle::get(&self.config.db).map_err(RuntimeError::DatabaseError)?;
+        let rows = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+        for row in rows {
+            let handle = Handle::extract(row).map_err(RuntimeError::DatabaseError)?;
+            // Skip people who are excluded or only in chats that the filters leave out of the export
+            if !self.config.is_exported_participant(&handle.rowid) {
+                continue;
+            }
+            insert
+                .execute(params![
+                    handle.rowid,
+                    handle.id,
+                    handle.person_centric_id,
+                    self.config.participants.get(&handle.rowid),
+                    self.config.real_participants.get(&handle.rowid),
+                ])
+                .map_err(RuntimeError::ExportDatabaseError)?;
+        }
+        Ok(())
+    }
+
+    /// Write a message and everything decoded from it
+    fn write_message(&self, message: &Message) -> Result<(), RuntimeError> {
+        let (announcement, group_title) = match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => (Some("name_change"), Some(name)),
+            Some(Announcement::PhotoChange) => (Some("photo_change"), None),
+            Some(Announcement::FullyUnsent) => (Some("fully_unsent"), None),
+            Some(Announcement::Unknown(_)) => (Some("unknown"), None),
+            None => (None, None),
+        };
+        let shared_location = if message.started_sharing_location() {
+            Some("started")
+        } else if message.stopped_sharing_location() {
+            Some("stopped")
+        } else {
+            None
+        };
+
+        self.db
+            .prepare_cached(
+                "INSERT INTO messages (
+                    id, guid, chat_id, sender_id, is_from_me, sender, service, variant, subject, text,
+                    date, date_delivered, date_read, date_edited, is_read, is_deleted,
+                    expressive, announcement, group_title, shared_location
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+            )
+            .and_then(|mut statement| {
+                statement.execute(params![
+                    message.rowid,
+                    message.guid,
+                    message.chat_id.or(message.deleted_from),
+                    SQLite::sender_id(message),
+                    message.is_from_me(),
+                    self.config.who(
+                        message.handle_id,
+                        message.is_from_me(),
+                        &message.destination_caller_id,
+                    ),
+                    message.service,
+                    SQLite::variant(message),
+                    message.subject,
+                    message.text,
+                    self.date(&message.date),
+                    self.date(&message.date_delivered),
+                    self.date(&message.date_read),
+                    self.date(&message.date_edited),
+                    message.is_read,
+                    message.is_deleted(),
+                    SQLite::expressive(message),
+                    announcement,
+                    group_title,
+                    shared_location,
+                ])
+            })
+            .map_err(RuntimeError::ExportDatabaseError)?;
+
+        // Announcements do not have a body
+        if message.is_announcement() {
+            return Ok(());
+        }
+
+        let mut attachments = Attachment::from_message(&self.config.db, message)
+            .map_err(RuntimeError::DatabaseError)?;
+        for attachment in attachments.iter_mut() {
+            self.write_attachment(attachment, message)?;
+        }
+
+        let mut replies = message
+            .get_replies(&self.config.db)
+            .map_err(RuntimeError::DatabaseError)?;
+
+        // Index of where we are in the attachment Vector
+        let mut attachment_index: usize = 0;
+
+        for (idx, message_part) in message.body().iter().enumerate() {
+            let (kind, text, attachment_id) = match message_part {
+                BubbleComponent::Text(text_attrs) => {
+                    for text_attr in text_attrs {
+                        let (effect, value) = SQLite::effect(&text_attr.effect);
+                        self.db
+                            .prepare_cached(
+                                "INSERT INTO text_effects (message_id, part_index, start, end, text, effect, value) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                            )
+                            .and_then(|mut statement| {
+                                statement.execute(params![
+                                    message.rowid,
+                                    idx,
+                                    text_attr.start,
+                                    text_attr.end,
+                                    message
+                                        .text
+                                        .as_ref()
+                                        .and_then(|text| text.get(text_attr.start..text_attr.end)),
+                                    effect,
+                                    value,
+                                ])
+                            })
+                            .map_err(RuntimeError::ExportDatabaseError)?;
+                    }
+
+                    // The part's text spans from the start of its first range to the end of its last
+                    let text = match (text_attrs.first(), text_attrs.last()) {
+                        (Some(first), Some(last)) => message
+                            .text
+                            .as_ref()
+                            .and_then(|text| text.get(first.start..last.end)),
+                        _ => None,
+                    };
+                    ("text", text, None)
+                }
+                BubbleComponent::Attachment(_) => {
+                    let attachment_id = attachments.get(attachment_index).map(|attachment| {
+                        // Stickers do not advance the attachment index
+                        if !attachment.is_sticker {
+                            attachment_index += 1;
+                        }
+                        attachment.rowid
+                    });
+                    ("attachment", None, attachment_id)
+                }
+                BubbleComponent::App => {
+                    if let Ok(balloon) = self.balloon(message) {
+                        self.write_balloon(message, &balloon)?;
+                    }
+                    ("app", None, None)
+                }
+                BubbleComponent::Retracted => ("retracted", None, None),
+            };
+
+            self.db
+                .prepare_cached(
+                    "INSERT INTO message_parts (message_id, part_index, type, text, attachment_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+                )
+                .and_then(|mut statement| {
+                    statement.execute(params![message.rowid, idx, kind, text, attachment_id])
+                })
+                .map_err(RuntimeError::ExportDatabaseError)?;
+
+            self.write_edits(message, idx)?;
+
+            // Handle Tapbacks
+            if let Some(tapbacks_map) = self.config.tapbacks.get(&message.guid) {
+                if let Some(tapbacks) = tapbacks_map.get(&idx) {
+                    for tapback in tapbacks {
+                        self.write_tapback(tapback, message, idx)?;
+                    }
+                }
+            }
+
+            // Handle Replies
+            if let Some(replies) = replies.get_mut(&idx) {
+                for reply in replies.iter().filter(|reply| !reply.is_tapback()) {
+                    self.db
+                        .prepare_cached(
+                            "INSERT OR IGNORE INTO replies (message_id, parent_id, part_index) VALUES (?1, ?2, ?3)",
+                        )
+                        .and_then(|mut statement| {
+                            statement.execute(params![reply.rowid, message.rowid, idx])
+                        })
+                        .map_err(RuntimeError::ExportDatabaseError)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write an attachment, copying it if requested
+    fn write_attachment(
+        &self,
+        attachment: &mut Attachment,
+        message: &Message,
+    ) -> Result<(), RuntimeError> {
+        let sticker_effect = if attachment.is_sticker {
+            attachment
+                .get_sticker_effect(
+                    &self.config.options.platform,
+                    &self.config.options.db_path,
+                    self.config.options.attachment_root.as_deref(),
+                )
+                .ok()
+                .flatten()
+                .map(|effect| effect.to_string())
+        } else {
+            None
+        };
+
+        // Copy the file, if requested
+        let path = self
+            .config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .map(|_| self.config.message_attachment_path(attachment));
+
+        self.db
+            .prepare_cached(
+                "INSERT OR IGNORE INTO attachments (
+                    id, message_id, filename, transfer_name, mime_type, uti, total_bytes, is_sticker, sticker_effect, path
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )
+            .and_then(|mut statement| {
+                statement.execute(params![
+                    attachment.rowid,
+                    message.rowid,
+                    attachment.filename,
+                    attachment.transfer_name,
+                    attachment.mime_type,
+                    attachment.uti,
+                    attachment.total_bytes,
+                    attachment.is_sticker,
+                    sticker_effect,
+                    path,
+                ])
+            })
+            .map_err(RuntimeError::ExportDatabaseError)?;
+        Ok(())
+    }
+
+    /// Write a tapback or sticker placed on a part of a message
+    fn write_tapback(
+        &self,
+        tapback: &Message,
+        message: &Message,
+        part_index: usize,
+    ) -> Result<(), RuntimeError> {
+        let (kind, name, added, attachment_id) = match tapback.variant() {
+            Variant::Tapback(_, added, tapback) => {
+                ("tapback", Some(tapback.to_string()), added, None)
+            }
+            Variant::Sticker(_) => {
+                let mut stickers = Attachment::from_message(&self.config.db, tapback)
+                    .map_err(RuntimeError::DatabaseError)?;
+                // Sticker messages have only one attachment, the sticker image
+                let attachment_id = match stickers.get_mut(0) {
+                    Some(sticker) => {
+                        self.write_attachment(sticker, tapback)?;
+                        Some(sticker.rowid)
+                    }
+                    None => None,
+                };
+                ("sticker", None, true, attachment_id)
+            }
+            _ => unreachable!(),
+        };
+
+        self.db
+            .prepare_cached(
+                "INSERT OR IGNORE INTO tapbacks (
+                    id, message_id, part_index, sender_id, is_from_me, sender, type, tapback, added, attachment_id, date
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )
+            .and_then(|mut statement| {
+                statement.execute(params![
+                    tapback.rowid,
+                    message.rowid,
+                    part_index,
+                    SQLite::sender_id(tapback),
+                    tapback.is_from_me(),
+                    self.config.who(
+                        tapback.handle_id,
+                        tapback.is_from_me(),
+                        &tapback.destination_caller_id,
+                    ),
+                    kind,
+                    name,
+                    added,
+                    attachment_id,
+                    self.date(&tapback.date),
+                ])
+            })
+            .map_err(RuntimeError::ExportDatabaseError)?;
+        Ok(())
+    }
+
+    /// Write the edit history of a message part
+    fn write_edits(&self, message: &Message, part_index: usize) -> Result<(), RuntimeError> {
+        let Some(edited_part) = message
+            .edited_parts
+            .as_ref()
+            .and_then(|edited| edited.part(part_index))
+        else {
+            return Ok(());
+        };
+
+        let mut insert = self
+            .db
+            .prepare_cached(
+                "INSERT INTO edits (message_id, part_index, revision, status, date, text, guid) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )
+            .map_err(RuntimeError::ExportDatabaseError)?;
+
+        match edited_part.status {
+            EditStatus::Edited => {
+                for (revision, event) in edited_part.edit_history.iter().enumerate() {
+                    insert
+                        .execute(params![
+                            message.rowid,
+                            part_index,
+                            revision,
+                            "edited",
+                            self.date(&event.date),
+                            event.text,
+                            event.guid,
+                        ])
+                        .map_err(RuntimeError::ExportDatabaseError)?;
+                }
+            }
+            EditStatus::Unsent => {
+                insert
+                    .execute(params![
+                        message.rowid,
+                        part_index,
+                        0,
+                        "unsent",
+                        self.date(&message.date_edited),
+                        None::<&str>,
+                        None::<&str>,
+                    ])
+                    .map_err(RuntimeError::ExportDatabaseError)?;
+            }
+            EditStatus::Original => {}
+        }
+        Ok(())
+    }
+
+    /// Write the decoded payload of an app message
+    fn write_balloon(&self, message: &Message, balloon: &Balloon) -> Result<(), RuntimeError> {
+        self.db
+            .prepare_cached(
+                "INSERT OR IGNORE INTO app_balloons (
+                    message_id, type, bundle_id, app_name, url, title, subtitle, caption, details
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )
+            .and_then(|mut statement| {
+                statement.execute(params![
+                    message.rowid,
+                    balloon.kind,
+                    balloon.bundle_id,
+                    balloon.app_name,
+                    balloon.url,
+                    balloon.title,
+                    balloon.subtitle,
+                    balloon.caption,
+                    balloon.details.to_string(),
+                ])
+            })
+            .map_err(RuntimeError::ExportDatabaseError)?;
+        Ok(())
+    }
+
+    /// Parse an app message's payload into the columns of the `app_balloons` table
+    fn balloon(&self, message: &Message) -> Result<Balloon, PlistParseError> {
+        let Variant::App(custom_balloon) = message.variant() else {
+            return Err(PlistParseError::WrongMessageType);
+        };
+
+        // Handwritten messages use a different payload type, so check that first
+        if message.is_handwriting() {
+            if let Some(payload) = message.raw_payload_data(&self.config.db) {
+                return match HandwrittenMessage::from_payload(&payload) {
+                    Ok(handwriting) => Ok(self.handwriting_balloon(message, &handwriting)),
+                    Err(why) => Err(PlistParseError::HandwritingError(why)),
+                };
+            }
+        }
+
+        let Some(payload) = message.payload_data(&self.config.db) else {
+            // Sometimes, URL messages are missing their payloads
+            if message.is_url() {
+                if let Some(text) = &message.text {
+                    return Ok(Balloon {
+                        kind: "url",
+                        url: Some(text.to_string()),
+                        ..Default::default()
+                    });
+                }
+            }
+            return Err(PlistParseError::NoPayload);
+        };
+
+        let parsed = parse_plist(&payload)?;
+
+        // Handle URL messages separately since they are a special case
+        if message.is_url() {
+            return Ok(match URLMessage::get_url_message_override(&parsed)? {
+                URLOverride::Normal(balloon) => Balloon {
+                    kind: "url",
+                    url: balloon
+                        .get_url()
+                        .or(message.text.as_deref())
+                        .map(String::from),
+                    title: balloon.title.map(String::from),
+                    subtitle: balloon.summary.map(String::from),
+                    details: json!({
+                        "site_name": balloon.site_name,
+                        "images": balloon.images,
+                        "icons": balloon.icons,
+                    }),
+                    ..Default::default()
+                },
+                URLOverride::AppleMusic(balloon) => Balloon {
+                    kind: "music",
+                    url: balloon.url.map(String::from),
+                    title: balloon.track_name.map(String::from),
+                    subtitle: balloon.artist.map(String::from),
+                    details: json!({
+                        "preview": balloon.preview,
+                        "track_name": balloon.track_name,
+                        "album": balloon.album,
+                        "artist": balloon.artist,
+                    }),
+                    ..Default::default()
+                },
+                URLOverride::Collaboration(balloon) => Balloon {
+                    kind: "collaboration",
+                    bundle_id: balloon.bundle_id.map(String::from),
+                    app_name: balloon.app_name.map(String::from),
+                    url: balloon.get_url().map(String::from),
+                    title: balloon.title.map(String::from),
+                    details: json!({"creation_date": balloon.creation_date}),
+                    ..Default::default()
+                },
+                URLOverride::AppStore(balloon) => Balloon {
+                    kind: "app_store",
+                    app_name: balloon.app_name.map(String::from),
+                    url: balloon.url.map(String::from),
+                    title: balloon.app_name.map(String::from),
+                    subtitle: balloon.description.map(String::from),
+                    details: json!({
+                        "platform": balloon.platform,
+                        "genre": balloon.genre,
+                    }),
+                    ..Default::default()
+                },
+                URLOverride::SharedPlacemark(balloon) => Balloon {
+                    kind: "placemark",
+                    url: balloon.get_url().map(String::from),
+                    title: balloon.place_name.map(String::from),
+                    subtitle: balloon.placemark.address.map(String::from),
+                    details: json!({
+                        "name": balloon.placemark.name,
+                        "street": balloon.placemark.street,
+                        "city": balloon.placemark.city,
+                        "state": balloon.placemark.state,
+                        "postal_code": balloon.placemark.postal_code,
+                        "country": balloon.placemark.country,
+                        "iso_country_code": balloon.placemark.iso_country_code,
+                        "sub_administrative_area": balloon.placemark.sub_administrative_area,
+                        "sub_locality": balloon.placemark.sub_locality,
+                    }),
+                    ..Default::default()
+                },
+            });
+        }
+
+        let app = AppMessage::from_map(&parsed)?;
+        Ok(match custom_balloon {
+            CustomBalloon::Application(bundle_id) => {
+                SQLite::app_balloon("app", Some(bundle_id), &app)
+            }
+            CustomBalloon::ApplePay => SQLite::app_balloon("apple_pay", None, &app),
+            CustomBalloon::Fitness => SQLite::app_balloon("fitness", None, &app),
+            CustomBalloon::Slideshow => SQLite::app_balloon("slideshow", None, &app),
+            CustomBalloon::FindMy => SQLite::app_balloon("find_my", None, &app),
+            CustomBalloon::CheckIn => SQLite::check_in_balloon(&app),
+            CustomBalloon::Handwriting => unreachable!(),
+            CustomBalloon::URL => unreachable!(),
+        })
+    }
+
+    fn app_balloon(kind: &'static str, bundle_id: Option<&str>, balloon: &AppMessage) -> Balloon {
+        Balloon {
+            kind,
+            bundle_id: bundle_id.map(String::from),
+            app_name: balloon.app_name.map(String::from),
+            url: balloon.url.map(String::from),
+            title: balloon.title.map(String::from),
+            subtitle: balloon.subtitle.map(String::from),
+            caption: balloon.caption.map(String::from),
+            details: json!({
+                "subcaption": balloon.subcaption,
+                "trailing_caption": balloon.trailing_caption,
+                "trailing_subcaption": balloon.trailing_subcaption,
+                "ldtext": balloon.ldtext,
+            }),
+        }
+    }
+
+    fn check_in_balloon(balloon: &AppMessage) -> Balloon {
+        let metadata: HashMap<&str, &str> = balloon.parse_query_string();
+
+        // Determine which stage of the check in the message represents
+        let (status, date_str) = if let Some(date_str) = metadata.get("estimatedEndTime") {
+            ("expected", Some(date_str))
+        } else if let Some(date_str) = metadata.get("triggerTime") {
+            ("expired", Some(date_str))
+        } else if let Some(date_str) = metadata.get("sendDate") {
+            ("checked_in", Some(date_str))
+        } else {
+            ("unknown", None)
+        };
+
+        let date = date_str
+            .map(|date_str| date_str.parse::<f64>().unwrap_or(0.) as i64 * TIMESTAMP_FACTOR)
+            .and_then(|date_stamp| get_local_time(&date_stamp, &0).ok())
+            .map(|date| date.to_rfc3339());
+
+        Balloon {
+            kind: "check_in",
+            app_name: balloon.app_name.map(String::from),
+            caption: balloon.caption.map(String::from),
+            details: json!({"status": status, "date": date}),
+            ..Default::default()
+        }
+    }
+
+    fn handwriting_balloon(&self, message: &Message, balloon: &HandwrittenMessage) -> Balloon {
+        let path = self
+            .config
+            .options
+            .attachment_manager
+            .handle_handwriting(message, balloon, self.config)
+            .and_then(|filepath| self.config.relative_path(PathBuf::from(&filepath)));
+
+        Balloon {
+            kind: "handwriting",
+            details: json!({
+                "id": balloon.id,
+                "path": path,
+                "width": balloon.width,
+                "height": balloon.height,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        path::Path,
+    };
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        Config, SQLite,
+    };
+    use imessage_database::{
+        message_types::text_effects::{Style, TextEffect},
+        tables::chat::Chat,
+    };
+
+    /// Build an exporter that writes to an in-memory database
+    fn fake_exporter(config: &Config) -> SQLite<'_> {
+        SQLite {
+            config,
+            db: SQLite::create(Path::new(":memory:")).unwrap(),
+        }
+    }
+
+    #[test]
+    fn can_create_schema() {
+        let options = fake_options_for(ExportType::Sqlite);
+        let config = fake_config(options);
+        let exporter = fake_exporter(&config);
+
+        let tables: i64 = exporter
+            .db
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tables, 11);
+    }
+
+    #[test]
+    fn can_skip_unselected_chats() {
+        let mut options = fake_options_for(ExportType::Sqlite);
+        options
+            .query_context
+            .set_selected_chat_ids(BTreeSet::from([2]));
+        let mut config = fake_config(options);
+        for rowid in [1, 2] {
+            config.chatrooms.insert(
+                rowid,
+                Chat {
+                    rowid,
+                    chat_identifier: format!("chat{rowid}"),
+                    service_name: Some("iMessage".to_string()),
+                    display_name: None,
+                },
+            );
+            config
+                .chatroom_participants
+                .insert(rowid, BTreeSet::from([rowid * 10]));
+            config.participants.insert(rowid * 10, format!("{rowid}0"));
+        }
+        config.prune_caches(&HashMap::new());
+        let exporter = fake_exporter(&config);
+
+        exporter.write_chats().unwrap();
+
+        let chats: Vec<i32> = exporter
+            .db
+            .prepare("SELECT id FROM chats")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(chats, vec![2]);
+
+        let members: Vec<i32> = exporter
+            .db
+            .prepare("SELECT participant_id FROM chat_participants")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(members, vec![20]);
+    }
+
+    #[test]
+    fn can_skip_excluded_chats_and_senders() {
+        let mut options = fake_options_for(ExportType::Sqlite);
+        options
+            .query_context
+            .set_excluded_chat_ids(BTreeSet::from([1]));
+        options
+            .query_context
+            .set_excluded_handle_ids(BTreeSet::from([21]));
+        let mut config = fake_config(options);
+        for rowid in [1, 2] {
+            config.chatrooms.insert(
+                rowid,
+                Chat {
+                    rowid,
+                    chat_identifier: format!("chat{rowid}"),
+                    service_name: Some("iMessage".to_string()),
+                    display_name: None,
+                },
+            );
+            config
+                .chatroom_participants
+                .insert(rowid, BTreeSet::from([rowid * 10, rowid * 10 + 1]));
+            config.participants.insert(rowid * 10, format!("{rowid}0"));
+            config
+                .participants
+                .insert(rowid * 10 + 1, format!("{rowid}1"));
+        }
+        config.prune_caches(&HashMap::new());
+        let exporter = fake_exporter(&config);
+
+        exporter.write_chats().unwrap();
+
+        let chats: Vec<i32> = exporter
+            .db
+            .prepare("SELECT id FROM chats")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(chats, vec![2]);
+
+        let members: Vec<i32> = exporter
+            .db
+            .prepare("SELECT participant_id FROM chat_participants")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(members, vec![20]);
+    }
+
+    #[test]
+    fn can_write_message_from_me() {
+        let options = fake_options_for(ExportType::Sqlite);
+        let config = fake_config(options);
+        let exporter = fake_exporter(&config);
+
+        let mut message = blank();
+        message.rowid = 1;
+        message.guid = "guid".to_string();
+        message.text = Some("Hello world".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(2);
+
+        exporter.write_message(&message).unwrap();
+
+        let row: (i32, Option<i32>, bool, String, String, Option<String>) = exporter
+            .db
+            .query_row(
+                "SELECT chat_id, sender_id, is_from_me, sender, variant, text FROM messages WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                2,
+                None,
+                true,
+                "Me".to_string(),
+                "normal".to_string(),
+                Some("Hello world".to_string())
+            )
+        );
+
+        let part: (String, Option<String>) = exporter
+            .db
+            .query_row(
+                "SELECT type, text FROM message_parts WHERE message_id = 1 AND part_index = 0",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(part, ("text".to_string(), Some("Hello world".to_string())));
+    }
+
+    #[test]
+    fn can_write_announcement_without_parts() {
+        let options = fake_options_for(ExportType::Sqlite);
+        let config = fake_config(options);
+        let exporter = fake_exporter(&config);
+
+        let mut message = blank();
+        message.rowid = 1;
+        message.group_title = Some("Hello world".to_string());
+
+        exporter.write_message(&message).unwrap();
+
+        let row: (String, String, String) = exporter
+            .db
+            .query_row(
+                "SELECT variant, announcement, group_title FROM messages WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                "announcement".to_string(),
+                "name_change".to_string(),
+                "Hello world".to_string()
+            )
+        );
+
+        let parts: i64 = exporter
+            .db
+            .query_row("SELECT COUNT(*) FROM message_parts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(parts, 0);
+    }
+
+    #[test]
+    fn can_get_sender_id() {
+        let mut message = blank();
+        message.handle_id = Some(5);
+        assert_eq!(SQLite::sender_id(&message), Some(5));
+
+        message.is_from_me = true;
+        assert_eq!(SQLite::sender_id(&message), None);
+
+        message.is_from_me = false;
+        message.handle_id = Some(0);
+        assert_eq!(SQLite::sender_id(&message), None);
+    }
+
+    #[test]
+    fn can_describe_effects() {
+        assert_eq!(SQLite::effect(&TextEffect::Default), ("default", None));
+        assert_eq!(
+            SQLite::effect(&TextEffect::Link("https://example.com")),
+            ("link", Some("https://example.com".to_string()))
+        );
+        assert_eq!(
+            SQLite::effect(&TextEffect::Styles(vec![Style::Bold, Style::Italic])),
+            ("styles", Some("Bold,Italic".to_string()))
+        );
+    }
+}
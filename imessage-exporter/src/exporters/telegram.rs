@@ -0,0 +1,937 @@
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        BTreeMap, HashMap, HashSet,
+    },
+    fs::{create_dir_all, remove_dir_all, File},
+    io::{copy, BufWriter, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    app::{error::RuntimeError, progress::build_progress_bar_export, runtime::Config},
+    exporters::exporter::Exporter,
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        edited::EditStatus,
+        text_effects::{Style, TextEffect},
+        url::URLMessage,
+        variants::{Announcement, BalloonProvider, Tapback, URLOverride, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        handle::Handle,
+        messages::{models::BubbleComponent, Message},
+        table::{Table, ORPHANED},
+    },
+    util::{dates::get_local_time, plist::parse_plist},
+};
+
+use serde_json::{json, Map, Value};
+
+/// Name of the file Telegram Desktop writes its machine-readable export to
+pub(crate) const RESULT: &str = "result.json";
+
+/// Directory that holds each chat's messages until they are assembled into the result file
+pub(crate) const FRAGMENTS: &str = ".telegram";
+
+/// Chat ID used for messages that do not belong to a conversation
+const ORPHANED_CHAT_ID: i32 = 0;
+
+/// User ID used for the database owner
+const SELF_USER_ID: i32 = 0;
+
+/// Placeholder Telegram Desktop writes when a file was not included in the export
+const FILE_NOT_INCLUDED: &str = "(File not included. Change data exporting settings to download.)";
+
+/// Exports messages in the layout of Telegram Desktop's `result.json` so tools built for Telegram archives can read them
+pub struct Telegram<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of deduplicated chat ID to a buffered writer holding that chat's messages
+    pub files: HashMap<i32, BufWriter<File>>,
+    /// Writer instance for orphaned messages
+    pub orphaned: BufWriter<File>,
+    /// Name, type, and ID of each chat that has messages, in the order they are listed
+    chats: BTreeMap<i32, Value>,
+    /// Chats that have already received at least one message
+    started: HashSet<i32>,
+    /// Telegram message IDs of messages that have replies, so replies can point at them
+    reply_targets: HashMap<String, u64>,
+    /// Telegram message ID given to the next message written
+    next_id: u64,
+    /// Map of phone number or email address to the handle ID it belongs to, used to resolve mentions
+    handles: HashMap<String, i32>,
+}
+
+impl<'a> Exporter<'a> for Telegram<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let directory = Telegram::fragments(config);
+        create_dir_all(&directory).map_err(|err| RuntimeError::CreateError(err, directory))?;
+
+        let mut orphaned = Telegram::fragments(config);
+        orphaned.push(ORPHANED);
+        orphaned.set_extension("json");
+
+        let file = File::options()
+            .append(true)
+            .create(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        // Mentions reference the address of the person mentioned, not their handle ID
+        let mut handles = HashMap::new();
+        let mut statement = Handle::get(&config.db).map_err(RuntimeError::DatabaseError)?;
+        let rows = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+        for row in rows {
+            let handle = Handle::extract(row).map_err(RuntimeError::DatabaseError)?;
+            handles.insert(handle.id, handle.rowid);
+        }
+
+        Ok(Telegram {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+            chats: BTreeMap::new(),
+            started: HashSet::new(),
+            reply_targets: HashMap::new(),
+            next_id: 1,
+            handles,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as Telegram JSON...",
+            self.config.options.export_path.display()
+        );
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            // Tapbacks are written as reactions on the messages they react to
+            if !msg.is_tapback() {
+                let records = if msg.is_announcement() {
+                    vec![self.service_record(&msg)]
+                } else {
+                    self.message_records(&msg)
+                        .map_err(RuntimeError::DatabaseError)?
+                };
+                for record in records {
+                    self.write_record(&msg, &record)?;
+                }
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        self.write_result()
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        match self.config.conversation(message) {
+            Some((chatroom, id)) => match self.files.entry(Telegram::telegram_id(*id)) {
+                Occupied(entry) => Ok(entry.into_mut()),
+                Vacant(entry) => {
+                    let mut path = Telegram::fragments(self.config);
+                    path.push(Telegram::telegram_id(*id).to_string());
+                    path.set_extension("json");
+
+                    let file = File::options()
+                        .append(true)
+                        .create(true)
+                        .open(&path)
+                        .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                    // Group chats have more than one other participant
+                    let is_group = self
+                        .config
+                        .chatroom_participants
+                        .get(&chatroom.rowid)
+                        .is_some_and(|participants| participants.len() > 1);
+                    self.chats.insert(
+                        Telegram::telegram_id(*id),
+                        json!({
+                            "name": chatroom
+                                .display_name()
+                                .map(str::to_string)
+                                .unwrap_or_else(|| self.config.filename(chatroom)),
+                            "type": if is_group { "private_group" } else { "personal_chat" },
+                            "id": Telegram::telegram_id(*id),
+                        }),
+                    );
+
+                    Ok(entry.insert(BufWriter::new(file)))
+                }
+            },
+            None => {
+                self.chats.entry(ORPHANED_CHAT_ID).or_insert_with(|| {
+                    json!({
+                        "name": ORPHANED,
+                        "type": "personal_chat",
+                        "id": ORPHANED_CHAT_ID,
+                    })
+                });
+                Ok(&mut self.orphaned)
+            }
+        }
+    }
+}
+
+impl<'a> Telegram<'a> {
+    /// Get the directory chat messages are written to before they are assembled
+    fn fragments(config: &Config) -> PathBuf {
+        let mut path = config.options.export_path.clone();
+        path.push(FRAGMENTS);
+        path
+    }
+
+    /// Write a message record to its chat's file, separating it from any previous records
+    fn write_record(&mut self, message: &Message, record: &Value) -> Result<(), RuntimeError> {
+        let chat_id = match self.config.conversation(message) {
+            Some((_, id)) => Telegram::telegram_id(*id),
+            None => ORPHANED_CHAT_ID,
+        };
+        let separator = if self.started.insert(chat_id) {
+            "\n"
+        } else {
+            ",\n"
+        };
+
+        let file = self.get_or_create_file(message)?;
+        Telegram::write_to_file(file, separator)?;
+        Telegram::write_to_file(file, &record.to_string())
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+
+    /// Assemble every chat's messages into the result file, then remove the intermediate files
+    fn write_result(&mut self) -> Result<(), RuntimeError> {
+        for buf in self.files.values_mut() {
+            buf.flush().map_err(RuntimeError::DiskError)?;
+        }
+        self.orphaned.flush().map_err(RuntimeError::DiskError)?;
+
+        let mut path = self.config.options.export_path.clone();
+        path.push(RESULT);
+        let file = File::create(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+        let mut result = BufWriter::new(file);
+
+        Telegram::write_to_file(
+            &mut result,
+            "{\n\"about\": \"Here is the data you requested. Use it to build your own Telegram archive viewer.\",\n\"chats\": {\n\"about\": \"This page lists all chats from this export.\",\n\"list\": [",
+        )?;
+        for (idx, (id, chat)) in self.chats.iter().enumerate() {
+            // Each chat object is written without its closing brace so the messages can be streamed into it
+            let header = chat.to_string();
+            let header = header.strip_suffix('}').unwrap_or(&header);
+            if idx > 0 {
+                Telegram::write_to_file(&mut result, ",")?;
+            }
+            Telegram::write_to_file(&mut result, &format!("\n{header},\"messages\":["))?;
+
+            let mut path = Telegram::fragments(self.config);
+            if *id == ORPHANED_CHAT_ID {
+                path.push(ORPHANED);
+            } else {
+                path.push(id.to_string());
+            }
+            path.set_extension("json");
+            let mut fragment =
+                File::open(&path).map_err(|err| RuntimeError::CreateError(err, path))?;
+            copy(&mut fragment, &mut result).map_err(RuntimeError::DiskError)?;
+
+            Telegram::write_to_file(&mut result, "\n]}")?;
+        }
+        Telegram::write_to_file(&mut result, "\n]\n}\n}\n")?;
+        result.flush().map_err(RuntimeError::DiskError)?;
+
+        remove_dir_all(Telegram::fragments(self.config)).map_err(RuntimeError::DiskError)
+    }
+
+    /// Convert a deduplicated chat or participant ID to a Telegram ID
+    ///
+    /// Deduplicated IDs start at 0, so they are shifted to leave 0 for the database owner and orphaned messages.
+    fn telegram_id(id: i32) -> i32 {
+        id + 1
+    }
+
+    /// Build a Telegram user ID for a participant, using the deduplicated handle so merged contacts share an ID
+    ///
+    /// Handles missing from the handle table get negative IDs built from the raw handle, so they are never credited to the database owner.
+    fn user_id(&self, handle_id: Option<i32>, is_from_me: bool) -> String {
+        if is_from_me {
+            return format!("user{SELF_USER_ID}");
+        }
+        let id = match handle_id.and_then(|id| self.config.real_participants.get(&id)) {
+            Some(id) => Telegram::telegram_id(*id),
+            None => -handle_id.unwrap_or_default() - 1,
+        };
+        format!("user{id}")
+    }
+
+    /// Insert the date of a message in Telegram's local time and Unix time formats
+    fn insert_date(&self, record: &mut Map<String, Value>, key: &str, stamp: &i64) {
+        if *stamp == 0 {
+            return;
+        }
+        if let Ok(date) = get_local_time(stamp, &self.config.offset) {
+            record.insert(
+                key.to_string(),
+                json!(date.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            );
+            record.insert(
+                format!("{key}_unixtime"),
+                json!(date.timestamp().to_string()),
+            );
+        }
+    }
+
+    /// Get the ID for the next message written
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Fields shared by every record for a message
+    fn base_record(&mut self, message: &Message, kind: &str) -> Map<String, Value> {
+        let id = self.next_id();
+        // Only messages that were replied to need to be looked up later
+        if message.has_replies() {
+            self.reply_targets.insert(message.guid.clone(), id);
+        }
+
+        let mut record = Map::new();
+        record.insert("id".to_string(), json!(id));
+        record.insert("type".to_string(), json!(kind));
+        self.insert_date(&mut record, "date", &message.date);
+        record
+    }
+
+    /// Build the records for a message
+    ///
+    /// Telegram messages carry at most one file, so each attachment after the first becomes its own record.
+    fn message_records(&mut self, message: &Message) -> Result<Vec<Value>, TableError> {
+        let mut record = self.base_record(message, "message");
+        if message.is_edited() {
+            self.insert_date(&mut record, "edited", &message.date_edited);
+        }
+        let sender = json!(self.config.who(
+            message.handle_id,
+            message.is_from_me(),
+            &message.destination_caller_id
+        ));
+        let sender_id = json!(self.user_id(message.handle_id, message.is_from_me()));
+        record.insert("from".to_string(), sender.clone());
+        record.insert("from_id".to_string(), sender_id.clone());
+
+        if let Some(reply_to) = message
+            .thread_originator_guid
+            .as_ref()
+            .and_then(|guid| self.reply_targets.get(guid))
+        {
+            record.insert("reply_to_message_id".to_string(), json!(reply_to));
+        }
+
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let mut media = attachments
+            .iter_mut()
+            .map(|attachment| self.media_record(attachment, message));
+        if let Some(first) = media.next() {
+            record.extend(first);
+        }
+
+        let entities = self.text_entities(message);
+        record.insert("text".to_string(), Telegram::text(&entities));
+        record.insert("text_entities".to_string(), Value::Array(entities));
+
+        let reactions = self.reactions(message);
+        if !reactions.is_empty() {
+            record.insert("reactions".to_string(), Value::Array(reactions));
+        }
+
+        let mut records = vec![Value::Object(record)];
+        for extra in media.collect::<Vec<_>>() {
+            let mut record = self.base_record(message, "message");
+            record.insert("from".to_string(), sender.clone());
+            record.insert("from_id".to_string(), sender_id.clone());
+            record.extend(extra);
+            record.insert("text".to_string(), json!(""));
+            record.insert("text_entities".to_string(), json!([]));
+            records.push(Value::Object(record));
+        }
+        Ok(records)
+    }
+
+    /// Build a service record for a group announcement
+    fn service_record(&mut self, message: &Message) -> Value {
+        let mut record = self.base_record(message, "service");
+        record.insert(
+            "actor".to_string(),
+            json!(self.config.who(
+                message.handle_id,
+                message.is_from_me(),
+                &message.destination_caller_id
+            )),
+        );
+        record.insert(
+            "actor_id".to_string(),
+            json!(self.user_id(message.handle_id, message.is_from_me())),
+        );
+
+        match message.get_announcement() {
+            Some(Announcement::NameChange(name)) => {
+                record.insert("action".to_string(), json!("edit_group_title"));
+                record.insert("title".to_string(), json!(name));
+            }
+            Some(Announcement::PhotoChange) => {
+                record.insert("action".to_string(), json!("edit_group_photo"));
+            }
+            Some(Announcement::FullyUnsent) => {
+                record.insert("action".to_string(), json!("delete_message"));
+            }
+            Some(Announcement::Unknown(_)) | None => {
+                record.insert("action".to_string(), json!("unknown"));
+            }
+        }
+        record.insert("text".to_string(), json!(""));
+        record.insert("text_entities".to_string(), json!([]));
+
+        Value::Object(record)
+    }
+
+    /// Describe an attachment using the fields Telegram uses for photos and files, copying it if requested
+    fn media_record(&self, attachment: &mut Attachment, message: &Message) -> Map<String, Value> {
+        let path = match self.config.options.attachment_manager.handle_attachment(
+            message,
+            attachment,
+            self.config,
+        ) {
+            Some(_) => self.config.message_attachment_path(attachment),
+            None => FILE_NOT_INCLUDED.to_string(),
+        };
+        let mime_type = attachment.mime_type.as_deref().unwrap_or_default();
+
+        let mut record = Map::new();
+        // Telegram only uses the `photo` field for still images sent as photos
+        if mime_type.starts_with("image/") && !attachment.is_sticker {
+            record.insert("photo".to_string(), json!(path));
+            return record;
+        }
+
+        record.insert("file".to_string(), json!(path));
+        record.insert(
+            "file_name".to_string(),
+            json!(attachment
+                .transfer_name
+                .as_deref()
+                .unwrap_or(attachment.filename())),
+        );
+        let media_type = if attachment.is_sticker {
+            Some("sticker")
+        } else if mime_type.starts_with("video/") {
+            Some("video_file")
+        } else if mime_type == "audio/x-caf" {
+            // Audio messages recorded in Messages are saved as Core Audio files
+            Some("voice_message")
+        } else if mime_type.starts_with("audio/") {
+            Some("audio_file")
+        } else {
+            None
+        };
+        if let Some(media_type) = media_type {
+            record.insert("media_type".to_string(), json!(media_type));
+        }
+        if !mime_type.is_empty() {
+            record.insert("mime_type".to_string(), json!(mime_type));
+        }
+        record
+    }
+
+    /// Build Telegram's `text_entities` from the formatted ranges of a message's text
+    fn text_entities(&self, message: &Message) -> Vec<Value> {
+        if let Variant::App(_) = message.variant() {
+            return match self.app_text(message) {
+                Ok(text) if !text.is_empty() => vec![json!({"type": "plain", "text": text})],
+                _ => Telegram::plain_entities(message.text.as_deref().unwrap_or_default()),
+            };
+        }
+        if message.is_shareplay() {
+            return vec![json!({"type": "plain", "text": "SharePlay Message Ended"})];
+        }
+
+        let text = match &message.text {
+            Some(text) => text,
+            None => return vec![],
+        };
+
+        let mut entities = vec![];
+        for (idx, component) in message.body().iter().enumerate() {
+            if let BubbleComponent::Text(ranges) = component {
+                // Unsent parts are not shown in the conversation, so they have no text
+                if message.is_part_edited(idx)
+                    && message
+                        .edited_parts
+                        .as_ref()
+                        .and_then(|edited| edited.part(idx))
+                        .is_some_and(|part| matches!(part.status, EditStatus::Unsent))
+                {
+                    continue;
+                }
+                for range in ranges {
+                    if let Some(part) = text.get(range.start..range.end) {
+                        // Attachments are exported as files, so drop their placeholders
+                        let part = part.replace('\u{FFFC}', "");
+                        if !part.is_empty() {
+                            entities.push(self.text_entity(&part, &range.effect));
+                        }
+                    }
+                }
+            }
+        }
+        entities
+    }
+
+    /// Build a single plain text entity, or nothing for empty text
+    fn plain_entities(text: &str) -> Vec<Value> {
+        if text.is_empty() {
+            return vec![];
+        }
+        vec![json!({"type": "plain", "text": text})]
+    }
+
+    /// Build the Telegram entity for a range of text
+    fn text_entity(&self, text: &str, effect: &TextEffect) -> Value {
+        match effect {
+            TextEffect::Mention(handle) => match self.handles.get(*handle) {
+                Some(handle_id) => json!({
+                    "type": "mention_name",
+                    "text": text,
+                    "user_id": self.config.real_participants.get(handle_id).map(|id| Telegram::telegram_id(*id)),
+                }),
+                None => json!({"type": "mention", "text": text}),
+            },
+            TextEffect::Link(url) => {
+                if text == *url {
+                    json!({"type": "link", "text": text})
+                } else {
+                    json!({"type": "text_link", "text": text, "href": url})
+                }
+            }
+            TextEffect::OTP => json!({"type": "code", "text": text}),
+            // Telegram entities carry a single style, so combined styles keep the first one
+            TextEffect::Styles(styles) => match styles.first() {
+                Some(Style::Bold) => json!({"type": "bold", "text": text}),
+                Some(Style::Italic) => json!({"type": "italic", "text": text}),
+                Some(Style::Strikethrough) => json!({"type": "strikethrough", "text": text}),
+                Some(Style::Underline) => json!({"type": "underline", "text": text}),
+                None => json!({"type": "plain", "text": text}),
+            },
+            TextEffect::Default | TextEffect::Animated(_) | TextEffect::Conversion(_) => {
+                json!({"type": "plain", "text": text})
+            }
+        }
+    }
+
+    /// Build Telegram's `text` field, which is a string for plain text and a mixed array for formatted text
+    fn text(entities: &[Value]) -> Value {
+        let is_plain = entities
+            .iter()
+            .all(|entity| entity.get("type").and_then(Value::as_str) == Some("plain"));
+        if is_plain {
+            return json!(entities
+                .iter()
+                .filter_map(|entity| entity.get("text").and_then(Value::as_str))
+                .collect::<String>());
+        }
+
+        Value::Array(
+            entities
+                .iter()
+                .map(|entity| match entity.get("type").and_then(Value::as_str) {
+                    Some("plain") => entity.get("text").cloned().unwrap_or_default(),
+                    _ => entity.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Describe an app message with its most specific caption, since Telegram has no equivalent
+    fn app_text(&self, message: &Message) -> Result<String, PlistParseError> {
+        let payload = match message.payload_data(&self.config.db) {
+            Some(payload) => payload,
+            // Sometimes, URL messages are missing their payloads
+            None => return Ok(message.text.clone().unwrap_or_default()),
+        };
+        let parsed = parse_plist(&payload)?;
+        let description = if message.is_url() {
+            match URLMessage::get_url_message_override(&parsed)? {
+                URLOverride::Normal(balloon) => {
+                    balloon.get_url().or(balloon.title).map(str::to_string)
+                }
+                URLOverride::AppleMusic(balloon) => balloon.url.map(str::to_string),
+                URLOverride::Collaboration(balloon) => balloon.get_url().map(str::to_string),
+                URLOverride::AppStore(balloon) => balloon.url.map(str::to_string),
+                URLOverride::SharedPlacemark(balloon) => balloon.get_url().map(str::to_string),
+            }
+        } else {
+            let balloon = AppMessage::from_map(&parsed)?;
+            balloon
+                .ldtext
+                .or(balloon.title)
+                .or(balloon.caption)
+                .or(balloon.app_name)
+                .map(str::to_string)
+        };
+        Ok(description.or(message.text.clone()).unwrap_or_default())
+    }
+
+    /// Build Telegram reactions from the tapbacks on a message, dropping tapbacks that were later removed
+    fn reactions(&self, message: &Message) -> Vec<Value> {
+        let tapbacks_map = match self.config.tapbacks.get(&message.guid) {
+            Some(tapbacks_map) => tapbacks_map,
+            None => return vec![],
+        };
+        let mut parts: Vec<_> = tapbacks_map.iter().collect();
+        parts.sort_by_key(|(idx, _)| **idx);
+
+        // Emoji and the senders who reacted with it, in the order they were first used
+        let mut reactions: Vec<(String, Vec<&Message>)> = vec![];
+        for (_, tapbacks) in parts {
+            for tapback in tapbacks {
+                if let Variant::Tapback(_, added, kind) = tapback.variant() {
+                    let emoji = Telegram::emoji(&kind);
+                    let sender = self.user_id(tapback.handle_id, tapback.is_from_me());
+                    let position = reactions.iter().position(|(e, _)| *e == emoji);
+                    if added {
+                        match position {
+                            Some(position) => reactions[position].1.push(tapback),
+                            None => reactions.push((emoji, vec![tapback])),
+                        }
+                    } else if let Some(position) = position {
+                        reactions[position]
+                            .1
+                            .retain(|msg| self.user_id(msg.handle_id, msg.is_from_me()) != sender);
+                    }
+                }
+            }
+        }
+
+        reactions
+            .into_iter()
+            .filter(|(_, senders)| !senders.is_empty())
+            .map(|(emoji, senders)| {
+                let recent: Vec<Value> = senders
+                    .iter()
+                    .map(|msg| {
+                        let mut record = Map::new();
+                        record.insert(
+                            "from".to_string(),
+                            json!(self.config.who(
+                                msg.handle_id,
+                                msg.is_from_me(),
+                                &msg.destination_caller_id
+                            )),
+                        );
+                        record.insert(
+                            "from_id".to_string(),
+                            json!(self.user_id(msg.handle_id, msg.is_from_me())),
+                        );
+                        self.insert_date(&mut record, "date", &msg.date);
+                        Value::Object(record)
+                    })
+                    .collect();
+                json!({
+                    "type": "emoji",
+                    "count": recent.len(),
+                    "emoji": emoji,
+                    "recent": recent,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the emoji Telegram would show for a tapback
+    fn emoji(tapback: &Tapback) -> String {
+        match tapback {
+            Tapback::Loved => "❤️",
+            Tapback::Liked => "👍",
+            Tapback::Disliked => "👎",
+            Tapback::Laughed => "😂",
+            Tapback::Emphasized => "‼️",
+            Tapback::Questioned => "❓",
+            Tapback::Emoji(emoji) => emoji.unwrap_or("❔"),
+        }
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var};
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        Exporter, Telegram,
+    };
+    use imessage_database::message_types::{
+        text_effects::{Style, TextEffect},
+        variants::Tapback,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Telegram);
+        let config = fake_config(options);
+        let exporter = Telegram::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+        assert_eq!(exporter.next_id, 1);
+    }
+
+    #[test]
+    fn can_build_message_record() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Telegram);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        config.real_participants.insert(999999, 4);
+        let mut exporter = Telegram::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.message_records(&message).unwrap();
+        let expected = vec![json!({
+            "id": 1,
+            "type": "message",
+            "date": "2022-05-17T17:29:42",
+            "date_unixtime": "1652833782",
+            "from": "Sample Contact",
+            "from_id": "user5",
+            "text": "Hello world",
+            "text_entities": [{"type": "plain", "text": "Hello world"}],
+        })];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_build_message_record_unknown_sender() {
+        let options = fake_options_for(ExportType::Telegram);
+        let config = fake_config(options);
+        let mut exporter = Telegram::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("Hello world".to_string());
+        message.handle_id = Some(42);
+
+        let actual = exporter.message_records(&message).unwrap();
+
+        assert_eq!(actual[0]["from_id"], json!("user-43"));
+    }
+
+    #[test]
+    fn can_keep_owner_reaction_when_unknown_sender_removes_theirs() {
+        let options = fake_options_for(ExportType::Telegram);
+        let mut config = fake_config(options);
+
+        let mut message = blank();
+        message.guid = "parent".to_string();
+        message.text = Some("Hello world".to_string());
+
+        let mut from_me = blank();
+        from_me.is_from_me = true;
+        from_me.associated_message_type = Some(2000);
+        from_me.associated_message_guid = Some("p:0/parent".to_string());
+
+        let mut added = blank();
+        added.handle_id = Some(42);
+        added.associated_message_type = Some(2000);
+        added.associated_message_guid = Some("p:0/parent".to_string());
+
+        let mut removed = blank();
+        removed.handle_id = Some(42);
+        removed.associated_message_type = Some(3000);
+        removed.associated_message_guid = Some("p:0/parent".to_string());
+
+        config.tapbacks.insert(
+            "parent".to_string(),
+            HashMap::from([(0, vec![from_me, added, removed])]),
+        );
+        let exporter = Telegram::new(&config).unwrap();
+
+        let actual = exporter.reactions(&message);
+
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0]["count"], json!(1));
+        assert_eq!(actual[0]["recent"][0]["from_id"], json!("user0"));
+    }
+
+    #[test]
+    fn can_build_reply_to_message_id() {
+        let options = fake_options_for(ExportType::Telegram);
+        let config = fake_config(options);
+        let mut exporter = Telegram::new(&config).unwrap();
+
+        let mut parent = blank();
+        parent.guid = "parent".to_string();
+        parent.text = Some("Parent".to_string());
+        parent.num_replies = 1;
+        parent.is_from_me = true;
+
+        let mut reply = blank();
+        reply.guid = "reply".to_string();
+        reply.text = Some("Reply".to_string());
+        reply.thread_originator_guid = Some("parent".to_string());
+
+        exporter.message_records(&parent).unwrap();
+        let actual = exporter.message_records(&reply).unwrap();
+
+        assert_eq!(actual[0]["id"], json!(2));
+        assert_eq!(actual[0]["reply_to_message_id"], json!(1));
+    }
+
+    #[test]
+    fn can_skip_reply_to_missing_message() {
+        let options = fake_options_for(ExportType::Telegram);
+        let config = fake_config(options);
+        let mut exporter = Telegram::new(&config).unwrap();
+
+        let mut reply = blank();
+        reply.text = Some("Reply".to_string());
+        reply.thread_originator_guid = Some("missing".to_string());
+
+        let actual = exporter.message_records(&reply).unwrap();
+
+        assert!(actual[0].get("reply_to_message_id").is_none());
+    }
+
+    #[test]
+    fn can_build_text_entities() {
+        let options = fake_options_for(ExportType::Telegram);
+        let mut config = fake_config(options);
+        config.real_participants.insert(7, 2);
+        let mut exporter = Telegram::new(&config).unwrap();
+        exporter.handles.insert("+15558675309".to_string(), 7);
+
+        assert_eq!(
+            exporter.text_entity(
+                "Bold",
+                &TextEffect::Styles(vec![Style::Bold, Style::Italic])
+            ),
+            json!({"type": "bold", "text": "Bold"})
+        );
+        assert_eq!(
+            exporter.text_entity("Italic", &TextEffect::Styles(vec![Style::Italic])),
+            json!({"type": "italic", "text": "Italic"})
+        );
+        assert_eq!(
+            exporter.text_entity(
+                "https://example.com",
+                &TextEffect::Link("https://example.com")
+            ),
+            json!({"type": "link", "text": "https://example.com"})
+        );
+        assert_eq!(
+            exporter.text_entity("here", &TextEffect::Link("https://example.com")),
+            json!({"type": "text_link", "text": "here", "href": "https://example.com"})
+        );
+        assert_eq!(
+            exporter.text_entity("Sample", &TextEffect::Mention("+15558675309")),
+            json!({"type": "mention_name", "text": "Sample", "user_id": 3})
+        );
+        assert_eq!(
+            exporter.text_entity("Nobody", &TextEffect::Mention("+15550000000")),
+            json!({"type": "mention", "text": "Nobody"})
+        );
+    }
+
+    #[test]
+    fn can_build_plain_text() {
+        let entities = vec![
+            json!({"type": "plain", "text": "Hello "}),
+            json!({"type": "plain", "text": "world"}),
+        ];
+        assert_eq!(Telegram::text(&entities), json!("Hello world"));
+        assert_eq!(Telegram::text(&[]), json!(""));
+    }
+
+    #[test]
+    fn can_build_formatted_text() {
+        let entities = vec![
+            json!({"type": "plain", "text": "Hello "}),
+            json!({"type": "bold", "text": "world"}),
+        ];
+        assert_eq!(
+            Telegram::text(&entities),
+            json!(["Hello ", {"type": "bold", "text": "world"}])
+        );
+    }
+
+    #[test]
+    fn can_get_emoji() {
+        assert_eq!(Telegram::emoji(&Tapback::Loved), "❤️");
+        assert_eq!(Telegram::emoji(&Tapback::Questioned), "❓");
+        assert_eq!(Telegram::emoji(&Tapback::Emoji(Some("🦀"))), "🦀");
+    }
+}
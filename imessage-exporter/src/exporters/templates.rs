@@ -0,0 +1,506 @@
+/*!
+ Loads user-supplied templates that change how HTML and TXT exports render messages.
+
+ Templates use [Jinja](https://jinja.palletsprojects.com/en/stable/templates/) syntax. Each template receives the same data as the
+ [`Writer`](crate::exporters::exporter::Writer) or [`BalloonFormatter`](crate::exporters::exporter::BalloonFormatter) method it replaces,
+ along with the default rendering of that data as `content`.
+*/
+
+use std::{
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+use minijinja::{context, Environment, Value};
+use serde_json::json;
+
+use crate::app::{error::RuntimeError, runtime::Config};
+
+use imessage_database::{
+    message_types::{
+        app::AppMessage,
+        app_store::AppStoreMessage,
+        collaboration::CollaborationMessage,
+        handwriting::HandwrittenMessage,
+        music::MusicMessage,
+        placemark::PlacemarkMessage,
+        url::URLMessage,
+        variants::{Announcement, Variant},
+    },
+    tables::{attachment::Attachment, messages::Message},
+    util::dates::format,
+};
+
+/// Names of the templates that can be supplied, without their file extension
+pub const TEMPLATE_NAMES: [&str; 18] = [
+    "message",
+    "reply",
+    "attachment",
+    "sticker",
+    "tapback",
+    "announcement",
+    "url",
+    "music",
+    "collaboration",
+    "app_store",
+    "placemark",
+    "handwriting",
+    "apple_pay",
+    "fitness",
+    "slideshow",
+    "find_my",
+    "check_in",
+    "generic_app",
+];
+
+/// Name of the file that replaces the default HTML stylesheet
+pub const STYLESHEET: &str = "style.css";
+
+/// Templates loaded from the directory passed to `--template-dir`
+pub struct Templates {
+    /// Environment that holds every template for the current export format
+    env: Environment<'static>,
+    /// File extension of the templates for the current export format
+    extension: &'static str,
+    /// Stylesheet that replaces the default HTML styles, if supplied
+    pub stylesheet: Option<String>,
+}
+
+impl Templates {
+    /// Load the templates in a directory that end with the given extension
+    ///
+    /// Templates for other export formats are ignored, so a single directory can theme both HTML and TXT exports.
+    pub fn load(dir: &Path, extension: &'static str) -> Result<Self, RuntimeError> {
+        let mut env = Environment::new();
+        // Templates usually end with a newline that separates one rendered message from the next
+        env.set_keep_trailing_newline(true);
+        let mut stylesheet = None;
+
+        let entries =
+            read_dir(dir).map_err(|err| RuntimeError::CreateError(err, dir.to_path_buf()))?;
+        for entry in entries {
+            let path = entry.map_err(RuntimeError::DiskError)?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let Some(filename) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if filename == STYLESHEET {
+                if extension == "html" {
+                    stylesheet = Some(
+                        read_to_string(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?,
+                    );
+                }
+                continue;
+            }
+
+            let name = match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if ext == extension => path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or_default(),
+                _ => continue,
+            };
+            if !TEMPLATE_NAMES.contains(&name) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "{filename} is not a valid template! Must be one of <{}> with a `.{extension}` extension",
+                    TEMPLATE_NAMES.join(", ")
+                )));
+            }
+
+            let source = read_to_string(&path)
+                .map_err(|err| RuntimeError::CreateError(err, path.clone()))?;
+            // The extension is kept in the template's name so HTML templates escape their data
+            env.add_template_owned(filename.to_string(), source)
+                .map_err(|why| {
+                    RuntimeError::InvalidOptions(format!(
+                        "Unable to parse template {filename}: {why}"
+                    ))
+                })?;
+        }
+
+        Ok(Templates {
+            env,
+            extension,
+            stylesheet,
+        })
+    }
+
+    /// Determine if a template with the given name was supplied
+    pub fn has(&self, name: &str) -> bool {
+        self.env.get_template(&self.filename(name)).is_ok()
+    }
+
+    /// Render a template in place of the default output, if one was supplied
+    ///
+    /// The context is only built if the template exists. If the template fails to render, the default output is used instead.
+    pub fn render(&self, name: &str, default: String, context: impl FnOnce() -> Value) -> String {
+        let Ok(template) = self.env.get_template(&self.filename(name)) else {
+            return default;
+        };
+
+        match template.render(context! {
+            content => Value::from_safe_string(default.clone()),
+            ..context()
+        }) {
+            Ok(rendered) => rendered,
+            Err(why) => {
+                eprintln!("Unable to render template {name}: {why}");
+                default
+            }
+        }
+    }
+
+    fn filename(&self, name: &str) -> String {
+        format!("{name}.{}", self.extension)
+    }
+}
+
+/// Describe a message: its sender, dates, and text
+pub fn message_context(config: &Config, message: &Message) -> Value {
+    let chat = config.conversation(message).map(|(chatroom, id)| {
+        json!({
+            "id": id,
+            "identifier": chatroom.chat_identifier,
+            "name": chatroom.display_name(),
+            "service": chatroom.service_name,
+        })
+    });
+
+    Value::from_serialize(json!({
+        "rowid": message.rowid,
+        "guid": message.guid,
+        "chat": chat,
+        "sender": config.who(message.handle_id, message.is_from_me(), &message.destination_caller_id),
+        "is_from_me": message.is_from_me(),
+        "date": format(&message.date(&config.offset)),
+        "time_until_read": message.time_until_read(&config.offset),
+        "service": message.service,
+        "subject": message.subject,
+        "text": message.text,
+        "is_reply": message.is_reply(),
+        "is_edited": message.is_edited(),
+        "is_deleted": message.is_deleted(),
+    }))
+}
+
+/// Describe a message that may be rendered in a thread, along with its rendered parts
+///
+/// `parts` holds the rendered content of each part of the message, with the tapbacks and replies to that part.
+pub fn thread_context(
+    config: &Config,
+    message: &Message,
+    indent: usize,
+    parts: Vec<Value>,
+) -> Value {
+    context! {
+        message => message_context(config, message),
+        indent => indent,
+        parts => parts,
+    }
+}
+
+/// Describe a rendered message part, with the tapbacks and replies to it
+pub fn part_context(content: &str, tapbacks: &[String], replies: &[String]) -> Value {
+    let safe = |items: &[String]| {
+        items
+            .iter()
+            .map(|item| Value::from_safe_string(item.clone()))
+            .collect::<Vec<Value>>()
+    };
+    context! {
+        content => Value::from_safe_string(content.to_string()),
+        tapbacks => safe(tapbacks),
+        replies => safe(replies),
+    }
+}
+
+/// Describe an attachment and where the export links to it
+pub fn attachment_context(
+    config: &Config,
+    message: &Message,
+    attachment: &Attachment,
+    path: &str,
+    sticker_effect: Option<String>,
+) -> Value {
+    context! {
+        message => message_context(config, message),
+        attachment => Value::from_serialize(json!({
+            "rowid": attachment.rowid,
+            "filename": attachment.filename(),
+            "transfer_name": attachment.transfer_name,
+            "mime_type": attachment.mime_type,
+            "uti": attachment.uti,
+            "total_bytes": attachment.total_bytes,
+            "file_size": attachment.file_size(),
+            "is_sticker": attachment.is_sticker,
+            "sticker_effect": sticker_effect,
+            "path": path,
+        })),
+    }
+}
+
+/// Describe a tapback or sticker reaction
+pub fn tapback_context(config: &Config, message: &Message) -> Value {
+    let tapback = match message.variant() {
+        Variant::Tapback(_, added, tapback) => json!({
+            "kind": tapback.to_string(),
+            "added": added,
+        }),
+        _ => json!({
+            "kind": "Sticker",
+            "added": true,
+        }),
+    };
+    context! {
+        message => message_context(config, message),
+        tapback => Value::from_serialize(tapback),
+    }
+}
+
+/// Describe a change to a conversation, like a new name or photo
+pub fn announcement_context(config: &Config, message: &Message) -> Value {
+    let announcement = match message.get_announcement() {
+        Some(Announcement::NameChange(name)) => json!({"kind": "name_change", "name": name}),
+        Some(Announcement::PhotoChange) => json!({"kind": "photo_change"}),
+        Some(Announcement::Unknown(action)) => json!({"kind": "unknown", "action": action}),
+        Some(Announcement::FullyUnsent) => json!({"kind": "fully_unsent"}),
+        None => json!({"kind": "unknown"}),
+    };
+    context! {
+        message => message_context(config, message),
+        announcement => Value::from_serialize(announcement),
+    }
+}
+
+/// Describe a balloon using the fields parsed from its payload
+fn balloon_context(config: &Config, message: &Message, balloon: serde_json::Value) -> Value {
+    context! {
+        message => message_context(config, message),
+        balloon => Value::from_serialize(balloon),
+    }
+}
+
+/// Describe a link preview
+pub fn url_context(config: &Config, message: &Message, balloon: &URLMessage) -> Value {
+    balloon_context(
+        config,
+        message,
+        json!({
+            "url": balloon.get_url().or(message.text.as_deref()),
+            "title": balloon.title,
+            "summary": balloon.summary,
+            "site_name": balloon.site_name,
+            "images": balloon.images,
+            "icons": balloon.icons,
+        }),
+    )
+}
+
+/// Describe an Apple Music link
+pub fn music_context(config: &Config, message: &Message, balloon: &MusicMessage) -> Value {
+    balloon_context(
+        config,
+        message,
+        json!({
+            "url": balloon.url,
+            "preview": balloon.preview,
+            "track_name": balloon.track_name,
+            "album": balloon.album,
+            "artist": balloon.artist,
+        }),
+    )
+}
+
+/// Describe a shared document or collaboration invite
+pub fn collaboration_context(
+    config: &Config,
+    message: &Message,
+    balloon: &CollaborationMessage,
+) -> Value {
+    balloon_context(
+        config,
+        message,
+        json!({
+            "url": balloon.get_url(),
+            "title": balloon.title,
+            "app_name": balloon.app_name,
+            "bundle_id": balloon.bundle_id,
+        }),
+    )
+}
+
+/// Describe an App Store link
+pub fn app_store_context(config: &Config, message: &Message, balloon: &AppStoreMessage) -> Value {
+    balloon_context(
+        config,
+        message,
+        json!({
+            "url": balloon.url,
+            "app_name": balloon.app_name,
+            "description": balloon.description,
+            "platform": balloon.platform,
+            "genre": balloon.genre,
+        }),
+    )
+}
+
+/// Describe a place shared from Maps
+pub fn placemark_context(config: &Config, message: &Message, balloon: &PlacemarkMessage) -> Value {
+    balloon_context(
+        config,
+        message,
+        json!({
+            "url": balloon.get_url(),
+            "place_name": balloon.place_name,
+            "name": balloon.placemark.name,
+            "address": balloon.placemark.address,
+            "street": balloon.placemark.street,
+            "city": balloon.placemark.city,
+            "state": balloon.placemark.state,
+            "postal_code": balloon.placemark.postal_code,
+            "country": balloon.placemark.country,
+        }),
+    )
+}
+
+/// Describe a handwritten message
+pub fn handwriting_context(
+    config: &Config,
+    message: &Message,
+    balloon: &HandwrittenMessage,
+) -> Value {
+    balloon_context(
+        config,
+        message,
+        json!({
+            "id": balloon.id,
+            "width": balloon.width,
+            "height": balloon.height,
+        }),
+    )
+}
+
+/// Describe an app balloon, such as Apple Pay, Fitness, Find My, Check In, or a third party app
+pub fn app_context(
+    config: &Config,
+    message: &Message,
+    balloon: &AppMessage,
+    bundle_id: Option<&str>,
+) -> Value {
+    balloon_context(
+        config,
+        message,
+        json!({
+            "bundle_id": bundle_id,
+            "app_name": balloon.app_name,
+            "url": balloon.url,
+            "image": balloon.image,
+            "title": balloon.title,
+            "subtitle": balloon.subtitle,
+            "caption": balloon.caption,
+            "subcaption": balloon.subcaption,
+            "trailing_caption": balloon.trailing_caption,
+            "trailing_subcaption": balloon.trailing_subcaption,
+            "ldtext": balloon.ldtext,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all, write},
+        path::PathBuf,
+    };
+
+    use minijinja::context;
+
+    use crate::exporters::templates::Templates;
+
+    fn template_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = PathBuf::from(format!("/tmp/imessage-exporter-templates-{name}"));
+        create_dir_all(&dir).unwrap();
+        for (filename, contents) in files {
+            write(dir.join(filename), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn can_load_templates_for_format() {
+        let dir = template_dir(
+            "format",
+            &[
+                ("message.html", "<p>{{ message.text }}</p>"),
+                ("message.txt", "{{ message.text }}"),
+                ("style.css", "body { color: red; }"),
+            ],
+        );
+
+        let html = Templates::load(&dir, "html").unwrap();
+        assert!(html.has("message"));
+        assert!(!html.has("attachment"));
+        assert_eq!(html.stylesheet.as_deref(), Some("body { color: red; }"));
+
+        let txt = Templates::load(&dir, "txt").unwrap();
+        assert!(txt.has("message"));
+        assert!(txt.stylesheet.is_none());
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_load_unknown_template() {
+        let dir = template_dir("unknown", &[("messages.html", "")]);
+        assert!(Templates::load(&dir, "html").is_err());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cant_load_invalid_template() {
+        let dir = template_dir("invalid", &[("message.html", "{% if %}")]);
+        assert!(Templates::load(&dir, "html").is_err());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_render_with_default_content() {
+        let dir = template_dir(
+            "render",
+            &[(
+                "message.html",
+                "<section>{{ content }}<i>{{ message.text }}</i></section>",
+            )],
+        );
+        let templates = Templates::load(&dir, "html").unwrap();
+
+        let actual = templates.render("message", "<p>Hi</p>".to_string(), || {
+            context! { message => context! { text => "<b>" } }
+        });
+        assert_eq!(actual, "<section><p>Hi</p><i>&lt;b&gt;</i></section>");
+
+        // Templates that were not supplied use the default output
+        let actual = templates.render("tapback", "<p>Hi</p>".to_string(), || unreachable!());
+        assert_eq!(actual, "<p>Hi</p>");
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_fall_back_when_render_fails() {
+        let dir = template_dir(
+            "fallback",
+            &[("message.txt", "{{ content | nonexistent }}")],
+        );
+        let templates = Templates::load(&dir, "txt").unwrap();
+
+        let actual = templates.render("message", "Hi".to_string(), || context! {});
+        assert_eq!(actual, "Hi");
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
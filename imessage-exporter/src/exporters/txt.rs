@@ -14,9 +14,18 @@ use crate::{
         attachment_manager::AttachmentManager, error::RuntimeError,
         progress::build_progress_bar_export, runtime::Config,
     },
-    exporters::exporter::{BalloonFormatter, Exporter, Writer},
+    exporters::{
+        exporter::{BalloonFormatter, Exporter, Writer},
+        templates::{
+            announcement_context, app_context, app_store_context, attachment_context,
+            collaboration_context, handwriting_context, music_context, part_context,
+            placemark_context, tapback_context, thread_context, url_context, Templates,
+        },
+    },
 };
 
+use minijinja::Value;
+
 use imessage_database::{
     error::{plist::PlistParseError, table::TableError},
     message_types::{
@@ -51,6 +60,8 @@ pub struct TXT<'a> {
     pub files: HashMap<String, BufWriter<File>>,
     /// Writer instance for orphaned messages
     pub orphaned: BufWriter<File>,
+    /// User-supplied templates that replace the default rendering, if any
+    pub templates: Option<Templates>,
 }
 
 impl<'a> Exporter<'a> for TXT<'a> {
@@ -65,10 +76,16 @@ impl<'a> Exporter<'a> for TXT<'a> {
             .open(&orphaned)
             .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
 
+        let templates = match &config.options.template_dir {
+            Some(dir) => Some(Templates::load(dir, "txt")?),
+            None => None,
+        };
+
         Ok(TXT {
             config,
             files: HashMap::new(),
             orphaned: BufWriter::new(file),
+            templates,
         })
     }
 
@@ -199,6 +216,10 @@ impl<'a> Writer<'a> for TXT<'a> {
         // Index of where we are in the attachment Vector
         let mut attachment_index: usize = 0;
 
+        // Rendered message parts, collected if a template renders the message
+        let template = self.message_template(indent_size);
+        let mut parts: Vec<Value> = vec![];
+
         // Render subject
         if let Some(subject) = &message.subject {
             self.add_line(&mut formatted_message, subject, &indent);
@@ -220,6 +241,10 @@ impl<'a> Writer<'a> for TXT<'a> {
 
         // Generate the message body from it's components
         for (idx, message_part) in message_parts.iter().enumerate() {
+            let part_start = formatted_message.len();
+            let mut part_tapbacks: Vec<String> = vec![];
+            let mut part_replies: Vec<String> = vec![];
+
             match message_part {
                 // Fitness messages have a prefix that we need to replace with the opposite if who sent the message
                 BubbleComponent::Text(text_attrs) => {
@@ -302,6 +327,7 @@ impl<'a> Writer<'a> for TXT<'a> {
                     }
                 }
             };
+            let part_end = formatted_message.len();
 
             // Handle expressives
             if message.expressive_send_style_id.is_some() {
@@ -321,11 +347,8 @@ impl<'a> Writer<'a> for TXT<'a> {
                         .try_for_each(|tapbacks| -> Result<(), TableError> {
                             let formatted = self.format_tapback(tapbacks)?;
                             if !formatted.is_empty() {
-                                self.add_line(
-                                    &mut formatted_tapbacks,
-                                    &self.format_tapback(tapbacks)?,
-                                    &indent,
-                                );
+                                self.add_line(&mut formatted_tapbacks, &formatted, &indent);
+                                part_tapbacks.push(formatted);
                             }
                             Ok(())
                         })?;
@@ -344,15 +367,21 @@ impl<'a> Writer<'a> for TXT<'a> {
                     .try_for_each(|reply| -> Result<(), TableError> {
                         let _ = reply.generate_text(&self.config.db);
                         if !reply.is_tapback() {
-                            self.add_line(
-                                &mut formatted_message,
-                                &self.format_message(reply, 4)?,
-                                &indent,
-                            );
+                            let formatted = self.format_message(reply, 4)?;
+                            self.add_line(&mut formatted_message, &formatted, &indent);
+                            part_replies.push(formatted);
                         }
                         Ok(())
                     })?;
             }
+
+            if template.is_some() {
+                parts.push(part_context(
+                    &formatted_message[part_start..part_end],
+                    &part_tapbacks,
+                    &part_replies,
+                ));
+            }
         }
 
         // Add a note if the message is a reply
@@ -369,7 +398,12 @@ impl<'a> Writer<'a> for TXT<'a> {
             formatted_message.push('\n');
         }
 
-        Ok(formatted_message)
+        Ok(match template {
+            Some(name) => self.apply_template(name, formatted_message, || {
+                thread_context(self.config, message, indent_size, parts)
+            }),
+            None => formatted_message,
+        })
     }
 
     fn format_attachment(
@@ -385,7 +419,10 @@ impl<'a> Writer<'a> for TXT<'a> {
             .ok_or(attachment.filename())?;
 
         // Build a relative filepath from the fully qualified one on the `Attachment`
-        Ok(self.config.message_attachment_path(attachment))
+        let path = self.config.message_attachment_path(attachment);
+        Ok(self.apply_template("attachment", path.clone(), || {
+            attachment_context(self.config, message, attachment, &path, None)
+        }))
     }
 
     fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
@@ -401,10 +438,18 @@ impl<'a> Writer<'a> for TXT<'a> {
                     &self.config.options.db_path,
                     self.config.options.attachment_root.as_deref(),
                 );
-                if let Ok(Some(sticker_effect)) = sticker_effect {
-                    return format!("{sticker_effect} Sticker from {who}: {path_to_sticker}");
-                }
-                format!("Sticker from {who}: {path_to_sticker}")
+                let sticker_effect = sticker_effect
+                    .ok()
+                    .flatten()
+                    .map(|effect| effect.to_string());
+                let rendered = match &sticker_effect {
+                    Some(effect) => format!("{effect} Sticker from {who}: {path_to_sticker}"),
+                    None => format!("Sticker from {who}: {path_to_sticker}"),
+                };
+                self.apply_template("sticker", rendered, || {
+                    let path = self.config.message_attachment_path(sticker);
+                    attachment_context(self.config, message, sticker, &path, sticker_effect)
+                })
             }
             Err(path) => format!("Sticker from {who}: {path}"),
         }
@@ -423,7 +468,11 @@ impl<'a> Writer<'a> for TXT<'a> {
             if message.is_handwriting() {
                 if let Some(payload) = message.raw_payload_data(&self.config.db) {
                     return match HandwrittenMessage::from_payload(&payload) {
-                        Ok(bubble) => Ok(self.format_handwriting(message, &bubble, indent)),
+                        Ok(bubble) => Ok(self.apply_template(
+                            "handwriting",
+                            self.format_handwriting(message, &bubble, indent),
+                            || handwriting_context(self.config, message, &bubble),
+                        )),
                         Err(why) => Err(PlistParseError::HandwritingError(why)),
                     };
                 }
@@ -435,33 +484,74 @@ impl<'a> Writer<'a> for TXT<'a> {
                     let parsed = parse_plist(&payload)?;
                     let bubble = URLMessage::get_url_message_override(&parsed)?;
                     match bubble {
-                        URLOverride::Normal(balloon) => self.format_url(message, &balloon, indent),
-                        URLOverride::AppleMusic(balloon) => self.format_music(&balloon, indent),
-                        URLOverride::Collaboration(balloon) => {
-                            self.format_collaboration(&balloon, indent)
-                        }
-                        URLOverride::AppStore(balloon) => self.format_app_store(&balloon, indent),
-                        URLOverride::SharedPlacemark(balloon) => {
-                            self.format_placemark(&balloon, indent)
-                        }
+                        URLOverride::Normal(balloon) => self.apply_template(
+                            "url",
+                            self.format_url(message, &balloon, indent),
+                            || url_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::AppleMusic(balloon) => self.apply_template(
+                            "music",
+                            self.format_music(&balloon, indent),
+                            || music_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::Collaboration(balloon) => self.apply_template(
+                            "collaboration",
+                            self.format_collaboration(&balloon, indent),
+                            || collaboration_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::AppStore(balloon) => self.apply_template(
+                            "app_store",
+                            self.format_app_store(&balloon, indent),
+                            || app_store_context(self.config, message, &balloon),
+                        ),
+                        URLOverride::SharedPlacemark(balloon) => self.apply_template(
+                            "placemark",
+                            self.format_placemark(&balloon, indent),
+                            || placemark_context(self.config, message, &balloon),
+                        ),
                     }
                 // Handwriting uses a different payload type than the rest of the branches
                 } else {
                     // Handle the app case
                     let parsed = parse_plist(&payload)?;
                     match AppMessage::from_map(&parsed) {
-                        Ok(bubble) => match balloon {
-                            CustomBalloon::Application(bundle_id) => {
-                                self.format_generic_app(&bubble, bundle_id, attachments, indent)
-                            }
-                            CustomBalloon::ApplePay => self.format_apple_pay(&bubble, indent),
-                            CustomBalloon::Fitness => self.format_fitness(&bubble, indent),
-                            CustomBalloon::Slideshow => self.format_slideshow(&bubble, indent),
-                            CustomBalloon::CheckIn => self.format_check_in(&bubble, indent),
-                            CustomBalloon::FindMy => self.format_find_my(&bubble, indent),
-                            CustomBalloon::Handwriting => unreachable!(),
-                            CustomBalloon::URL => unreachable!(),
-                        },
+                        Ok(bubble) => {
+                            let (name, rendered) = match balloon {
+                                CustomBalloon::Application(bundle_id) => (
+                                    "generic_app",
+                                    self.format_generic_app(
+                                        &bubble,
+                                        bundle_id,
+                                        attachments,
+                                        indent,
+                                    ),
+                                ),
+                                CustomBalloon::ApplePay => {
+                                    ("apple_pay", self.format_apple_pay(&bubble, indent))
+                                }
+                                CustomBalloon::Fitness => {
+                                    ("fitness", self.format_fitness(&bubble, indent))
+                                }
+                                CustomBalloon::Slideshow => {
+                                    ("slideshow", self.format_slideshow(&bubble, indent))
+                                }
+                                CustomBalloon::CheckIn => {
+                                    ("check_in", self.format_check_in(&bubble, indent))
+                                }
+                                CustomBalloon::FindMy => {
+                                    ("find_my", self.format_find_my(&bubble, indent))
+                                }
+                                CustomBalloon::Handwriting => unreachable!(),
+                                CustomBalloon::URL => unreachable!(),
+                            };
+                            let bundle_id = match balloon {
+                                CustomBalloon::Application(bundle_id) => Some(bundle_id),
+                                _ => None,
+                            };
+                            self.apply_template(name, rendered, || {
+                                app_context(self.config, message, &bubble, bundle_id)
+                            })
+                        }
                         Err(why) => return Err(why),
                     }
                 };
@@ -487,12 +577,13 @@ impl<'a> Writer<'a> for TXT<'a> {
                 if !added {
                     return Ok(String::new());
                 }
-                Ok(format!(
+                let rendered = format!(
                     "{} by {}",
                     tapback,
                     self.config
                         .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id),
-                ))
+                );
+                Ok(self.apply_template("tapback", rendered, || tapback_context(self.config, msg)))
             }
             Variant::Sticker(_) => {
                 let mut paths = Attachment::from_message(&self.config.db, msg)?;
@@ -500,11 +591,12 @@ impl<'a> Writer<'a> for TXT<'a> {
                     self.config
                         .who(msg.handle_id, msg.is_from_me(), &msg.destination_caller_id);
                 // Sticker messages have only one attachment, the sticker image
-                Ok(if let Some(sticker) = paths.get_mut(0) {
+                let rendered = if let Some(sticker) = paths.get_mut(0) {
                     format!("{} from {who}", self.format_sticker(sticker, msg))
                 } else {
                     format!("Sticker from {who} not found!")
-                })
+                };
+                Ok(self.apply_template("tapback", rendered, || tapback_context(self.config, msg)))
             }
             _ => unreachable!(),
         }
@@ -545,7 +637,7 @@ impl<'a> Writer<'a> for TXT<'a> {
 
         let timestamp = format(&msg.date(&self.config.offset));
 
-        return match msg.get_announcement() {
+        let rendered = match msg.get_announcement() {
             Some(announcement) => match announcement {
                 Announcement::NameChange(name) => {
                     format!("{timestamp} {who} renamed the conversation to {name}\n\n")
@@ -560,6 +652,10 @@ impl<'a> Writer<'a> for TXT<'a> {
             },
             None => String::from("Unable to format announcement!\n\n"),
         };
+
+        self.apply_template("announcement", rendered, || {
+            announcement_context(self.config, msg)
+        })
     }
 
     fn format_shareplay(&self) -> &str {
@@ -1008,6 +1104,33 @@ impl<'a> TXT<'a> {
         date
     }
 
+    /// Render a user-supplied template in place of the default output, if one was supplied
+    fn apply_template(
+        &self,
+        name: &str,
+        default: String,
+        context: impl FnOnce() -> Value,
+    ) -> String {
+        match &self.templates {
+            Some(templates) => templates.render(name, default, context),
+            None => default,
+        }
+    }
+
+    /// Get the name of the template that renders a message, if one was supplied
+    ///
+    /// Messages rendered in a thread use the `reply` template if there is one, otherwise every message uses the `message` template.
+    fn message_template(&self, indent_size: usize) -> Option<&'static str> {
+        let templates = self.templates.as_ref()?;
+        if indent_size > 0 && templates.has("reply") {
+            Some("reply")
+        } else if templates.has("message") {
+            Some("message")
+        } else {
+            None
+        }
+    }
+
     fn add_line(&self, string: &mut String, part: &str, indent: &str) {
         if !part.is_empty() {
             string.push_str(indent);
@@ -1020,97 +1143,21 @@ impl<'a> TXT<'a> {
 #[cfg(test)]
 mod tests {
     use std::{
-        collections::HashMap,
         env::{current_dir, set_var},
+        fs::{create_dir_all, remove_dir_all, write},
         path::PathBuf,
     };
 
     use crate::{
-        app::attachment_manager::AttachmentManager, exporters::exporter::Writer, Config, Exporter,
-        Options, TXT,
+        app::fixtures::{blank, fake_config, fake_options},
+        exporters::exporter::Writer,
+        Exporter, TXT,
     };
     use imessage_database::{
-        tables::{
-            attachment::Attachment,
-            messages::Message,
-            table::{get_connection, ME},
-        },
-        util::{
-            dates::get_offset, dirs::default_db_path, platform::Platform,
-            query_context::QueryContext,
-        },
+        tables::{attachment::Attachment, table::ME},
+        util::platform::Platform,
     };
 
-    pub(super) fn blank() -> Message {
-        Message {
-            rowid: i32::default(),
-            guid: String::default(),
-            text: None,
-            service: Some("iMessage".to_string()),
-            handle_id: Some(i32::default()),
-            destination_caller_id: None,
-            subject: None,
-            date: i64::default(),
-            date_read: i64::default(),
-            date_delivered: i64::default(),
-            is_from_me: false,
-            is_read: false,
-            item_type: 0,
-            other_handle: 0,
-            share_status: false,
-            share_direction: false,
-            group_title: None,
-            group_action_type: 0,
-            associated_message_guid: None,
-            associated_message_type: Some(i32::default()),
-            balloon_bundle_id: None,
-            expressive_send_style_id: None,
-            thread_originator_guid: None,
-            thread_originator_part: None,
-            date_edited: 0,
-            chat_id: None,
-            associated_message_emoji: None,
-            num_attachments: 0,
-            deleted_from: None,
-            num_replies: 0,
-            components: None,
-            edited_parts: None,
-        }
-    }
-
-    pub(super) fn fake_options() -> Options {
-        Options {
-            db_path: default_db_path(),
-            attachment_root: None,
-            attachment_manager: AttachmentManager::Disabled,
-            diagnostic: false,
-            export_type: None,
-            export_path: PathBuf::from("/tmp"),
-            query_context: QueryContext::default(),
-            no_lazy: false,
-            custom_name: None,
-            use_caller_id: false,
-            platform: Platform::macOS,
-            ignore_disk_space: false,
-        }
-    }
-
-    pub(super) fn fake_config(options: Options) -> Config {
-        let db = get_connection(&options.get_db_path()).unwrap();
-        Config {
-            chatrooms: HashMap::new(),
-            real_chatrooms: HashMap::new(),
-            chatroom_participants: HashMap::new(),
-            participants: HashMap::new(),
-            real_participants: HashMap::new(),
-            tapbacks: HashMap::new(),
-            options,
-            offset: get_offset(),
-            db,
-            converter: None,
-        }
-    }
-
     pub(super) fn fake_attachment() -> Attachment {
         Attachment {
             rowid: 0,
@@ -1460,6 +1507,41 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_txt_tapback_me_template() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        let template_dir = PathBuf::from("/tmp/imessage-exporter-txt-template");
+        create_dir_all(&template_dir).unwrap();
+        write(
+            template_dir.join("tapback.txt"),
+            "{{ message.sender }} reacted with {{ tapback.kind }} ({{ content }})",
+        )
+        .unwrap();
+
+        // Create exporter
+        let mut options = fake_options();
+        options.template_dir = Some(template_dir.clone());
+        let mut config = fake_config(options);
+        config.participants.insert(0, ME.to_string());
+
+        let exporter = TXT::new(&config).unwrap();
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.associated_message_type = Some(2000);
+        message.associated_message_guid = Some("fake_guid".to_string());
+
+        let actual = exporter.format_tapback(&message).unwrap();
+        let expected = "Me reacted with Loved (Loved by Me)";
+
+        assert_eq!(actual, expected);
+
+        remove_dir_all(&template_dir).unwrap();
+    }
+
     #[test]
     fn can_format_txt_tapback_them() {
         // Set timezone to PST for consistent Local time
@@ -1751,10 +1833,11 @@ mod tests {
 
 #[cfg(test)]
 mod balloon_format_tests {
-    use std::env::set_var;
-
-    use super::tests::{blank, fake_config, fake_options};
-    use crate::{exporters::exporter::BalloonFormatter, Exporter, TXT};
+    use crate::{
+        app::fixtures::{blank, fake_config, fake_options},
+        exporters::exporter::BalloonFormatter,
+        Exporter, TXT,
+    };
     use imessage_database::message_types::{
         app::AppMessage,
         app_store::AppStoreMessage,
@@ -1763,6 +1846,7 @@ mod balloon_format_tests {
         placemark::{Placemark, PlacemarkMessage},
         url::URLMessage,
     };
+    use std::env::set_var;
 
     #[test]
     fn can_format_txt_url() {
@@ -2111,9 +2195,11 @@ mod edited_tests {
         io::Read,
     };
 
-    use super::tests::{blank, fake_config, fake_options};
-
-    use crate::{exporters::exporter::Writer, Exporter, TXT};
+    use crate::{
+        app::fixtures::{blank, fake_config, fake_options},
+        exporters::exporter::Writer,
+        Exporter, TXT,
+    };
     use imessage_database::{
         message_types::edited::{EditStatus, EditedMessage, EditedMessagePart},
         util::typedstream::parser::TypedStreamReader,
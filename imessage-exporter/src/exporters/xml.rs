@@ -0,0 +1,775 @@
+use std::{
+    borrow::Cow,
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap,
+    },
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    app::{
+        error::RuntimeError, progress::build_progress_bar_export, runtime::Config,
+        sanitizers::sanitize_xml,
+    },
+    exporters::exporter::{Exporter, Writer},
+};
+
+use imessage_database::{
+    error::{plist::PlistParseError, table::TableError},
+    message_types::{
+        app::AppMessage,
+        edited::{EditStatus, EditedMessage},
+        expressives::{BubbleEffect, Expressive, ScreenEffect},
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{BalloonProvider, URLOverride, Variant},
+    },
+    tables::{
+        attachment::Attachment,
+        handle::Handle,
+        messages::Message,
+        table::{Table, ORPHANED, UNKNOWN},
+    },
+    util::{
+        dates::{format, get_local_time},
+        plist::parse_plist,
+    },
+};
+
+/// Name of the file written when all conversations are exported together
+const COMBINED: &str = "messages";
+
+/// XML declaration written at the top of every file
+const DECLARATION: &str = "<?xml version='1.0' encoding='UTF-8' standalone='yes' ?>\n";
+
+/// Width reserved for the root element's opening tag, so the message count can be filled in after all messages are written
+const ROOT_WIDTH: usize = 40;
+
+/// Placeholder Android uses for the device owner's address in MMS messages
+const SELF_ADDRESS: &str = "insert-address-token";
+
+/// MMS address type for the sender of a message
+const PDU_FROM: u8 = 137;
+/// MMS address type for a recipient of a message
+const PDU_TO: u8 = 151;
+
+/// Character set identifier for UTF-8 text
+const UTF_8: u8 = 106;
+
+/// Exports messages to the schema used by the Android SMS Backup & Restore app
+///
+/// Tapbacks and announcements have no equivalent on Android, so they are not exported.
+pub struct XML<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Handles to files we want to write messages to
+    /// Map of resolved chatroom file location to a buffered writer
+    pub files: HashMap<String, BufWriter<File>>,
+    /// Writer instance for orphaned messages, or for every message when chats are combined
+    pub orphaned: BufWriter<File>,
+    /// Number of messages written to each file, keyed the same way as `files`
+    counts: HashMap<String, usize>,
+    /// Number of messages written to the orphaned file
+    orphaned_count: usize,
+    /// Map of handle ID to the phone number or email address it represents
+    handles: HashMap<i32, String>,
+}
+
+impl<'a> Exporter<'a> for XML<'a> {
+    fn new(config: &'a Config) -> Result<Self, RuntimeError> {
+        let mut orphaned = config.options.export_path.clone();
+        if config.options.combine_chats {
+            orphaned.push(COMBINED);
+        } else {
+            orphaned.push(ORPHANED);
+        }
+        orphaned.set_extension("xml");
+
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&orphaned)
+            .map_err(|err| RuntimeError::CreateError(err, orphaned))?;
+
+        // The participant cache merges handles that belong to the same person, but addresses need the original values
+        let mut handles = HashMap::new();
+        let mut statement = Handle::get(&config.db).map_err(RuntimeError::DatabaseError)?;
+        let rows = statement
+            .query_map([], |row| Ok(Handle::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Handle(err)))?;
+        for row in rows {
+            let handle = Handle::extract(row).map_err(RuntimeError::DatabaseError)?;
+            handles.insert(handle.rowid, handle.id);
+        }
+
+        Ok(XML {
+            config,
+            files: HashMap::new(),
+            orphaned: BufWriter::new(file),
+            counts: HashMap::new(),
+            orphaned_count: 0,
+            handles,
+        })
+    }
+
+    fn iter_messages(&mut self) -> Result<(), RuntimeError> {
+        // Tell the user what we are doing
+        eprintln!(
+            "Exporting to {} as xml...",
+            self.config.options.export_path.display()
+        );
+
+        // Write orphaned file headers
+        XML::write_headers(&mut self.orphaned)?;
+
+        // Keep track of current message ROWID
+        let mut current_message_row = -1;
+
+        // Set up progress bar
+        let mut current_message = 0;
+        let total_messages =
+            Message::get_count(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+        let pb = build_progress_bar_export(total_messages);
+
+        let mut statement =
+            Message::stream_rows(&self.config.db, &self.config.options.query_context)
+                .map_err(RuntimeError::DatabaseError)?;
+
+        let messages = statement
+            .query_map([], |row| Ok(Message::from_row(row)))
+            .map_err(|err| RuntimeError::DatabaseError(TableError::Messages(err)))?;
+
+        for message in messages {
+            let mut msg = Message::extract(message).map_err(RuntimeError::DatabaseError)?;
+
+            // Early escape if we try and render the same message GUID twice
+            // See https://github.com/ReagentX/imessage-exporter/issues/135 for rationale
+            if msg.rowid == current_message_row {
+                current_message += 1;
+                continue;
+            }
+            current_message_row = msg.rowid;
+
+            // Generate the text of the message
+            let _ = msg.generate_text(&self.config.db);
+
+            if !msg.is_announcement() && !msg.is_tapback() {
+                let message = self
+                    .format_message(&msg, 0)
+                    .map_err(RuntimeError::DatabaseError)?;
+                // Messages with no text or attachments cannot be restored
+                if !message.is_empty() {
+                    match self.file_key(&msg) {
+                        Some(key) => *self.counts.entry(key).or_insert(0) += 1,
+                        None => self.orphaned_count += 1,
+                    }
+                    XML::write_to_file(self.get_or_create_file(&msg)?, &message)?;
+                }
+            }
+            current_message += 1;
+            if current_message % 99 == 0 {
+                pb.set_position(current_message);
+            }
+        }
+        pb.finish();
+
+        eprintln!("Writing XML footers...");
+        for (filename, buf) in self.files.iter_mut() {
+            XML::write_footer(buf, *self.counts.get(filename).unwrap_or(&0))?;
+        }
+        XML::write_footer(&mut self.orphaned, self.orphaned_count)?;
+
+        Ok(())
+    }
+
+    /// Create a file for the given chat, caching it so we don't need to build it later
+    fn get_or_create_file(
+        &mut self,
+        message: &Message,
+    ) -> Result<&mut BufWriter<File>, RuntimeError> {
+        if self.config.options.combine_chats {
+            return Ok(&mut self.orphaned);
+        }
+
+        match self.config.conversation(message) {
+            Some((chatroom, _)) => {
+                let filename = self.config.filename(chatroom);
+                match self.files.entry(filename) {
+                    Occupied(entry) => Ok(entry.into_mut()),
+                    Vacant(entry) => {
+                        let mut path = self.config.options.export_path.clone();
+                        path.push(self.config.filename(chatroom));
+                        path.set_extension("xml");
+
+                        // The message count is written over the header when the export finishes, so the file cannot be opened for appending
+                        let file = File::options()
+                            .write(true)
+                            .create(true)
+                            .truncate(true)
+                            .open(&path)
+                            .map_err(|err| RuntimeError::CreateError(err, path))?;
+
+                        let mut buf = BufWriter::new(file);
+                        XML::write_headers(&mut buf)?;
+
+                        Ok(entry.insert(buf))
+                    }
+                }
+            }
+            None => Ok(&mut self.orphaned),
+        }
+    }
+}
+
+impl<'a> Writer<'a> for XML<'a> {
+    fn format_message(&self, message: &Message, _: usize) -> Result<String, TableError> {
+        let body = self.body(message);
+
+        let mut attachments = Attachment::from_message(&self.config.db, message)?;
+        let parts: Vec<String> = attachments
+            .iter_mut()
+            .filter_map(|attachment| self.format_attachment(attachment, message).ok())
+            .collect();
+
+        if body.is_empty() && parts.is_empty() {
+            return Ok(String::new());
+        }
+
+        let recipients = self.recipients(message);
+        let date = self.timestamp(&message.date);
+        let readable_date = sanitize_xml(&format(&message.date(&self.config.offset))).to_string();
+        let contact_name = sanitize_xml(&self.contact_name(message)).to_string();
+
+        // Conversations with a single other person and no attachments are plain SMS
+        if recipients.len() <= 1 && parts.is_empty() {
+            let address = recipients
+                .first()
+                .map(|address| sanitize_xml(address))
+                .unwrap_or(Cow::Borrowed(UNKNOWN));
+            return Ok(format!(
+                "  <sms protocol=\"0\" address=\"{address}\" date=\"{date}\" type=\"{}\" subject=\"null\" body=\"{}\" toa=\"null\" sc_toa=\"null\" service_center=\"null\" read=\"{}\" status=\"-1\" locked=\"0\" date_sent=\"{date}\" readable_date=\"{readable_date}\" contact_name=\"{contact_name}\" />\n",
+                if message.is_from_me() { 2 } else { 1 },
+                sanitize_xml(&body),
+                XML::read(message),
+            ));
+        }
+
+        // Everything else is MMS, which supports multiple recipients and attachments
+        let mut out_s = format!(
+            "  <mms date=\"{date}\" ct_t=\"application/vnd.wap.multipart.related\" msg_box=\"{}\" address=\"{}\" m_type=\"{}\" sub=\"{}\" read=\"{}\" seen=\"1\" text_only=\"{}\" locked=\"0\" date_sent=\"0\" m_cls=\"personal\" sub_id=\"-1\" readable_date=\"{readable_date}\" contact_name=\"{contact_name}\">\n",
+            if message.is_from_me() { 2 } else { 1 },
+            sanitize_xml(&recipients.join("~")),
+            if message.is_from_me() { 128 } else { 132 },
+            message
+                .subject
+                .as_deref()
+                .map(sanitize_xml)
+                .unwrap_or(Cow::Borrowed("null")),
+            XML::read(message),
+            u8::from(parts.is_empty()),
+        );
+
+        out_s.push_str("    <parts>\n");
+        if !body.is_empty() {
+            out_s.push_str(&format!(
+                "      <part seq=\"0\" ct=\"text/plain\" name=\"null\" chset=\"{UTF_8}\" cd=\"null\" fn=\"null\" cid=\"null\" cl=\"text.txt\" ctt_s=\"null\" ctt_t=\"null\" text=\"{}\" />\n",
+                sanitize_xml(&body)
+            ));
+        }
+        parts.iter().for_each(|part| out_s.push_str(part));
+        out_s.push_str("    </parts>\n");
+
+        out_s.push_str("    <addrs>\n");
+        for (address, kind) in self.addresses(message, &recipients) {
+            out_s.push_str(&format!(
+                "      <addr address=\"{}\" type=\"{kind}\" charset=\"{UTF_8}\" />\n",
+                sanitize_xml(address)
+            ));
+        }
+        out_s.push_str("    </addrs>\n");
+        out_s.push_str("  </mms>\n");
+
+        Ok(out_s)
+    }
+
+    fn format_attachment(
+        &self,
+        attachment: &'a mut Attachment,
+        message: &Message,
+    ) -> Result<String, &'a str> {
+        // Copy the file, if requested, so converted images are embedded instead of the originals
+        self.config
+            .options
+            .attachment_manager
+            .handle_attachment(message, attachment, self.config)
+            .ok_or(attachment.filename())?;
+
+        let bytes = self
+            .config
+            .message_attachment_bytes(attachment)
+            .ok_or(attachment.filename())?;
+
+        let name = sanitize_xml(attachment.filename());
+        Ok(format!(
+            "      <part seq=\"0\" ct=\"{}\" name=\"{name}\" chset=\"null\" cd=\"null\" fn=\"null\" cid=\"&lt;{name}&gt;\" cl=\"{name}\" ctt_s=\"null\" ctt_t=\"null\" text=\"null\" data=\"{}\" />\n",
+            sanitize_xml(self.config.message_attachment_content_type(attachment)),
+            STANDARD.encode(bytes)
+        ))
+    }
+
+    fn format_sticker(&self, sticker: &'a mut Attachment, message: &Message) -> String {
+        // Stickers are sent as regular image attachments
+        self.format_attachment(sticker, message).unwrap_or_default()
+    }
+
+    fn format_app(
+        &self,
+        message: &'a Message,
+        _: &mut Vec<Attachment>,
+        _: &str,
+    ) -> Result<String, PlistParseError> {
+        if let Variant::App(_) = message.variant() {
+            if let Some(payload) = message.payload_data(&self.config.db) {
+                let parsed = parse_plist(&payload)?;
+                // URL messages are described by their link, everything else by its most specific caption
+                let description = if message.is_url() {
+                    match URLMessage::get_url_message_override(&parsed)? {
+                        URLOverride::Normal(balloon) => {
+                            balloon.get_url().or(balloon.title).map(str::to_string)
+                        }
+                        URLOverride::AppleMusic(balloon) => balloon.url.map(str::to_string),
+                        URLOverride::Collaboration(balloon) => {
+                            balloon.get_url().map(str::to_string)
+                        }
+                        URLOverride::AppStore(balloon) => balloon.url.map(str::to_string),
+                        URLOverride::SharedPlacemark(balloon) => {
+                            balloon.get_url().map(str::to_string)
+                        }
+                    }
+                } else {
+                    let balloon = AppMessage::from_map(&parsed)?;
+                    balloon
+                        .ldtext
+                        .or(balloon.title)
+                        .or(balloon.caption)
+                        .or(balloon.app_name)
+                        .map(str::to_string)
+                };
+                return Ok(description.or(message.text.clone()).unwrap_or_default());
+            }
+            // Sometimes, URL messages are missing their payloads
+            if message.is_url() {
+                if let Some(text) = &message.text {
+                    return Ok(text.to_string());
+                }
+            }
+            return Err(PlistParseError::NoPayload);
+        }
+        Err(PlistParseError::WrongMessageType)
+    }
+
+    fn format_tapback(&self, _: &Message) -> Result<String, TableError> {
+        // Android has no equivalent to tapbacks
+        Ok(String::new())
+    }
+
+    fn format_expressive(&self, msg: &'a Message) -> &'a str {
+        match msg.get_expressive() {
+            Expressive::Screen(effect) => match effect {
+                ScreenEffect::Confetti => "Sent with Confetti",
+                ScreenEffect::Echo => "Sent with Echo",
+                ScreenEffect::Fireworks => "Sent with Fireworks",
+                ScreenEffect::Balloons => "Sent with Balloons",
+                ScreenEffect::Heart => "Sent with Heart",
+                ScreenEffect::Lasers => "Sent with Lasers",
+                ScreenEffect::ShootingStar => "Sent with Shooting Star",
+                ScreenEffect::Sparkles => "Sent with Sparkles",
+                ScreenEffect::Spotlight => "Sent with Spotlight",
+            },
+            Expressive::Bubble(effect) => match effect {
+                BubbleEffect::Slam => "Sent with Slam",
+                BubbleEffect::Loud => "Sent with Loud",
+                BubbleEffect::Gentle => "Sent with Gentle",
+                BubbleEffect::InvisibleInk => "Sent with Invisible Ink",
+            },
+            Expressive::Unknown(effect) => effect,
+            Expressive::None => "",
+        }
+    }
+
+    fn format_announcement(&self, _: &'a Message) -> String {
+        // Android has no equivalent to group announcements
+        String::new()
+    }
+
+    fn format_shareplay(&self) -> &str {
+        "SharePlay Message Ended"
+    }
+
+    fn format_shared_location(&self, msg: &'a Message) -> &str {
+        // Handle Shared Location
+        if msg.started_sharing_location() {
+            return "Started sharing location!";
+        } else if msg.stopped_sharing_location() {
+            return "Stopped sharing location!";
+        }
+        "Shared location!"
+    }
+
+    fn format_edited(
+        &self,
+        _: &'a Message,
+        edited_message: &'a EditedMessage,
+        message_part_idx: usize,
+        _: &str,
+    ) -> Option<String> {
+        // Only the most recent version of an edited part is restored
+        let edited_message_part = edited_message.part(message_part_idx)?;
+        match edited_message_part.status {
+            EditStatus::Edited => edited_message_part
+                .edit_history
+                .last()
+                .map(|event| event.text.clone()),
+            EditStatus::Unsent => Some(String::new()),
+            EditStatus::Original => None,
+        }
+    }
+
+    fn format_attributed(&'a self, msg: &'a str, _: &'a TextEffect) -> Cow<'a, str> {
+        // SMS has no way to represent formatted text
+        Cow::Borrowed(msg)
+    }
+
+    fn write_to_file(file: &mut BufWriter<File>, text: &str) -> Result<(), RuntimeError> {
+        file.write_all(text.as_bytes())
+            .map_err(RuntimeError::DiskError)
+    }
+}
+
+impl<'a> XML<'a> {
+    /// Build the opening tag of the root element, padded to a fixed width
+    fn root(count: usize) -> String {
+        format!("{:<ROOT_WIDTH$}>\n", format!("<smses count=\"{count}\""))
+    }
+
+    /// Write the XML declaration and a placeholder root element to a new file
+    fn write_headers(file: &mut BufWriter<File>) -> Result<(), RuntimeError> {
+        XML::write_to_file(file, DECLARATION)?;
+        XML::write_to_file(file, &XML::root(0))
+    }
+
+    /// Close the root element and fill in the number of messages the file contains
+    fn write_footer(file: &mut BufWriter<File>, count: usize) -> Result<(), RuntimeError> {
+        XML::write_to_file(file, "</smses>\n")?;
+        file.seek(SeekFrom::Start(DECLARATION.len() as u64))
+            .map_err(RuntimeError::DiskError)?;
+        XML::write_to_file(file, &XML::root(count))?;
+        file.flush().map_err(RuntimeError::DiskError)
+    }
+
+    /// Get the key of the file a message is written to, or `None` if it goes to the orphaned file
+    fn file_key(&self, message: &Message) -> Option<String> {
+        if self.config.options.combine_chats {
+            return None;
+        }
+        self.config
+            .conversation(message)
+            .map(|(chatroom, _)| self.config.filename(chatroom))
+    }
+
+    /// Build the plain text body of a message
+    fn body(&self, message: &Message) -> String {
+        if message.is_shareplay() {
+            return self.format_shareplay().to_string();
+        }
+        if message.started_sharing_location() || message.stopped_sharing_location() {
+            return self.format_shared_location(message).to_string();
+        }
+        if matches!(message.variant(), Variant::App(_)) {
+            if let Ok(app) = self.format_app(message, &mut vec![], "") {
+                return app;
+            }
+        }
+        message
+            .text
+            .as_deref()
+            .unwrap_or_default()
+            // Attachments are exported as their own parts, so drop their placeholders
+            .replace('\u{FFFC}', "")
+            .trim()
+            .to_string()
+    }
+
+    /// Get the addresses of everyone in the conversation other than the database owner
+    fn recipients(&self, message: &Message) -> Vec<&str> {
+        let participants = message
+            .chat_id
+            .and_then(|chat_id| self.config.chatroom_participants.get(&chat_id));
+
+        match participants {
+            Some(participants) if !participants.is_empty() => participants
+                .iter()
+                .filter_map(|handle_id| self.handles.get(handle_id))
+                .map(String::as_str)
+                .collect(),
+            // Orphaned messages only know who sent them
+            _ => message
+                .handle_id
+                .and_then(|handle_id| self.handles.get(&handle_id))
+                .map(|address| vec![address.as_str()])
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Build the MMS address list, marking who sent the message and who received it
+    fn addresses<'b>(&'b self, message: &'b Message, recipients: &[&'b str]) -> Vec<(&'b str, u8)> {
+        let sender = if message.is_from_me() {
+            message
+                .destination_caller_id
+                .as_deref()
+                .unwrap_or(SELF_ADDRESS)
+        } else {
+            message
+                .handle_id
+                .and_then(|handle_id| self.handles.get(&handle_id))
+                .map(String::as_str)
+                .unwrap_or(UNKNOWN)
+        };
+
+        let mut addresses = vec![(sender, PDU_FROM)];
+        recipients
+            .iter()
+            .filter(|recipient| **recipient != sender)
+            .for_each(|recipient| addresses.push((recipient, PDU_TO)));
+
+        // Received messages were also sent to the database owner
+        if !message.is_from_me() {
+            addresses.push((
+                message
+                    .destination_caller_id
+                    .as_deref()
+                    .unwrap_or(SELF_ADDRESS),
+                PDU_TO,
+            ));
+        }
+        addresses
+    }
+
+    /// Get the display name for the other people in the conversation
+    fn contact_name(&self, message: &Message) -> String {
+        let participants = message
+            .chat_id
+            .and_then(|chat_id| self.config.chatroom_participants.get(&chat_id));
+
+        match participants {
+            Some(participants) if !participants.is_empty() => participants
+                .iter()
+                .map(|handle_id| self.config.who(Some(*handle_id), false, &None))
+                .collect::<Vec<&str>>()
+                .join(", "),
+            _ => self
+                .config
+                .who(message.handle_id, false, &message.destination_caller_id)
+                .to_string(),
+        }
+    }
+
+    /// Convert an iMessage timestamp to milliseconds since the Unix epoch, using `0` for unset timestamps
+    fn timestamp(&self, stamp: &i64) -> i64 {
+        if *stamp == 0 {
+            return 0;
+        }
+        get_local_time(stamp, &self.config.offset)
+            .map(|date| date.timestamp_millis())
+            .unwrap_or(0)
+    }
+
+    /// Android expects `1` for messages that have been read
+    fn read(message: &Message) -> u8 {
+        u8::from(message.is_from_me() || message.is_read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        env::set_var,
+        fs::{read_to_string, remove_file, File},
+        io::BufWriter,
+        path::PathBuf,
+    };
+
+    use crate::{
+        app::{
+            export_type::ExportType,
+            fixtures::{blank, fake_config, fake_options_for},
+        },
+        exporters::exporter::Writer,
+        Exporter, XML,
+    };
+
+    #[test]
+    fn can_create() {
+        let options = fake_options_for(ExportType::Xml);
+        let config = fake_config(options);
+        let exporter = XML::new(&config).unwrap();
+        assert_eq!(exporter.files.len(), 0);
+    }
+
+    #[test]
+    fn can_build_root() {
+        assert_eq!(XML::root(0).len(), XML::root(usize::MAX).len());
+        assert!(XML::root(42).starts_with("<smses count=\"42\" "));
+        assert!(XML::root(42).ends_with(">\n"));
+    }
+
+    #[test]
+    fn can_write_count_in_footer() {
+        let path = PathBuf::from("/tmp/can_write_count_in_footer.xml");
+        let file = File::create(&path).unwrap();
+        let mut buf = BufWriter::new(file);
+
+        XML::write_headers(&mut buf).unwrap();
+        XML::write_to_file(&mut buf, "  <sms />\n").unwrap();
+        XML::write_footer(&mut buf, 1).unwrap();
+
+        let actual = read_to_string(&path).unwrap();
+        let expected = format!(
+            "<?xml version='1.0' encoding='UTF-8' standalone='yes' ?>\n{}  <sms />\n</smses>\n",
+            XML::root(1)
+        );
+
+        assert_eq!(actual, expected);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn can_format_xml_sms_from_me() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Xml);
+        let mut config = fake_config(options);
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([999999]));
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let mut exporter = XML::new(&config).unwrap();
+        exporter.handles.insert(999999, "+15558675309".to_string());
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello \"world\"\nBye".to_string());
+        message.is_from_me = true;
+        message.chat_id = Some(1);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "  <sms protocol=\"0\" address=\"+15558675309\" date=\"1652833782000\" type=\"2\" subject=\"null\" body=\"Hello &quot;world&quot;&#10;Bye\" toa=\"null\" sc_toa=\"null\" service_center=\"null\" read=\"1\" status=\"-1\" locked=\"0\" date_sent=\"1652833782000\" readable_date=\"May 17, 2022  5:29:42 PM\" contact_name=\"Sample Contact\" />\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_xml_sms_from_them_unread() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Xml);
+        let mut config = fake_config(options);
+        config
+            .participants
+            .insert(999999, "Sample Contact".to_string());
+        let mut exporter = XML::new(&config).unwrap();
+        exporter.handles.insert(999999, "+15558675309".to_string());
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello world".to_string());
+        message.handle_id = Some(999999);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = "  <sms protocol=\"0\" address=\"+15558675309\" date=\"1652833782000\" type=\"1\" subject=\"null\" body=\"Hello world\" toa=\"null\" sc_toa=\"null\" service_center=\"null\" read=\"0\" status=\"-1\" locked=\"0\" date_sent=\"1652833782000\" readable_date=\"May 17, 2022  5:29:42 PM\" contact_name=\"Sample Contact\" />\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn can_format_xml_group_mms() {
+        // Set timezone to PST for consistent Local time
+        set_var("TZ", "PST");
+
+        // Create exporter
+        let options = fake_options_for(ExportType::Xml);
+        let mut config = fake_config(options);
+        config
+            .chatroom_participants
+            .insert(1, BTreeSet::from([999998, 999999]));
+        config.participants.insert(999998, "Contact A".to_string());
+        config.participants.insert(999999, "Contact B".to_string());
+        let mut exporter = XML::new(&config).unwrap();
+        exporter.handles.insert(999998, "+15550000001".to_string());
+        exporter.handles.insert(999999, "+15550000002".to_string());
+
+        let mut message = blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.text = Some("Hello group".to_string());
+        message.handle_id = Some(999999);
+        message.is_read = true;
+        message.chat_id = Some(1);
+
+        let actual = exporter.format_message(&message, 0).unwrap();
+        let expected = concat!(
+            "  <mms date=\"1652833782000\" ct_t=\"application/vnd.wap.multipart.related\" msg_box=\"1\" address=\"+15550000001~+15550000002\" m_type=\"132\" sub=\"null\" read=\"1\" seen=\"1\" text_only=\"1\" locked=\"0\" date_sent=\"0\" m_cls=\"personal\" sub_id=\"-1\" readable_date=\"May 17, 2022  5:29:42 PM\" contact_name=\"Contact A, Contact B\">\n",
+            "    <parts>\n",
+            "      <part seq=\"0\" ct=\"text/plain\" name=\"null\" chset=\"106\" cd=\"null\" fn=\"null\" cid=\"null\" cl=\"text.txt\" ctt_s=\"null\" ctt_t=\"null\" text=\"Hello group\" />\n",
+            "    </parts>\n",
+            "    <addrs>\n",
+            "      <addr address=\"+15550000002\" type=\"137\" charset=\"106\" />\n",
+            "      <addr address=\"+15550000001\" type=\"151\" charset=\"106\" />\n",
+            "      <addr address=\"insert-address-token\" type=\"151\" charset=\"106\" />\n",
+            "    </addrs>\n",
+            "  </mms>\n",
+        );
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cant_format_xml_empty() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Xml);
+        let config = fake_config(options);
+        let exporter = XML::new(&config).unwrap();
+
+        let message = blank();
+
+        assert_eq!(exporter.format_message(&message, 0).unwrap(), "");
+    }
+
+    #[test]
+    fn can_format_xml_body_without_placeholders() {
+        // Create exporter
+        let options = fake_options_for(ExportType::Xml);
+        let config = fake_config(options);
+        let exporter = XML::new(&config).unwrap();
+
+        let mut message = blank();
+        message.text = Some("\u{FFFC}Look at this".to_string());
+
+        assert_eq!(exporter.body(&message), "Look at this");
+    }
+}
@@ -3,7 +3,11 @@
 mod app;
 mod exporters;
 
-pub use exporters::{exporter::Exporter, html::HTML, txt::TXT};
+pub use exporters::{
+    contacts::Contacts, csv::CSV, exporter::Exporter, html::HTML, json::JSON, links::Links,
+    locations::Locations, mbox::MBOX, md::MD, parquet::Parquet, slack::Slack, sqlite::SQLite,
+    telegram::Telegram, txt::TXT, xml::XML,
+};
 
 use app::{
     options::{from_command_line, Options},